@@ -0,0 +1,73 @@
+//! Allele validation throughput: the lookup-table validator against the
+//! regular expressions it replaced.
+//!
+//! Run with `cargo bench --bench validation`.
+
+use regex::Regex;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use vcf_lib::validation::ValidationPolicy;
+
+const ITERATIONS: usize = 200_000;
+
+fn alleles() -> Vec<String> {
+    let mut alleles: Vec<String> = ["A", "C", "GT", "ACGTN", "RYKM", "acgt", "AC.G", "<DEL>"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+    alleles.push("ACGT".repeat(25));
+
+    alleles
+}
+
+fn bench<F: Fn(&str) -> bool>(name: &str, alleles: &[String], f: F) -> Duration {
+    let start = Instant::now();
+    let mut valid = 0;
+    for _ in 0..ITERATIONS {
+        for allele in alleles {
+            valid += f(black_box(allele)) as usize;
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<24} {:>8.1} ns/allele ({} valid)",
+        name,
+        elapsed.as_nanos() as f64 / (ITERATIONS * alleles.len()) as f64,
+        valid / ITERATIONS
+    );
+
+    elapsed
+}
+
+fn main() {
+    let alleles = alleles();
+
+    for (name, policy, pattern) in [
+        (
+            "iupac",
+            ValidationPolicy::iupac(),
+            r"\A[ACGTURYKMSWBDHVN]+\z",
+        ),
+        (
+            "lowercase_tolerant",
+            ValidationPolicy::lowercase_tolerant(false),
+            r"(?i)\A[ACGTURYKMSWBDHVN]+\z",
+        ),
+    ] {
+        let regex = Regex::new(pattern).unwrap();
+
+        let table = bench(&format!("{} table", name), &alleles, |a| {
+            policy.validate_reference(a).is_ok()
+        });
+        let regex = bench(&format!("{} regex", name), &alleles, |a| {
+            !a.is_empty() && regex.is_match(a)
+        });
+
+        println!(
+            "{:<24} {:>8.1}x",
+            format!("{} speedup", name),
+            regex.as_secs_f64() / table.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * C interface of vcf-lib, built with the `ffi` feature.
+ *
+ * Functions returning vcf_status store a message for vcf_last_error() on
+ * failure. Strings returned as `char *` are owned by the caller and freed
+ * with vcf_string_free(); `const char *` results borrow from their record
+ * or reader.
+ */
+
+#ifndef VCF_LIB_H
+#define VCF_LIB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum vcf_status {
+    VCF_OK = 0,
+    VCF_NULL_POINTER = 1,
+    VCF_INVALID_UTF8 = 2,
+    VCF_INVALID = 3,
+} vcf_status;
+
+typedef enum vcf_type {
+    VCF_UNKNOWN = -1,
+    VCF_SNV = 0,
+    VCF_DELETION = 1,
+    VCF_INSERTION = 2,
+    VCF_INDEL = 3,
+    VCF_MNV = 4,
+} vcf_type;
+
+typedef struct vcf_variant {
+    uint64_t position;
+    char *reference;
+    char *alternate;
+} vcf_variant;
+
+typedef struct vcf_record vcf_record;
+
+typedef struct vcf_reader vcf_reader;
+
+/* Message of the last error on this thread, or NULL. */
+const char *vcf_last_error(void);
+
+void vcf_string_free(char *s);
+
+/* Trims the shared bases of the alleles; free `out` with vcf_variant_free(). */
+vcf_status vcf_normalize(uint64_t position, const char *reference,
+                         const char *alternate, vcf_variant *out);
+
+void vcf_variant_free(vcf_variant *variant);
+
+/* Classifies normalized alleles; VCF_UNKNOWN if they are not a variant. */
+vcf_type vcf_variant_type(const char *reference, const char *alternate);
+
+/* Parses a data line; free `*out` with vcf_record_free(). */
+vcf_status vcf_record_parse(const char *line, vcf_record **out);
+
+void vcf_record_free(vcf_record *record);
+
+const char *vcf_record_chrom(const vcf_record *record);
+
+uint64_t vcf_record_pos(const vcf_record *record);
+
+const char *vcf_record_ref(const vcf_record *record);
+
+size_t vcf_record_alt_count(const vcf_record *record);
+
+/* ALT allele `i`, or NULL past the last one. */
+const char *vcf_record_alt(const vcf_record *record, size_t i);
+
+/* INFO value as a new string: "" for a flag, NULL if the key is absent. */
+char *vcf_record_info(const vcf_record *record, const char *key);
+
+/* The record as a data line without newline, as a new string. */
+char *vcf_record_to_string(const vcf_record *record);
+
+/* Opens a plain, gzip or BGZF file; free `*out` with vcf_reader_free().
+ * Needs the `fs` feature. */
+vcf_status vcf_reader_open(const char *path, vcf_reader **out);
+
+/* Reads a plain, gzip or BGZF VCF from a copy of `len` bytes at `data`. */
+vcf_status vcf_reader_from_memory(const uint8_t *data, size_t len,
+                                  vcf_reader **out);
+
+/* Reads the next record, or sets `*out` to NULL at the end of the input;
+ * free the record with vcf_record_free(). */
+vcf_status vcf_reader_next(vcf_reader *reader, vcf_record **out);
+
+/* The header as text, including the #CHROM line. */
+const char *vcf_reader_header(const vcf_reader *reader);
+
+size_t vcf_reader_sample_count(const vcf_reader *reader);
+
+/* Name of sample `i`, or NULL past the last one. */
+const char *vcf_reader_sample(const vcf_reader *reader, size_t i);
+
+void vcf_reader_free(vcf_reader *reader);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VCF_LIB_H */
@@ -0,0 +1,61 @@
+import gzip
+import io
+import os
+import tempfile
+import unittest
+
+import vcf_lib
+
+VCF = (
+    "##fileformat=VCFv4.3\n"
+    "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n"
+    "1\t10\trs1\tCAGT\tCT,C\t.\tPASS\tDP=3;DB\tGT\t0/1\n"
+    "1\t20\t.\tA\tG\t.\t.\t.\tGT\t1/1\n"
+)
+
+
+class TestVcfLib(unittest.TestCase):
+    def test_normalize(self):
+        self.assertEqual(vcf_lib.normalize(10, "CAGT", "CT"), (10, "CAG", "C"))
+        self.assertEqual(vcf_lib.variant_type("CAG", "C"), "Deletion")
+        self.assertIsNone(vcf_lib.variant_type("A", "A"))
+        with self.assertRaisesRegex(vcf_lib.VcfError, "non-ACGT"):
+            vcf_lib.normalize(10, "A", "X")
+
+    def test_record(self):
+        record = vcf_lib.Record("1\t10\trs1\tA\tT,G\t.\tPASS\tDP=3;DB\tGT\t0/1\n")
+        self.assertEqual((record.chrom, record.pos, record.ref), ("1", 10, "A"))
+        self.assertEqual(record.alts, ["T", "G"])
+        self.assertEqual(record.info("DP"), "3")
+        self.assertEqual(record.info("DB"), "")
+        self.assertIsNone(record.info("AF"))
+        self.assertEqual(str(record), "1\t10\trs1\tA\tT,G\t.\tPASS\tDP=3;DB\tGT\t0/1")
+        with self.assertRaises(vcf_lib.VcfError):
+            vcf_lib.Record("1\tx\t.\tA\tT")
+
+    def test_reader(self):
+        reader = vcf_lib.Reader(io.StringIO(VCF))
+        self.assertEqual(reader.header, ["##fileformat=VCFv4.3"])
+        self.assertEqual(reader.samples, ["S1"])
+        self.assertEqual([r.pos for r in reader], [10, 20])
+        with self.assertRaises(vcf_lib.VcfError):
+            vcf_lib.Reader(io.StringIO("1\t10\t.\tA\tT\n"))
+
+    def test_reader_path(self):
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "calls.vcf.gz")
+            with gzip.open(path, "wt") as f:
+                f.write(VCF)
+
+            with vcf_lib.Reader(path) as reader:
+                self.assertEqual(reader.samples, ["S1"])
+                self.assertEqual([r.alts for r in reader], [["CT", "C"], ["G"]])
+            with vcf_lib.Reader(io.BytesIO(gzip.compress(VCF.encode()))) as reader:
+                self.assertEqual(len(list(reader)), 2)
+
+            with self.assertRaises(vcf_lib.VcfError):
+                vcf_lib.Reader(os.path.join(directory, "missing.vcf"))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,239 @@
+"""Python bindings of vcf-lib over its C interface.
+
+Build the shared library with the `python` feature, which enables the C
+interface, and point `VCF_LIB_PATH` at it unless it is copied next to this
+package:
+
+    cargo build --release --features python
+
+Strings are passed as UTF-8. Calls the library rejects raise `VcfError`
+with the library's message.
+"""
+
+import ctypes
+import os
+import sys
+
+__all__ = ["VcfError", "normalize", "variant_type", "Record", "Reader"]
+
+_OK = 0
+_TYPES = {0: "SNV", 1: "Deletion", 2: "Insertion", 3: "Indel", 4: "MNV"}
+
+
+class VcfError(ValueError):
+    """An input rejected by the library."""
+
+
+class _Variant(ctypes.Structure):
+    _fields_ = [
+        ("position", ctypes.c_uint64),
+        ("reference", ctypes.c_void_p),
+        ("alternate", ctypes.c_void_p),
+    ]
+
+
+def _library_name():
+    if sys.platform == "win32":
+        return "vcf_lib.dll"
+    if sys.platform == "darwin":
+        return "libvcf_lib.dylib"
+    return "libvcf_lib.so"
+
+
+def _load():
+    path = os.environ.get("VCF_LIB_PATH") or os.path.join(
+        os.path.dirname(os.path.abspath(__file__)), _library_name()
+    )
+    lib = ctypes.CDLL(path)
+
+    c_str, c_owned = ctypes.c_char_p, ctypes.c_void_p
+    record, reader = ctypes.c_void_p, ctypes.c_void_p
+    signatures = {
+        "vcf_last_error": ([], c_str),
+        "vcf_string_free": ([c_owned], None),
+        "vcf_normalize": (
+            [ctypes.c_uint64, c_str, c_str, ctypes.POINTER(_Variant)],
+            ctypes.c_int,
+        ),
+        "vcf_variant_free": ([ctypes.POINTER(_Variant)], None),
+        "vcf_variant_type": ([c_str, c_str], ctypes.c_int),
+        "vcf_record_parse": ([c_str, ctypes.POINTER(record)], ctypes.c_int),
+        "vcf_record_free": ([record], None),
+        "vcf_record_chrom": ([record], c_str),
+        "vcf_record_pos": ([record], ctypes.c_uint64),
+        "vcf_record_ref": ([record], c_str),
+        "vcf_record_alt_count": ([record], ctypes.c_size_t),
+        "vcf_record_alt": ([record, ctypes.c_size_t], c_str),
+        "vcf_record_info": ([record, c_str], c_owned),
+        "vcf_record_to_string": ([record], c_owned),
+        "vcf_reader_open": ([c_str, ctypes.POINTER(reader)], ctypes.c_int),
+        "vcf_reader_from_memory": (
+            [ctypes.c_char_p, ctypes.c_size_t, ctypes.POINTER(reader)],
+            ctypes.c_int,
+        ),
+        "vcf_reader_next": ([reader, ctypes.POINTER(record)], ctypes.c_int),
+        "vcf_reader_header": ([reader], c_str),
+        "vcf_reader_sample_count": ([reader], ctypes.c_size_t),
+        "vcf_reader_sample": ([reader, ctypes.c_size_t], c_str),
+        "vcf_reader_free": ([reader], None),
+    }
+    for name, (arguments, result) in signatures.items():
+        function = getattr(lib, name)
+        function.argtypes = arguments
+        function.restype = result
+
+    return lib
+
+
+_lib = _load()
+
+
+def _encode(s):
+    return s.encode("utf-8")
+
+
+def _decode(s):
+    return s.decode("utf-8")
+
+
+def _take(pointer):
+    """Decodes a string returned by the library and frees it."""
+    if not pointer:
+        return None
+    try:
+        return _decode(ctypes.string_at(pointer))
+    finally:
+        _lib.vcf_string_free(pointer)
+
+
+def _check(status):
+    if status != _OK:
+        message = _lib.vcf_last_error()
+        raise VcfError(_decode(message) if message else "status %d" % status)
+
+
+def normalize(position, reference, alternate):
+    """Trims the shared bases of two alleles; returns `(position, ref, alt)`."""
+    variant = _Variant()
+    _check(
+        _lib.vcf_normalize(
+            position, _encode(reference), _encode(alternate), ctypes.byref(variant)
+        )
+    )
+    try:
+        return (
+            variant.position,
+            _decode(ctypes.string_at(variant.reference)),
+            _decode(ctypes.string_at(variant.alternate)),
+        )
+    finally:
+        _lib.vcf_variant_free(ctypes.byref(variant))
+
+
+def variant_type(reference, alternate):
+    """Type of already normalized alleles, or `None` if they are no variant."""
+    return _TYPES.get(_lib.vcf_variant_type(_encode(reference), _encode(alternate)))
+
+
+class Record:
+    """A parsed data line."""
+
+    def __init__(self, line):
+        handle = ctypes.c_void_p()
+        _check(_lib.vcf_record_parse(_encode(line.rstrip("\r\n")), ctypes.byref(handle)))
+        self._handle = handle
+
+    @classmethod
+    def _wrap(cls, handle):
+        record = cls.__new__(cls)
+        record._handle = handle
+        return record
+
+    def __del__(self):
+        handle = getattr(self, "_handle", None)
+        if handle:
+            _lib.vcf_record_free(handle)
+            self._handle = None
+
+    @property
+    def chrom(self):
+        return _decode(_lib.vcf_record_chrom(self._handle))
+
+    @property
+    def pos(self):
+        return _lib.vcf_record_pos(self._handle)
+
+    @property
+    def ref(self):
+        return _decode(_lib.vcf_record_ref(self._handle))
+
+    @property
+    def alts(self):
+        count = _lib.vcf_record_alt_count(self._handle)
+        return [_decode(_lib.vcf_record_alt(self._handle, i)) for i in range(count)]
+
+    def info(self, key):
+        """Value of an INFO key; `""` for a set flag, `None` if absent."""
+        return _take(_lib.vcf_record_info(self._handle, _encode(key)))
+
+    def __str__(self):
+        return _take(_lib.vcf_record_to_string(self._handle))
+
+    def __repr__(self):
+        return "Record(%r)" % str(self)
+
+
+class Reader:
+    """Records of a plain, gzip or BGZF compressed VCF, read by the library.
+
+    `source` is a path, or an open file whose whole content is read. The
+    header is read on construction: `header` holds its `##` lines and
+    `samples` the sample names of the `#CHROM` line.
+    """
+
+    def __init__(self, source):
+        handle = ctypes.c_void_p()
+        if isinstance(source, (str, os.PathLike)):
+            path = _encode(os.fspath(source))
+            _check(_lib.vcf_reader_open(path, ctypes.byref(handle)))
+        else:
+            data = source.read()
+            if isinstance(data, str):
+                data = _encode(data)
+            _check(_lib.vcf_reader_from_memory(data, len(data), ctypes.byref(handle)))
+        self._handle = handle
+
+        header = _decode(_lib.vcf_reader_header(handle))
+        self.header = [line for line in header.splitlines() if line.startswith("##")]
+        self.samples = [
+            _decode(_lib.vcf_reader_sample(handle, i))
+            for i in range(_lib.vcf_reader_sample_count(handle))
+        ]
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if not self._handle:
+            raise StopIteration
+
+        record = ctypes.c_void_p()
+        _check(_lib.vcf_reader_next(self._handle, ctypes.byref(record)))
+        if not record:
+            raise StopIteration
+        return Record._wrap(record)
+
+    def close(self):
+        handle = getattr(self, "_handle", None)
+        if handle:
+            _lib.vcf_reader_free(handle)
+            self._handle = None
+
+    def __del__(self):
+        self.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.close()
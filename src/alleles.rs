@@ -0,0 +1,358 @@
+//! Mapping of allele indices between records.
+
+use crate::errors::{Error, Result};
+use crate::genotype::Genotype;
+use crate::header::{Header, Number};
+use crate::record::Record;
+
+/// Maps the alleles of one record onto those of another.
+///
+/// Index `0` is REF. Alleles without a counterpart map to `None`; values of
+/// output alleles without a source are filled with `.`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlleleRemap {
+    map: Vec<Option<usize>>,
+    len: usize,
+}
+
+impl AlleleRemap {
+    /// `map[i]` is the output index of input allele `i`; `len` is the number
+    /// of output alleles including REF.
+    pub fn new(map: Vec<Option<usize>>, len: usize) -> Self {
+        Self { map, len }
+    }
+
+    /// Keeps the ALT alleles for which `keep` is `true`, like
+    /// [`Record::retain_alternates`].
+    pub fn retain(keep: &[bool]) -> Self {
+        let mut map = vec![Some(0)];
+        let mut len = 1;
+
+        for &kept in keep {
+            map.push(kept.then_some(len));
+            len += kept as usize;
+        }
+
+        Self { map, len }
+    }
+
+    /// Selects and orders the ALT alleles of a record with `alternates` of
+    /// them: output ALT `j` is input ALT `order[j]`, counted from 0 like
+    /// [`Record::select_alternates`]. ALT alleles left out map to `None`.
+    pub fn select(alternates: usize, order: &[usize]) -> Self {
+        let mut map = vec![None; alternates + 1];
+        map[0] = Some(0);
+        for (j, &i) in order.iter().enumerate() {
+            if let Some(m) = map.get_mut(i + 1) {
+                m.get_or_insert(j + 1);
+            }
+        }
+
+        Self {
+            map,
+            len: order.len() + 1,
+        }
+    }
+
+    /// Maps the ALT alleles `from` onto `to` by exact match.
+    pub fn between<S: AsRef<str>, T: AsRef<str>>(from: &[S], to: &[T]) -> Self {
+        let mut map = vec![Some(0)];
+        for allele in from {
+            map.push(
+                to.iter()
+                    .position(|a| a.as_ref() == allele.as_ref())
+                    .map(|i| i + 1),
+            );
+        }
+
+        Self {
+            map,
+            len: to.len() + 1,
+        }
+    }
+
+    /// Output index of input allele `i`.
+    pub fn allele(&self, i: usize) -> Option<usize> {
+        self.map.get(i).copied().flatten()
+    }
+
+    /// Returns `true` if every input allele keeps its index.
+    pub fn is_identity(&self) -> bool {
+        self.map.len() == self.len && self.map.iter().enumerate().all(|(i, &j)| j == Some(i))
+    }
+
+    /// Renumbers a genotype; alleles without a counterpart become missing.
+    pub fn genotype(&self, genotype: &Genotype) -> Genotype {
+        Genotype {
+            alleles: genotype
+                .alleles
+                .iter()
+                .map(|a| a.and_then(|i| self.allele(i)))
+                .collect(),
+            phased: genotype.phased.clone(),
+        }
+    }
+
+    /// Remaps a comma-separated value declared with `number`.
+    ///
+    /// `G` values are remapped for the ploidy their count implies, up to
+    /// [`MAX_PLOIDY`], in the VCF order of [`genotype_index`]; `A` values of
+    /// ALT alleles mapped from REF become `.`. Values whose count does not
+    /// match the declaration, and other numbers, are returned unchanged.
+    pub fn values(&self, value: &str, number: Number) -> String {
+        if value == "." {
+            return value.to_string();
+        }
+
+        let values: Vec<&str> = value.split(',').collect();
+        let n = self.map.len();
+
+        let source: Vec<Option<usize>> = match number {
+            Number::A if values.len() == n - 1 => (1..self.len)
+                .map(|j| self.source(j).and_then(|i| i.checked_sub(1)))
+                .collect(),
+            Number::R | Number::G if values.len() == n => {
+                (0..self.len).map(|j| self.source(j)).collect()
+            }
+            Number::G => {
+                let Some(ploidy) = (2..=MAX_PLOIDY)
+                    .find(|&p| genotype_count(n, p).is_ok_and(|c| c == values.len()))
+                else {
+                    return value.to_string();
+                };
+                if genotype_count(self.len, ploidy).is_err() {
+                    return value.to_string();
+                }
+
+                genotypes(self.len, ploidy)
+                    .iter()
+                    .map(|genotype| {
+                        let mut alleles = genotype
+                            .iter()
+                            .map(|&j| self.source(j))
+                            .collect::<Option<Vec<usize>>>()?;
+                        alleles.sort_unstable();
+                        genotype_index(&alleles).ok()
+                    })
+                    .collect()
+            }
+            _ => return value.to_string(),
+        };
+
+        source
+            .iter()
+            .map(|i| i.map_or(".", |i| values[i]))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Remaps the `GT`, and INFO and FORMAT values declared with `Number=A`,
+    /// `R` or `G` in `header`, of `record`.
+    ///
+    /// ALT alleles are left to the caller.
+    pub fn apply(&self, header: &Header, record: &mut Record) {
+        for (key, value) in record.info.iter_mut() {
+            if let (Some(d), Some(v)) = (header.info(key), value.as_mut()) {
+                *v = self.values(v, d.number);
+            }
+        }
+
+        let numbers: Vec<Option<Number>> = record
+            .format
+            .iter()
+            .map(|key| header.format(key).map(|d| d.number))
+            .collect();
+
+        for values in record.samples.iter_mut() {
+            for (j, value) in values.iter_mut().enumerate() {
+                if record.format[j] == "GT" {
+                    if let Ok(gt) = value.parse::<Genotype>() {
+                        *value = self.genotype(&gt).to_string();
+                    }
+                } else if let Some(number) = numbers[j] {
+                    *value = self.values(value, number);
+                }
+            }
+        }
+    }
+
+    /// Input allele mapped to output allele `j`.
+    fn source(&self, j: usize) -> Option<usize> {
+        self.map.iter().position(|&m| m == Some(j))
+    }
+}
+
+/// Largest ploidy of the `Number=G` values remapped by [`AlleleRemap::values`].
+pub const MAX_PLOIDY: usize = 8;
+
+/// Index of an unphased genotype, given its allele indices in ascending
+/// order, among the `Number=G` values of the VCF specification: the sum of
+/// `C(alleles[m] + m, m + 1)`. For diploid calls `j/k` this is
+/// `k * (k + 1) / 2 + j`.
+///
+/// Fails with [`Error::GenotypeCountError`] if the index overflows `usize`.
+pub fn genotype_index(alleles: &[usize]) -> Result<usize> {
+    let overflow = || {
+        let count = alleles.iter().max().map_or(0, |a| a.saturating_add(1));
+        Error::GenotypeCountError(count, alleles.len())
+    };
+
+    alleles
+        .iter()
+        .enumerate()
+        .try_fold(0usize, |index, (m, &a)| {
+            let n = a.checked_add(m).ok_or_else(overflow)?;
+            let c = binomial(n, m + 1).ok_or_else(overflow)?;
+            index.checked_add(c).ok_or_else(overflow)
+        })
+}
+
+/// Number of unphased genotypes of `ploidy` over `alleles` alleles,
+/// including REF: `C(alleles + ploidy - 1, ploidy)`.
+///
+/// Fails with [`Error::GenotypeCountError`] if the count overflows `usize`.
+pub fn genotype_count(alleles: usize, ploidy: usize) -> Result<usize> {
+    if alleles == 0 {
+        return Ok(0);
+    }
+
+    (alleles - 1)
+        .checked_add(ploidy)
+        .and_then(|n| binomial(n, ploidy))
+        .ok_or(Error::GenotypeCountError(alleles, ploidy))
+}
+
+/// Genotypes of `ploidy` over `alleles` alleles, as ascending allele
+/// indices, in the order of [`genotype_index`].
+fn genotypes(alleles: usize, ploidy: usize) -> Vec<Vec<usize>> {
+    let mut genotypes = vec![Vec::new()];
+    for _ in 0..ploidy {
+        genotypes = genotypes
+            .into_iter()
+            .flat_map(|g: Vec<usize>| {
+                let first = g.last().copied().unwrap_or(0);
+                (first..alleles).map(move |a| {
+                    let mut g = g.clone();
+                    g.push(a);
+                    g
+                })
+            })
+            .collect();
+    }
+    genotypes.sort_by_key(|g| genotype_index(g).ok());
+
+    genotypes
+}
+
+/// `C(n, k)`, or `None` if it overflows `usize`.
+fn binomial(n: usize, k: usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+
+    // Each step is exact, and its product fits in 128 bits.
+    (0..k.min(n - k)).try_fold(1usize, |c, i| {
+        usize::try_from(c as u128 * (n - i) as u128 / (i + 1) as u128).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_between_1() {
+        let remap = AlleleRemap::between(&["G", "T"], &["T", "C", "G"]);
+
+        assert_eq!(remap.allele(0), Some(0));
+        assert_eq!(remap.allele(1), Some(3));
+        assert_eq!(remap.allele(2), Some(1));
+        assert!(!remap.is_identity());
+        assert!(AlleleRemap::between(&["A"], &["A"]).is_identity());
+    }
+
+    #[test]
+    fn test_values_1() {
+        // Input REF,G,T onto output REF,T,C,G.
+        let remap = AlleleRemap::between(&["G", "T"], &["T", "C", "G"]);
+
+        assert_eq!(remap.values("0.1,0.2", Number::A), "0.2,.,0.1");
+        assert_eq!(remap.values("5,1,2", Number::R), "5,2,.,1");
+        assert_eq!(remap.values("5,1", Number::R), "5,1");
+        assert_eq!(remap.values("3", Number::Count(1)), "3");
+        assert_eq!(remap.values(".", Number::A), ".");
+
+        // 0/0 0/1 1/1 0/2 1/2 2/2 with 1=G, 2=T.
+        assert_eq!(
+            remap.values("0,10,20,30,40,50", Number::G),
+            "0,30,50,.,.,.,10,40,.,20"
+        );
+    }
+
+    #[test]
+    fn test_genotype_1() {
+        let remap = AlleleRemap::retain(&[false, true]);
+        let gt: Genotype = "1|2".parse().unwrap();
+
+        assert_eq!(remap.genotype(&gt).to_string(), ".|1");
+        assert_eq!(remap.values("0,1,2,3,4,5", Number::G), "0,3,5");
+    }
+
+    #[test]
+    fn test_select_1() {
+        // REF,A,B,C onto REF,C,A.
+        let remap = AlleleRemap::select(3, &[2, 0]);
+
+        assert_eq!(remap.allele(1), Some(2));
+        assert_eq!(remap.allele(2), None);
+        assert_eq!(remap.allele(3), Some(1));
+        assert_eq!(remap.values("1,2,3", Number::A), "3,1");
+        assert_eq!(remap.values("0,1,2,3", Number::R), "0,3,1");
+        assert!(AlleleRemap::select(2, &[0, 1]).is_identity());
+
+        // Diploid PL over REF,A,B,C: 00 01 11 02 12 22 03 13 23 33.
+        let pl = "0,1,2,3,4,5,6,7,8,9";
+        assert_eq!(remap.values(pl, Number::G), "0,6,9,1,7,2");
+    }
+
+    #[test]
+    fn test_genotype_index_1() {
+        assert_eq!(genotype_index(&[0, 0]).unwrap(), 0);
+        assert_eq!(genotype_index(&[1, 2]).unwrap(), 4);
+        assert_eq!(genotype_index(&[0, 0, 1]).unwrap(), 1);
+        assert_eq!(genotype_index(&[1, 1, 1]).unwrap(), 3);
+        assert_eq!(genotype_count(3, 2).unwrap(), 6);
+        assert_eq!(genotype_count(2, 3).unwrap(), 4);
+
+        let indices: Vec<usize> = genotypes(3, 3)
+            .iter()
+            .map(|g| genotype_index(g).unwrap())
+            .collect();
+        assert_eq!(indices, (0..10).collect::<Vec<_>>());
+
+        // Triploid over REF,A onto REF,A,B with B unseen: 000 001 011 111.
+        let remap = AlleleRemap::new(vec![Some(0), Some(1)], 3);
+        assert_eq!(remap.values("0,1,2,3", Number::G), "0,1,2,3,.,.,.,.,.,.");
+    }
+
+    #[test]
+    fn test_genotype_index_2() {
+        assert_eq!(
+            genotype_count(1 << 31, 2).unwrap(),
+            (1 << 30) * ((1 << 31) + 1)
+        );
+        assert_eq!(genotype_count(usize::MAX, 1).unwrap(), usize::MAX);
+        for (alleles, ploidy) in [(1 << 40, 2), (usize::MAX, 2), (2, usize::MAX)] {
+            assert!(matches!(
+                genotype_count(alleles, ploidy),
+                Err(Error::GenotypeCountError(..))
+            ));
+        }
+        assert!(genotype_index(&[usize::MAX, usize::MAX]).is_err());
+        assert!(genotype_index(&[1 << 40; 8]).is_err());
+
+        // Values of too many alleles to enumerate are left unchanged.
+        let remap = AlleleRemap::new(vec![Some(0), Some(1)], usize::MAX);
+        assert_eq!(remap.values("0,1,2", Number::G), "0,1,2");
+    }
+}
@@ -0,0 +1,385 @@
+//! Transfer of IDs and INFO fields from an annotation VCF such as dbSNP or
+//! ClinVar, like `bcftools annotate -a`.
+//!
+//! Records match annotation records with an equal [`VariantKey`], so the two
+//! files may pad and trim their alleles differently. Each ALT matches on its
+//! own: `Number=A` and `R` values are taken per allele, other values from
+//! the first matching ALT.
+
+use crate::bgzf;
+use crate::errors::{Error, Result};
+use crate::header::{Header, Number};
+use crate::index::{find_index, Index};
+use crate::key::VariantKey;
+use crate::pipeline::skip;
+use crate::reader::Reader;
+use crate::record::Record;
+use crate::regions::{RegionQuery, RegionSet};
+use crate::summary::{RunSummary, Timer};
+use crate::writer::Writer;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Positions of the annotation file loaded at once.
+const WINDOW: u64 = 100_000;
+
+/// What [`Annotator`] copies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotateOptions {
+    /// Copies the IDs of matching annotation records.
+    pub ids: bool,
+    /// INFO keys to copy.
+    pub info: Vec<String>,
+    /// Replaces IDs and INFO values already set; otherwise only missing
+    /// ones are filled.
+    pub overwrite: bool,
+}
+
+impl Default for AnnotateOptions {
+    fn default() -> Self {
+        Self {
+            ids: true,
+            info: Vec::new(),
+            overwrite: false,
+        }
+    }
+}
+
+/// Values of one ALT allele of an annotation record.
+struct Annotation {
+    ids: Vec<String>,
+    /// Value of each key of [`AnnotateOptions::info`]; `Some(None)` is a
+    /// flag, and `Number=R` values hold the REF and this ALT.
+    values: Vec<Option<Option<String>>>,
+}
+
+/// Annotates records from a BGZF-compressed and indexed annotation VCF.
+///
+/// The annotation file is read through its index in windows around the
+/// annotated records, so sorted input reads it once.
+pub struct Annotator {
+    /// Moved into each query, and back once it ends.
+    reader: Option<Reader<bgzf::Reader<File>>>,
+    index: Index,
+    header: Header,
+    options: AnnotateOptions,
+    /// Loaded window: CHROM and 1-based inclusive range of key positions.
+    window: Option<(String, u64, u64)>,
+    annotations: HashMap<VariantKey, Annotation>,
+}
+
+impl Annotator {
+    /// Opens the annotation VCF at `path`, which needs a `.tbi` or `.csi`
+    /// index next to it.
+    pub fn from_path<P: AsRef<Path>>(path: P, options: AnnotateOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let index_path =
+            find_index(path).ok_or_else(|| Error::MissingIndexError(path.display().to_string()))?;
+        let index = Index::from_path(index_path)?;
+        let reader = Reader::new(bgzf::Reader::new(File::open(path)?))?;
+
+        Ok(Self {
+            header: reader.header().clone(),
+            reader: Some(reader),
+            index,
+            options,
+            window: None,
+            annotations: HashMap::new(),
+        })
+    }
+
+    /// Header of the annotation file.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Adds the annotation file's definitions of the copied INFO keys that
+    /// are missing from `header`.
+    pub fn update_header(&self, header: &mut Header) {
+        for key in &self.options.info {
+            if let (None, Some(d)) = (header.info(key), self.header.info(key)) {
+                header.infos.push(d.clone());
+            }
+        }
+    }
+
+    /// Copies IDs and INFO values from matching annotation records; returns
+    /// `true` if `record` changed.
+    pub fn annotate(&mut self, record: &mut Record) -> Result<bool> {
+        let keys = VariantKey::from_record(record);
+        for key in &keys {
+            let loaded = self.window.as_ref().is_some_and(|(chrom, start, end)| {
+                *chrom == key.chrom && (*start..=*end).contains(&key.position)
+            });
+            if !loaded {
+                self.load(&key.chrom, key.position)?;
+            }
+        }
+
+        let matches: Vec<Option<&Annotation>> =
+            keys.iter().map(|k| self.annotations.get(k)).collect();
+        let mut changed = false;
+
+        if self.options.ids && (record.ids.is_empty() || self.options.overwrite) {
+            let mut ids: Vec<String> = Vec::new();
+            for id in matches.iter().flatten().flat_map(|a| &a.ids) {
+                if !ids.contains(id) {
+                    ids.push(id.clone());
+                }
+            }
+            if !ids.is_empty() && ids != record.ids {
+                record.ids = ids;
+                changed = true;
+            }
+        }
+
+        for (i, key) in self.options.info.iter().enumerate() {
+            if record.has_info(key) && !self.options.overwrite {
+                continue;
+            }
+
+            let values: Vec<Option<&Option<String>>> = matches
+                .iter()
+                .map(|a| a.and_then(|a| a.values[i].as_ref()))
+                .collect();
+            let Some(first) = values.iter().flatten().next() else {
+                continue;
+            };
+
+            fn text<'v>(v: &Option<&'v Option<String>>) -> Option<&'v str> {
+                v.and_then(|v| v.as_deref())
+            }
+            let value = match self.header.info(key).map(|d| d.number) {
+                Some(Number::A) => Some(
+                    values
+                        .iter()
+                        .map(|v| text(v).unwrap_or("."))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                Some(Number::R) => {
+                    let reference = first.as_deref().and_then(|v| v.split_once(','));
+                    let mut parts = vec![reference.map_or(".", |(r, _)| r)];
+                    parts.extend(values.iter().map(|v| {
+                        text(v)
+                            .and_then(|v| v.split_once(','))
+                            .map_or(".", |(_, a)| a)
+                    }));
+                    Some(parts.join(","))
+                }
+                _ => (*first).clone(),
+            };
+
+            if record.info.iter().all(|(k, v)| k != key || *v != value) {
+                record.set_info(key, value);
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Loads the annotations with keys in `pos..pos + WINDOW` on `chrom`.
+    fn load(&mut self, chrom: &str, pos: u64) -> Result<()> {
+        let end = pos + WINDOW - 1;
+        self.window = Some((chrom.to_string(), pos, end));
+        self.annotations.clear();
+
+        let mut regions = RegionSet::new();
+        regions.insert(chrom, pos, end);
+        // The reader is only missing if a query panicked.
+        let reader = self
+            .reader
+            .take()
+            .ok_or_else(|| io::Error::other("annotation reader lost in an interrupted query"))?;
+        let mut query = RegionQuery::new(reader, &self.index, regions);
+
+        let mut result = Ok(());
+        for record in query.by_ref() {
+            match record {
+                Ok(record) => self.insert(&record, chrom, pos, end),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.reader = Some(query.into_inner());
+
+        result
+    }
+
+    /// Adds the ALT alleles of `record` with keys in the window.
+    fn insert(&mut self, record: &Record, chrom: &str, start: u64, end: u64) {
+        let n = record.alternates.len();
+        for (j, key) in VariantKey::from_record(record).into_iter().enumerate() {
+            if key.chrom != chrom || !(start..=end).contains(&key.position) {
+                continue;
+            }
+
+            let values = self
+                .options
+                .info
+                .iter()
+                .map(|k| {
+                    let value = record.info.iter().find(|(key, _)| key == k)?.1.as_deref();
+                    let Some(value) = value else {
+                        return Some(None);
+                    };
+                    let parts: Vec<&str> = value.split(',').collect();
+                    match self.header.info(k).map(|d| d.number) {
+                        Some(Number::A) => parts
+                            .get(j)
+                            .filter(|_| parts.len() == n)
+                            .map(|v| Some(v.to_string())),
+                        Some(Number::R) if parts.len() == n + 1 => {
+                            Some(Some(format!("{},{}", parts[0], parts[j + 1])))
+                        }
+                        Some(Number::R) => None,
+                        _ => Some(Some(value.to_string())),
+                    }
+                })
+                .collect();
+
+            self.annotations.entry(key).or_insert(Annotation {
+                ids: record.ids.clone(),
+                values,
+            });
+        }
+    }
+}
+
+/// Annotates every record of a stream with `annotator` and writes the
+/// records after `header`, extended with the copied INFO definitions.
+pub fn annotate_stream<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    annotator: &mut Annotator,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("annotate_stream");
+
+    let mut header = header.clone();
+    annotator.update_header(&mut header);
+    writer.write_header(&header)?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        let mut record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        if annotator.annotate(&mut record)? {
+            summary.modified += 1;
+        }
+
+        writer.write_record(&record)?;
+        summary.records_out += 1;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCategory;
+    use crate::index::tests::tbi_bytes;
+    use crate::index::Chunk;
+
+    const ANNOTATION: &str = "##fileformat=VCFv4.2\n\
+        ##INFO=<ID=CLNSIG,Number=.,Type=String,Description=\"Significance\">\n\
+        ##INFO=<ID=CAF,Number=R,Type=Float,Description=\"Allele frequencies\">\n\
+        ##INFO=<ID=AC,Number=A,Type=Integer,Description=\"Allele count\">\n\
+        ##INFO=<ID=COMMON,Number=0,Type=Flag,Description=\"Common\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t100\trs1\tA\tG,T\t.\t.\tCAF=0.7,0.2,0.1;AC=20,10;COMMON\n\
+        1\t200\trs2\tCAT\tC\t.\t.\tCLNSIG=Pathogenic\n\
+        2\t50\trs3\tG\tA\t.\t.\tCLNSIG=Benign\n";
+
+    /// Writes `ANNOTATION` and a TBI index with one chunk per contig.
+    fn write_indexed(path: &Path) {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(ANNOTATION.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = Reader::new(bgzf::Reader::new(std::io::Cursor::new(&bytes))).unwrap();
+        let start = reader.voffset();
+        for _ in 0..2 {
+            reader.next().unwrap().unwrap();
+        }
+        let middle = reader.voffset();
+        reader.next().unwrap().unwrap();
+        let end = reader.voffset();
+
+        std::fs::write(path, &bytes).unwrap();
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".tbi");
+        let chunks = [Chunk { start, end: middle }, Chunk { start: middle, end }];
+        std::fs::write(index_path, tbi_bytes(&["1", "2"], &chunks)).unwrap();
+    }
+
+    #[test]
+    fn test_annotate_1() {
+        let path =
+            std::env::temp_dir().join(format!("vcf-lib-annotate-{}.vcf.gz", std::process::id()));
+        write_indexed(&path);
+
+        let options = AnnotateOptions {
+            info: ["CLNSIG", "CAF", "AC", "COMMON"].map(String::from).to_vec(),
+            ..AnnotateOptions::default()
+        };
+        let mut annotator = Annotator::from_path(&path, options).unwrap();
+
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t100\t.\tA\tT,C\t.\t.\t.\n\
+            1\t199\t.\tGCAT\tGC\t.\t.\tCLNSIG=Keep\n\
+            1\t300\t.\tA\tT\t.\t.\t.\n\
+            2\t50\tmine\tG\tA\t.\t.\t.\n";
+        let reader = Reader::new(vcf.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut writer = Writer::new(Vec::new());
+        let summary = annotate_stream(reader, &header, &mut writer, &mut annotator).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        annotator.reader = None;
+        annotator.window = None;
+        let mut record: Record = "1\t100\t.\tA\tT\t.\t.\t.".parse().unwrap();
+        let err = annotator.annotate(&mut record).unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Io);
+
+        std::fs::remove_file(&path).unwrap();
+        let mut index_path = path.into_os_string();
+        index_path.push(".tbi");
+        std::fs::remove_file(index_path).unwrap();
+
+        let body: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            body,
+            vec![
+                "1\t100\trs1\tA\tT,C\t.\t.\tCAF=0.7,0.1,.;AC=10,.;COMMON",
+                "1\t199\trs2\tGCAT\tGC\t.\t.\tCLNSIG=Keep",
+                "1\t300\t.\tA\tT\t.\t.\t.",
+                "2\t50\tmine\tG\tA\t.\t.\tCLNSIG=Benign",
+            ]
+        );
+        assert!(text.contains("##INFO=<ID=CAF,Number=R,"));
+        assert_eq!((summary.records_in, summary.modified), (4, 3));
+        assert!(Annotator::from_path("missing.vcf.gz", AnnotateOptions::default()).is_err());
+    }
+}
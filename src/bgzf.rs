@@ -0,0 +1,487 @@
+//! Blocked GNU Zip Format (BGZF) as used by bgzip and tabix.
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+/// The empty block that terminates every well-formed BGZF file.
+pub const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Largest amount of uncompressed data written to one block.
+pub const MAX_BLOCK_DATA_SIZE: usize = 0xff00;
+
+/// Size of the gzip header of a block, including the BGZF subfield.
+pub const HEADER_SIZE: usize = 18;
+const FOOTER_SIZE: usize = 8;
+/// Largest uncompressed size of a block allowed by the format.
+const MAX_ISIZE: usize = 0x10000;
+
+/// Combines a compressed block offset and an offset within the uncompressed block.
+pub fn voffset(block_offset: u64, within: u16) -> u64 {
+    (block_offset << 16) | within as u64
+}
+
+/// Splits a virtual offset into its compressed block offset and offset within the block.
+pub fn split_voffset(voffset: u64) -> (u64, u16) {
+    (voffset >> 16, (voffset & 0xffff) as u16)
+}
+
+/// Returns `true` if `bytes` start with a gzip header carrying the BGZF `BC` subfield.
+pub fn is_bgzf(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_SIZE
+        && bytes[0..4] == [0x1f, 0x8b, 0x08, 0x04]
+        && bytes[12..14] == [b'B', b'C']
+}
+
+/// Returns `true` if `bytes` start with a gzip header.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0..2] == [0x1f, 0x8b]
+}
+
+/// Returns the size of the compressed block starting with `header`, which
+/// holds at least [`HEADER_SIZE`] bytes.
+pub fn block_size(header: &[u8]) -> io::Result<usize> {
+    if !is_bgzf(header) {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid BGZF header",
+        ))?
+    }
+
+    let block_size = u16::from_le_bytes([header[16], header[17]]) as usize + 1;
+    if block_size < HEADER_SIZE + FOOTER_SIZE {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid BGZF block size",
+        ))?
+    }
+
+    Ok(block_size)
+}
+
+/// Reads one whole block, returning its compressed bytes, or `None` at a clean EOF.
+pub fn read_raw_block<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_SIZE];
+
+    let n = read_full(reader, &mut header)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if n < HEADER_SIZE {
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated BGZF header",
+        ))?
+    }
+    let block_size = block_size(&header)?;
+
+    let mut block = header.to_vec();
+    block.resize(block_size, 0);
+    if read_full(reader, &mut block[HEADER_SIZE..])? < block_size - HEADER_SIZE {
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated BGZF block",
+        ))?
+    }
+
+    Ok(Some(block))
+}
+
+/// Decompresses a block obtained with [`read_raw_block`], verifying its CRC32.
+///
+/// A block declaring, or inflating to, more than 64 KiB is rejected as corrupt.
+pub fn inflate_block(block: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    let footer = &block[block.len() - FOOTER_SIZE..];
+    let crc32 = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+    let isize = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]) as usize;
+    if isize > MAX_ISIZE {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "BGZF block size exceeds 64 KiB",
+        ))?
+    }
+
+    out.clear();
+    out.reserve(isize);
+    DeflateDecoder::new(&block[HEADER_SIZE..block.len() - FOOTER_SIZE])
+        .take(isize as u64 + 1)
+        .read_to_end(out)?;
+
+    let mut crc = Crc::new();
+    crc.update(out);
+    if out.len() != isize || crc.sum() != crc32 {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "BGZF block checksum mismatch",
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Compresses `data` (at most [`MAX_BLOCK_DATA_SIZE`] bytes) into one block.
+pub fn deflate_block(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len()), level);
+    encoder.write_all(data)?;
+    let cdata = encoder.finish()?;
+
+    let block_size = HEADER_SIZE + cdata.len() + FOOTER_SIZE;
+    if block_size > 0x10000 {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "BGZF block too large",
+        ))?
+    }
+
+    let mut crc = Crc::new();
+    crc.update(data);
+
+    let mut block = Vec::with_capacity(block_size);
+    block.extend_from_slice(&EOF_BLOCK[..16]);
+    block.extend_from_slice(&((block_size - 1) as u16).to_le_bytes());
+    block.extend_from_slice(&cdata);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+    Ok(block)
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(n)
+}
+
+/// Decompressing reader over a BGZF stream.
+///
+/// The inner reader is expected to be positioned at the start of the stream,
+/// which is compressed offset `0` for [`Reader::voffset`].
+pub struct Reader<R> {
+    inner: R,
+    data: Vec<u8>,
+    pos: usize,
+    block_offset: u64,
+    next_block_offset: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            data: Vec::new(),
+            pos: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+        }
+    }
+
+    /// Virtual offset of the next byte to be read.
+    pub fn voffset(&self) -> u64 {
+        if self.pos < self.data.len() {
+            voffset(self.block_offset, self.pos as u16)
+        } else {
+            voffset(self.next_block_offset, 0)
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Loads the next non-empty block; returns `false` at EOF.
+    fn read_block(&mut self) -> io::Result<bool> {
+        loop {
+            self.block_offset = self.next_block_offset;
+            match read_raw_block(&mut self.inner)? {
+                None => return Ok(false),
+                Some(block) => {
+                    self.next_block_offset += block.len() as u64;
+                    inflate_block(&block, &mut self.data)?;
+                    self.pos = 0;
+                    if !self.data.is_empty() {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Moves to a virtual offset previously obtained from [`Reader::voffset`],
+    /// [`Writer::voffset`] or an index.
+    pub fn seek_voffset(&mut self, voffset: u64) -> io::Result<()> {
+        let (block_offset, within) = split_voffset(voffset);
+
+        self.inner.seek(SeekFrom::Start(block_offset))?;
+        self.block_offset = block_offset;
+        self.next_block_offset = block_offset;
+        self.data.clear();
+        self.pos = 0;
+
+        if within > 0 {
+            if !self.read_block()? || self.block_offset != block_offset {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "virtual offset points past the end of a block",
+                ))?
+            }
+            if within as usize > self.data.len() {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "virtual offset points past the end of a block",
+                ))?
+            }
+            self.pos = within as usize;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.data.len() && !self.read_block()? {
+            return Ok(&[]);
+        }
+
+        Ok(&self.data[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.data.len());
+    }
+}
+
+/// Compressing writer producing a BGZF stream.
+///
+/// Call [`Writer::finish`] to write the EOF block; dropping the writer does so
+/// on a best-effort basis.
+pub struct Writer<W: Write> {
+    inner: Option<W>,
+    buf: Vec<u8>,
+    level: Compression,
+    block_offset: u64,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_level(inner, Compression::default())
+    }
+
+    pub fn with_level(inner: W, level: Compression) -> Self {
+        Self {
+            inner: Some(inner),
+            buf: Vec::with_capacity(MAX_BLOCK_DATA_SIZE),
+            level,
+            block_offset: 0,
+        }
+    }
+
+    /// Virtual offset at which the next written byte will be found.
+    pub fn voffset(&self) -> u64 {
+        voffset(self.block_offset, self.buf.len() as u16)
+    }
+
+    /// Ends the current block so that the next byte starts a new one.
+    ///
+    /// Useful to align records with block boundaries, e.g. at chromosome changes.
+    pub fn flush_block(&mut self) -> io::Result<()> {
+        self.write_block()
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let block = deflate_block(&self.buf, self.level)?;
+        self.inner.as_mut().unwrap().write_all(&block)?;
+        self.block_offset += block.len() as u64;
+        self.buf.clear();
+
+        Ok(())
+    }
+
+    /// Flushes pending data and writes the EOF block, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        let mut inner = self.inner.take().unwrap();
+        inner.write_all(&EOF_BLOCK)?;
+        inner.flush()?;
+
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_BLOCK_DATA_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+
+        if self.buf.len() == MAX_BLOCK_DATA_SIZE {
+            self.write_block()?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_block();
+            let _ = self.inner.as_mut().unwrap().write_all(&EOF_BLOCK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_1() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let bytes = compress(&data);
+
+        assert!(is_bgzf(&bytes));
+        assert!(bytes.ends_with(&EOF_BLOCK));
+
+        let mut out = Vec::new();
+        Reader::new(&bytes[..]).read_to_end(&mut out).unwrap();
+
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_roundtrip_2() {
+        let bytes = compress(b"");
+
+        assert_eq!(bytes, EOF_BLOCK);
+
+        let mut out = Vec::new();
+        Reader::new(&bytes[..]).read_to_end(&mut out).unwrap();
+
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_voffset_1() {
+        let mut writer = Writer::new(Vec::new());
+        let mut offsets = Vec::new();
+
+        for i in 0..3 {
+            offsets.push(writer.voffset());
+            writeln!(writer, "line {}", i).unwrap();
+            if i == 1 {
+                writer.flush_block().unwrap();
+            }
+        }
+        let bytes = writer.finish().unwrap();
+
+        assert_eq!(split_voffset(offsets[1]), (0, 7));
+        assert_eq!(split_voffset(offsets[2]).1, 0);
+        assert!(split_voffset(offsets[2]).0 > 0);
+
+        let mut reader = Reader::new(io::Cursor::new(bytes));
+        let mut line = String::new();
+
+        for (i, &offset) in offsets.iter().enumerate() {
+            assert_eq!(reader.voffset(), offset);
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("line {}\n", i));
+        }
+
+        reader.seek_voffset(offsets[1]).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+
+        assert_eq!(line, "line 1\n");
+
+        reader.seek_voffset(offsets[2]).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+
+        assert_eq!(line, "line 2\n");
+        assert!(reader.seek_voffset(voffset(0, 100)).is_err());
+    }
+
+    #[test]
+    fn test_read_raw_block_1() {
+        let bytes = compress(b"hello");
+        let mut reader = &bytes[..bytes.len() - EOF_BLOCK.len() - 3];
+
+        let err = read_raw_block(&mut reader).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_inflate_block_1() {
+        let mut block = deflate_block(b"hello", Compression::default()).unwrap();
+        let n = block.len();
+        block[n - 8] ^= 0xff;
+
+        assert!(inflate_block(&block, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_inflate_block_2() {
+        let mut block = deflate_block(b"hello", Compression::default()).unwrap();
+        let n = block.len();
+        block[n - 1] = 0xff;
+
+        let err = inflate_block(&block, &mut Vec::new()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("64 KiB"));
+
+        let data = vec![b'A'; 1000];
+        let mut block = deflate_block(&data, Compression::default()).unwrap();
+        let n = block.len();
+        block[n - 4..].copy_from_slice(&10u32.to_le_bytes());
+
+        assert!(inflate_block(&block, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Command-line driver for the library's stream operations, for comparing
+//! its behavior with `bcftools norm`, `view` and `stats`.
+//!
+//! Input is a plain, gzip or BGZF file, or plain VCF on standard input when
+//! no file or `-` is given. Records go to standard output or `-o FILE`, and
+//! the run summary to standard error as JSON; the exit status is 1 when a
+//! record was skipped.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process::ExitCode;
+use vcf_lib::decompose::MnvPolicy;
+use vcf_lib::errors::Result;
+use vcf_lib::pipeline::{decompose_stream, normalize_stream, validate};
+use vcf_lib::reader::Reader;
+use vcf_lib::record::NormalizeOptions;
+use vcf_lib::reference::MemoryReference;
+use vcf_lib::stats::stats;
+use vcf_lib::summary::RunSummary;
+use vcf_lib::writer::Writer;
+use vcf_lib::VariantType;
+
+const USAGE: &str = "\
+usage: vcf-tool <command> [options] [FILE]
+
+commands:
+  normalize [-f FASTA] [-o OUT]  trim alleles; left-align indels with -f
+  decompose [-o OUT]             split MNVs into SNVs; output is not
+                                 re-sorted, so split MNVs may come after
+                                 the records that follow them
+  validate                       check records against the header version
+  stats                          print counts of variants and genotypes
+";
+
+enum Command {
+    Normalize,
+    Decompose,
+    Validate,
+    Stats,
+}
+
+struct Args {
+    command: Command,
+    input: Option<String>,
+    output: Option<String>,
+    fasta: Option<String>,
+}
+
+/// Parses the arguments after the program name; `Err` holds the message to
+/// print before the usage, which is empty for `--help`.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> std::result::Result<Args, String> {
+    let mut args = args.into_iter();
+    let command = match args.next().ok_or("missing command")?.as_str() {
+        "normalize" => Command::Normalize,
+        "decompose" => Command::Decompose,
+        "validate" => Command::Validate,
+        "stats" => Command::Stats,
+        "-h" | "--help" => return Err(String::new()),
+        command => return Err(format!("unknown command {}", command)),
+    };
+    let mut parsed = Args {
+        command,
+        input: None,
+        output: None,
+        fasta: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "-o" | "--output" => &mut parsed.output,
+            "-f" | "--fasta" => &mut parsed.fasta,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {}", arg));
+            }
+            _ => {
+                if parsed.input.replace(arg).is_some() {
+                    return Err("more than one input file".to_string());
+                }
+                continue;
+            }
+        };
+        *slot = Some(args.next().ok_or(format!("{} needs a value", arg))?);
+    }
+
+    Ok(parsed)
+}
+
+fn open(input: Option<&str>) -> Result<Reader<Box<dyn BufRead>>> {
+    match input {
+        None | Some("-") => Reader::new(Box::new(io::stdin().lock()) as Box<dyn BufRead>),
+        Some(path) => Reader::from_path(path),
+    }
+}
+
+fn create(output: Option<&str>) -> Result<Writer<Box<dyn Write>>> {
+    let inner: Box<dyn Write> = match output {
+        None | Some("-") => Box::new(BufWriter::new(io::stdout().lock())),
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+    };
+
+    Ok(Writer::new(inner))
+}
+
+/// Reads every sequence of a plain FASTA file into memory.
+fn read_fasta(path: &str) -> Result<MemoryReference> {
+    let mut reference = MemoryReference::new();
+    let text = fs::read_to_string(path)?;
+
+    let mut insert = |name: Option<&str>, sequence: &mut String| {
+        if let Some(name) = name {
+            reference.insert(name, sequence);
+        }
+        sequence.clear();
+    };
+
+    let mut name = None;
+    let mut sequence = String::new();
+    for line in text.lines() {
+        match line.strip_prefix('>') {
+            Some(header) => {
+                insert(name, &mut sequence);
+                name = header.split_whitespace().next();
+            }
+            None => sequence.push_str(line.trim_end()),
+        }
+    }
+    insert(name, &mut sequence);
+
+    Ok(reference)
+}
+
+fn print_stats(args: &Args) -> Result<()> {
+    let reader = open(args.input.as_deref())?;
+    let header = reader.header().clone();
+    let stats = stats(reader, &header)?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let rows = [
+        ("records", stats.records),
+        ("multiallelic", stats.multiallelic),
+        ("snvs", stats.variant_count(VariantType::SNV)),
+        ("mnvs", stats.variant_count(VariantType::MNV)),
+        ("insertions", stats.insertions),
+        ("deletions", stats.deletions),
+        ("complex_indels", stats.complex_indels),
+        ("symbolic", stats.symbolic),
+        ("other", stats.other),
+        ("transitions", stats.transitions),
+        ("transversions", stats.transversions),
+        ("singletons", stats.singletons),
+    ];
+    for (name, count) in rows {
+        writeln!(out, "SN\t{}\t{}", name, count)?;
+    }
+    if let Some(ts_tv) = stats.ts_tv() {
+        writeln!(out, "SN\tts_tv\t{:.3}", ts_tv)?;
+    }
+    for (length, count) in &stats.indel_lengths {
+        writeln!(out, "IDD\t{}\t{}", length, count)?;
+    }
+    for (name, sample) in stats.samples.iter().zip(&stats.per_sample) {
+        writeln!(
+            out,
+            "PSC\t{}\t{}\t{}\t{}\t{}\t{}",
+            name, sample.hom_ref, sample.het, sample.hom_alt, sample.missing, sample.singletons
+        )?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Runs the command and returns its summary, if it has one.
+fn run(args: &Args) -> Result<Option<RunSummary>> {
+    let summary = match args.command {
+        Command::Normalize => {
+            let reference = args.fasta.as_deref().map(read_fasta).transpose()?;
+            let reader = open(args.input.as_deref())?;
+            let header = reader.header().clone();
+            let mut writer = create(args.output.as_deref())?;
+            let summary = normalize_stream(
+                reader,
+                &header,
+                &mut writer,
+                reference.as_ref().map(|r| r as _),
+                &NormalizeOptions::default(),
+            )?;
+            writer.flush()?;
+            summary
+        }
+        Command::Decompose => {
+            let reader = open(args.input.as_deref())?;
+            let header = reader.header().clone();
+            let mut writer = create(args.output.as_deref())?;
+            let summary = decompose_stream(reader, &header, &mut writer, MnvPolicy::Split)?;
+            writer.flush()?;
+            summary
+        }
+        Command::Validate => {
+            let reader = open(args.input.as_deref())?;
+            let header = reader.header().clone();
+            validate(reader, &header)?
+        }
+        Command::Stats => {
+            print_stats(args)?;
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(summary))
+}
+
+/// Parses the arguments after the program name and runs the command,
+/// returning the exit status: 2 for bad arguments, 1 for errors and skipped
+/// records.
+fn status<I: IntoIterator<Item = String>>(args: I) -> u8 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("vcf-tool: {}", message);
+            }
+            eprint!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    match run(&args) {
+        Ok(Some(summary)) => {
+            eprintln!("{}", summary.to_json());
+            u8::from(summary.skipped > 0)
+        }
+        Ok(None) => 0,
+        Err(e) => {
+            eprintln!("vcf-tool: {}", e);
+            1
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    ExitCode::from(status(std::env::args().skip(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t10\ta\tAC\tGT\t.\t.\t.\n\
+        1\t11\tb\tC\tA\t.\t.\t.\n";
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vcf-tool-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_parse_args_1() {
+        let parsed = parse_args(args(&[
+            "normalize",
+            "-f",
+            "ref.fa",
+            "in.vcf",
+            "--output",
+            "o",
+        ]));
+        let parsed = parsed.unwrap();
+        assert!(matches!(parsed.command, Command::Normalize));
+        assert_eq!(parsed.input.as_deref(), Some("in.vcf"));
+        assert_eq!(parsed.output.as_deref(), Some("o"));
+        assert_eq!(parsed.fasta.as_deref(), Some("ref.fa"));
+
+        let parsed = parse_args(args(&["stats", "-"])).unwrap();
+        assert!(matches!(parsed.command, Command::Stats));
+        assert_eq!(parsed.input.as_deref(), Some("-"));
+
+        for (bad, message) in [
+            (&[][..], "missing command"),
+            (&["merge"][..], "unknown command merge"),
+            (&["validate", "-x"][..], "unknown option -x"),
+            (&["decompose", "-o"][..], "-o needs a value"),
+            (&["validate", "a", "b"][..], "more than one input file"),
+            (&["--help"][..], ""),
+            (&["stats", "-h"][..], ""),
+        ] {
+            assert_eq!(parse_args(args(bad)).err().as_deref(), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_status_1() {
+        let input = temp_path("in.vcf");
+        let output = temp_path("out.vcf");
+        let invalid = temp_path("invalid.vcf");
+        fs::write(&input, VCF).unwrap();
+        fs::write(&invalid, format!("{}1\t12\tc\tZ\tA\t.\t.\t.\n", VCF)).unwrap();
+        let (input_arg, output_arg) = (input.to_str().unwrap(), output.to_str().unwrap());
+
+        assert_eq!(status(args(&[])), 2);
+        assert_eq!(status(args(&["--help"])), 2);
+        assert_eq!(status(args(&["validate", input_arg])), 0);
+        assert_eq!(status(args(&["validate", invalid.to_str().unwrap()])), 1);
+        assert_eq!(status(args(&["validate", "/nonexistent/in.vcf"])), 1);
+
+        assert_eq!(status(args(&["decompose", input_arg, "-o", output_arg])), 0);
+        let positions: Vec<u64> = Reader::from_path(&output)
+            .unwrap()
+            .map(|r| r.unwrap().pos)
+            .collect();
+        // The split MNV is not re-sorted with the record after it.
+        assert_eq!(positions, vec![10, 11, 11]);
+
+        for path in [input, output, invalid] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Programmatic construction of records.
+//!
+//! ```
+//! use vcf_lib::builder::RecordBuilder;
+//!
+//! let record = RecordBuilder::new()
+//!     .chrom("1")
+//!     .pos(1000)
+//!     .ref_allele("A")
+//!     .alt("T")
+//!     .info("DP", 30)
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(record.to_string(), "1\t1000\t.\tA\tT\t.\t.\tDP=30");
+//! ```
+
+use crate::errors::{Error, Result};
+use crate::header::{Header, Number, ValueType};
+use crate::record::Record;
+use crate::validation::ValidationPolicy;
+use std::fmt::Display;
+
+/// Builder of a [`Record`] checked by [`RecordBuilder::build`].
+///
+/// `build` fails if CHROM, POS or REF is missing, or if an allele is
+/// rejected by the validation policy. With [`RecordBuilder::header`], INFO
+/// and FORMAT keys must also be declared, with values of the declared type
+/// and, for fixed, `A` and `R` numbers, count.
+#[derive(Debug, Clone)]
+pub struct RecordBuilder<'h> {
+    chrom: Option<String>,
+    pos: Option<u64>,
+    reference: Option<String>,
+    record: Record,
+    policy: ValidationPolicy,
+    header: Option<&'h Header>,
+}
+
+impl<'h> RecordBuilder<'h> {
+    pub fn new() -> Self {
+        Self {
+            chrom: None,
+            pos: None,
+            reference: None,
+            record: Record::new("", 0, "", &[]),
+            policy: ValidationPolicy::default(),
+            header: None,
+        }
+    }
+
+    pub fn chrom(mut self, chrom: &str) -> Self {
+        self.chrom = Some(chrom.to_string());
+        self
+    }
+
+    pub fn pos(mut self, pos: u64) -> Self {
+        self.pos = Some(pos);
+        self
+    }
+
+    /// Adds an ID.
+    pub fn id(mut self, id: &str) -> Self {
+        self.record.ids.push(id.to_string());
+        self
+    }
+
+    pub fn ref_allele(mut self, reference: &str) -> Self {
+        self.reference = Some(reference.to_string());
+        self
+    }
+
+    /// Adds an ALT allele.
+    pub fn alt(mut self, alternate: &str) -> Self {
+        self.record.alternates.push(alternate.to_string());
+        self
+    }
+
+    pub fn qual(mut self, qual: f64) -> Self {
+        self.record.qual = Some(qual);
+        self
+    }
+
+    /// Adds a FILTER.
+    pub fn filter(mut self, filter: &str) -> Self {
+        self.record.filters.push(filter.to_string());
+        self
+    }
+
+    /// Sets an INFO value; several values are passed comma-joined, e.g. `"0.1,0.2"`.
+    pub fn info<V: Display>(mut self, key: &str, value: V) -> Self {
+        self.record.set_info(key, Some(value.to_string()));
+        self
+    }
+
+    /// Sets an INFO flag.
+    pub fn flag(mut self, key: &str) -> Self {
+        self.record.set_info(key, None);
+        self
+    }
+
+    /// Sets a FORMAT value of sample `sample`.
+    pub fn format<V: Display>(mut self, sample: usize, key: &str, value: V) -> Self {
+        if self.record.samples.len() <= sample {
+            self.record.samples.resize(sample + 1, Vec::new());
+        }
+        self.record
+            .set_format_value(sample, key, &value.to_string())
+            .expect("the sample column exists");
+        self
+    }
+
+    /// Validates alleles with `policy` instead of the default policy.
+    pub fn policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Checks INFO and FORMAT values against the definitions of `header`,
+    /// and pads the sample columns to its samples.
+    pub fn header(mut self, header: &'h Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    /// Checks the record and returns it.
+    pub fn build(self) -> Result<Record> {
+        let mut record = self.record;
+
+        record.chrom = self
+            .chrom
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| Error::MissingFieldError("CHROM".to_string()))?;
+        record.pos = self
+            .pos
+            .ok_or_else(|| Error::MissingFieldError("POS".to_string()))?;
+        record.reference = self
+            .reference
+            .ok_or_else(|| Error::MissingFieldError("REF".to_string()))?;
+        let keys = record.format.len();
+        for sample in &mut record.samples {
+            sample.resize(keys, ".".to_string());
+        }
+
+        self.policy.validate_reference(&record.reference)?;
+        for alternate in &record.alternates {
+            self.policy.validate_alternate(alternate)?;
+        }
+
+        if let Some(header) = self.header {
+            check_values(&mut record, header)?;
+        }
+
+        Ok(record)
+    }
+}
+
+impl Default for RecordBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn check_values(record: &mut Record, header: &Header) -> Result<()> {
+    let alleles = record.alternates.len();
+
+    for (key, value) in &record.info {
+        let definition = header.info(key).ok_or_else(|| {
+            Error::FieldValueError(format!("INFO/{}", key), "undeclared".to_string())
+        })?;
+        let field = format!("INFO/{}", key);
+
+        match (definition.value_type, value) {
+            (ValueType::Flag, None) => {}
+            (ValueType::Flag, Some(value)) => Err(Error::FieldValueError(field, value.clone()))?,
+            (_, None) => Err(Error::FieldValueError(field, "missing value".to_string()))?,
+            (value_type, Some(value)) => {
+                check_value(&field, value, definition.number, value_type, alleles)?
+            }
+        }
+    }
+
+    if record.format.is_empty() {
+        return Ok(());
+    }
+    if record.samples.len() > header.samples.len() {
+        Err(Error::FieldValueError(
+            "FORMAT".to_string(),
+            format!("{} samples declared", header.samples.len()),
+        ))?
+    }
+    let keys = record.format.len();
+    record
+        .samples
+        .resize(header.samples.len(), vec![".".to_string(); keys]);
+
+    for (i, key) in record.format.iter().enumerate() {
+        let definition = header.format(key).ok_or_else(|| {
+            Error::FieldValueError(format!("FORMAT/{}", key), "undeclared".to_string())
+        })?;
+        let field = format!("FORMAT/{}", key);
+
+        for sample in &record.samples {
+            match sample[i].as_str() {
+                "." if key != "GT" => {}
+                value => check_value(
+                    &field,
+                    value,
+                    definition.number,
+                    definition.value_type,
+                    alleles,
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the comma-separated values of `field`; `.` is a missing value.
+///
+/// `GT` is parsed as a genotype.
+fn check_value(
+    field: &str,
+    value: &str,
+    number: Number,
+    value_type: ValueType,
+    alleles: usize,
+) -> Result<()> {
+    let error = || Error::FieldValueError(field.to_string(), value.to_string());
+
+    if field == "FORMAT/GT" {
+        let genotype: crate::genotype::Genotype = value.parse()?;
+        if genotype.alleles.iter().flatten().any(|&a| a > alleles) {
+            Err(error())?
+        }
+        return Ok(());
+    }
+
+    let values: Vec<&str> = value.split(',').collect();
+    let expected = match number {
+        Number::Count(n) => Some(n as usize),
+        Number::A => Some(alleles),
+        Number::R => Some(alleles + 1),
+        _ => None,
+    };
+    if expected.is_some_and(|n| n != values.len()) {
+        Err(error())?
+    }
+
+    for v in values.into_iter().filter(|&v| v != ".") {
+        let valid = match value_type {
+            ValueType::Integer => v.parse::<i64>().is_ok(),
+            ValueType::Float => v.parse::<f64>().is_ok(),
+            ValueType::Character => v.chars().count() == 1,
+            ValueType::String | ValueType::Flag => true,
+        };
+        if !valid {
+            Err(error())?
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const HEADER: &str = "##fileformat=VCFv4.3\n\
+        ##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n\
+        ##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\">\n\
+        ##INFO=<ID=DB,Number=0,Type=Flag,Description=\"dbSNP\">\n\
+        ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+        ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Depths\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n";
+
+    fn header() -> Header {
+        Reader::new(HEADER.as_bytes()).unwrap().header().clone()
+    }
+
+    fn site() -> RecordBuilder<'static> {
+        RecordBuilder::new()
+            .chrom("1")
+            .pos(10)
+            .ref_allele("A")
+            .alt("T")
+    }
+
+    #[test]
+    fn test_record_builder_1() {
+        let header = header();
+        let record = site()
+            .id("rs1")
+            .qual(30.0)
+            .filter("PASS")
+            .info("DP", 30)
+            .info("AF", 0.5)
+            .flag("DB")
+            .format(0, "GT", "0/1")
+            .format(0, "AD", "3,4")
+            .header(&header)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            record.to_string(),
+            "1\t10\trs1\tA\tT\t30\tPASS\tDP=30;AF=0.5;DB\tGT:AD\t0/1:3,4\t.:."
+        );
+        assert_eq!(record, record.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn test_record_builder_2() {
+        let header = header();
+        let fails = |builder: RecordBuilder| builder.header(&header).build().is_err();
+
+        assert!(RecordBuilder::new().pos(1).ref_allele("A").build().is_err());
+        assert!(RecordBuilder::new()
+            .chrom("1")
+            .ref_allele("A")
+            .build()
+            .is_err());
+        assert!(site().alt("X").build().is_err());
+        assert!(site().info("XX", 1).build().is_ok());
+
+        assert!(fails(site().info("XX", 1)));
+        assert!(fails(site().info("DP", "many")));
+        assert!(fails(site().info("DP", "1,2")));
+        assert!(fails(site().info("AF", "0.1,0.2")));
+        assert!(fails(site().info("DB", 1)));
+        assert!(fails(site().format(0, "AD", 3)));
+        assert!(fails(site().format(0, "GT", "0/2")));
+        assert!(fails(site().format(2, "GT", "0/1")));
+        assert!(!fails(
+            site().alt("G").info("AF", "0.1,.").format(1, "AD", "1,2,3")
+        ));
+    }
+}
@@ -0,0 +1,368 @@
+//! Memoization of expensive per-variant annotations.
+
+use crate::errors::Result;
+use crate::record::{normalize_owned, NormalizedVariant};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+#[cfg(feature = "fs")]
+use crate::errors::Error;
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Cache key identifying a variant after normalization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub chrom: String,
+    pub variant: NormalizedVariant,
+}
+
+impl CacheKey {
+    pub fn new(chrom: &str, position: u64, reference: &str, alternate: &str) -> Result<Self> {
+        Ok(Self {
+            chrom: chrom.to_string(),
+            variant: normalize_owned(position, reference.to_string(), alternate.to_string())?,
+        })
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}-{}",
+            self.chrom, self.variant.position, self.variant.reference, self.variant.alternate
+        )
+    }
+}
+
+/// Storage for annotation values keyed by normalized variant.
+pub trait AnnotationCache<V> {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<V>>;
+
+    fn insert(&mut self, key: CacheKey, value: V) -> Result<()>;
+
+    /// Returns the cached value, computing and storing it with `f` on a miss.
+    fn get_or_insert_with<F>(&mut self, key: &CacheKey, f: F) -> Result<V>
+    where
+        V: Clone,
+        F: FnOnce() -> Result<V>,
+    {
+        if let Some(value) = self.get(key)? {
+            return Ok(value);
+        }
+
+        let value = f()?;
+        self.insert(key.clone(), value.clone())?;
+
+        Ok(value)
+    }
+}
+
+/// In-memory cache evicting the least recently used entry once full.
+pub struct LruCache<V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, (V, u64)>,
+    order: BTreeMap<u64, CacheKey>,
+}
+
+impl<V> LruCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn touch(&mut self, key: &CacheKey) -> Option<&V> {
+        let (value, tick) = self.entries.get_mut(key)?;
+
+        self.order.remove(tick);
+        self.tick += 1;
+        *tick = self.tick;
+        self.order.insert(self.tick, key.clone());
+
+        Some(value)
+    }
+}
+
+impl<V: Clone> AnnotationCache<V> for LruCache<V> {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<V>> {
+        Ok(self.touch(key).cloned())
+    }
+
+    fn insert(&mut self, key: CacheKey, value: V) -> Result<()> {
+        self.tick += 1;
+
+        if let Some((_, tick)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.tick, key);
+
+        while self.entries.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+/// Persistent cache of string annotations in an append-only file.
+///
+/// Only keys and value offsets are held in memory; values are read back from
+/// the file on access. Later entries for the same key take precedence.
+pub struct DiskCache {
+    file: File,
+    offsets: HashMap<String, (u64, usize)>,
+    end: u64,
+}
+
+#[cfg(feature = "fs")]
+impl DiskCache {
+    /// Opens or creates the cache file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        let mut offsets = HashMap::new();
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        let mut offset = 0u64;
+
+        loop {
+            line.clear();
+            let n = reader.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+
+            let (key, value) = line
+                .trim_end_matches('\n')
+                .split_once('\t')
+                .ok_or_else(|| Error::CacheFormatError(line.clone()))?;
+            let value_offset = offset + key.len() as u64 + 1;
+            offsets.insert(key.to_string(), (value_offset, value.len()));
+
+            offset += n as u64;
+        }
+
+        Ok(Self {
+            file,
+            offsets,
+            end: offset,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl AnnotationCache<String> for DiskCache {
+    fn get(&mut self, key: &CacheKey) -> Result<Option<String>> {
+        let (offset, len) = match self.offsets.get(&key.to_string()) {
+            Some(&entry) => entry,
+            None => return Ok(None),
+        };
+
+        let mut buf = vec![0; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+
+        let value = String::from_utf8(buf).map_err(|e| Error::CacheFormatError(e.to_string()))?;
+
+        Ok(Some(unescape(&value)))
+    }
+
+    fn insert(&mut self, key: CacheKey, value: String) -> Result<()> {
+        let key = key.to_string();
+        let value = escape(&value);
+
+        writeln!(self.file, "{}\t{}", key, value)?;
+
+        let value_offset = self.end + key.len() as u64 + 1;
+        self.end = value_offset + value.len() as u64 + 1;
+        self.offsets.insert(key, (value_offset, value.len()));
+
+        Ok(())
+    }
+}
+
+/// Two-level cache consulting `front` before `back` and filling `front` on hits.
+pub struct TieredCache<A, B> {
+    pub front: A,
+    pub back: B,
+}
+
+impl<V: Clone, A: AnnotationCache<V>, B: AnnotationCache<V>> AnnotationCache<V>
+    for TieredCache<A, B>
+{
+    fn get(&mut self, key: &CacheKey) -> Result<Option<V>> {
+        if let Some(value) = self.front.get(key)? {
+            return Ok(Some(value));
+        }
+
+        let value = self.back.get(key)?;
+        if let Some(value) = &value {
+            self.front.insert(key.clone(), value.clone())?;
+        }
+
+        Ok(value)
+    }
+
+    fn insert(&mut self, key: CacheKey, value: V) -> Result<()> {
+        self.back.insert(key.clone(), value.clone())?;
+        self.front.insert(key, value)
+    }
+}
+
+#[cfg(feature = "fs")]
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+#[cfg(feature = "fs")]
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('t')) => {
+                out.push('\t');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(pos: u64, reference: &str, alternate: &str) -> CacheKey {
+        CacheKey::new("1", pos, reference, alternate).unwrap()
+    }
+
+    #[test]
+    fn test_cache_key_1() {
+        assert_eq!(key(1000, "ATCC", "ATACC"), key(1001, "TCC", "TACC"));
+        assert_eq!(key(1000, "ATCC", "ATACC").to_string(), "1-1001-T-TA");
+        assert!(CacheKey::new("1", 1, "", "A").is_err());
+    }
+
+    #[test]
+    fn test_lru_cache_1() {
+        let mut cache = LruCache::new(2);
+        cache.insert(key(1, "A", "T"), 1).unwrap();
+        cache.insert(key(2, "A", "T"), 2).unwrap();
+
+        assert_eq!(cache.get(&key(1, "A", "T")).unwrap(), Some(1));
+
+        cache.insert(key(3, "A", "T"), 3).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key(2, "A", "T")).unwrap(), None);
+        assert_eq!(cache.get(&key(1, "A", "T")).unwrap(), Some(1));
+        assert_eq!(cache.get(&key(3, "A", "T")).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn test_get_or_insert_with_1() {
+        let mut cache = LruCache::new(10);
+        let mut calls = 0;
+
+        for _ in 0..3 {
+            let value = cache
+                .get_or_insert_with(&key(1, "A", "T"), || {
+                    calls += 1;
+                    Ok("missense".to_string())
+                })
+                .unwrap();
+            assert_eq!(value, "missense");
+        }
+
+        assert_eq!(calls, 1);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_disk_cache_1() {
+        let path = std::env::temp_dir().join(format!("vcf-lib-cache-{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let mut cache = DiskCache::open(&path).unwrap();
+            cache
+                .insert(key(1, "A", "T"), "a\tb\nc\\d".to_string())
+                .unwrap();
+            cache.insert(key(2, "A", "T"), "x".to_string()).unwrap();
+            cache.insert(key(2, "A", "T"), "y".to_string()).unwrap();
+
+            assert_eq!(cache.get(&key(2, "A", "T")).unwrap().as_deref(), Some("y"));
+        }
+
+        let mut cache = DiskCache::open(&path).unwrap();
+        let first = cache.get(&key(1, "A", "T")).unwrap();
+        let second = cache.get(&key(2, "A", "T")).unwrap();
+        let missing = cache.get(&key(3, "A", "T")).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(first.as_deref(), Some("a\tb\nc\\d"));
+        assert_eq!(second.as_deref(), Some("y"));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_tiered_cache_1() {
+        let mut back = LruCache::new(10);
+        back.insert(key(1, "A", "T"), 7).unwrap();
+        let mut cache = TieredCache {
+            front: LruCache::new(10),
+            back,
+        };
+
+        assert_eq!(cache.get(&key(1, "A", "T")).unwrap(), Some(7));
+        assert_eq!(cache.front.len(), 1);
+    }
+}
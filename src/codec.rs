@@ -0,0 +1,235 @@
+//! Compression of temporary files.
+//!
+//! Spill files are written once and read back once, so the codec trades
+//! ratio for speed. The zstd and lz4 codecs are enabled by the cargo features
+//! of the same name.
+
+use crate::errors::{Error, Result};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// Compression of spill and other temporary files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Uncompressed.
+    None,
+    /// Raw deflate at the fastest level; always available.
+    Deflate,
+    /// Zstandard at level 1.
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// LZ4 frames.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Default for Codec {
+    /// LZ4 if enabled, then zstd, then no compression.
+    ///
+    /// Deflate is never the default: it is slower than the disk on most
+    /// machines, so it only pays off on slow or metered storage.
+    #[allow(unreachable_code)]
+    fn default() -> Self {
+        #[cfg(feature = "lz4")]
+        return Self::Lz4;
+        #[cfg(feature = "zstd")]
+        return Self::Zstd;
+
+        Self::None
+    }
+}
+
+impl Codec {
+    /// Codecs enabled in this build.
+    pub fn available() -> Vec<Self> {
+        vec![
+            Self::None,
+            Self::Deflate,
+            #[cfg(feature = "zstd")]
+            Self::Zstd,
+            #[cfg(feature = "lz4")]
+            Self::Lz4,
+        ]
+    }
+
+    /// Suffix for file names, including the dot, or `""` for [`Codec::None`].
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Deflate => ".deflate",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => ".zst",
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => ".lz4",
+        }
+    }
+
+    /// Compresses everything written to `inner`.
+    ///
+    /// Call [`CodecWriter::finish`] when done; dropping the writer may lose
+    /// buffered data.
+    pub fn writer<W: Write>(self, inner: W) -> io::Result<CodecWriter<W>> {
+        Ok(match self {
+            Self::None => CodecWriter::None(inner),
+            Self::Deflate => CodecWriter::Deflate(flate2::write::DeflateEncoder::new(
+                inner,
+                flate2::Compression::fast(),
+            )),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => CodecWriter::Zstd(zstd::Encoder::new(inner, 1)?),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => CodecWriter::Lz4(lz4_flex::frame::FrameEncoder::new(inner)),
+        })
+    }
+
+    /// Decompresses data written by [`Codec::writer`].
+    pub fn reader<R: Read>(self, inner: R) -> io::Result<CodecReader<R>> {
+        Ok(match self {
+            Self::None => CodecReader::None(inner),
+            Self::Deflate => CodecReader::Deflate(flate2::read::DeflateDecoder::new(inner)),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => CodecReader::Zstd(zstd::Decoder::new(inner)?),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => CodecReader::Lz4(lz4_flex::frame::FrameDecoder::new(inner)),
+        })
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Deflate => "deflate",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zstd",
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => "lz4",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    /// Parses a codec name; names of codecs disabled in this build fail.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::available()
+            .into_iter()
+            .find(|codec| codec.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::UnsupportedCodecError(s.to_string()))
+    }
+}
+
+/// Writer returned by [`Codec::writer`].
+pub enum CodecWriter<W: Write> {
+    None(W),
+    Deflate(flate2::write::DeflateEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+}
+
+impl<W: Write> CodecWriter<W> {
+    /// Writes the end of the compressed stream and returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Self::None(mut w) => w.flush().map(|_| w),
+            Self::Deflate(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.finish(),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(w) => w.finish().map_err(io::Error::from),
+        }
+    }
+}
+
+impl<W: Write> Write for CodecWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::None(w) => w.write(buf),
+            Self::Deflate(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.write(buf),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::None(w) => w.flush(),
+            Self::Deflate(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.flush(),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(w) => w.flush(),
+        }
+    }
+}
+
+/// Reader returned by [`Codec::reader`].
+pub enum CodecReader<R: Read> {
+    None(R),
+    Deflate(flate2::read::DeflateDecoder<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Decoder<'static, io::BufReader<R>>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameDecoder<R>),
+}
+
+impl<R: Read> Read for CodecReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::None(r) => r.read(buf),
+            Self::Deflate(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(r) => r.read(buf),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(r) => r.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_codec_1() {
+        let lines: Vec<String> = (0..2000)
+            .map(|i| format!("1\t{}\t.\tA\tT\t.\t.\t.", i))
+            .collect();
+        let text = lines.join("\n");
+
+        for codec in Codec::available() {
+            let mut writer = codec.writer(Vec::new()).unwrap();
+            writer.write_all(text.as_bytes()).unwrap();
+            let compressed = writer.finish().unwrap();
+
+            if codec != Codec::None {
+                assert!(compressed.len() < text.len() / 4, "{}", codec);
+            }
+
+            let reader = BufReader::new(codec.reader(compressed.as_slice()).unwrap());
+            let read: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+            assert_eq!(read, lines, "{}", codec);
+        }
+    }
+
+    #[test]
+    fn test_codec_2() {
+        assert_eq!("none".parse::<Codec>().unwrap(), Codec::None);
+        assert_eq!("Deflate".parse::<Codec>().unwrap(), Codec::Deflate);
+        assert!("brotli".parse::<Codec>().is_err());
+
+        for codec in Codec::available() {
+            assert_eq!(codec.to_string().parse::<Codec>().unwrap(), codec);
+        }
+        assert!(Codec::available().contains(&Codec::default()));
+    }
+}
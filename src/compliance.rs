@@ -0,0 +1,233 @@
+//! Version-specific rules of the VCF specification.
+
+use crate::errors::{Error, Result};
+use crate::header::{Header, Version};
+use crate::record::Record;
+use crate::validation::{Alphabet, Lowercase, ValidationPolicy};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::borrow::Cow;
+
+static REGEX_INFO_KEY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\A([A-Za-z_][0-9A-Za-z_.]*|1000G)\z").unwrap());
+
+/// Characters that VCFv4.3 and later require to be percent-encoded in values.
+const RESERVED: &[char] = &['%', ':', ';', '=', ',', '\r', '\n', '\t'];
+
+/// Parsing and validation rules for one VCF version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compliance {
+    pub version: Version,
+}
+
+impl Compliance {
+    pub fn new(version: Version) -> Self {
+        Self { version }
+    }
+
+    /// Rules for the version declared by `header`.
+    pub fn for_header(header: &Header) -> Result<Self> {
+        Ok(Self::new(header.fileformat.parse()?))
+    }
+
+    /// Allele validation mandated by the spec: `ACGTN` in either case, plus
+    /// symbolic alleles.
+    pub fn validation_policy(&self) -> ValidationPolicy {
+        ValidationPolicy {
+            alphabet: Alphabet::Strict,
+            lowercase: Lowercase::Accept,
+            symbolic: true,
+        }
+    }
+
+    /// `*` for overlapping deletions was introduced in VCFv4.2.
+    pub fn allows_star_allele(&self) -> bool {
+        self.version >= Version::V4_2
+    }
+
+    /// INFO string values are percent-encoded from VCFv4.3.
+    pub fn percent_encoding(&self) -> bool {
+        self.version >= Version::V4_3
+    }
+
+    /// VCFv4.4 redefined `SVLEN` as a non-negative length.
+    pub fn unsigned_svlen(&self) -> bool {
+        self.version >= Version::V4_4
+    }
+
+    /// Checks a record against the rules of this version.
+    pub fn check_record(&self, record: &Record) -> Result<()> {
+        let policy = self.validation_policy();
+
+        policy.validate_reference(&record.reference)?;
+
+        for alt in &record.alternates {
+            if alt == "*" && !self.allows_star_allele() {
+                Err(self.error("`*` allele is not allowed"))?
+            }
+            policy.validate_alternate(alt)?;
+        }
+
+        for (key, value) in &record.info {
+            if self.version >= Version::V4_3 && !REGEX_INFO_KEY.is_match(key) {
+                Err(self.error(&format!("invalid INFO key `{}`", key)))?
+            }
+
+            if let Some(value) = value {
+                if self.percent_encoding() {
+                    if value.contains('=') {
+                        Err(self.error(&format!("unencoded `=` in INFO/{}", key)))?
+                    }
+                    decode_percent(value)?;
+                }
+            }
+        }
+
+        if let Some(svlen) = record.info("SVLEN") {
+            for len in svlen.split(',').filter_map(|v| v.parse::<i64>().ok()) {
+                if self.unsigned_svlen() && len < 0 {
+                    Err(self.error("SVLEN must not be negative"))?
+                }
+                if !self.unsigned_svlen() && record.info("SVTYPE") == Some("DEL") && len > 0 {
+                    Err(self.error("SVLEN of a deletion must be negative"))?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes an INFO value when this version percent-encodes them.
+    pub fn decode<'a>(&self, value: &'a str) -> Result<Cow<'a, str>> {
+        if self.percent_encoding() {
+            decode_percent(value)
+        } else {
+            Ok(Cow::Borrowed(value))
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::NonCompliantRecordError(self.version.to_string(), message.to_string())
+    }
+}
+
+/// Decodes `%XX` escapes.
+pub fn decode_percent(value: &str) -> Result<Cow<'_, str>> {
+    if !value.contains('%') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = value
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| Error::PercentEncodingError(value.to_string()))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(out)
+        .map(Cow::Owned)
+        .map_err(|_| Error::PercentEncodingError(value.to_string()))
+}
+
+/// Encodes the characters reserved by VCFv4.3 as `%XX`.
+pub fn encode_percent(value: &str) -> Cow<'_, str> {
+    if !value.contains(RESERVED) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut out = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        if RESERVED.contains(&c) {
+            out.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            out.push(c);
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: &str) -> Record {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn test_check_record_1() {
+        let r = record("1\t10\t.\tA\t*\t.\t.\t.");
+
+        assert!(Compliance::new(Version::V4_1).check_record(&r).is_err());
+        assert!(Compliance::new(Version::V4_2).check_record(&r).is_ok());
+    }
+
+    #[test]
+    fn test_check_record_2() {
+        let r = record("1\t10\t.\tA\tR\t.\t.\t.");
+
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_err());
+
+        let r = record("1\t10\t.\tacgt\t<DEL>,T[2:100[\t.\t.\t.");
+
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_ok());
+    }
+
+    #[test]
+    fn test_check_record_3() {
+        let r = record("1\t10\t.\tA\tT\t.\t.\t1KG=1;NOTE=a=b");
+
+        assert!(Compliance::new(Version::V4_2).check_record(&r).is_ok());
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_err());
+
+        let r = record("1\t10\t.\tA\tT\t.\t.\tNOTE=50%");
+
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_err());
+    }
+
+    #[test]
+    fn test_check_record_4() {
+        let r = record("1\t10\t.\tA\t<DEL>\t.\t.\tSVTYPE=DEL;SVLEN=-100");
+
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_ok());
+        assert!(Compliance::new(Version::V4_4).check_record(&r).is_err());
+
+        let r = record("1\t10\t.\tA\t<DEL>\t.\t.\tSVTYPE=DEL;SVLEN=100");
+
+        assert!(Compliance::new(Version::V4_3).check_record(&r).is_err());
+        assert!(Compliance::new(Version::V4_4).check_record(&r).is_ok());
+    }
+
+    #[test]
+    fn test_percent_1() {
+        assert_eq!(decode_percent("a%3Bb%25").unwrap(), "a;b%");
+        assert!(matches!(decode_percent("plain").unwrap(), Cow::Borrowed(_)));
+        assert!(decode_percent("%zz").is_err());
+        assert_eq!(encode_percent("x=1;y,2"), "x%3D1%3By%2C2");
+        assert_eq!(
+            decode_percent(&encode_percent("a:b\tc%")).unwrap(),
+            "a:b\tc%"
+        );
+    }
+
+    #[test]
+    fn test_decode_1() {
+        assert_eq!(
+            Compliance::new(Version::V4_2).decode("a%3B").unwrap(),
+            "a%3B"
+        );
+        assert_eq!(Compliance::new(Version::V4_4).decode("a%3B").unwrap(), "a;");
+    }
+}
@@ -0,0 +1,521 @@
+//! Genotype concordance of two call sets of the same samples.
+//!
+//! A [`Comparer`] splits multiallelic records, walks two sorted files in
+//! lockstep with [`Intersect`], pairing sites on normalized alleles, and
+//! compares the genotypes of the samples both files share. [`Concordance`]
+//! adds up the site and genotype counts; taking A as the truth set, its
+//! precision and recall are the site-level metrics of benchmarking tools
+//! such as `hap.py`.
+
+use crate::errors::Result;
+use crate::genotype::Genotype;
+use crate::header::Header;
+use crate::isec::{Intersect, SetItem};
+use crate::record::{NormalizedVariant, Record};
+use std::collections::{HashMap, VecDeque};
+
+/// How the genotypes of one sample compare at one site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GenotypeMatch {
+    /// Both genotypes are homozygous REF.
+    RefMatch,
+    /// The same alleles, carrying an ALT allele, regardless of phase.
+    Match,
+    Mismatch,
+    /// Either genotype is missing, partially called or fails to parse.
+    Missing,
+    /// An ALT allele called at a site only in A.
+    OnlyA,
+    /// An ALT allele called at a site only in B.
+    OnlyB,
+    /// No ALT allele called at a site only in one file.
+    NoVariant,
+}
+
+/// One site of the comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteConcordance {
+    pub item: SetItem,
+    /// One per shared sample, in the order of [`Comparer::samples`].
+    pub genotypes: Vec<GenotypeMatch>,
+}
+
+impl SiteConcordance {
+    /// Fraction of the called genotypes that match; `None` if there are none.
+    pub fn concordance(&self) -> Option<f64> {
+        let count = |f: fn(&GenotypeMatch) -> bool| self.genotypes.iter().filter(|g| f(g)).count();
+        let matches = count(|g| matches!(g, GenotypeMatch::RefMatch | GenotypeMatch::Match));
+        let mismatches = count(|g| *g == GenotypeMatch::Mismatch);
+
+        (matches + mismatches > 0).then(|| matches as f64 / (matches + mismatches) as f64)
+    }
+}
+
+/// Genotype counts of one sample.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleConcordance {
+    pub ref_matches: u64,
+    pub matches: u64,
+    pub mismatches: u64,
+    pub missing: u64,
+    pub only_a: u64,
+    pub only_b: u64,
+}
+
+impl SampleConcordance {
+    /// Fraction of the genotypes called in both files that match.
+    pub fn concordance(&self) -> Option<f64> {
+        let matches = self.ref_matches + self.matches;
+        let total = matches + self.mismatches;
+
+        (total > 0).then(|| matches as f64 / total as f64)
+    }
+
+    /// Fraction of the genotypes with an ALT allele in either file that
+    /// match, leaving out the many homozygous REF matches.
+    pub fn non_reference_concordance(&self) -> Option<f64> {
+        let total = self.matches + self.mismatches + self.only_a + self.only_b;
+
+        (total > 0).then(|| self.matches as f64 / total as f64)
+    }
+
+    pub fn add(&mut self, genotype: GenotypeMatch) {
+        match genotype {
+            GenotypeMatch::RefMatch => self.ref_matches += 1,
+            GenotypeMatch::Match => self.matches += 1,
+            GenotypeMatch::Mismatch => self.mismatches += 1,
+            GenotypeMatch::Missing => self.missing += 1,
+            GenotypeMatch::OnlyA => self.only_a += 1,
+            GenotypeMatch::OnlyB => self.only_b += 1,
+            GenotypeMatch::NoVariant => {}
+        }
+    }
+}
+
+/// Counts over the sites of a comparison.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Concordance {
+    /// Samples in both files, in the order of A.
+    pub samples: Vec<String>,
+    pub both: u64,
+    pub only_a: u64,
+    pub only_b: u64,
+    /// Per-sample counts, in the order of `samples`.
+    pub per_sample: Vec<SampleConcordance>,
+}
+
+impl Concordance {
+    pub fn new(samples: &[String]) -> Self {
+        Self {
+            samples: samples.to_vec(),
+            per_sample: vec![SampleConcordance::default(); samples.len()],
+            ..Self::default()
+        }
+    }
+
+    /// Sites of B also in A; `None` without sites in B.
+    pub fn precision(&self) -> Option<f64> {
+        let total = self.both + self.only_b;
+
+        (total > 0).then(|| self.both as f64 / total as f64)
+    }
+
+    /// Sites of A also in B; `None` without sites in A.
+    pub fn recall(&self) -> Option<f64> {
+        let total = self.both + self.only_a;
+
+        (total > 0).then(|| self.both as f64 / total as f64)
+    }
+
+    pub fn f1(&self) -> Option<f64> {
+        let (precision, recall) = (self.precision()?, self.recall()?);
+
+        (precision + recall > 0.0).then(|| 2.0 * precision * recall / (precision + recall))
+    }
+
+    pub fn add(&mut self, site: &SiteConcordance) {
+        match site.item {
+            SetItem::Both(..) => self.both += 1,
+            SetItem::OnlyA(_) => self.only_a += 1,
+            SetItem::OnlyB(_) => self.only_b += 1,
+        }
+        for (counts, &genotype) in self.per_sample.iter_mut().zip(&site.genotypes) {
+            counts.add(genotype);
+        }
+    }
+}
+
+/// Iterator adapter comparing the genotypes of two sorted record streams.
+///
+/// Multiallelic records are first split into one record per ALT allele,
+/// with values declared `Number=A`, `R` or `G` subset, and other ALT alleles
+/// of `GT` becoming REF, as by `bcftools norm -m-`; sites are then paired
+/// allele by allele as by [`Intersect`], so split and unsplit files compare
+/// alike. Genotypes are compared as the normalized alleles they carry, so
+/// `0/1` of `A>G` matches `1/0` of the same allele written with other
+/// padding. Samples are paired by name.
+pub struct Comparer<A: Iterator<Item = Result<Record>>, B: Iterator<Item = Result<Record>>> {
+    inner: Intersect<Biallelic<A>, Biallelic<B>>,
+    samples: Vec<String>,
+    /// Sample index in A and in B of each shared sample.
+    columns: Vec<(usize, usize)>,
+}
+
+impl<A, B> Comparer<A, B>
+where
+    A: Iterator<Item = Result<Record>>,
+    B: Iterator<Item = Result<Record>>,
+{
+    pub fn new(a: A, header_a: &Header, b: B, header_b: &Header) -> Self {
+        let index_b: HashMap<&str, usize> = header_b
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.as_str(), i))
+            .collect();
+
+        let (samples, columns) = header_a
+            .samples
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((s.clone(), (i, *index_b.get(s.as_str())?))))
+            .unzip();
+
+        Self {
+            inner: Intersect::new(
+                Biallelic::new(a, header_a),
+                header_a,
+                Biallelic::new(b, header_b),
+                header_b,
+            ),
+            samples,
+            columns,
+        }
+    }
+
+    /// Samples in both files, in the order of A.
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// Compares the genotypes of the shared samples at `item`, whose records
+    /// may be multiallelic.
+    pub fn compare(&self, item: &SetItem) -> Vec<GenotypeMatch> {
+        let variants =
+            |record: Option<&Record>| record.map_or_else(Vec::new, Record::normalized_alternates);
+        let (x, y) = match item {
+            SetItem::Both(x, y) => (Some(x), Some(y)),
+            SetItem::OnlyA(x) => (Some(x), None),
+            SetItem::OnlyB(y) => (None, Some(y)),
+        };
+        let (variants_x, variants_y) = (variants(x), variants(y));
+
+        self.columns
+            .iter()
+            .map(|&(a, b)| {
+                let a = x.and_then(|x| alleles(x, &variants_x, a));
+                let b = y.and_then(|y| alleles(y, &variants_y, b));
+                match item {
+                    SetItem::Both(..) => compare(a, b),
+                    SetItem::OnlyA(_) => only(a, GenotypeMatch::OnlyA),
+                    SetItem::OnlyB(_) => only(b, GenotypeMatch::OnlyB),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<A, B> Iterator for Comparer<A, B>
+where
+    A: Iterator<Item = Result<Record>>,
+    B: Iterator<Item = Result<Record>>,
+{
+    type Item = Result<SiteConcordance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = match self.inner.next()? {
+            Ok(item) => item,
+            Err(e) => return Some(Err(e)),
+        };
+        let genotypes = self.compare(&item);
+
+        Some(Ok(SiteConcordance { item, genotypes }))
+    }
+}
+
+/// Iterator adapter splitting multiallelic records into biallelic ones.
+struct Biallelic<I: Iterator<Item = Result<Record>>> {
+    inner: I,
+    header: Header,
+    ready: VecDeque<Record>,
+}
+
+impl<I: Iterator<Item = Result<Record>>> Biallelic<I> {
+    fn new(inner: I, header: &Header) -> Self {
+        Self {
+            inner,
+            header: header.clone(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for Biallelic<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.ready.pop_front() {
+            return Some(Ok(record));
+        }
+
+        match self.inner.next()? {
+            Ok(record) if record.alternates.len() > 1 => {
+                self.ready = split(&record, &self.header).into();
+                self.ready.pop_front().map(Ok)
+            }
+            item => Some(item),
+        }
+    }
+}
+
+/// One record per ALT allele of `record`, in ALT order.
+fn split(record: &Record, header: &Header) -> Vec<Record> {
+    let count = record.alternates.len();
+
+    (1..=count)
+        .map(|allele| {
+            let keep: Vec<bool> = (1..=count).map(|i| i == allele).collect();
+            let mut part = record.clone();
+            part.retain_alternates(header, &keep);
+
+            for sample in 0..record.samples.len() {
+                let Ok(Some(genotype)) = record.genotype(sample) else {
+                    continue;
+                };
+                let alleles: Vec<Option<usize>> = genotype
+                    .alleles
+                    .iter()
+                    .map(|a| a.map(|i| usize::from(i == allele)))
+                    .collect();
+                let genotype = Genotype {
+                    alleles,
+                    phased: genotype.phased,
+                };
+                part.set_genotype(sample, &genotype)
+                    .expect("the sample column exists");
+            }
+
+            part
+        })
+        .collect()
+}
+
+/// Alleles of the genotype of `sample`, `None` standing for REF, in sorted
+/// order; `None` if the genotype is not fully called. `variants` are the
+/// normalized ALT alleles of `record`.
+fn alleles(
+    record: &Record,
+    variants: &[NormalizedVariant],
+    sample: usize,
+) -> Option<Vec<Option<NormalizedVariant>>> {
+    let genotype = record.genotype(sample).ok().flatten()?;
+
+    let mut alleles = genotype
+        .alleles
+        .iter()
+        .map(|&a| match a? {
+            0 => Some(None),
+            i => variants.get(i - 1).cloned().map(Some),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    alleles.sort_by(|x, y| key(x).cmp(&key(y)));
+
+    Some(alleles)
+}
+
+fn key(allele: &Option<NormalizedVariant>) -> Option<(u64, &str, &str)> {
+    allele
+        .as_ref()
+        .map(|v| (v.position, v.reference.as_str(), v.alternate.as_str()))
+}
+
+fn compare(
+    a: Option<Vec<Option<NormalizedVariant>>>,
+    b: Option<Vec<Option<NormalizedVariant>>>,
+) -> GenotypeMatch {
+    let (Some(a), Some(b)) = (a, b) else {
+        return GenotypeMatch::Missing;
+    };
+
+    match (a == b, a.iter().all(Option::is_none)) {
+        (true, true) => GenotypeMatch::RefMatch,
+        (true, false) => GenotypeMatch::Match,
+        (false, _) => GenotypeMatch::Mismatch,
+    }
+}
+
+fn only(alleles: Option<Vec<Option<NormalizedVariant>>>, status: GenotypeMatch) -> GenotypeMatch {
+    match alleles {
+        Some(alleles) if alleles.iter().any(Option::is_some) => status,
+        _ => GenotypeMatch::NoVariant,
+    }
+}
+
+/// Compares two sorted record streams site by site.
+pub fn concordance<A, B>(a: A, header_a: &Header, b: B, header_b: &Header) -> Result<Concordance>
+where
+    A: IntoIterator<Item = Result<Record>>,
+    B: IntoIterator<Item = Result<Record>>,
+{
+    let comparer = Comparer::new(a.into_iter(), header_a, b.into_iter(), header_b);
+    let mut concordance = Concordance::new(comparer.samples());
+    for site in comparer {
+        concordance.add(&site?);
+    }
+
+    Ok(concordance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{InfoDefinition, Number, ValueType};
+
+    fn records(lines: &[&str]) -> Vec<Result<Record>> {
+        lines.iter().map(|l| l.parse()).collect()
+    }
+
+    fn header(samples: &[&str]) -> Header {
+        let mut header = Header::new("VCFv4.3");
+        header.samples = samples.iter().map(|s| s.to_string()).collect();
+        header
+    }
+
+    const A: &[&str] = &[
+        "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\t1/1\t0/0",
+        "1\t200\t.\tC\tT,A\t.\t.\t.\tGT\t1/2\t0/2\t./.",
+        "1\t300\t.\tG\tC\t.\t.\t.\tGT\t0/1\t0/0\t0/1",
+    ];
+
+    const B: &[&str] = &[
+        "1\t100\t.\tAC\tGC\t.\t.\t.\tGT\t0/0\t1/0\t1|1",
+        "1\t200\t.\tC\tA,T\t.\t.\t.\tGT\t1/1\t0/1\t2/1",
+        "1\t400\t.\tT\tA\t.\t.\t.\tGT\t0/0\t0/0\t0/0",
+    ];
+
+    #[test]
+    fn test_comparer_1() {
+        let (header_a, header_b) = (header(&["S1", "S2", "S3"]), header(&["S2", "S9", "S1"]));
+        let comparer = Comparer::new(
+            records(A).into_iter(),
+            &header_a,
+            records(B).into_iter(),
+            &header_b,
+        );
+        assert_eq!(comparer.samples(), &["S1".to_string(), "S2".to_string()]);
+
+        let sites: Vec<SiteConcordance> = comparer.map(|s| s.unwrap()).collect();
+        let genotypes: Vec<Vec<GenotypeMatch>> =
+            sites.iter().map(|s| s.genotypes.clone()).collect();
+
+        use GenotypeMatch::*;
+        assert_eq!(
+            genotypes,
+            vec![
+                vec![Mismatch, Mismatch],
+                vec![Match, Mismatch],
+                vec![Match, RefMatch],
+                vec![OnlyA, NoVariant],
+                vec![NoVariant, NoVariant],
+            ]
+        );
+        assert_eq!(sites[1].concordance(), Some(0.5));
+    }
+
+    #[test]
+    fn test_concordance_1() {
+        let (header_a, header_b) = (header(&["S1", "S2", "S3"]), header(&["S1", "S2", "S3"]));
+        let concordance = concordance(records(A), &header_a, records(B), &header_b).unwrap();
+
+        assert_eq!(
+            (concordance.both, concordance.only_a, concordance.only_b),
+            (3, 1, 1)
+        );
+        assert_eq!(concordance.precision(), Some(0.75));
+        assert_eq!(concordance.recall(), Some(0.75));
+        assert_eq!(
+            concordance.per_sample[1],
+            SampleConcordance {
+                ref_matches: 1,
+                matches: 1,
+                mismatches: 1,
+                ..SampleConcordance::default()
+            }
+        );
+        assert_eq!(concordance.per_sample[2].missing, 2);
+        assert_eq!(concordance.per_sample[2].only_a, 1);
+        assert_eq!(concordance.per_sample[1].concordance(), Some(2.0 / 3.0));
+        assert_eq!(
+            concordance.per_sample[0].non_reference_concordance(),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn test_concordance_2() {
+        let mut header = header(&["S1", "S2"]);
+        header
+            .infos
+            .push(InfoDefinition::new("AF", Number::A, ValueType::Float, ""));
+        let unsplit = &["1\t200\t.\tC\tT,A\t.\t.\tAF=0.25,0.5\tGT\t1/2\t2|0"];
+        let split = &[
+            "1\t200\t.\tC\tA\t.\t.\tAF=0.5\tGT\t0/1\t1|0",
+            "1\t200\t.\tCG\tTG\t.\t.\tAF=0.25\tGT\t1/0\t0|0",
+        ];
+
+        for (a, b) in [
+            (&unsplit[..], &split[..]),
+            (split, unsplit),
+            (unsplit, unsplit),
+        ] {
+            let concordance = concordance(records(a), &header, records(b), &header).unwrap();
+
+            assert_eq!(
+                (concordance.both, concordance.only_a, concordance.only_b),
+                (2, 0, 0)
+            );
+            assert_eq!(concordance.per_sample[0].matches, 2);
+            assert_eq!(
+                (
+                    concordance.per_sample[1].matches,
+                    concordance.per_sample[1].ref_matches
+                ),
+                (1, 1)
+            );
+        }
+
+        let comparer = Comparer::new(
+            records(unsplit).into_iter(),
+            &header,
+            records(split).into_iter(),
+            &header,
+        );
+        let mut pairs: Vec<String> = comparer
+            .map(|site| match site.unwrap().item {
+                SetItem::Both(x, y) => format!(
+                    "{} AF={} GT={} / {}",
+                    x.alternates[0],
+                    x.info("AF").unwrap(),
+                    x.format_value(1, "GT").unwrap(),
+                    y.alternates[0]
+                ),
+                item => panic!("unpaired {:?}", item),
+            })
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec!["A AF=0.5 GT=1|0 / A", "T AF=0.25 GT=0|0 / TG"]);
+    }
+}
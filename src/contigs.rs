@@ -0,0 +1,268 @@
+//! Consistency of `##contig` lines with a reference genome.
+//!
+//! [`check_contigs`] compares the names, `length` and `md5` attributes of
+//! the contigs declared in a header with a [`ReferenceSequence`], so that a
+//! VCF called against another assembly (GRCh37 against GRCh38, `1` against
+//! `chr1`) is caught before any coordinates are trusted. Checksums follow
+//! the SAM specification: the MD5 of the uppercase bases.
+
+use crate::errors::Result;
+use crate::header::Header;
+use crate::reference::ReferenceSequence;
+
+/// Bases hashed per fetch from the reference.
+const WINDOW: u64 = 1 << 20;
+
+/// A contig declared in the header that does not match the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContigIssue {
+    /// The reference has no sequence of that name.
+    NotInReference(String),
+    LengthMismatch {
+        contig: String,
+        header: u64,
+        reference: u64,
+    },
+    /// Checksums as lowercase hexadecimal.
+    Md5Mismatch {
+        contig: String,
+        header: String,
+        reference: String,
+    },
+}
+
+/// Checks every `##contig` line against `reference`.
+///
+/// Lengths are compared when declared. Checksums are compared when declared
+/// and `verify_md5` is set, which reads every declared contig in full.
+pub fn check_contigs(
+    header: &Header,
+    reference: &dyn ReferenceSequence,
+    verify_md5: bool,
+) -> Result<Vec<ContigIssue>> {
+    let mut issues = Vec::new();
+
+    for contig in &header.contigs {
+        let Some(length) = reference.length(&contig.id) else {
+            issues.push(ContigIssue::NotInReference(contig.id.clone()));
+            continue;
+        };
+
+        if let Some(declared) = contig.length {
+            if declared != length {
+                issues.push(ContigIssue::LengthMismatch {
+                    contig: contig.id.clone(),
+                    header: declared,
+                    reference: length,
+                });
+                continue;
+            }
+        }
+
+        let declared = contig
+            .attributes
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("md5"))
+            .map(|(_, v)| v.to_ascii_lowercase());
+        if let (Some(declared), true) = (declared, verify_md5) {
+            let actual = sequence_md5(reference, &contig.id)?;
+            if declared != actual {
+                issues.push(ContigIssue::Md5Mismatch {
+                    contig: contig.id.clone(),
+                    header: declared,
+                    reference: actual,
+                });
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+/// MD5 of the bases of `chrom`, as lowercase hexadecimal.
+pub fn sequence_md5(reference: &dyn ReferenceSequence, chrom: &str) -> Result<String> {
+    let length = reference.length(chrom).unwrap_or(0);
+
+    let mut md5 = Md5::new();
+    let mut start = 1;
+    while start <= length {
+        md5.update(&reference.fetch(chrom, start, start + WINDOW - 1)?);
+        start += WINDOW;
+    }
+
+    Ok(md5.finish().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Per-round shift amounts of MD5 (RFC 1321).
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// `floor(abs(sin(i + 1)) * 2^32)`.
+const SINES: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Incremental MD5.
+struct Md5 {
+    state: [u32; 4],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Md5 {
+    fn new() -> Self {
+        Self {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+
+        if !self.buffer.is_empty() {
+            let n = data.len().min(64 - self.buffer.len());
+            self.buffer.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.buffer.len() < 64 {
+                return;
+            }
+            let block = std::mem::take(&mut self.buffer);
+            self.compress(&block);
+            self.buffer = block;
+            self.buffer.clear();
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    fn finish(mut self) -> [u8; 16] {
+        let bits = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80];
+        padding.resize((55usize.wrapping_sub(self.buffer.len()) % 64) + 1, 0);
+        padding.extend_from_slice(&bits.to_le_bytes());
+        let length = self.length;
+        self.update(&padding);
+        self.length = length;
+
+        let mut digest = [0; 16];
+        for (i, word) in self.state.iter().enumerate() {
+            digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let words: Vec<u32> = block
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = self.state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(SINES[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::MemoryReference;
+
+    fn md5(data: &[u8]) -> String {
+        let mut md5 = Md5::new();
+        for chunk in data.chunks(7) {
+            md5.update(chunk);
+        }
+
+        md5.finish().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_md5_1() {
+        assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn test_check_contigs_1() {
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "acgt");
+        reference.insert("2", "ACGTN");
+        reference.insert("3", "TT");
+
+        let text = format!(
+            "##fileformat=VCFv4.3\n\
+            ##contig=<ID=1,length=4,md5={}>\n\
+            ##contig=<ID=2,length=6>\n\
+            ##contig=<ID=3,md5=0123456789abcdef0123456789abcdef>\n\
+            ##contig=<ID=chr4,length=10>\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n",
+            md5(b"ACGT").to_uppercase()
+        );
+        let header = crate::reader::Reader::new(text.as_bytes())
+            .unwrap()
+            .header()
+            .clone();
+
+        let issues = check_contigs(&header, &reference, true).unwrap();
+        assert_eq!(
+            issues,
+            vec![
+                ContigIssue::LengthMismatch {
+                    contig: "2".to_string(),
+                    header: 6,
+                    reference: 5
+                },
+                ContigIssue::Md5Mismatch {
+                    contig: "3".to_string(),
+                    header: "0123456789abcdef0123456789abcdef".to_string(),
+                    reference: md5(b"TT"),
+                },
+                ContigIssue::NotInReference("chr4".to_string()),
+            ]
+        );
+        assert_eq!(check_contigs(&header, &reference, false).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,281 @@
+//! Incremental parsing of VCF bytes as they arrive.
+//!
+//! A [`Decoder`] does no I/O: bytes are pushed with [`Decoder::feed`] and
+//! records of complete lines are pulled by iterating it. An async task can so
+//! read a VCF from a socket or object storage with its runtime's own reads
+//! and parse it without blocking, one chunk at a time:
+//!
+//! ```ignore
+//! let mut decoder = Decoder::bgzf();
+//! loop {
+//!     let n = stream.read(&mut buf).await?;
+//!     if n == 0 {
+//!         break;
+//!     }
+//!     decoder.feed(&buf[..n])?;
+//!     for record in decoder.by_ref() {
+//!         handle(record?);
+//!     }
+//! }
+//! decoder.finish()?;
+//! for record in decoder.by_ref() {
+//!     handle(record?);
+//! }
+//! ```
+//!
+//! The crate has no async reader of its own: there is no `tokio` feature or
+//! `AsyncVcfReader` yet, and a loop like the one above is the way to read
+//! asynchronously.
+
+use crate::bgzf;
+use crate::errors::{Error, ErrorContext, Result};
+use crate::header::Header;
+use crate::record::Record;
+use std::io;
+
+/// Push parser of plain or BGZF-compressed VCF.
+///
+/// The iterator returns `None` when no complete line is buffered; after
+/// [`Decoder::finish`] it also returns a last line without newline.
+pub struct Decoder {
+    /// Compressed bytes not yet forming a whole block; `None` for plain text.
+    compressed: Option<Vec<u8>>,
+    /// Uncompressed bytes not yet parsed.
+    text: Vec<u8>,
+    /// Start of the first unparsed line in `text`.
+    start: usize,
+    header: Header,
+    header_done: bool,
+    finished: bool,
+    line_number: u64,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    /// Decoder of uncompressed VCF.
+    pub fn new() -> Self {
+        Self {
+            compressed: None,
+            text: Vec::new(),
+            start: 0,
+            header: Header::new(""),
+            header_done: false,
+            finished: false,
+            line_number: 0,
+        }
+    }
+
+    /// Decoder of BGZF-compressed VCF.
+    pub fn bgzf() -> Self {
+        Self {
+            compressed: Some(Vec::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Adds the next bytes of the input, decompressing the blocks they
+    /// complete and parsing the header lines they complete.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<()> {
+        match &mut self.compressed {
+            Some(compressed) => {
+                compressed.extend_from_slice(bytes);
+                self.inflate()?;
+            }
+            None => self.text.extend_from_slice(bytes),
+        }
+
+        self.parse_header()
+    }
+
+    /// Marks the end of the input; fails if it ends inside a BGZF block or
+    /// before the end of the header.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.compressed.as_ref().is_some_and(|c| !c.is_empty()) {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated BGZF block",
+            ))?
+        }
+
+        self.finished = true;
+        self.parse_header()?;
+        if !self.header_done {
+            Err(Error::HeaderMissingFileformatError())?
+        }
+
+        Ok(())
+    }
+
+    /// Returns the header once all of its lines have been fed.
+    pub fn header(&self) -> Option<&Header> {
+        self.header_done.then_some(&self.header)
+    }
+
+    /// Number of lines parsed so far, including the header.
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    /// Decompresses every whole block of `compressed` into `text`.
+    fn inflate(&mut self) -> Result<()> {
+        let Some(compressed) = &mut self.compressed else {
+            return Ok(());
+        };
+
+        let mut offset = 0;
+        let mut block = Vec::new();
+        while compressed.len() - offset >= bgzf::HEADER_SIZE {
+            let size = bgzf::block_size(&compressed[offset..])?;
+            if compressed.len() - offset < size {
+                break;
+            }
+            bgzf::inflate_block(&compressed[offset..offset + size], &mut block)?;
+            self.text.extend_from_slice(&block);
+            offset += size;
+        }
+        compressed.drain(..offset);
+
+        Ok(())
+    }
+
+    /// Returns the next complete line without its line terminator.
+    fn next_line(&mut self) -> Option<Result<String>> {
+        let rest = &self.text[self.start..];
+        let (line, consumed) = match rest.iter().position(|&b| b == b'\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None if self.finished && !rest.is_empty() => (rest, rest.len()),
+            None => {
+                self.text.drain(..self.start);
+                self.start = 0;
+                return None;
+            }
+        };
+
+        let line = match std::str::from_utf8(line) {
+            Ok(line) => Ok(line.trim_end_matches('\r').to_string()),
+            Err(_) => Err(Error::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ))),
+        };
+        self.start += consumed;
+        self.line_number += 1;
+
+        Some(line)
+    }
+
+    fn parse_header(&mut self) -> Result<()> {
+        while !self.header_done {
+            let Some(line) = self.next_line() else {
+                break;
+            };
+            let line = line?;
+            self.header.parse_line(&line)?;
+
+            if line.starts_with("#CHROM") {
+                if self.header.fileformat.is_empty() {
+                    Err(Error::HeaderMissingFileformatError())?
+                }
+                self.header_done = true;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.header_done {
+            return None;
+        }
+
+        loop {
+            match self.next_line()? {
+                Ok(line) if line.trim_end().is_empty() => continue,
+                Ok(line) => {
+                    let context = ErrorContext::line(self.line_number);
+                    return Some(line.parse().map_err(|e: Error| e.with_context(context)));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+    use std::io::Write;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        ##contig=<ID=1,length=1000>\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+        1\t10\t.\tA\tT\t.\tPASS\tDP=3\tGT\t0/1\n\
+        \n\
+        1\t20\trs1\tAT\tA\t50\t.\t.\tGT\t1/1";
+
+    fn decode(mut decoder: Decoder, bytes: &[u8], chunk: usize) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for chunk in bytes.chunks(chunk) {
+            decoder.feed(chunk)?;
+            for record in decoder.by_ref() {
+                records.push(record?);
+            }
+        }
+        decoder.finish()?;
+        for record in decoder.by_ref() {
+            records.push(record?);
+        }
+        assert_eq!(decoder.header().unwrap().samples, vec!["S1"]);
+
+        Ok(records)
+    }
+
+    #[test]
+    fn test_decoder_1() {
+        let expected: Vec<Record> = Reader::new(VCF.as_bytes())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        for chunk in [1, 7, 4096] {
+            assert_eq!(
+                decode(Decoder::new(), VCF.as_bytes(), chunk).unwrap(),
+                expected
+            );
+        }
+
+        let mut partial = Decoder::new();
+        partial.feed(&VCF.as_bytes()[..30]).unwrap();
+        assert!(partial.header().is_none());
+        assert!(partial.next().is_none());
+        assert!(partial.finish().is_err());
+    }
+
+    #[test]
+    fn test_decoder_2() {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(&VCF.as_bytes()[..100]).unwrap();
+        writer.flush_block().unwrap();
+        writer.write_all(&VCF.as_bytes()[100..]).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        for chunk in [1, 13, 4096] {
+            let records = decode(Decoder::bgzf(), &bytes, chunk).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[1].ids, vec!["rs1"]);
+        }
+
+        assert!(decode(Decoder::bgzf(), &bytes[..bytes.len() - 1], 64).is_err());
+        assert!(decode(Decoder::bgzf(), VCF.as_bytes(), 64).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+//! Decomposition of MNVs into smaller variants, and merging of phased SNVs
+//! into MNVs.
+
+use crate::errors::Result;
+use crate::genotype::Genotype;
+use crate::header::{Header, InfoDefinition, Number, ValueType};
+use crate::record::{normalize_owned, NormalizedVariant, Record};
+use crate::reference::ReferenceSequence;
+use std::collections::VecDeque;
+
+/// INFO key listing the positions of the SNVs merged into an MNV.
+pub const MERGED_KEY: &str = "MERGED_POS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// Coding sequence of a transcript used to locate codons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub id: String,
+    pub chrom: String,
+    pub strand: Strand,
+    /// CDS segments as 1-based inclusive ranges in ascending genomic order.
+    pub cds: Vec<(u64, u64)>,
+}
+
+impl Transcript {
+    /// Returns the 0-based codon number covering `pos`, counted from the start codon.
+    pub fn codon_index(&self, chrom: &str, pos: u64) -> Option<u64> {
+        if chrom != self.chrom {
+            return None;
+        }
+
+        let mut offset = 0;
+        let segments: Box<dyn Iterator<Item = &(u64, u64)>> = match self.strand {
+            Strand::Forward => Box::new(self.cds.iter()),
+            Strand::Reverse => Box::new(self.cds.iter().rev()),
+        };
+
+        for &(start, end) in segments {
+            if (start..=end).contains(&pos) {
+                offset += match self.strand {
+                    Strand::Forward => pos - start,
+                    Strand::Reverse => end - pos,
+                };
+                return Some(offset / 3);
+            }
+            offset += end - start + 1;
+        }
+
+        None
+    }
+}
+
+/// How [`split_mnv`] treats substitutions spanning several bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MnvPolicy<'a> {
+    /// Keep MNVs intact.
+    Keep,
+    /// Split every MNV into SNVs.
+    Split,
+    /// Keep changed bases together when they fall in the same codon of one of
+    /// the transcripts, and split them otherwise.
+    ///
+    /// Bases outside every CDS are split into SNVs.
+    Codon(&'a [Transcript]),
+}
+
+/// Splits an MNV according to `policy`.
+///
+/// Alleles of different lengths are only normalized, never split.
+pub fn split_mnv(
+    chrom: &str,
+    position: u64,
+    reference: &str,
+    alternate: &str,
+    policy: MnvPolicy,
+) -> Result<Vec<NormalizedVariant>> {
+    let v = normalize_owned(position, reference.to_string(), alternate.to_string())?;
+
+    if v.reference.len() != v.alternate.len() || v.reference.len() == 1 {
+        return Ok(vec![v]);
+    }
+
+    let r = v.reference.as_bytes();
+    let a = v.alternate.as_bytes();
+
+    // (transcript, codon) covering the base at offset `i`.
+    let key = |i: usize| -> Option<(usize, u64)> {
+        match policy {
+            MnvPolicy::Codon(transcripts) => transcripts
+                .iter()
+                .enumerate()
+                .find_map(|(t, tx)| tx.codon_index(chrom, v.position + i as u64).map(|c| (t, c))),
+            _ => None,
+        }
+    };
+
+    let mut groups: Vec<(usize, usize, _)> = Vec::new();
+
+    for i in (0..r.len()).filter(|&i| r[i] != a[i]) {
+        let k = key(i);
+        match groups.last_mut() {
+            Some((_, end, last)) if policy == MnvPolicy::Keep || (k.is_some() && k == *last) => {
+                *end = i
+            }
+            _ => groups.push((i, i, k)),
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(start, end, _)| NormalizedVariant {
+            position: v.position + start as u64,
+            reference: v.reference[start..=end].to_string(),
+            alternate: v.alternate[start..=end].to_string(),
+        })
+        .collect())
+}
+
+/// Splits the MNV of a biallelic record into one record per resulting variant.
+///
+/// Other records are returned unchanged. INFO and sample fields are copied
+/// to every resulting record.
+pub fn decompose_mnv(record: &Record, policy: MnvPolicy) -> Result<Vec<Record>> {
+    if record.alternates.len() != 1 {
+        return Ok(vec![record.clone()]);
+    }
+
+    let variants = split_mnv(
+        &record.chrom,
+        record.pos,
+        &record.reference,
+        &record.alternates[0],
+        policy,
+    )?;
+
+    if variants.len() == 1 && variants[0].reference.len() == record.reference.len() {
+        return Ok(vec![record.clone()]);
+    }
+
+    Ok(variants
+        .into_iter()
+        .map(|v| {
+            let mut r = record.clone();
+            r.pos = v.position;
+            r.reference = v.reference;
+            r.alternates = vec![v.alternate];
+            r
+        })
+        .collect())
+}
+
+/// Options of [`PhasedSnvMerger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeOptions {
+    /// Largest number of unchanged bases between two merged SNVs. Gaps need
+    /// a reference sequence for their bases.
+    pub max_gap: u64,
+}
+
+impl Default for MergeOptions {
+    /// A gap of one base, enough to join the outer bases of a codon.
+    fn default() -> Self {
+        Self { max_gap: 1 }
+    }
+}
+
+/// Merges nearby SNVs of a sorted stream that lie on the same haplotypes
+/// into MNV records, so that consequences can be predicted per codon.
+///
+/// Biallelic SNVs merge when every sample has the same fully called `GT` at
+/// both, phased or homozygous, with equal `PS` if present, and some sample
+/// carries the ALT. The MNV spans the SNVs and the reference bases between
+/// them; it keeps the INFO and FORMAT values of the first SNV, the lowest
+/// QUAL and all IDs and FILTERs, and lists the merged positions in
+/// [`MERGED_KEY`]. Other records pass through.
+pub struct PhasedSnvMerger<'a, I> {
+    inner: I,
+    reference: Option<&'a dyn ReferenceSequence>,
+    options: MergeOptions,
+    /// SNVs that may still merge with the next record.
+    block: Vec<Record>,
+    queue: VecDeque<Result<Record>>,
+    merged: u64,
+}
+
+impl<'a, I: Iterator<Item = Result<Record>>> PhasedSnvMerger<'a, I> {
+    pub fn new(
+        inner: I,
+        reference: Option<&'a dyn ReferenceSequence>,
+        options: MergeOptions,
+    ) -> Self {
+        Self {
+            inner,
+            reference,
+            options,
+            block: Vec::new(),
+            queue: VecDeque::new(),
+            merged: 0,
+        }
+    }
+
+    /// Adds the definition of [`MERGED_KEY`] to `header` if missing.
+    pub fn update_header(header: &mut Header) {
+        if header.info(MERGED_KEY).is_none() {
+            header.infos.push(InfoDefinition::new(
+                MERGED_KEY,
+                Number::Unknown,
+                ValueType::Integer,
+                "Positions of the phased SNVs merged into this MNV",
+            ));
+        }
+    }
+
+    /// Number of SNVs merged into MNVs so far.
+    pub fn merged(&self) -> u64 {
+        self.merged
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Returns `true` if `next` can extend a block ending with `last`.
+    fn joins(&self, last: &Record, next: &Record) -> bool {
+        if last.chrom != next.chrom || next.pos <= last.pos {
+            return false;
+        }
+        let gap = next.pos - last.pos - 1;
+        if gap > self.options.max_gap || (gap > 0 && self.reference.is_none()) {
+            return false;
+        }
+        if last.samples.len() != next.samples.len() {
+            return false;
+        }
+
+        let resolved =
+            |gt: &Genotype| gt.is_phased() || gt.alleles.iter().all(|&x| x == gt.alleles[0]);
+
+        let mut carried = false;
+        for s in 0..last.samples.len() {
+            let (Ok(Some(a)), Ok(Some(b))) = (last.genotype(s), next.genotype(s)) else {
+                return false;
+            };
+            if a.alleles != b.alleles || a.alleles.contains(&None) || !resolved(&a) || !resolved(&b)
+            {
+                return false;
+            }
+            if last.format_value(s, "PS") != next.format_value(s, "PS") {
+                return false;
+            }
+            carried |= a.count(1) > 0;
+        }
+
+        carried
+    }
+
+    /// Builds the MNV of a block, or returns its only SNV.
+    fn finish(&mut self, mut block: Vec<Record>) -> Result<Record> {
+        if block.len() == 1 {
+            return Ok(block.remove(0));
+        }
+
+        let first = &block[0];
+        let last = &block[block.len() - 1];
+        let bases = match self.reference {
+            Some(sequence) => sequence.fetch(&first.chrom, first.pos, last.pos)?,
+            None => Vec::new(),
+        };
+
+        let mut merged = first.clone();
+        merged.reference.clear();
+        merged.alternates = vec![String::new()];
+        let mut snvs = block.iter().peekable();
+        for pos in first.pos..=last.pos {
+            match snvs.next_if(|r| r.pos == pos) {
+                Some(snv) => {
+                    merged.reference.push_str(&snv.reference);
+                    merged.alternates[0].push_str(&snv.alternates[0]);
+                }
+                None => {
+                    let base = bases
+                        .get((pos - first.pos) as usize)
+                        .map_or('N', |&b| b as char);
+                    merged.reference.push(base);
+                    merged.alternates[0].push(base);
+                }
+            }
+        }
+
+        for snv in &block[1..] {
+            for id in &snv.ids {
+                if !merged.ids.contains(id) {
+                    merged.ids.push(id.clone());
+                }
+            }
+            for filter in &snv.filters {
+                if !merged.filters.contains(filter) {
+                    merged.filters.push(filter.clone());
+                }
+            }
+            merged.qual = match (merged.qual, snv.qual) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                _ => None,
+            };
+        }
+        if merged.filters.len() > 1 {
+            merged.filters.retain(|f| f != "PASS");
+        }
+        let positions: Vec<String> = block.iter().map(|r| r.pos.to_string()).collect();
+        merged.set_info(MERGED_KEY, Some(positions.join(",")));
+
+        self.merged += block.len() as u64;
+        Ok(merged)
+    }
+}
+
+/// Returns `true` for biallelic SNVs.
+fn is_snv(record: &Record) -> bool {
+    record.reference.len() == 1
+        && record.alternates.len() == 1
+        && record.alternates[0].len() == 1
+        && record.alternates[0] != "*"
+        && !record.reference.eq_ignore_ascii_case(&record.alternates[0])
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for PhasedSnvMerger<'_, I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.queue.pop_front() {
+                return Some(item);
+            }
+
+            let done = match self.inner.next() {
+                Some(Ok(record)) if is_snv(&record) => {
+                    if self
+                        .block
+                        .last()
+                        .is_some_and(|last| self.joins(last, &record))
+                    {
+                        self.block.push(record);
+                        continue;
+                    }
+                    std::mem::replace(&mut self.block, vec![record])
+                }
+                Some(item) => {
+                    self.queue.push_back(item);
+                    std::mem::take(&mut self.block)
+                }
+                None if self.block.is_empty() => return None,
+                None => std::mem::take(&mut self.block),
+            };
+
+            if !done.is_empty() {
+                return Some(self.finish(done));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::MemoryReference;
+
+    fn transcript(strand: Strand) -> Transcript {
+        Transcript {
+            id: "NM_1".to_string(),
+            chrom: "1".to_string(),
+            strand,
+            cds: vec![(100, 104), (200, 210)],
+        }
+    }
+
+    fn alleles(variants: &[NormalizedVariant]) -> Vec<(u64, &str, &str)> {
+        variants
+            .iter()
+            .map(|v| (v.position, v.reference.as_str(), v.alternate.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_codon_index_1() {
+        let tx = transcript(Strand::Forward);
+
+        assert_eq!(tx.codon_index("1", 100), Some(0));
+        assert_eq!(tx.codon_index("1", 103), Some(1));
+        assert_eq!(tx.codon_index("1", 200), Some(1));
+        assert_eq!(tx.codon_index("1", 201), Some(2));
+        assert_eq!(tx.codon_index("1", 150), None);
+        assert_eq!(tx.codon_index("2", 100), None);
+    }
+
+    #[test]
+    fn test_codon_index_2() {
+        let tx = transcript(Strand::Reverse);
+
+        assert_eq!(tx.codon_index("1", 210), Some(0));
+        assert_eq!(tx.codon_index("1", 208), Some(0));
+        assert_eq!(tx.codon_index("1", 200), Some(3));
+        assert_eq!(tx.codon_index("1", 104), Some(3));
+        assert_eq!(tx.codon_index("1", 103), Some(4));
+    }
+
+    #[test]
+    fn test_split_mnv_1() {
+        let v = split_mnv("1", 99, "AACGT", "ATCAT", MnvPolicy::Split).unwrap();
+
+        assert_eq!(alleles(&v), vec![(100, "A", "T"), (102, "G", "A")]);
+
+        let v = split_mnv("1", 99, "AACGT", "ATCAT", MnvPolicy::Keep).unwrap();
+
+        assert_eq!(alleles(&v), vec![(100, "ACG", "TCA")]);
+    }
+
+    #[test]
+    fn test_split_mnv_2() {
+        let transcripts = [transcript(Strand::Forward)];
+        let policy = MnvPolicy::Codon(&transcripts);
+
+        // 100..=102 is codon 0, 103..=104 + 200 is codon 1.
+        let v = split_mnv("1", 100, "ACGTA", "TCAAC", policy).unwrap();
+
+        assert_eq!(alleles(&v), vec![(100, "ACG", "TCA"), (103, "TA", "AC")]);
+
+        let v = split_mnv("1", 148, "AAA", "CCC", policy).unwrap();
+
+        assert_eq!(
+            alleles(&v),
+            vec![(148, "A", "C"), (149, "A", "C"), (150, "A", "C")]
+        );
+    }
+
+    #[test]
+    fn test_split_mnv_3() {
+        let v = split_mnv("1", 100, "A", "AT", MnvPolicy::Split).unwrap();
+
+        assert_eq!(alleles(&v), vec![(100, "A", "AT")]);
+        assert!(split_mnv("1", 100, "", "AT", MnvPolicy::Split).is_err());
+    }
+
+    #[test]
+    fn test_decompose_mnv_1() {
+        let record: Record = "1\t100\t.\tACG\tTCA\t50\tPASS\tDP=3\tGT\t0/1"
+            .parse()
+            .unwrap();
+        let records = decompose_mnv(&record, MnvPolicy::Split).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1].to_string(),
+            "1\t102\t.\tG\tA\t50\tPASS\tDP=3\tGT\t0/1"
+        );
+
+        let records = decompose_mnv(&record, MnvPolicy::Keep).unwrap();
+
+        assert_eq!(records, vec![record]);
+    }
+
+    #[test]
+    fn test_phased_snv_merger_1() {
+        let lines = [
+            "1\t2\trs1\tC\tT\t40\tPASS\tDP=5\tGT:PS\t0|1:2\t1|1:2",
+            "1\t3\trs2\tG\tA\t30\tq10\t.\tGT:PS\t0|1:2\t1|1:2",
+            "1\t5\t.\tG\tC\t50\tPASS\t.\tGT:PS\t0|1:2\t1|1:2",
+            "1\t6\t.\tT\tA\t50\tPASS\t.\tGT:PS\t1|0:2\t1|1:2",
+            "1\t7\t.\tA\tAT\t50\tPASS\t.\tGT:PS\t1|0:2\t1|1:2",
+            "1\t8\t.\tC\tG\t50\tPASS\t.\tGT:PS\t1/0:2\t1|1:2",
+            "1\t9\t.\tA\tG\t50\tPASS\t.\tGT:PS\t1/0:2\t1|1:2",
+        ];
+        let records = || lines.iter().map(|l| l.parse::<Record>());
+
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "ACGTGTACA");
+        let mut merger = PhasedSnvMerger::new(records(), Some(&reference), MergeOptions::default());
+        let merged: Vec<String> = merger.by_ref().map(|r| r.unwrap().to_string()).collect();
+
+        assert_eq!(
+            merged[0],
+            "1\t2\trs1;rs2\tCGTG\tTATC\t30\tq10\tDP=5;MERGED_POS=2,3,5\tGT:PS\t0|1:2\t1|1:2"
+        );
+        assert_eq!(merged.len(), 5);
+        assert_eq!(merger.merged(), 3);
+
+        // Without a reference only adjacent SNVs merge.
+        let merger = PhasedSnvMerger::new(records(), None, MergeOptions::default());
+        let alleles: Vec<String> = merger.map(|r| r.unwrap().reference).collect();
+        assert_eq!(alleles, vec!["CG", "G", "T", "A", "C", "A"]);
+    }
+
+    #[test]
+    fn test_phased_snv_merger_2() {
+        let lines = [
+            "1\t10\t.\tA\tC\t.\t.\t.\tGT\t0|1",
+            "1\t11\t.\tG\tT\t.\t.\t.\tGT\t0/1",
+            "1\t12\t.\tC\tA\t.\t.\t.\tGT\t1/1",
+            "1\t13\t.\tT\tG\t.\t.\t.\tGT\t1|1",
+        ];
+        let records = lines.iter().map(|l| l.parse::<Record>());
+
+        let merger = PhasedSnvMerger::new(records, None, MergeOptions::default());
+        let alleles: Vec<String> = merger.map(|r| r.unwrap().reference).collect();
+
+        // An unphased heterozygous SNV after a phased one stays apart, while
+        // homozygous SNVs merge whether phased or not.
+        assert_eq!(alleles, vec!["A", "G", "CT"]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Removal of records that describe the same variant.
+
+use crate::errors::{Error, Result};
+use crate::record::{NormalizedVariant, Record};
+use std::collections::VecDeque;
+
+/// What to do with a record identical to an earlier one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Drop the later record.
+    #[default]
+    KeepFirst,
+    /// Drop the later record after adding its IDs, and INFO keys missing from
+    /// the first record, to the first record.
+    MergeInfo,
+    /// Fail with [`Error::DuplicateRecordError`].
+    Error,
+}
+
+/// A record waiting for possible duplicates.
+struct Pending {
+    record: Record,
+    variants: Vec<NormalizedVariant>,
+    /// Largest normalized position; no later record can be a duplicate past it.
+    reach: u64,
+}
+
+/// Collapses records of a sorted stream with the same CHROM and the same
+/// ALT alleles, in the same order, after normalization.
+///
+/// Decomposition and left alignment often produce such duplicates, possibly
+/// at different positions of the input. Records are yielded in input order
+/// once no later record can duplicate them.
+pub struct Deduplicator<I: Iterator<Item = Result<Record>>> {
+    inner: I,
+    policy: DuplicatePolicy,
+    pending: VecDeque<Pending>,
+    duplicates: u64,
+    /// Next input record, read before settling pending records.
+    ahead: Option<Record>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> Deduplicator<I> {
+    pub fn new(inner: I, policy: DuplicatePolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            pending: VecDeque::new(),
+            duplicates: 0,
+            ahead: None,
+            done: false,
+        }
+    }
+
+    /// Number of records collapsed so far.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Adds `record` to the pending records, unless it duplicates one.
+    fn push(&mut self, record: Record) -> Result<()> {
+        let variants = record.normalized_alternates();
+
+        let first = self
+            .pending
+            .iter_mut()
+            .find(|p| p.record.chrom == record.chrom && p.variants == variants);
+
+        let Some(first) = first else {
+            let reach = variants
+                .iter()
+                .map(|v| v.position)
+                .max()
+                .unwrap_or(record.pos);
+            self.pending.push_back(Pending {
+                record,
+                variants,
+                reach,
+            });
+            return Ok(());
+        };
+
+        match self.policy {
+            DuplicatePolicy::KeepFirst => {}
+            DuplicatePolicy::MergeInfo => {
+                let first = &mut first.record;
+                for id in record.ids {
+                    if !first.ids.contains(&id) {
+                        first.ids.push(id);
+                    }
+                }
+                for (key, value) in record.info {
+                    if !first.has_info(&key) {
+                        first.info.push((key, value));
+                    }
+                }
+            }
+            DuplicatePolicy::Error => Err(Error::DuplicateRecordError(record.chrom, record.pos))?,
+        }
+        self.duplicates += 1;
+
+        Ok(())
+    }
+
+    /// Returns the front record if no record at `(chrom, pos)` or later can
+    /// duplicate it; `None` settles every pending record.
+    fn pop_settled(&mut self, next: Option<(&str, u64)>) -> Option<Record> {
+        let front = self.pending.front()?;
+        let settled =
+            next.is_none_or(|(chrom, pos)| front.record.chrom != chrom || front.reach < pos);
+
+        if settled {
+            self.pending.pop_front().map(|p| p.record)
+        } else {
+            None
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for Deduplicator<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.ahead.is_none() && !self.done {
+                match self.inner.next() {
+                    Some(Ok(record)) => self.ahead = Some(record),
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.done = true,
+                }
+            }
+
+            let Some(record) = self.ahead.take() else {
+                return self.pop_settled(None).map(Ok);
+            };
+
+            if let Some(settled) = self.pop_settled(Some((&record.chrom, record.pos))) {
+                self.ahead = Some(record);
+                return Some(Ok(settled));
+            }
+            if let Err(e) = self.push(record) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDS: &[&str] = &[
+        "1\t10\trs1\tCAT\tCGT\t.\t.\tDP=3",
+        "1\t11\trs2\tA\tG\t.\t.\tDP=5;AF=0.5",
+        "1\t11\t.\tA\tT\t.\t.\t.",
+        "1\t11\t.\tA\tG\t.\t.\tDB",
+        "2\t11\t.\tA\tG\t.\t.\t.",
+    ];
+
+    fn dedup(policy: DuplicatePolicy) -> (Result<Vec<String>>, u64) {
+        let records = RECORDS.iter().map(|l| l.parse());
+        let mut dedup = Deduplicator::new(records, policy);
+        let lines = dedup.by_ref().map(|r| r.map(|r| r.to_string())).collect();
+
+        (lines, dedup.duplicates())
+    }
+
+    #[test]
+    fn test_deduplicator_1() {
+        let (lines, duplicates) = dedup(DuplicatePolicy::KeepFirst);
+
+        assert_eq!(
+            lines.unwrap(),
+            vec![
+                "1\t10\trs1\tCAT\tCGT\t.\t.\tDP=3",
+                "1\t11\t.\tA\tT\t.\t.\t.",
+                "2\t11\t.\tA\tG\t.\t.\t."
+            ]
+        );
+        assert_eq!(duplicates, 2);
+    }
+
+    #[test]
+    fn test_deduplicator_2() {
+        let (lines, _) = dedup(DuplicatePolicy::MergeInfo);
+
+        assert_eq!(
+            lines.unwrap()[0],
+            "1\t10\trs1;rs2\tCAT\tCGT\t.\t.\tDP=3;AF=0.5;DB"
+        );
+
+        let (lines, _) = dedup(DuplicatePolicy::Error);
+
+        assert!(matches!(lines, Err(Error::DuplicateRecordError(c, 11)) if c == "1"));
+    }
+}
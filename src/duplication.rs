@@ -0,0 +1,224 @@
+//! Insertions that duplicate the adjacent reference sequence.
+
+use crate::errors::Result;
+use crate::header::{AltDefinition, Header, InfoDefinition, Number, ValueType};
+use crate::record::{normalize_with_reference, Direction, NormalizeOptions, Record};
+use crate::reference::ReferenceSequence;
+use crate::validation::is_symbolic;
+
+/// A tandem duplication of `start..=end`, 1-based inclusive.
+///
+/// Coordinates follow the HGVS 3' rule: the insertion is shifted as far
+/// right as the reference allows, and the duplicated copy is the one
+/// immediately before the insertion point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplication {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    /// Duplicated bases.
+    pub sequence: String,
+}
+
+impl Duplication {
+    /// Returns the duplication described by an insertion, or `None` if the
+    /// variant is not an insertion of the preceding reference bases.
+    ///
+    /// REF must match `sequence` at `position`.
+    pub fn detect<S: ReferenceSequence + ?Sized>(
+        chrom: &str,
+        position: u64,
+        reference: &str,
+        alternate: &str,
+        sequence: &S,
+    ) -> Result<Option<Self>> {
+        let options = NormalizeOptions {
+            direction: Direction::Right,
+            ..NormalizeOptions::default()
+        };
+        let v =
+            normalize_with_reference(chrom, position, reference, alternate, sequence, &options)?;
+
+        // Insertions are padded with the preceding base, except at the contig
+        // start where nothing precedes them.
+        let inserted = match v.alternate.strip_prefix(v.reference.as_str()) {
+            Some(inserted) if v.reference.len() == 1 && !inserted.is_empty() => inserted,
+            _ => return Ok(None),
+        };
+
+        let len = inserted.len() as u64;
+        if v.position < len {
+            return Ok(None);
+        }
+        let start = v.position - len + 1;
+
+        if sequence.fetch(chrom, start, v.position)? != inserted.as_bytes() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            chrom: chrom.to_string(),
+            start,
+            end: v.position,
+            sequence: inserted.to_string(),
+        }))
+    }
+
+    /// Like [`Duplication::detect`], for the ALT allele of a biallelic record.
+    ///
+    /// Multiallelic records and symbolic alleles are never duplications.
+    pub fn from_record<S: ReferenceSequence + ?Sized>(
+        record: &Record,
+        sequence: &S,
+    ) -> Result<Option<Self>> {
+        match record.alternates.as_slice() {
+            [alt] if !is_symbolic(alt) => {
+                Self::detect(&record.chrom, record.pos, &record.reference, alt, sequence)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequence.is_empty()
+    }
+
+    /// Genomic HGVS description, e.g. `1:g.5_7dup`.
+    pub fn hgvs(&self) -> String {
+        if self.start == self.end {
+            format!("{}:g.{}dup", self.chrom, self.start)
+        } else {
+            format!("{}:g.{}_{}dup", self.chrom, self.start, self.end)
+        }
+    }
+
+    /// Rewrites `record` as a symbolic `<DUP>` with `SVTYPE`, `END` and
+    /// `SVLEN`.
+    ///
+    /// POS is the base before the duplication, or its first base at the
+    /// contig start.
+    pub fn apply_symbolic<S: ReferenceSequence + ?Sized>(
+        &self,
+        record: &mut Record,
+        sequence: &S,
+    ) -> Result<()> {
+        let (pos, base) = match sequence.base(&self.chrom, self.start - 1)? {
+            Some(base) => (self.start - 1, base),
+            None => (self.start, self.sequence.as_bytes()[0]),
+        };
+
+        record.pos = pos;
+        record.reference = (base as char).to_string();
+        record.alternates = vec!["<DUP>".to_string()];
+        record.set_info("SVTYPE", Some("DUP".to_string()));
+        record.set_info("END", Some(self.end.to_string()));
+        record.set_info("SVLEN", Some(self.len().to_string()));
+
+        Ok(())
+    }
+
+    /// Adds the definitions used by [`Duplication::apply_symbolic`] that are
+    /// missing from `header`.
+    pub fn update_header(header: &mut Header) {
+        if !header.alts.iter().any(|a| a.id == "DUP") {
+            header.alts.push(AltDefinition::new("DUP", "Duplication"));
+        }
+
+        let infos = [
+            (
+                "SVTYPE",
+                Number::Count(1),
+                ValueType::String,
+                "Type of structural variant",
+            ),
+            (
+                "END",
+                Number::Count(1),
+                ValueType::Integer,
+                "End position of the variant described in this record",
+            ),
+            (
+                "SVLEN",
+                Number::A,
+                ValueType::Integer,
+                "Difference in length between REF and ALT alleles",
+            ),
+        ];
+        for (id, number, value_type, description) in infos {
+            if header.info(id).is_none() {
+                header
+                    .infos
+                    .push(InfoDefinition::new(id, number, value_type, description));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::MemoryReference;
+
+    fn reference() -> MemoryReference {
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "ACAGCAGTTG");
+
+        reference
+    }
+
+    #[test]
+    fn test_detect_1() {
+        let reference = reference();
+
+        // CAG inserted after 1:4 is shifted through the repeat to 1:7.
+        let dup = Duplication::detect("1", 4, "G", "GCAG", &reference)
+            .unwrap()
+            .unwrap();
+        assert_eq!((dup.start, dup.end, dup.len()), (5, 7, 3));
+        assert_eq!(dup.sequence, "CAG");
+        assert_eq!(dup.hgvs(), "1:g.5_7dup");
+
+        let dup = Duplication::detect("1", 8, "T", "TT", &reference)
+            .unwrap()
+            .unwrap();
+        assert_eq!(dup.hgvs(), "1:g.9dup");
+
+        assert_eq!(
+            Duplication::detect("1", 4, "G", "GTC", &reference).unwrap(),
+            None
+        );
+        assert_eq!(
+            Duplication::detect("1", 4, "GC", "G", &reference).unwrap(),
+            None
+        );
+        assert!(Duplication::detect("1", 4, "A", "AC", &reference).is_err());
+    }
+
+    #[test]
+    fn test_apply_symbolic_1() {
+        let reference = reference();
+        let mut record: Record = "1\t4\trs1\tG\tGCAG\t30\tPASS\tDP=5".parse().unwrap();
+
+        let dup = Duplication::from_record(&record, &reference)
+            .unwrap()
+            .unwrap();
+        dup.apply_symbolic(&mut record, &reference).unwrap();
+
+        assert_eq!(
+            record.to_string(),
+            "1\t4\trs1\tG\t<DUP>\t30\tPASS\tDP=5;SVTYPE=DUP;END=7;SVLEN=3"
+        );
+        assert_eq!(Duplication::from_record(&record, &reference).unwrap(), None);
+
+        let mut header = Header::new("VCFv4.3");
+        Duplication::update_header(&mut header);
+        Duplication::update_header(&mut header);
+
+        assert_eq!(header.alts.len(), 1);
+        assert_eq!(header.infos.len(), 3);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+use std::path::PathBuf;
 use thiserror::Error;
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -15,4 +17,314 @@ pub enum Error {
 
     #[error("Alternate bases contains non-ACGT characters: {0}")]
     AltBasesInvalidSymbolError(String),
+
+    #[error("Allele contains non-IUPAC characters: {0}")]
+    IupacInvalidSymbolError(String),
+
+    #[error("Record has too few columns: {0}")]
+    RecordColumnsError(usize),
+
+    #[error("Record has an invalid position: {0}")]
+    RecordPositionError(String),
+
+    #[error("Record has an invalid quality: {0}")]
+    RecordQualError(String),
+
+    #[error("Malformed header line: {0}")]
+    HeaderLineError(String),
+
+    #[error("Invalid {0} value in header: {1}")]
+    HeaderValueError(String, String),
+
+    #[error("Header has no ##fileformat line")]
+    HeaderMissingFileformatError(),
+
+    #[error("Malformed index: {0}")]
+    IndexFormatError(String),
+
+    #[error("Malformed cache entry: {0}")]
+    CacheFormatError(String),
+
+    #[error("Unknown contig: {0}")]
+    UnknownContigError(String),
+
+    #[error("Reference bases at {0}:{1} do not match the genome: {2} != {3}")]
+    RefMismatchError(String, u64, String, String),
+
+    #[error("Unsupported VCF version: {0}")]
+    UnsupportedVersionError(String),
+
+    #[error("Record does not comply with {0}: {1}")]
+    NonCompliantRecordError(String, String),
+
+    #[error("Invalid percent-encoding: {0}")]
+    PercentEncodingError(String),
+
+    #[error("Invalid genotype: {0}")]
+    GenotypeParseError(String),
+
+    #[error("Unsorted record at {0}:{1} after {2}:{3}")]
+    UnsortedRecordError(String, u64, String, u64),
+
+    #[error("Duplicate sample: {0}")]
+    DuplicateSampleError(String),
+
+    #[error("Duplicate record at {0}:{1}")]
+    DuplicateRecordError(String, u64),
+
+    #[error("Invalid region: {0}")]
+    RegionFormatError(String),
+
+    #[error("Invalid expression: {0}")]
+    ExpressionError(String),
+
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodecError(String),
+
+    #[error("No index for {0}")]
+    MissingIndexError(String),
+
+    #[error("Invalid field path: {0}")]
+    FieldPathError(String),
+
+    #[error("Malformed chain: {0}")]
+    ChainFormatError(String),
+
+    #[error("Invalid variant key: {0}")]
+    VariantKeyError(String),
+
+    #[error("Malformed pedigree: {0}")]
+    PedigreeFormatError(String),
+
+    #[error("Record is missing required field: {0}")]
+    MissingFieldError(String),
+
+    #[error("Invalid value for {0}: {1}")]
+    FieldValueError(String, String),
+
+    #[error("Sample index out of range: {0}")]
+    SampleIndexError(usize),
+
+    #[error("Too many genotypes of {0} alleles and ploidy {1}")]
+    GenotypeCountError(usize, usize),
+
+    #[error("{0}: {1}")]
+    ContextError(ErrorContext, #[source] Box<Error>),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+impl Error {
+    /// The error without its [`ErrorContext`], e.g. for matching on variants.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::ContextError(_, error) => error.inner(),
+            error => error,
+        }
+    }
+
+    /// Where the error occurred, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::ContextError(context, _) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Attaches `context`; parts already known are kept.
+    pub fn with_context(self, context: ErrorContext) -> Error {
+        match self {
+            Error::ContextError(known, error) => Error::ContextError(
+                ErrorContext {
+                    path: known.path.or(context.path),
+                    line: known.line.or(context.line),
+                    column: known.column.or(context.column),
+                    field: known.field.or(context.field),
+                },
+                error,
+            ),
+            error => {
+                let mut context = context;
+                if let (None, Some((column, field))) = (context.column, error.field()) {
+                    context.column = Some(column);
+                    context.field = Some(field.to_string());
+                }
+                Error::ContextError(context, Box::new(error))
+            }
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self.inner() {
+            Error::HeaderLineError(..)
+            | Error::HeaderValueError(..)
+            | Error::HeaderMissingFileformatError()
+            | Error::UnsupportedVersionError(..) => ErrorCategory::Header,
+            Error::RecordColumnsError(..)
+            | Error::RecordPositionError(..)
+            | Error::RecordQualError(..)
+            | Error::PercentEncodingError(..)
+            | Error::GenotypeParseError(..)
+            | Error::MissingFieldError(..)
+            | Error::FieldValueError(..) => ErrorCategory::Record,
+            Error::RefBasesEmptyError()
+            | Error::AltBasesEmptyError()
+            | Error::RefBasesInvalidSymbolError(..)
+            | Error::AltBasesInvalidSymbolError(..)
+            | Error::IupacInvalidSymbolError(..)
+            | Error::RefMismatchError(..) => ErrorCategory::Allele,
+            Error::NonCompliantRecordError(..) => ErrorCategory::Compliance,
+            Error::UnsortedRecordError(..)
+            | Error::DuplicateSampleError(..)
+            | Error::DuplicateRecordError(..) => ErrorCategory::Order,
+            Error::IndexFormatError(..)
+            | Error::CacheFormatError(..)
+            | Error::RegionFormatError(..)
+            | Error::ChainFormatError(..)
+            | Error::PedigreeFormatError(..)
+            | Error::UnknownContigError(..)
+            | Error::MissingIndexError(..) => ErrorCategory::Auxiliary,
+            Error::ExpressionError(..)
+            | Error::UnsupportedCodecError(..)
+            | Error::FieldPathError(..)
+            | Error::VariantKeyError(..)
+            | Error::SampleIndexError(..)
+            | Error::GenotypeCountError(..) => ErrorCategory::Argument,
+            Error::IoError(..) => ErrorCategory::Io,
+            Error::ContextError(_, error) => error.category(),
+        }
+    }
+
+    /// The data line column, counted from 1, and field an error is about.
+    fn field(&self) -> Option<(usize, &'static str)> {
+        match self {
+            Error::RecordPositionError(..) => Some((2, "POS")),
+            Error::RefBasesEmptyError() | Error::RefBasesInvalidSymbolError(..) => Some((4, "REF")),
+            Error::AltBasesEmptyError() | Error::AltBasesInvalidSymbolError(..) => Some((5, "ALT")),
+            Error::RecordQualError(..) => Some((6, "QUAL")),
+            _ => None,
+        }
+    }
+}
+
+/// Machine-readable kind of an [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ErrorCategory {
+    /// Malformed or unsupported header.
+    Header,
+    /// Malformed data line.
+    Record,
+    /// Invalid REF or ALT alleles.
+    Allele,
+    /// Record breaking the rules of its VCF version.
+    Compliance,
+    /// Unsorted or duplicate records or samples.
+    Order,
+    /// Malformed or missing index, cache, region, chain or contig.
+    Auxiliary,
+    /// Invalid expression, field path, key or codec given by the caller.
+    Argument,
+    Io,
+}
+
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Header => "header",
+            ErrorCategory::Record => "record",
+            ErrorCategory::Allele => "allele",
+            ErrorCategory::Compliance => "compliance",
+            ErrorCategory::Order => "order",
+            ErrorCategory::Auxiliary => "auxiliary",
+            ErrorCategory::Argument => "argument",
+            ErrorCategory::Io => "io",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Location of an error in its input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub path: Option<PathBuf>,
+    /// Line number, counted from 1 and including header lines.
+    pub line: Option<u64>,
+    /// Tab-separated column, counted from 1.
+    pub column: Option<usize>,
+    /// Name of the column, e.g. `POS`.
+    pub field: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn line(line: u64) -> Self {
+        Self {
+            line: Some(line),
+            ..Self::default()
+        }
+    }
+
+    pub fn path<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    /// Writes e.g. `in.vcf, line 12, column 2 (POS)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(path) = &self.path {
+            parts.push(path.display().to_string());
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("line {}", line));
+        }
+        match (self.column, &self.field) {
+            (Some(column), Some(field)) => parts.push(format!("column {} ({})", column, field)),
+            (Some(column), None) => parts.push(format!("column {}", column)),
+            (None, Some(field)) => parts.push(field.to_string()),
+            (None, None) => {}
+        }
+
+        f.write_str(&parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_context_1() {
+        let error = Error::RecordPositionError("x".to_string()).with_context(ErrorContext::line(5));
+
+        assert_eq!(
+            error.to_string(),
+            "line 5, column 2 (POS): Record has an invalid position: x"
+        );
+        assert_eq!(error.category(), ErrorCategory::Record);
+
+        let error = error.with_context(ErrorContext::path("in.vcf"));
+        let context = error.context().unwrap();
+        assert_eq!(context.line, Some(5));
+        assert_eq!(context.field.as_deref(), Some("POS"));
+        assert!(error
+            .to_string()
+            .starts_with("in.vcf, line 5, column 2 (POS): "));
+        assert!(matches!(error.inner(), Error::RecordPositionError(_)));
+
+        let io = Error::from(std::io::Error::other("x"));
+        assert_eq!(io.category(), ErrorCategory::Io);
+        assert!(io.context().is_none());
+    }
 }
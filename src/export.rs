@@ -0,0 +1,337 @@
+//! Flattening of records into tables for dataframe tooling.
+//!
+//! A [`Flattener`] turns each record into rows of a fixed set of columns:
+//! the site columns `CHROM` to `FILTER`, then the selected INFO keys, then
+//! the selected FORMAT keys. With [`Layout::Site`] and [`Layout::Allele`]
+//! FORMAT keys become one `SAMPLE:KEY` column per sample; with
+//! [`Layout::Sample`] a `SAMPLE` column follows `FILTER` and each sample is
+//! a row of its own. [`export_tsv`] writes the rows as tab-separated text.
+//!
+//! There are no Arrow or Parquet writers yet; they are an open follow-up
+//! and would take their columns and rows from a [`Flattener`] as well.
+
+use crate::errors::Result;
+use crate::header::{Header, Number};
+use crate::pipeline::skip;
+use crate::record::Record;
+use crate::summary::{RunSummary, Timer};
+use std::io::Write;
+
+/// What one row stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// One row per record, with all ALT alleles in one column.
+    #[default]
+    Site,
+    /// One row per ALT allele. `Number=A` and `Number=R` values are split
+    /// to the allele's value.
+    Allele,
+    /// One row per record and sample.
+    Sample,
+}
+
+/// Columns and rows of an export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub layout: Layout,
+    /// INFO keys to export; flags are `1` when set.
+    pub info: Vec<String>,
+    /// FORMAT keys to export.
+    pub format: Vec<String>,
+    /// Text written for missing values by [`export_tsv`].
+    pub missing: String,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Site,
+            info: Vec::new(),
+            format: Vec::new(),
+            missing: ".".to_string(),
+        }
+    }
+}
+
+/// Flattens records into rows of optional values.
+pub struct Flattener {
+    options: ExportOptions,
+    samples: Vec<String>,
+    info: Vec<(String, Number)>,
+    format: Vec<(String, Number)>,
+}
+
+impl Flattener {
+    pub fn new(header: &Header, options: ExportOptions) -> Self {
+        let info = options
+            .info
+            .iter()
+            .map(|k| {
+                (
+                    k.clone(),
+                    header.info(k).map_or(Number::Unknown, |d| d.number),
+                )
+            })
+            .collect();
+        let format = options
+            .format
+            .iter()
+            .map(|k| {
+                (
+                    k.clone(),
+                    header.format(k).map_or(Number::Unknown, |d| d.number),
+                )
+            })
+            .collect();
+
+        Self {
+            options,
+            samples: header.samples.clone(),
+            info,
+            format,
+        }
+    }
+
+    /// Names of the columns, in row order.
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = ["CHROM", "POS", "ID", "REF", "ALT", "QUAL", "FILTER"]
+            .map(String::from)
+            .to_vec();
+        if self.options.layout == Layout::Sample {
+            columns.push("SAMPLE".to_string());
+        }
+        columns.extend(self.info.iter().map(|(k, _)| k.clone()));
+        match self.options.layout {
+            Layout::Sample => columns.extend(self.format.iter().map(|(k, _)| k.clone())),
+            _ => {
+                for sample in &self.samples {
+                    columns.extend(self.format.iter().map(|(k, _)| format!("{}:{}", sample, k)));
+                }
+            }
+        }
+
+        columns
+    }
+
+    /// Rows of `record`; missing values are `None`.
+    ///
+    /// A record without ALT alleles is a single row with [`Layout::Allele`],
+    /// and so is a header without samples with [`Layout::Sample`], where
+    /// `SAMPLE` and the FORMAT columns are missing.
+    pub fn rows(&self, record: &Record) -> Vec<Vec<Option<String>>> {
+        match self.options.layout {
+            Layout::Site => vec![self.row(record, None, None)],
+            Layout::Allele if record.alternates.is_empty() => vec![self.row(record, None, None)],
+            Layout::Allele => (0..record.alternates.len())
+                .map(|i| self.row(record, Some(i), None))
+                .collect(),
+            Layout::Sample if self.samples.is_empty() => {
+                let mut row = self.row(record, None, None);
+                row.insert(7, None);
+                row.extend(self.format.iter().map(|_| None));
+                vec![row]
+            }
+            Layout::Sample => (0..self.samples.len())
+                .map(|s| self.row(record, None, Some(s)))
+                .collect(),
+        }
+    }
+
+    fn row(
+        &self,
+        record: &Record,
+        allele: Option<usize>,
+        sample: Option<usize>,
+    ) -> Vec<Option<String>> {
+        let joined = |values: &[String], separator: &str| {
+            (!values.is_empty()).then(|| values.join(separator))
+        };
+
+        let mut row = vec![
+            Some(record.chrom.clone()),
+            Some(record.pos.to_string()),
+            joined(&record.ids, ";"),
+            Some(record.reference.clone()),
+            match allele {
+                Some(i) => Some(record.alternates[i].clone()),
+                None => joined(&record.alternates, ","),
+            },
+            record.qual.map(|q| q.to_string()),
+            joined(&record.filters, ";"),
+        ];
+        if let Some(s) = sample {
+            row.push(Some(self.samples[s].clone()));
+        }
+
+        for (key, number) in &self.info {
+            let value = match record.info(key) {
+                None if record.has_info(key) => Some("1"),
+                value => value,
+            };
+            row.push(pick(value, *number, allele));
+        }
+
+        let samples = match sample {
+            Some(s) => s..s + 1,
+            None => 0..self.samples.len(),
+        };
+        for s in samples {
+            for (key, number) in &self.format {
+                row.push(pick(record.format_value(s, key), *number, allele));
+            }
+        }
+
+        row
+    }
+}
+
+/// The value of `allele` for `Number=A` and `Number=R` fields, else the
+/// whole value; `.` is missing.
+fn pick(value: Option<&str>, number: Number, allele: Option<usize>) -> Option<String> {
+    let value = match (number, allele) {
+        (Number::A, Some(i)) => value?.split(',').nth(i),
+        (Number::R, Some(i)) => value?.split(',').nth(i + 1),
+        _ => value,
+    };
+
+    value.filter(|&v| v != ".").map(String::from)
+}
+
+/// Writes the records as a table with a line of column names.
+///
+/// Records that fail to parse are skipped; `records_out` counts rows.
+pub fn export_tsv<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut W,
+    options: ExportOptions,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("export");
+
+    let missing = options.missing.clone();
+    let flattener = Flattener::new(header, options);
+    writeln!(writer, "{}", flattener.columns().join("\t"))?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        for row in flattener.rows(&record) {
+            let values: Vec<&str> = row
+                .iter()
+                .map(|v| v.as_deref().unwrap_or(&missing))
+                .collect();
+            writeln!(writer, "{}", values.join("\t"))?;
+            summary.records_out += 1;
+        }
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        ##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\">\n\
+        ##INFO=<ID=DB,Number=0,Type=Flag,Description=\"dbSNP\">\n\
+        ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+        ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Depths\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n\
+        1\t10\trs1\tA\tT,G\t30\tPASS\tAF=0.1,0.2;DB\tGT:AD\t0/1:5,3,0\t1/2:.\n\
+        1\tx\t.\tA\tT\t.\t.\t.\tGT\t0/1\t0/0\n";
+
+    fn export(layout: Layout) -> (Vec<String>, RunSummary) {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let options = ExportOptions {
+            layout,
+            info: vec!["AF".to_string(), "DB".to_string()],
+            format: vec!["GT".to_string(), "AD".to_string()],
+            ..ExportOptions::default()
+        };
+
+        let mut out = Vec::new();
+        let summary = export_tsv(reader, &header, &mut out, options).unwrap();
+        let lines = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+
+        (lines, summary)
+    }
+
+    #[test]
+    fn test_export_tsv_1() {
+        let (lines, summary) = export(Layout::Site);
+        assert_eq!(
+            lines,
+            vec![
+                "CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tAF\tDB\tS1:GT\tS1:AD\tS2:GT\tS2:AD",
+                "1\t10\trs1\tA\tT,G\t30\tPASS\t0.1,0.2\t1\t0/1\t5,3,0\t1/2\t.",
+            ]
+        );
+        assert_eq!(summary.skipped, 1);
+
+        let (lines, summary) = export(Layout::Allele);
+        assert_eq!(
+            lines[1],
+            "1\t10\trs1\tA\tT\t30\tPASS\t0.1\t1\t0/1\t3\t1/2\t."
+        );
+        assert_eq!(
+            lines[2],
+            "1\t10\trs1\tA\tG\t30\tPASS\t0.2\t1\t0/1\t0\t1/2\t."
+        );
+        assert_eq!(summary.records_out, 2);
+
+        let (lines, _) = export(Layout::Sample);
+        assert_eq!(
+            lines,
+            vec![
+                "CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tSAMPLE\tAF\tDB\tGT\tAD",
+                "1\t10\trs1\tA\tT,G\t30\tPASS\tS1\t0.1,0.2\t1\t0/1\t5,3,0",
+                "1\t10\trs1\tA\tT,G\t30\tPASS\tS2\t0.1,0.2\t1\t1/2\t.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_tsv_2() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t10\t.\tA\tT\t.\t.\t.\n";
+        let reader = Reader::new(vcf.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let options = ExportOptions {
+            layout: Layout::Sample,
+            format: vec!["GT".to_string()],
+            ..ExportOptions::default()
+        };
+
+        let mut out = Vec::new();
+        let summary = export_tsv(reader, &header, &mut out, options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tSAMPLE\tGT\n\
+             1\t10\t.\tA\tT\t.\t.\t.\t.\n"
+        );
+        assert_eq!(summary.records_out, 1);
+    }
+}
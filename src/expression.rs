@@ -0,0 +1,547 @@
+//! Filter expressions over records, in the style of `bcftools -i/-e`.
+//!
+//! An expression compares record fields with literals or other fields:
+//!
+//! ```text
+//! QUAL>30 && INFO/DP>10 && FMT/GQ[0]>20
+//! FILTER=="PASS" || (INFO/DB && !(N_ALT>1))
+//! FMT/GT[*]=="het" && INFO/AF[1]<0.05
+//! ```
+//!
+//! Fields are [`FieldPath`]s such as `INFO/DP` or `FMT/AD[*:1]`.
+//!
+//! Fields may hold several values, e.g. one per ALT or sample; a comparison is
+//! true if any value satisfies it. Missing values never do. A field on its own
+//! is true if it is present and not zero, which tests INFO flags. `FMT/GT`
+//! can also be compared with `"het"`, `"hom"`, `"ref"`, `"alt"` and
+//! `"mis"`. Numbers are compared numerically, other values as text; a
+//! comparison with NaN is false. Parentheses and `!` nest at most
+//! [`MAX_DEPTH`] deep.
+
+use crate::errors::{Error, Result};
+use crate::field::FieldPath;
+use crate::genotype::Genotype;
+use crate::record::Record;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Deepest nesting of parentheses and `!` accepted by [`Expression::parse`].
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    /// `ordering` is `None` for values without an order, such as NaN.
+    fn holds(self, ordering: Option<Ordering>) -> bool {
+        let Some(ordering) = ordering else {
+            return false;
+        };
+
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Literal(String),
+    Field(FieldPath),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// Two or more operands, kept flat so that long chains do not nest.
+    Or(Vec<Node>),
+    And(Vec<Node>),
+    Not(Box<Node>),
+    Compare(Operand, Op, Operand),
+    Present(Operand),
+}
+
+/// A compiled filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+            source: expression,
+        };
+
+        let root = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            Err(parser.error())?
+        }
+
+        Ok(Self { root })
+    }
+
+    /// Returns `true` if `record` satisfies the expression.
+    pub fn matches(&self, record: &Record) -> bool {
+        evaluate(&self.root, record)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let error = || Error::ExpressionError(source.to_string());
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+
+        // A minus sign starts a number where an operand is expected.
+        let operand_expected = !matches!(
+            tokens.last(),
+            Some(Token::Number(_) | Token::Text(_) | Token::Name(_) | Token::Close)
+        );
+
+        let (token, len) = match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'&' if next == Some(b'&') => (Token::And, 2),
+            b'|' if next == Some(b'|') => (Token::Or, 2),
+            b'=' if next == Some(b'=') => (Token::Op(Op::Eq), 2),
+            b'=' => (Token::Op(Op::Eq), 1),
+            b'!' if next == Some(b'=') => (Token::Op(Op::Ne), 2),
+            b'!' => (Token::Not, 1),
+            b'<' if next == Some(b'=') => (Token::Op(Op::Le), 2),
+            b'<' => (Token::Op(Op::Lt), 1),
+            b'>' if next == Some(b'=') => (Token::Op(Op::Ge), 2),
+            b'>' => (Token::Op(Op::Gt), 1),
+            b'(' => (Token::Open, 1),
+            b')' => (Token::Close, 1),
+            b'"' | b'\'' => {
+                let end = source[i + 1..].find(c as char).ok_or_else(error)?;
+                (Token::Text(source[i + 1..i + 1 + end].to_string()), end + 2)
+            }
+            c if c.is_ascii_digit()
+                || (c == b'.' && next.is_some_and(|n| n.is_ascii_digit()))
+                || (c == b'-' && operand_expected) =>
+            {
+                let len = number_len(&bytes[i..]);
+                let number = source[i..i + len].parse().map_err(|_| error())?;
+                (Token::Number(number), len)
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let mut len = bytes[i..]
+                    .iter()
+                    .take_while(|&&b| {
+                        b.is_ascii_alphanumeric() || b == b'_' || b == b'/' || b == b'.'
+                    })
+                    .count();
+                // Indices are part of the field path.
+                if bytes.get(i + len) == Some(&b'[') {
+                    len += source[i + len..].find(']').ok_or_else(error)? + 1;
+                }
+                (Token::Name(source[i..i + len].to_string()), len)
+            }
+            _ => Err(error())?,
+        };
+
+        tokens.push(token);
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+/// Length of the number at the start of `bytes`, with an optional sign,
+/// fraction and exponent.
+fn number_len(bytes: &[u8]) -> usize {
+    let digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+
+    let mut len = usize::from(bytes[0] == b'-');
+    len += digits(len);
+    if bytes.get(len) == Some(&b'.') {
+        len += 1 + digits(len + 1);
+    }
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(len + 1), Some(b'+' | b'-')));
+        let exponent = digits(len + 1 + sign);
+        if exponent > 0 {
+            len += 1 + sign + exponent;
+        }
+    }
+
+    len
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Nesting depth of the node being parsed.
+    depth: usize,
+    source: &'a str,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> Error {
+        Error::ExpressionError(self.source.to_string())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn take(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        if self.take() != Some(token) {
+            Err(self.error())?
+        }
+
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Node> {
+        let mut nodes = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            nodes.push(self.and()?);
+        }
+
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::Or(nodes)
+        })
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        let mut nodes = vec![self.unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            nodes.push(self.unary()?);
+        }
+
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::And(nodes)
+        })
+    }
+
+    /// Parses a nested node with `parse`, failing beyond [`MAX_DEPTH`].
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Node>) -> Result<Node> {
+        if self.depth == MAX_DEPTH {
+            Err(self.error())?
+        }
+
+        self.depth += 1;
+        let node = parse(self);
+        self.depth -= 1;
+
+        node
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Node::Not(Box::new(self.nested(Self::unary)?)))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let node = self.nested(Self::or)?;
+                self.expect(Token::Close)?;
+                Ok(node)
+            }
+            _ => {
+                let left = self.operand()?;
+                match self.peek() {
+                    Some(&Token::Op(op)) => {
+                        self.pos += 1;
+                        Ok(Node::Compare(left, op, self.operand()?))
+                    }
+                    _ => Ok(Node::Present(left)),
+                }
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.take() {
+            Some(Token::Number(n)) => Ok(Operand::Literal(n.to_string())),
+            Some(Token::Text(s)) => Ok(Operand::Literal(s)),
+            Some(Token::Name(name)) => FieldPath::parse(&name)
+                .map(Operand::Field)
+                .map_err(|_| self.error()),
+            _ => Err(self.error()),
+        }
+    }
+}
+
+fn evaluate(node: &Node, record: &Record) -> bool {
+    match node {
+        Node::Or(nodes) => nodes.iter().any(|n| evaluate(n, record)),
+        Node::And(nodes) => nodes.iter().all(|n| evaluate(n, record)),
+        Node::Not(a) => !evaluate(a, record),
+        Node::Present(operand) => values(operand, record)
+            .iter()
+            .any(|v| v.parse::<f64>().map_or(!v.is_empty(), |n| n != 0.0)),
+        Node::Compare(a, op, b) => {
+            if let Some(matches) =
+                genotype_class(a, *op, b, record).or_else(|| genotype_class(b, *op, a, record))
+            {
+                return matches;
+            }
+
+            let (a, b) = (values(a, record), values(b, record));
+            a.iter().any(|x| b.iter().any(|y| op.holds(compare(x, y))))
+        }
+    }
+}
+
+fn compare(a: &str, b: &str) -> Option<Ordering> {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y),
+        _ => Some(a.cmp(b)),
+    }
+}
+
+/// Evaluates `FMT/GT == "het"` and similar comparisons, or returns `None` if
+/// `field` and `class` are not such a pair.
+fn genotype_class(field: &Operand, op: Op, class: &Operand, record: &Record) -> Option<bool> {
+    let (Operand::Field(field), Operand::Literal(class)) = (field, class) else {
+        return None;
+    };
+    if !matches!(op, Op::Eq | Op::Ne) {
+        return None;
+    }
+
+    let test: fn(&Genotype) -> bool = match class.as_str() {
+        "het" => |gt| gt.is_het(),
+        "hom" => |gt| gt.is_hom_ref() || gt.is_hom_alt(),
+        "ref" => |gt| gt.is_hom_ref(),
+        "alt" => |gt| gt.alleles.iter().any(|a| a.is_some_and(|i| i > 0)),
+        "mis" => |gt| gt.is_missing(),
+        _ => return None,
+    };
+
+    Some(
+        field
+            .genotypes(record)?
+            .iter()
+            .any(|gt| test(gt) == (op == Op::Eq)),
+    )
+}
+
+/// Values of an operand, without missing values.
+fn values<'a>(operand: &'a Operand, record: &'a Record) -> Vec<Cow<'a, str>> {
+    match operand {
+        Operand::Literal(s) => vec![Cow::Borrowed(s.as_str())],
+        Operand::Field(field) => field.present(record),
+    }
+}
+
+/// Keeps the records of a stream that match an [`Expression`] (`-i`), or
+/// those that do not (`-e`).
+pub struct ExpressionFilter<I: Iterator<Item = Result<Record>>> {
+    inner: I,
+    expression: Expression,
+    exclude: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> ExpressionFilter<I> {
+    /// Keeps the records matching `expression`.
+    pub fn new(inner: I, expression: Expression) -> Self {
+        Self {
+            inner,
+            expression,
+            exclude: false,
+        }
+    }
+
+    /// Keeps the records not matching `expression`.
+    pub fn excluding(inner: I, expression: Expression) -> Self {
+        Self {
+            inner,
+            expression,
+            exclude: true,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for ExpressionFilter<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(record) if self.expression.matches(&record) == self.exclude => continue,
+                item => return Some(item),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        "1\t100\trs1\tA\tG,T\t45.5\tPASS\tDP=12;AF=0.5,0.01;DB;SVTYPE=DEL\tGT:GQ:AD\t0/1:30:5,7,0\t1/1:15:0,9,1\t./.:.:."
+            .parse()
+            .unwrap()
+    }
+
+    fn matches(expression: &str) -> bool {
+        Expression::parse(expression).unwrap().matches(&record())
+    }
+
+    #[test]
+    fn test_expression_1() {
+        assert!(matches("QUAL>30 && INFO/DP>10 && FMT/GQ[0]>20"));
+        assert!(!matches("QUAL>30 && INFO/DP>10 && FMT/GQ[1]>20"));
+        assert!(matches("QUAL>=45.5 && QUAL<=45.5 && POS==100"));
+        assert!(matches("FILTER==\"PASS\" && CHROM=\"1\" && ID!='rs2'"));
+        assert!(matches("DP>1e1 && -1<AF[1]"));
+        assert!(matches("INFO/AF[1]<0.05 && !(INFO/AF[0]<0.05)"));
+        assert!(matches("N_ALT==2 && ALT[1]==\"T\" && REF==\"A\""));
+        assert!(matches("INFO/SVTYPE==\"DEL\""));
+    }
+
+    #[test]
+    fn test_expression_2() {
+        // Flags, missing fields and values.
+        assert!(matches("DB"));
+        assert!(!matches("INFO/XX"));
+        assert!(!matches("INFO/XX>0 || INFO/XX<=0"));
+        assert!(matches("!INFO/XX"));
+        assert!(!matches("FMT/GQ[2]>=0"));
+
+        // Any sample or value satisfies the comparison.
+        assert!(matches("FMT/GQ<20"));
+        assert!(matches("FMT/AD[*:2]>0"));
+        assert!(!matches("FMT/AD[0:2]>0"));
+        assert!(matches("FMT/AD[1:1]==9"));
+    }
+
+    #[test]
+    fn test_expression_3() {
+        assert!(matches("FMT/GT[0]==\"het\" && FMT/GT[1]==\"hom\""));
+        assert!(matches("FMT/GT==\"mis\" && FMT/GT[*]!=\"ref\""));
+        assert!(!matches("FMT/GT==\"ref\""));
+        assert!(matches("FMT/GT[1]==\"1/1\""));
+
+        for bad in [
+            "QUAL>",
+            "QUAL>30 &&",
+            "(QUAL>30",
+            "QUAL[0]>1",
+            "FMT/GQ[x]>1",
+            "QUAL # 3",
+            "\"open",
+        ] {
+            assert!(Expression::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_expression_4() {
+        let record: Record = "1\t100\t.\tA\tG\tnan\t.\tAF=NaN".parse().unwrap();
+        let matches = |expression: &str| Expression::parse(expression).unwrap().matches(&record);
+
+        for op in ["==", "!=", "<", "<=", ">", ">="] {
+            assert!(!matches(&format!("QUAL{op}0")), "{op}");
+            assert!(!matches(&format!("INFO/AF{op}INFO/AF")), "{op}");
+        }
+
+        let nested = |depth: usize| format!("{}DB{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(Expression::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Expression::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Expression::parse(&"!".repeat(100_000)).is_err());
+        let mixed = |depth: usize| format!("{}DB{}", "!(".repeat(depth), ")".repeat(depth));
+        assert!(Expression::parse(&mixed(MAX_DEPTH / 2)).is_ok());
+        assert!(Expression::parse(&mixed(MAX_DEPTH / 2 + 1)).is_err());
+
+        // Chains of binary operators do not count towards the depth.
+        assert!(matches(&vec!["POS>1"; 100_000].join(" && ")));
+        assert!(!matches(&vec!["POS<1"; 100_000].join(" || ")));
+        assert!(matches(
+            &vec!["(POS<1 || POS>1) && !POS<1"; 50_000].join(" || ")
+        ));
+    }
+
+    #[test]
+    fn test_expression_filter_1() {
+        let lines = [
+            "1\t1\t.\tA\tG\t10\t.\t.",
+            "1\t2\t.\tA\tG\t50\t.\t.",
+            "1\t3\t.\tA\tG\t.\t.\t.",
+        ];
+        let records = || lines.iter().map(|l| l.parse::<Record>());
+        let expression: Expression = "QUAL>30".parse().unwrap();
+
+        let kept: Vec<u64> = ExpressionFilter::new(records(), expression.clone())
+            .map(|r| r.unwrap().pos)
+            .collect();
+        let excluded: Vec<u64> = ExpressionFilter::excluding(records(), expression)
+            .map(|r| r.unwrap().pos)
+            .collect();
+
+        assert_eq!(kept, vec![2]);
+        assert_eq!(excluded, vec![1, 3]);
+    }
+}
@@ -0,0 +1,662 @@
+//! C interface to normalization, classification, record parsing and
+//! reading.
+//!
+//! `include/vcf_lib.h` declares these functions, and a test checks it
+//! against them; `cargo build --release --features ffi` builds the shared
+//! (`cdylib`) and static (`staticlib`) libraries to link against. The
+//! `python` feature enables this interface, with file reading, for the
+//! ctypes bindings in `python/vcf_lib`, loaded from the `cdylib`.
+//!
+//! Functions that can fail return a [`VcfStatus`]; the message of the last
+//! error on the calling thread is kept for [`vcf_last_error`]. Strings
+//! returned to the caller are owned by it and freed with
+//! [`vcf_string_free`]; strings borrowed from a record live as long as the
+//! record.
+
+use crate::bgzf;
+use crate::errors::Error;
+use crate::reader::Reader;
+use crate::record::{normalize, variant_type, Record};
+use crate::VariantType;
+use flate2::bufread::MultiGzDecoder;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::io::{BufRead, BufReader, Cursor};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Outcome of a fallible call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcfStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    /// The library rejected the input; see [`vcf_last_error`].
+    Invalid = 3,
+}
+
+/// [`VariantType`], plus a value for alleles that are not a variant.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcfType {
+    Unknown = -1,
+    Snv = 0,
+    Deletion = 1,
+    Insertion = 2,
+    Indel = 3,
+    Mnv = 4,
+}
+
+impl From<Option<VariantType>> for VcfType {
+    fn from(variant_type: Option<VariantType>) -> Self {
+        match variant_type {
+            Some(VariantType::SNV) => Self::Snv,
+            Some(VariantType::Deletion) => Self::Deletion,
+            Some(VariantType::Insertion) => Self::Insertion,
+            Some(VariantType::Indel) => Self::Indel,
+            Some(VariantType::MNV) => Self::Mnv,
+            None => Self::Unknown,
+        }
+    }
+}
+
+/// Normalized variant; free its alleles with [`vcf_variant_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct VcfVariant {
+    pub position: u64,
+    pub reference: *mut c_char,
+    pub alternate: *mut c_char,
+}
+
+/// Parsed record, opaque to C.
+pub struct VcfRecord {
+    record: Record,
+    chrom: CString,
+    reference: CString,
+    alternates: Vec<CString>,
+}
+
+impl VcfRecord {
+    fn new(record: Record) -> Self {
+        Self {
+            chrom: to_c(&record.chrom),
+            reference: to_c(&record.reference),
+            alternates: record.alternates.iter().map(|a| to_c(a)).collect(),
+            record,
+        }
+    }
+}
+
+/// Reader of a VCF file or buffer, opaque to C.
+pub struct VcfReader {
+    reader: Reader<Box<dyn BufRead>>,
+    header: CString,
+    samples: Vec<CString>,
+}
+
+impl VcfReader {
+    fn new(reader: Reader<Box<dyn BufRead>>) -> Self {
+        Self {
+            header: to_c(&reader.header().to_string()),
+            samples: reader.header().samples.iter().map(|s| to_c(s)).collect(),
+            reader,
+        }
+    }
+}
+
+fn set_error(status: VcfStatus, message: String) -> VcfStatus {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+
+    status
+}
+
+fn fail(error: Error) -> VcfStatus {
+    set_error(VcfStatus::Invalid, error.to_string())
+}
+
+/// Borrows a C string argument.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn borrow<'a>(s: *const c_char, name: &str) -> Result<&'a str, VcfStatus> {
+    if s.is_null() {
+        return Err(set_error(
+            VcfStatus::NullPointer,
+            format!("{} is null", name),
+        ));
+    }
+
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| set_error(VcfStatus::InvalidUtf8, format!("{} is not UTF-8", name)))
+}
+
+/// Converts an allele or field for returning; the library never produces
+/// values with interior NULs.
+fn to_c(s: &str) -> CString {
+    CString::new(s).unwrap_or_default()
+}
+
+/// Message of the last error on this thread, or null; valid until the next
+/// failing call on the thread.
+#[no_mangle]
+pub extern "C" fn vcf_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Trims the shared bases of `reference` and `alternate` into `out`.
+///
+/// # Safety
+///
+/// `reference` and `alternate` must be null or NUL-terminated strings, and
+/// `out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_normalize(
+    position: u64,
+    reference: *const c_char,
+    alternate: *const c_char,
+    out: *mut VcfVariant,
+) -> VcfStatus {
+    let (reference, alternate) = match (
+        borrow(reference, "reference"),
+        borrow(alternate, "alternate"),
+    ) {
+        (Ok(r), Ok(a)) => (r, a),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+    if out.is_null() {
+        return set_error(VcfStatus::NullPointer, "out is null".to_string());
+    }
+
+    match normalize(position, reference, alternate) {
+        Ok((position, reference, alternate)) => {
+            out.write(VcfVariant {
+                position,
+                reference: to_c(reference).into_raw(),
+                alternate: to_c(alternate).into_raw(),
+            });
+            VcfStatus::Ok
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Frees the alleles of a variant filled by [`vcf_normalize`].
+///
+/// # Safety
+///
+/// `variant` must be null or point to a variant filled by [`vcf_normalize`]
+/// whose alleles have not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_variant_free(variant: *mut VcfVariant) {
+    if let Some(variant) = variant.as_mut() {
+        vcf_string_free(variant.reference);
+        vcf_string_free(variant.alternate);
+        variant.reference = ptr::null_mut();
+        variant.alternate = ptr::null_mut();
+    }
+}
+
+/// Classifies an already normalized pair of alleles.
+///
+/// # Safety
+///
+/// `reference` and `alternate` must be null or NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_variant_type(
+    reference: *const c_char,
+    alternate: *const c_char,
+) -> VcfType {
+    match (
+        borrow(reference, "reference"),
+        borrow(alternate, "alternate"),
+    ) {
+        (Ok(r), Ok(a)) => variant_type(r, a).into(),
+        _ => VcfType::Unknown,
+    }
+}
+
+/// Parses a data line into `out`; free the record with [`vcf_record_free`].
+///
+/// # Safety
+///
+/// `line` must be null or a NUL-terminated string, and `out` null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_parse(
+    line: *const c_char,
+    out: *mut *mut VcfRecord,
+) -> VcfStatus {
+    let line = match borrow(line, "line") {
+        Ok(line) => line,
+        Err(status) => return status,
+    };
+    if out.is_null() {
+        return set_error(VcfStatus::NullPointer, "out is null".to_string());
+    }
+
+    match line.parse::<Record>() {
+        Ok(record) => {
+            out.write(Box::into_raw(Box::new(VcfRecord::new(record))));
+            VcfStatus::Ok
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Frees a record returned by [`vcf_record_parse`].
+///
+/// # Safety
+///
+/// `record` must be null or a record returned by [`vcf_record_parse`] and
+/// not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_free(record: *mut VcfRecord) {
+    if !record.is_null() {
+        drop(Box::from_raw(record));
+    }
+}
+
+/// CHROM of `record`.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_chrom(record: *const VcfRecord) -> *const c_char {
+    (&*record).chrom.as_ptr()
+}
+
+/// POS of `record`.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_pos(record: *const VcfRecord) -> u64 {
+    (&*record).record.pos
+}
+
+/// REF of `record`.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_ref(record: *const VcfRecord) -> *const c_char {
+    (&*record).reference.as_ptr()
+}
+
+/// Number of ALT alleles of `record`.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_alt_count(record: *const VcfRecord) -> usize {
+    (&*record).alternates.len()
+}
+
+/// ALT allele `i` of `record`, or null if there is none.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_alt(record: *const VcfRecord, i: usize) -> *const c_char {
+    (&*record)
+        .alternates
+        .get(i)
+        .map_or(ptr::null(), |a| a.as_ptr())
+}
+
+/// Value of the INFO `key` of `record`, as a new string; a flag gives an
+/// empty string and a missing key null.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`], and
+/// `key` null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_info(
+    record: *const VcfRecord,
+    key: *const c_char,
+) -> *mut c_char {
+    let Ok(key) = borrow(key, "key") else {
+        return ptr::null_mut();
+    };
+    let record = &(&*record).record;
+    if !record.has_info(key) {
+        return ptr::null_mut();
+    }
+
+    to_c(record.info(key).unwrap_or("")).into_raw()
+}
+
+/// Formats `record` as a data line, without newline, as a new string.
+///
+/// # Safety
+///
+/// `record` must be a live record returned by [`vcf_record_parse`].
+#[no_mangle]
+pub unsafe extern "C" fn vcf_record_to_string(record: *const VcfRecord) -> *mut c_char {
+    to_c(&(&*record).record.to_string()).into_raw()
+}
+
+#[cfg(feature = "fs")]
+/// Opens a plain, gzip or BGZF compressed VCF file and reads its header into
+/// `out`; free the reader with [`vcf_reader_free`].
+///
+/// # Safety
+///
+/// `path` must be null or a NUL-terminated string, and `out` null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_open(
+    path: *const c_char,
+    out: *mut *mut VcfReader,
+) -> VcfStatus {
+    let path = match borrow(path, "path") {
+        Ok(path) => path,
+        Err(status) => return status,
+    };
+    if out.is_null() {
+        return set_error(VcfStatus::NullPointer, "out is null".to_string());
+    }
+
+    match Reader::from_path(path) {
+        Ok(reader) => {
+            out.write(Box::into_raw(Box::new(VcfReader::new(reader))));
+            VcfStatus::Ok
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Reads a plain, gzip or BGZF compressed VCF from the `len` bytes at
+/// `data`, which are copied, and its header into `out`; free the reader
+/// with [`vcf_reader_free`].
+///
+/// # Safety
+///
+/// `data` must be null or valid for reads of `len` bytes, and `out` null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_from_memory(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut VcfReader,
+) -> VcfStatus {
+    if data.is_null() {
+        return set_error(VcfStatus::NullPointer, "data is null".to_string());
+    }
+    if out.is_null() {
+        return set_error(VcfStatus::NullPointer, "out is null".to_string());
+    }
+
+    let data = std::slice::from_raw_parts(data, len).to_vec();
+    let inner: Box<dyn BufRead> = if bgzf::is_gzip(&data) {
+        Box::new(BufReader::new(MultiGzDecoder::new(Cursor::new(data))))
+    } else {
+        Box::new(Cursor::new(data))
+    };
+
+    match Reader::new(inner) {
+        Ok(reader) => {
+            out.write(Box::into_raw(Box::new(VcfReader::new(reader))));
+            VcfStatus::Ok
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Reads the next record into `out`, or null at the end of the input; free
+/// the record with [`vcf_record_free`].
+///
+/// # Safety
+///
+/// `reader` must be a live reader, and `out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_next(
+    reader: *mut VcfReader,
+    out: *mut *mut VcfRecord,
+) -> VcfStatus {
+    if out.is_null() {
+        return set_error(VcfStatus::NullPointer, "out is null".to_string());
+    }
+
+    match (&mut *reader).reader.next() {
+        Some(Ok(record)) => {
+            out.write(Box::into_raw(Box::new(VcfRecord::new(record))));
+            VcfStatus::Ok
+        }
+        Some(Err(e)) => fail(e),
+        None => {
+            out.write(ptr::null_mut());
+            VcfStatus::Ok
+        }
+    }
+}
+
+/// Header of `reader` as text, including the `#CHROM` line.
+///
+/// # Safety
+///
+/// `reader` must be a live reader.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_header(reader: *const VcfReader) -> *const c_char {
+    (&*reader).header.as_ptr()
+}
+
+/// Number of samples of `reader`.
+///
+/// # Safety
+///
+/// `reader` must be a live reader.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_sample_count(reader: *const VcfReader) -> usize {
+    (&*reader).samples.len()
+}
+
+/// Name of sample `i` of `reader`, or null if there is none.
+///
+/// # Safety
+///
+/// `reader` must be a live reader.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_sample(reader: *const VcfReader, i: usize) -> *const c_char {
+    (&*reader)
+        .samples
+        .get(i)
+        .map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// Frees a reader, closing its file.
+///
+/// # Safety
+///
+/// `reader` must be null or a reader returned by this library and not yet
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn vcf_reader_free(reader: *mut VcfReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn text(s: *const c_char) -> String {
+        CStr::from_ptr(s).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_normalize_1() {
+        unsafe {
+            let mut variant = VcfVariant {
+                position: 0,
+                reference: ptr::null_mut(),
+                alternate: ptr::null_mut(),
+            };
+            let status = vcf_normalize(10, c("CAGT").as_ptr(), c("CT").as_ptr(), &mut variant);
+
+            assert_eq!(status, VcfStatus::Ok);
+            assert_eq!(variant.position, 10);
+            assert_eq!(text(variant.reference), "CAG");
+            assert_eq!(text(variant.alternate), "C");
+            assert_eq!(
+                vcf_variant_type(variant.reference, variant.alternate),
+                VcfType::Deletion
+            );
+            vcf_variant_free(&mut variant);
+            assert!(variant.reference.is_null());
+
+            let status = vcf_normalize(10, c("A").as_ptr(), c("X").as_ptr(), &mut variant);
+            assert_eq!(status, VcfStatus::Invalid);
+            assert!(text(vcf_last_error()).contains("non-ACGT"));
+            assert_eq!(
+                vcf_normalize(1, ptr::null(), c("A").as_ptr(), &mut variant),
+                VcfStatus::NullPointer
+            );
+            assert_eq!(
+                vcf_variant_type(c("A").as_ptr(), c("A").as_ptr()),
+                VcfType::Unknown
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_parse_1() {
+        unsafe {
+            let line = c("1\t10\trs1\tA\tT,G\t.\tPASS\tDP=3;DB\tGT\t0/1");
+            let mut record = ptr::null_mut();
+            assert_eq!(vcf_record_parse(line.as_ptr(), &mut record), VcfStatus::Ok);
+
+            assert_eq!(text(vcf_record_chrom(record)), "1");
+            assert_eq!(vcf_record_pos(record), 10);
+            assert_eq!(text(vcf_record_ref(record)), "A");
+            assert_eq!(vcf_record_alt_count(record), 2);
+            assert_eq!(text(vcf_record_alt(record, 1)), "G");
+            assert!(vcf_record_alt(record, 2).is_null());
+
+            let dp = vcf_record_info(record, c("DP").as_ptr());
+            assert_eq!(text(dp), "3");
+            vcf_string_free(dp);
+            let db = vcf_record_info(record, c("DB").as_ptr());
+            assert_eq!(text(db), "");
+            vcf_string_free(db);
+            assert!(vcf_record_info(record, c("AF").as_ptr()).is_null());
+
+            let formatted = vcf_record_to_string(record);
+            assert_eq!(text(formatted), line.to_str().unwrap());
+            vcf_string_free(formatted);
+            vcf_record_free(record);
+
+            let mut record = ptr::null_mut();
+            assert_eq!(
+                vcf_record_parse(c("1\tx").as_ptr(), &mut record),
+                VcfStatus::Invalid
+            );
+            assert!(record.is_null());
+        }
+    }
+
+    #[test]
+    fn test_reader_from_memory_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n\
+            1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\t1/1\n\
+            1\tx\t.\tC\tA\t.\t.\t.\tGT\t0/1\t1/1\n";
+        let mut writer = bgzf::Writer::new(Vec::new());
+        std::io::Write::write_all(&mut writer, vcf.as_bytes()).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        for data in [vcf.as_bytes(), &compressed] {
+            unsafe {
+                let mut reader = ptr::null_mut();
+                let status = vcf_reader_from_memory(data.as_ptr(), data.len(), &mut reader);
+                assert_eq!(status, VcfStatus::Ok);
+
+                assert!(text(vcf_reader_header(reader)).starts_with("##fileformat=VCFv4.3\n"));
+                assert_eq!(vcf_reader_sample_count(reader), 2);
+                assert_eq!(text(vcf_reader_sample(reader, 1)), "S2");
+                assert!(vcf_reader_sample(reader, 2).is_null());
+
+                let mut record = ptr::null_mut();
+                assert_eq!(vcf_reader_next(reader, &mut record), VcfStatus::Ok);
+                assert_eq!(vcf_record_pos(record), 10);
+                vcf_record_free(record);
+                assert_eq!(vcf_reader_next(reader, &mut record), VcfStatus::Invalid);
+                assert_eq!(vcf_reader_next(reader, &mut record), VcfStatus::Ok);
+                assert!(record.is_null());
+                vcf_reader_free(reader);
+            }
+        }
+
+        unsafe {
+            let mut reader = ptr::null_mut();
+            let data = b"1\t10\t.\tA\tT\n";
+            assert_eq!(
+                vcf_reader_from_memory(data.as_ptr(), data.len(), &mut reader),
+                VcfStatus::Invalid
+            );
+            #[cfg(feature = "fs")]
+            assert_eq!(
+                vcf_reader_open(c("/nonexistent.vcf").as_ptr(), &mut reader),
+                VcfStatus::Invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_1() {
+        // Name and parameter count of each function.
+        let functions = |pattern: &str, source: &'static str| {
+            Regex::new(pattern)
+                .unwrap()
+                .captures_iter(source)
+                .map(|c| {
+                    let count = c[2]
+                        .split(',')
+                        .filter(|p| !matches!(p.trim(), "" | "void"))
+                        .count();
+                    (c.get(1).unwrap().as_str(), count)
+                })
+                .collect::<std::collections::BTreeMap<_, _>>()
+        };
+
+        let exported = functions(r#"extern "C" fn (\w+)\(([^)]*)\)"#, include_str!("ffi.rs"));
+        let declared = functions(
+            r"(?m)^(?:const )?\w+ \*?(vcf_\w+)\(([^)]*)\)",
+            include_str!("../include/vcf_lib.h"),
+        );
+        assert_eq!(exported.len(), 21);
+        assert_eq!(exported, declared);
+    }
+}
@@ -0,0 +1,293 @@
+//! Addressing of record fields by path, e.g. `INFO/AF[1]` or `FORMAT/AD[*:1]`.
+//!
+//! Paths are `CHROM`, `POS`, `ID`, `REF`, `ALT`, `QUAL`, `FILTER`, `N_ALT`,
+//! `INFO/KEY` and `FORMAT/KEY` (or `FMT/KEY`); other names are read as INFO
+//! keys. `[i]` selects the `i`-th value of a field. For FORMAT keys, `[s]`
+//! selects sample `s`, `[s:i]` its `i`-th value, and `*` any sample or value.
+//!
+//! A path addresses every value it matches, in record order: one per ALT,
+//! sample or comma-separated value. Values written as `.` are missing;
+//! absent fields, samples and indices past the end address nothing. INFO
+//! flags have the value `1`, and `GT` is never split.
+
+use crate::errors::{Error, Result};
+use crate::genotype::Genotype;
+use crate::record::Record;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Index into the values of a field; `None` is `*`.
+type Index = Option<usize>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Chrom,
+    Pos,
+    Id,
+    Ref,
+    Alt(Index),
+    Qual,
+    Filter,
+    NAlt,
+    Info(String, Index),
+    Format(String, Index, Index),
+}
+
+/// A compiled field path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    field: Field,
+}
+
+impl FieldPath {
+    pub fn parse(path: &str) -> Result<Self> {
+        let error = || Error::FieldPathError(path.to_string());
+
+        let (name, indices) = match path.split_once('[') {
+            Some((name, rest)) => {
+                let inner = rest.strip_suffix(']').ok_or_else(error)?;
+                let index = |s: &str| match s.trim() {
+                    "*" => Ok(None),
+                    s => s.parse().map(Some).map_err(|_| error()),
+                };
+                let indices = match inner.split_once(':') {
+                    Some((a, b)) => (Some(index(a)?), Some(index(b)?)),
+                    None => (Some(index(inner)?), None),
+                };
+                (name, indices)
+            }
+            None => (path, (None, None)),
+        };
+        let (first, second) = indices;
+
+        let scalar = |field: Field| match first {
+            None => Ok(field),
+            Some(_) => Err(error()),
+        };
+        let field = match name {
+            "CHROM" => scalar(Field::Chrom)?,
+            "POS" => scalar(Field::Pos)?,
+            "ID" => scalar(Field::Id)?,
+            "REF" => scalar(Field::Ref)?,
+            "QUAL" => scalar(Field::Qual)?,
+            "FILTER" => scalar(Field::Filter)?,
+            "N_ALT" => scalar(Field::NAlt)?,
+            "ALT" if second.is_none() => Field::Alt(first.flatten()),
+            _ => {
+                let format = name
+                    .strip_prefix("FMT/")
+                    .or_else(|| name.strip_prefix("FORMAT/"));
+                let key = format.unwrap_or_else(|| name.strip_prefix("INFO/").unwrap_or(name));
+
+                let valid = !key.is_empty()
+                    && key
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'.');
+                if !valid || (format.is_none() && second.is_some()) {
+                    Err(error())?
+                }
+
+                match format {
+                    Some(_) => Field::Format(key.to_string(), first.flatten(), second.flatten()),
+                    None => Field::Info(key.to_string(), first.flatten()),
+                }
+            }
+        };
+
+        Ok(Self { field })
+    }
+
+    /// Values addressed in `record`; missing values are `None`.
+    pub fn values<'a>(&self, record: &'a Record) -> Vec<Option<Cow<'a, str>>> {
+        let pick = |values: Vec<Cow<'a, str>>, index: &Index| match index {
+            Some(i) => values.into_iter().nth(*i).into_iter().collect(),
+            None => values,
+        };
+        let borrowed = |values: &'a [String]| values.iter().map(|s| Cow::Borrowed(s.as_str()));
+
+        let values: Vec<Cow<str>> = match &self.field {
+            Field::Chrom => vec![Cow::Borrowed(record.chrom.as_str())],
+            Field::Pos => vec![Cow::Owned(record.pos.to_string())],
+            Field::Id => borrowed(&record.ids).collect(),
+            Field::Ref => vec![Cow::Borrowed(record.reference.as_str())],
+            Field::Alt(index) => pick(borrowed(&record.alternates).collect(), index),
+            Field::Qual => vec![record
+                .qual
+                .map_or(Cow::Borrowed("."), |q| Cow::Owned(q.to_string()))],
+            Field::Filter => borrowed(&record.filters).collect(),
+            Field::NAlt => vec![Cow::Owned(record.alternates.len().to_string())],
+            Field::Info(key, index) => match record.info.iter().find(|(k, _)| k == key) {
+                Some((_, Some(value))) => {
+                    pick(value.split(',').map(Cow::Borrowed).collect(), index)
+                }
+                Some((_, None)) => vec![Cow::Borrowed("1")],
+                None => Vec::new(),
+            },
+            Field::Format(key, sample, index) => {
+                let Some(j) = record.format_index(key) else {
+                    return Vec::new();
+                };
+                let samples: Vec<&Vec<String>> = match sample {
+                    Some(s) => record.samples.get(*s).into_iter().collect(),
+                    None => record.samples.iter().collect(),
+                };
+
+                samples
+                    .into_iter()
+                    .filter_map(|values| values.get(j))
+                    .flat_map(|value| {
+                        let parts: Vec<Cow<str>> = if key == "GT" {
+                            vec![Cow::Borrowed(value.as_str())]
+                        } else {
+                            value.split(',').map(Cow::Borrowed).collect()
+                        };
+                        pick(parts, index)
+                    })
+                    .collect()
+            }
+        };
+
+        values
+            .into_iter()
+            .map(|v| if v == "." { None } else { Some(v) })
+            .collect()
+    }
+
+    /// Values addressed in `record`, without missing values.
+    pub fn present<'a>(&self, record: &'a Record) -> Vec<Cow<'a, str>> {
+        self.values(record).into_iter().flatten().collect()
+    }
+
+    /// Genotypes of the addressed samples, or `None` if the path is not
+    /// `FORMAT/GT` without a value index.
+    ///
+    /// Samples without a valid `GT` are left out.
+    pub fn genotypes(&self, record: &Record) -> Option<Vec<Genotype>> {
+        let Field::Format(key, sample, None) = &self.field else {
+            return None;
+        };
+        if key != "GT" {
+            return None;
+        }
+
+        let samples = match sample {
+            Some(s) => *s..s.saturating_add(1),
+            None => 0..record.samples.len(),
+        };
+
+        Some(
+            samples
+                .filter_map(|s| record.genotype(s).ok().flatten())
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for FieldPath {
+    /// Writes the canonical form of the path, with `INFO/` and `FORMAT/`
+    /// prefixes.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let index = |i: &Index| i.map_or("*".to_string(), |i| i.to_string());
+
+        match &self.field {
+            Field::Chrom => f.write_str("CHROM"),
+            Field::Pos => f.write_str("POS"),
+            Field::Id => f.write_str("ID"),
+            Field::Ref => f.write_str("REF"),
+            Field::Alt(None) => f.write_str("ALT"),
+            Field::Alt(i) => write!(f, "ALT[{}]", index(i)),
+            Field::Qual => f.write_str("QUAL"),
+            Field::Filter => f.write_str("FILTER"),
+            Field::NAlt => f.write_str("N_ALT"),
+            Field::Info(key, None) => write!(f, "INFO/{}", key),
+            Field::Info(key, i) => write!(f, "INFO/{}[{}]", key, index(i)),
+            Field::Format(key, None, None) => write!(f, "FORMAT/{}", key),
+            Field::Format(key, s, None) => write!(f, "FORMAT/{}[{}]", key, index(s)),
+            Field::Format(key, s, i) => write!(f, "FORMAT/{}[{}:{}]", key, index(s), index(i)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        "1\t100\trs1\tA\tG,T\t.\tPASS\tAF=0.5,.;DB\tGT:AD\t0/1:5,7,0\t./.:.\t1/1"
+            .parse()
+            .unwrap()
+    }
+
+    fn values(path: &str) -> Vec<Option<String>> {
+        let path = FieldPath::parse(path).unwrap();
+        path.values(&record())
+            .into_iter()
+            .map(|v| v.map(|v| v.into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_values_1() {
+        let some = |v: &str| Some(v.to_string());
+
+        assert_eq!(values("POS"), vec![some("100")]);
+        assert_eq!(values("QUAL"), vec![None]);
+        assert_eq!(values("ALT"), vec![some("G"), some("T")]);
+        assert_eq!(values("ALT[1]"), vec![some("T")]);
+        assert_eq!(values("AF"), vec![some("0.5"), None]);
+        assert_eq!(values("INFO/DB"), vec![some("1")]);
+        assert_eq!(values("INFO/XX"), vec![]);
+        assert_eq!(values("FORMAT/AD[*:1]"), vec![some("7")]);
+        assert_eq!(values("FMT/AD[1]"), vec![None]);
+        assert_eq!(values("FMT/AD[2]"), vec![]);
+        assert_eq!(values("FMT/AD[0:*]"), vec![some("5"), some("7"), some("0")]);
+        assert_eq!(
+            values("FMT/GT"),
+            vec![some("0/1"), some("./."), some("1/1")]
+        );
+
+        let gt = FieldPath::parse("FORMAT/GT[*]").unwrap();
+        assert_eq!(gt.genotypes(&record()).unwrap().len(), 3);
+        let gt = FieldPath::parse(&format!("FORMAT/GT[{}]", usize::MAX)).unwrap();
+        assert_eq!(gt.genotypes(&record()), Some(vec![]));
+        assert!(FieldPath::parse("FORMAT/AD")
+            .unwrap()
+            .genotypes(&record())
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_1() {
+        for (path, canonical) in [
+            ("FMT/AD[*:1]", "FORMAT/AD[*:1]"),
+            ("DP", "INFO/DP"),
+            ("ALT[*]", "ALT"),
+            ("FORMAT/GQ[0]", "FORMAT/GQ[0]"),
+        ] {
+            let parsed: FieldPath = path.parse().unwrap();
+            assert_eq!(parsed.to_string(), canonical);
+            assert_eq!(canonical.parse::<FieldPath>().unwrap(), parsed);
+        }
+
+        for bad in [
+            "QUAL[0]",
+            "INFO/AF[0:1]",
+            "FMT/AD[x]",
+            "FMT/AD[0",
+            "INFO/",
+            "A/B",
+            "",
+        ] {
+            assert!(FieldPath::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Genotype calls.
+
+use crate::errors::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed `GT` value.
+///
+/// `alleles` holds allele indices (`0` is REF, `None` is missing) and
+/// `phased[i]` tells whether allele `i + 1` is joined by `|` rather than `/`.
+/// A leading phase indicator (VCFv4.4) is accepted but not kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Genotype {
+    pub alleles: Vec<Option<usize>>,
+    pub phased: Vec<bool>,
+}
+
+impl Genotype {
+    /// Unphased genotype of the given alleles.
+    pub fn unphased(alleles: &[Option<usize>]) -> Self {
+        Self {
+            alleles: alleles.to_vec(),
+            phased: vec![false; alleles.len().saturating_sub(1)],
+        }
+    }
+
+    /// Phased genotype of the given alleles.
+    pub fn phased(alleles: &[Option<usize>]) -> Self {
+        Self {
+            alleles: alleles.to_vec(),
+            phased: vec![true; alleles.len().saturating_sub(1)],
+        }
+    }
+
+    /// Fully missing genotype of the given ploidy.
+    pub fn missing(ploidy: usize) -> Self {
+        Self::unphased(&vec![None; ploidy.max(1)])
+    }
+
+    pub fn ploidy(&self) -> usize {
+        self.alleles.len()
+    }
+
+    /// Returns `true` if every allele is missing.
+    pub fn is_missing(&self) -> bool {
+        self.alleles.iter().all(|a| a.is_none())
+    }
+
+    /// Returns `true` if some, but not all, alleles are missing (a half-call).
+    pub fn is_partially_missing(&self) -> bool {
+        !self.is_missing() && self.alleles.iter().any(|a| a.is_none())
+    }
+
+    pub fn is_phased(&self) -> bool {
+        !self.phased.is_empty() && self.phased.iter().all(|&p| p)
+    }
+
+    pub fn is_hom_ref(&self) -> bool {
+        self.alleles.iter().all(|&a| a == Some(0))
+    }
+
+    /// Returns `true` for fully called genotypes with at least two distinct alleles.
+    pub fn is_het(&self) -> bool {
+        match self.called() {
+            Some(first) => self.alleles.iter().any(|&a| a != Some(first)),
+            None => false,
+        }
+    }
+
+    /// Returns `true` for fully called genotypes of a single non-REF allele.
+    pub fn is_hom_alt(&self) -> bool {
+        match self.called() {
+            Some(first) => first > 0 && self.alleles.iter().all(|&a| a == Some(first)),
+            None => false,
+        }
+    }
+
+    /// Number of copies of allele `index`.
+    pub fn count(&self, index: usize) -> usize {
+        self.alleles.iter().filter(|&&a| a == Some(index)).count()
+    }
+
+    fn called(&self) -> Option<usize> {
+        if self.alleles.iter().any(|a| a.is_none()) {
+            return None;
+        }
+
+        self.alleles.first().copied().flatten()
+    }
+}
+
+impl FromStr for Genotype {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rest = s.strip_prefix(['/', '|']).unwrap_or(s);
+        let mut alleles = Vec::new();
+        let mut phased = Vec::new();
+
+        loop {
+            let end = rest.find(['/', '|']).unwrap_or(rest.len());
+
+            alleles.push(match &rest[..end] {
+                "." => None,
+                a => Some(
+                    a.parse()
+                        .map_err(|_| Error::GenotypeParseError(s.to_string()))?,
+                ),
+            });
+
+            match rest.as_bytes().get(end) {
+                Some(&separator) => {
+                    phased.push(separator == b'|');
+                    rest = &rest[end + 1..];
+                }
+                None => break,
+            }
+        }
+
+        Ok(Self { alleles, phased })
+    }
+}
+
+impl fmt::Display for Genotype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, allele) in self.alleles.iter().enumerate() {
+            if i > 0 {
+                f.write_str(if self.phased[i - 1] { "|" } else { "/" })?;
+            }
+            match allele {
+                Some(a) => write!(f, "{}", a)?,
+                None => f.write_str(".")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_1() {
+        let gt: Genotype = "0/1".parse().unwrap();
+
+        assert_eq!(gt.alleles, vec![Some(0), Some(1)]);
+        assert!(gt.is_het());
+        assert!(!gt.is_phased());
+        assert_eq!(gt.to_string(), "0/1");
+    }
+
+    #[test]
+    fn test_parse_2() {
+        let gt: Genotype = "1|2/.".parse().unwrap();
+
+        assert_eq!(gt.alleles, vec![Some(1), Some(2), None]);
+        assert_eq!(gt.phased, vec![true, false]);
+        assert!(gt.is_partially_missing());
+        assert!(!gt.is_het());
+        assert_eq!(gt.to_string(), "1|2/.");
+    }
+
+    #[test]
+    fn test_parse_3() {
+        let gt: Genotype = ".".parse().unwrap();
+
+        assert!(gt.is_missing());
+        assert_eq!(gt.ploidy(), 1);
+
+        let gt: Genotype = "|1|1".parse().unwrap();
+
+        assert!(gt.is_phased());
+        assert!(gt.is_hom_alt());
+        assert_eq!(gt.count(1), 2);
+        assert_eq!(gt.to_string(), "1|1");
+    }
+
+    #[test]
+    fn test_parse_err_1() {
+        assert!("".parse::<Genotype>().is_err());
+        assert!("0/x".parse::<Genotype>().is_err());
+        assert!("0//1".parse::<Genotype>().is_err());
+    }
+
+    #[test]
+    fn test_constructors_1() {
+        assert_eq!(Genotype::missing(2).to_string(), "./.");
+        assert_eq!(Genotype::phased(&[Some(0), Some(1)]).to_string(), "0|1");
+        assert!(Genotype::unphased(&[Some(0), Some(0)]).is_hom_ref());
+    }
+}
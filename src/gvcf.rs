@@ -0,0 +1,247 @@
+//! gVCF reference blocks.
+//!
+//! A gVCF describes every position of the genome. Stretches without a
+//! variant are written as reference blocks: records whose only ALT allele is
+//! `<NON_REF>` (or `<*>`) and whose INFO `END` gives the last position of the
+//! block. See [`Record::block_range`].
+//!
+//! [`ExpandBlocks`] turns blocks into one record per position, and
+//! [`CollapseBlocks`] merges adjacent blocks with the same genotypes.
+
+use crate::errors::Result;
+use crate::record::Record;
+use crate::reference::ReferenceSequence;
+
+/// Splits every reference block of a stream into one record per position.
+///
+/// Each record keeps the FORMAT values of its block and loses `END`. REF is
+/// taken from the reference sequence when one is given; otherwise positions
+/// past the first base of a block get `N`. Other records pass through.
+pub struct ExpandBlocks<'a, I> {
+    inner: I,
+    reference: Option<&'a dyn ReferenceSequence>,
+    /// Block being expanded and its next position.
+    block: Option<(Record, u64)>,
+}
+
+impl<'a, I: Iterator<Item = Result<Record>>> ExpandBlocks<'a, I> {
+    pub fn new(inner: I, reference: Option<&'a dyn ReferenceSequence>) -> Self {
+        Self {
+            inner,
+            reference,
+            block: None,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Record for position `pos` of `block`.
+    fn expand_at(&self, block: &Record, pos: u64) -> Result<Record> {
+        let mut record = block.clone();
+        record.remove_info("END");
+        record.pos = pos;
+
+        let base = match self.reference {
+            Some(sequence) => sequence.base(&block.chrom, pos)?,
+            None => None,
+        };
+        record.reference = match base {
+            Some(base) => (base as char).to_string(),
+            None if pos == block.pos => block.reference.get(0..1).unwrap_or("N").to_string(),
+            None => "N".to_string(),
+        };
+
+        Ok(record)
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for ExpandBlocks<'_, I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.block.is_none() {
+            match self.inner.next()? {
+                Ok(record) if record.is_reference_block() => {
+                    let pos = record.pos;
+                    self.block = Some((record, pos));
+                }
+                item => return Some(item),
+            }
+        }
+
+        let (block, pos) = self.block.take()?;
+        let record = self.expand_at(&block, pos);
+        if pos < block.end() {
+            self.block = Some((block, pos + 1));
+        }
+
+        Some(record)
+    }
+}
+
+/// Merges adjacent reference blocks of a sorted stream.
+///
+/// Two blocks merge when the second starts right after the first ends on
+/// the same CHROM, and they have the same ALT, FILTER, FORMAT keys and
+/// `GT`s. Other FORMAT values must be equal, or integers, of which the
+/// smallest is kept, as GATK does for `DP`, `GQ` and `MIN_DP`. The merged
+/// block keeps the REF and INFO of the first and the `END` of the last.
+pub struct CollapseBlocks<I> {
+    inner: I,
+    pending: Option<Record>,
+    collapsed: u64,
+}
+
+impl<I: Iterator<Item = Result<Record>>> CollapseBlocks<I> {
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            pending: None,
+            collapsed: 0,
+        }
+    }
+
+    /// Number of blocks merged into an earlier one so far.
+    pub fn collapsed(&self) -> u64 {
+        self.collapsed
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+/// Merges `next` into `block` if the two can be collapsed.
+fn merge(block: &mut Record, next: &Record) -> bool {
+    let mergeable = block.is_reference_block()
+        && next.is_reference_block()
+        && block.chrom == next.chrom
+        && next.pos == block.end() + 1
+        && block.alternates == next.alternates
+        && block.filters == next.filters
+        && block.format == next.format
+        && block.samples.len() == next.samples.len();
+    if !mergeable {
+        return false;
+    }
+
+    let mut samples = block.samples.clone();
+    for (values, other) in samples.iter_mut().zip(&next.samples) {
+        if values.len() != other.len() {
+            return false;
+        }
+        for (j, (value, other)) in values.iter_mut().zip(other).enumerate() {
+            if value == other {
+                continue;
+            }
+            if block.format[j] == "GT" {
+                return false;
+            }
+            match (value.parse::<i64>(), other.parse::<i64>()) {
+                (Ok(a), Ok(b)) => *value = a.min(b).to_string(),
+                _ => return false,
+            }
+        }
+    }
+
+    block.samples = samples;
+    block.set_info("END", Some(next.end().to_string()));
+    true
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for CollapseBlocks<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.inner.next() {
+                Some(Ok(record)) => record,
+                Some(Err(e)) => return Some(Err(e)),
+                None => return self.pending.take().map(Ok),
+            };
+
+            match &mut self.pending {
+                Some(block) => {
+                    if !merge(block, &record) {
+                        return self.pending.replace(record).map(Ok);
+                    }
+                    self.collapsed += 1;
+                }
+                None => self.pending = Some(record),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference::MemoryReference;
+
+    fn records(lines: &[&str]) -> Vec<Result<Record>> {
+        lines.iter().map(|l| l.parse()).collect()
+    }
+
+    fn lines<I: Iterator<Item = Result<Record>>>(records: I) -> Vec<String> {
+        records.map(|r| r.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand_blocks_1() {
+        let input = [
+            "1\t2\t.\tC\t<NON_REF>\t.\t.\tEND=4\tGT:DP\t0/0:7",
+            "1\t5\t.\tG\tT,<NON_REF>\t.\t.\t.\tGT\t0/1",
+        ];
+
+        let expanded = lines(ExpandBlocks::new(records(&input).into_iter(), None));
+        assert_eq!(
+            expanded,
+            vec![
+                "1\t2\t.\tC\t<NON_REF>\t.\t.\t.\tGT:DP\t0/0:7",
+                "1\t3\t.\tN\t<NON_REF>\t.\t.\t.\tGT:DP\t0/0:7",
+                "1\t4\t.\tN\t<NON_REF>\t.\t.\t.\tGT:DP\t0/0:7",
+                input[1],
+            ]
+        );
+
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "ACGTG");
+        let expanded: Vec<String> =
+            ExpandBlocks::new(records(&input[..1]).into_iter(), Some(&reference))
+                .map(|r| r.unwrap().reference)
+                .collect();
+        assert_eq!(expanded, vec!["C", "G", "T"]);
+    }
+
+    #[test]
+    fn test_collapse_blocks_1() {
+        let input = [
+            "1\t1\t.\tA\t<NON_REF>\t.\t.\tEND=3\tGT:GQ\t0/0:30",
+            "1\t4\t.\tC\t<NON_REF>\t.\t.\t.\tGT:GQ\t0/0:20",
+            "1\t5\t.\tG\t<NON_REF>\t.\t.\tEND=9\tGT:GQ\t0/0:25",
+            "1\t10\t.\tT\t<NON_REF>\t.\t.\t.\tGT:GQ\t./.:0",
+            "1\t11\t.\tA\tC,<NON_REF>\t.\t.\t.\tGT:GQ\t0/1:50",
+            "1\t12\t.\tA\t<NON_REF>\t.\t.\t.\tGT:GQ\t./.:0",
+        ];
+
+        let mut collapse = CollapseBlocks::new(records(&input).into_iter());
+        let collapsed = lines(collapse.by_ref());
+
+        assert_eq!(
+            collapsed,
+            vec![
+                "1\t1\t.\tA\t<NON_REF>\t.\t.\tEND=9\tGT:GQ\t0/0:20",
+                input[3],
+                input[4],
+                input[5],
+            ]
+        );
+        assert_eq!(collapse.collapsed(), 2);
+
+        let record: Record = collapsed[0].parse().unwrap();
+        assert_eq!(record.block_range(), Some(1..=9));
+        assert_eq!(record.variant_alternates().count(), 0);
+    }
+}
@@ -0,0 +1,820 @@
+use crate::errors::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// VCF specification version declared by `##fileformat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    V4_1,
+    V4_2,
+    V4_3,
+    V4_4,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Version::V4_1 => "VCFv4.1",
+            Version::V4_2 => "VCFv4.2",
+            Version::V4_3 => "VCFv4.3",
+            Version::V4_4 => "VCFv4.4",
+        })
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "VCFv4.1" => Ok(Version::V4_1),
+            "VCFv4.2" => Ok(Version::V4_2),
+            "VCFv4.3" => Ok(Version::V4_3),
+            "VCFv4.4" => Ok(Version::V4_4),
+            v => Err(Error::UnsupportedVersionError(v.to_string())),
+        }
+    }
+}
+
+/// Number of values declared for an INFO or FORMAT field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Number {
+    Count(u32),
+    /// One value per ALT allele.
+    A,
+    /// One value per allele, including REF.
+    R,
+    /// One value per possible genotype.
+    G,
+    /// Unknown or varying (`.`).
+    Unknown,
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Count(n) => write!(f, "{}", n),
+            Number::A => f.write_str("A"),
+            Number::R => f.write_str("R"),
+            Number::G => f.write_str("G"),
+            Number::Unknown => f.write_str("."),
+        }
+    }
+}
+
+impl FromStr for Number {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "A" => Ok(Number::A),
+            "R" => Ok(Number::R),
+            "G" => Ok(Number::G),
+            "." => Ok(Number::Unknown),
+            n => n
+                .parse()
+                .map(Number::Count)
+                .map_err(|_| Error::HeaderValueError("Number".to_string(), n.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueType {
+    Integer,
+    Float,
+    Flag,
+    Character,
+    String,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Integer => "Integer",
+            ValueType::Float => "Float",
+            ValueType::Flag => "Flag",
+            ValueType::Character => "Character",
+            ValueType::String => "String",
+        })
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "Integer" => Ok(ValueType::Integer),
+            "Float" => Ok(ValueType::Float),
+            "Flag" => Ok(ValueType::Flag),
+            "Character" => Ok(ValueType::Character),
+            "String" => Ok(ValueType::String),
+            t => Err(Error::HeaderValueError("Type".to_string(), t.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfoDefinition {
+    pub id: String,
+    pub number: Number,
+    pub value_type: ValueType,
+    pub description: String,
+}
+
+impl InfoDefinition {
+    pub fn new(id: &str, number: Number, value_type: ValueType, description: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            number,
+            value_type,
+            description: description.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatDefinition {
+    pub id: String,
+    pub number: Number,
+    pub value_type: ValueType,
+    pub description: String,
+}
+
+impl FormatDefinition {
+    pub fn new(id: &str, number: Number, value_type: ValueType, description: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            number,
+            value_type,
+            description: description.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FilterDefinition {
+    pub id: String,
+    pub description: String,
+}
+
+impl FilterDefinition {
+    pub fn new(id: &str, description: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            description: description.to_string(),
+        }
+    }
+}
+
+/// Symbolic ALT allele definition (`##ALT=<ID=DEL,...>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AltDefinition {
+    pub id: String,
+    pub description: String,
+}
+
+impl AltDefinition {
+    pub fn new(id: &str, description: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            description: description.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContigDefinition {
+    pub id: String,
+    pub length: Option<u64>,
+    /// Remaining attributes such as `md5` or `assembly`, in declaration order.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl ContigDefinition {
+    pub fn new(id: &str, length: Option<u64>) -> Self {
+        Self {
+            id: id.to_string(),
+            length,
+            attributes: Vec::new(),
+        }
+    }
+}
+
+/// Curated starting points for [`Header::template`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderPreset {
+    /// Minimal VCFv4.3 germline call set.
+    Germline,
+    /// Fields emitted by GATK HaplotypeCaller/GenotypeGVCFs.
+    Gatk,
+    /// Structural variant caller output (Manta/Delly style).
+    StructuralVariant,
+    /// GATK-style gVCF with `<NON_REF>` and reference blocks.
+    Gvcf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+    /// Value of `##fileformat`, e.g. `VCFv4.3`.
+    pub fileformat: String,
+    pub infos: Vec<InfoDefinition>,
+    pub formats: Vec<FormatDefinition>,
+    pub filters: Vec<FilterDefinition>,
+    pub alts: Vec<AltDefinition>,
+    pub contigs: Vec<ContigDefinition>,
+    /// Unstructured `##key=value` lines, in declaration order.
+    pub other: Vec<(String, String)>,
+    pub samples: Vec<String>,
+}
+
+impl Header {
+    pub fn new(fileformat: &str) -> Self {
+        Self {
+            fileformat: fileformat.to_string(),
+            infos: Vec::new(),
+            formats: Vec::new(),
+            filters: Vec::new(),
+            alts: Vec::new(),
+            contigs: Vec::new(),
+            other: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Builds a header pre-populated with the spec definitions used by `preset`.
+    pub fn template(preset: HeaderPreset) -> Self {
+        match preset {
+            HeaderPreset::Germline => germline_template(),
+            HeaderPreset::Gatk => gatk_template(),
+            HeaderPreset::StructuralVariant => structural_variant_template(),
+            HeaderPreset::Gvcf => gvcf_template(),
+        }
+    }
+
+    /// Adds the definition described by one header line (`##...` or `#CHROM...`).
+    pub fn parse_line(&mut self, line: &str) -> Result<()> {
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if let Some(columns) = line.strip_prefix("#CHROM") {
+            self.samples = columns.split('\t').skip(9).map(|s| s.to_string()).collect();
+            return Ok(());
+        }
+
+        let (key, value) = line
+            .strip_prefix("##")
+            .and_then(|l| l.split_once('='))
+            .ok_or_else(|| Error::HeaderLineError(line.to_string()))?;
+
+        match key {
+            "fileformat" => self.fileformat = value.to_string(),
+            "INFO" => {
+                let fields = parse_structured(line, value)?;
+                self.infos.push(InfoDefinition {
+                    id: required(line, &fields, "ID")?.to_string(),
+                    number: required(line, &fields, "Number")?.parse()?,
+                    value_type: required(line, &fields, "Type")?.parse()?,
+                    description: optional(&fields, "Description").unwrap_or("").to_string(),
+                });
+            }
+            "FORMAT" => {
+                let fields = parse_structured(line, value)?;
+                self.formats.push(FormatDefinition {
+                    id: required(line, &fields, "ID")?.to_string(),
+                    number: required(line, &fields, "Number")?.parse()?,
+                    value_type: required(line, &fields, "Type")?.parse()?,
+                    description: optional(&fields, "Description").unwrap_or("").to_string(),
+                });
+            }
+            "FILTER" => {
+                let fields = parse_structured(line, value)?;
+                self.filters.push(FilterDefinition {
+                    id: required(line, &fields, "ID")?.to_string(),
+                    description: optional(&fields, "Description").unwrap_or("").to_string(),
+                });
+            }
+            "ALT" => {
+                let fields = parse_structured(line, value)?;
+                self.alts.push(AltDefinition {
+                    id: required(line, &fields, "ID")?.to_string(),
+                    description: optional(&fields, "Description").unwrap_or("").to_string(),
+                });
+            }
+            "contig" => {
+                let fields = parse_structured(line, value)?;
+                let length = match optional(&fields, "length") {
+                    Some(l) => Some(l.parse().map_err(|_| {
+                        Error::HeaderValueError("length".to_string(), l.to_string())
+                    })?),
+                    None => None,
+                };
+                self.contigs.push(ContigDefinition {
+                    id: required(line, &fields, "ID")?.to_string(),
+                    length,
+                    attributes: fields
+                        .into_iter()
+                        .filter(|(k, _)| k != "ID" && k != "length")
+                        .collect(),
+                });
+            }
+            _ => self.other.push((key.to_string(), value.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Version declared by `##fileformat`, if it is one this crate knows about.
+    pub fn version(&self) -> Option<Version> {
+        self.fileformat.parse().ok()
+    }
+
+    pub fn info(&self, id: &str) -> Option<&InfoDefinition> {
+        self.infos.iter().find(|d| d.id == id)
+    }
+
+    pub fn format(&self, id: &str) -> Option<&FormatDefinition> {
+        self.formats.iter().find(|d| d.id == id)
+    }
+
+    pub fn filter(&self, id: &str) -> Option<&FilterDefinition> {
+        self.filters.iter().find(|d| d.id == id)
+    }
+
+    pub fn contig(&self, id: &str) -> Option<&ContigDefinition> {
+        self.contigs.iter().find(|d| d.id == id)
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "##fileformat={}", self.fileformat)?;
+
+        for (key, value) in &self.other {
+            writeln!(f, "##{}={}", key, value)?;
+        }
+
+        for d in &self.filters {
+            writeln!(
+                f,
+                "##FILTER=<ID={},Description={}>",
+                d.id,
+                quote(&d.description)
+            )?;
+        }
+
+        for d in &self.infos {
+            writeln!(
+                f,
+                "##INFO=<ID={},Number={},Type={},Description={}>",
+                d.id,
+                d.number,
+                d.value_type,
+                quote(&d.description)
+            )?;
+        }
+
+        for d in &self.formats {
+            writeln!(
+                f,
+                "##FORMAT=<ID={},Number={},Type={},Description={}>",
+                d.id,
+                d.number,
+                d.value_type,
+                quote(&d.description)
+            )?;
+        }
+
+        for d in &self.alts {
+            writeln!(
+                f,
+                "##ALT=<ID={},Description={}>",
+                d.id,
+                quote(&d.description)
+            )?;
+        }
+
+        for d in &self.contigs {
+            write!(f, "##contig=<ID={}", d.id)?;
+            if let Some(length) = d.length {
+                write!(f, ",length={}", length)?;
+            }
+            for (key, value) in &d.attributes {
+                if value.contains([',', '"', '>', ' ']) {
+                    write!(f, ",{}={}", key, quote(value))?;
+                } else {
+                    write!(f, ",{}={}", key, value)?;
+                }
+            }
+            writeln!(f, ">")?;
+        }
+
+        write!(f, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+        if !self.samples.is_empty() {
+            write!(f, "\tFORMAT")?;
+            for sample in &self.samples {
+                write!(f, "\t{}", sample)?;
+            }
+        }
+
+        writeln!(f)
+    }
+}
+
+impl FromStr for Header {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut header = Header::new("");
+
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            header.parse_line(line)?;
+        }
+
+        if header.fileformat.is_empty() {
+            Err(Error::HeaderMissingFileformatError())?
+        }
+
+        Ok(header)
+    }
+}
+
+/// Splits `<ID=x,Description="...">` into key/value pairs, unquoting values.
+fn parse_structured(line: &str, value: &str) -> Result<Vec<(String, String)>> {
+    let inner = value
+        .strip_prefix('<')
+        .and_then(|v| v.strip_suffix('>'))
+        .ok_or_else(|| Error::HeaderLineError(line.to_string()))?;
+
+    let mut fields = Vec::new();
+    let mut chars = inner.chars().peekable();
+
+    while chars.peek().is_some() {
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let mut value = String::new();
+
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('\\') => value.extend(chars.next()),
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => Err(Error::HeaderLineError(line.to_string()))?,
+                }
+            }
+            if let Some(c) = chars.next() {
+                if c != ',' {
+                    Err(Error::HeaderLineError(line.to_string()))?
+                }
+            }
+        } else {
+            value = chars.by_ref().take_while(|&c| c != ',').collect();
+        }
+
+        fields.push((key, value));
+    }
+
+    Ok(fields)
+}
+
+fn optional<'a>(fields: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn required<'a>(line: &str, fields: &'a [(String, String)], key: &str) -> Result<&'a str> {
+    optional(fields, key).ok_or_else(|| Error::HeaderLineError(line.to_string()))
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn germline_template() -> Header {
+    let mut header = Header::new("VCFv4.3");
+
+    header.filters = vec![FilterDefinition::new("PASS", "All filters passed")];
+    header.infos = vec![
+        InfoDefinition::new(
+            "AC",
+            Number::A,
+            ValueType::Integer,
+            "Allele count in genotypes",
+        ),
+        InfoDefinition::new(
+            "AN",
+            Number::Count(1),
+            ValueType::Integer,
+            "Total number of alleles in called genotypes",
+        ),
+        InfoDefinition::new("AF", Number::A, ValueType::Float, "Allele frequency"),
+        InfoDefinition::new(
+            "DP",
+            Number::Count(1),
+            ValueType::Integer,
+            "Combined depth across samples",
+        ),
+    ];
+    header.formats = vec![
+        FormatDefinition::new("GT", Number::Count(1), ValueType::String, "Genotype"),
+        FormatDefinition::new(
+            "GQ",
+            Number::Count(1),
+            ValueType::Integer,
+            "Conditional genotype quality",
+        ),
+        FormatDefinition::new("DP", Number::Count(1), ValueType::Integer, "Read depth"),
+        FormatDefinition::new(
+            "AD",
+            Number::R,
+            ValueType::Integer,
+            "Read depth for each allele",
+        ),
+    ];
+
+    header
+}
+
+fn gatk_template() -> Header {
+    let mut header = Header::new("VCFv4.2");
+
+    header.filters = vec![
+        FilterDefinition::new("PASS", "All filters passed"),
+        FilterDefinition::new("LowQual", "Low quality"),
+    ];
+    header.infos = vec![
+        InfoDefinition::new("AC", Number::A, ValueType::Integer, "Allele count in genotypes, for each ALT allele, in the same order as listed"),
+        InfoDefinition::new("AF", Number::A, ValueType::Float, "Allele Frequency, for each ALT allele, in the same order as listed"),
+        InfoDefinition::new("AN", Number::Count(1), ValueType::Integer, "Total number of alleles in called genotypes"),
+        InfoDefinition::new("BaseQRankSum", Number::Count(1), ValueType::Float, "Z-score from Wilcoxon rank sum test of Alt Vs. Ref base qualities"),
+        InfoDefinition::new("DP", Number::Count(1), ValueType::Integer, "Approximate read depth; some reads may have been filtered"),
+        InfoDefinition::new("ExcessHet", Number::Count(1), ValueType::Float, "Phred-scaled p-value for exact test of excess heterozygosity"),
+        InfoDefinition::new("FS", Number::Count(1), ValueType::Float, "Phred-scaled p-value using Fisher's exact test to detect strand bias"),
+        InfoDefinition::new("MLEAC", Number::A, ValueType::Integer, "Maximum likelihood expectation (MLE) for the allele counts (not necessarily the same as the AC), for each ALT allele, in the same order as listed"),
+        InfoDefinition::new("MLEAF", Number::A, ValueType::Float, "Maximum likelihood expectation (MLE) for the allele frequency (not necessarily the same as the AF), for each ALT allele, in the same order as listed"),
+        InfoDefinition::new("MQ", Number::Count(1), ValueType::Float, "RMS Mapping Quality"),
+        InfoDefinition::new("MQRankSum", Number::Count(1), ValueType::Float, "Z-score From Wilcoxon rank sum test of Alt vs. Ref read mapping qualities"),
+        InfoDefinition::new("QD", Number::Count(1), ValueType::Float, "Variant Confidence/Quality by Depth"),
+        InfoDefinition::new("ReadPosRankSum", Number::Count(1), ValueType::Float, "Z-score from Wilcoxon rank sum test of Alt vs. Ref read position bias"),
+        InfoDefinition::new("SOR", Number::Count(1), ValueType::Float, "Symmetric Odds Ratio of 2x2 contingency table to detect strand bias"),
+    ];
+    header.formats = vec![
+        FormatDefinition::new("GT", Number::Count(1), ValueType::String, "Genotype"),
+        FormatDefinition::new("AD", Number::R, ValueType::Integer, "Allelic depths for the ref and alt alleles in the order listed"),
+        FormatDefinition::new("DP", Number::Count(1), ValueType::Integer, "Approximate read depth (reads with MQ=255 or with bad mates are filtered)"),
+        FormatDefinition::new("GQ", Number::Count(1), ValueType::Integer, "Genotype Quality"),
+        FormatDefinition::new("PGT", Number::Count(1), ValueType::String, "Physical phasing haplotype information, describing how the alternate alleles are phased in relation to one another"),
+        FormatDefinition::new("PID", Number::Count(1), ValueType::String, "Physical phasing ID information, where each unique ID within a given sample (but not across samples) connects records within a phasing group"),
+        FormatDefinition::new("PL", Number::G, ValueType::Integer, "Normalized, Phred-scaled likelihoods for genotypes as defined in the VCF specification"),
+        FormatDefinition::new("PS", Number::Count(1), ValueType::Integer, "Phasing set (typically the position of the first variant in the set)"),
+    ];
+
+    header
+}
+
+fn structural_variant_template() -> Header {
+    let mut header = Header::new("VCFv4.3");
+
+    header.filters = vec![FilterDefinition::new("PASS", "All filters passed")];
+    header.infos = vec![
+        InfoDefinition::new(
+            "IMPRECISE",
+            Number::Count(0),
+            ValueType::Flag,
+            "Imprecise structural variation",
+        ),
+        InfoDefinition::new(
+            "SVTYPE",
+            Number::Count(1),
+            ValueType::String,
+            "Type of structural variant",
+        ),
+        InfoDefinition::new(
+            "SVLEN",
+            Number::A,
+            ValueType::Integer,
+            "Difference in length between REF and ALT alleles",
+        ),
+        InfoDefinition::new(
+            "END",
+            Number::Count(1),
+            ValueType::Integer,
+            "End position of the variant described in this record",
+        ),
+        InfoDefinition::new(
+            "CIPOS",
+            Number::Count(2),
+            ValueType::Integer,
+            "Confidence interval around POS for imprecise variants",
+        ),
+        InfoDefinition::new(
+            "CIEND",
+            Number::Count(2),
+            ValueType::Integer,
+            "Confidence interval around END for imprecise variants",
+        ),
+        InfoDefinition::new(
+            "MATEID",
+            Number::Unknown,
+            ValueType::String,
+            "ID of mate breakends",
+        ),
+        InfoDefinition::new(
+            "EVENT",
+            Number::Count(1),
+            ValueType::String,
+            "ID of event associated to breakend",
+        ),
+    ];
+    header.formats = vec![
+        FormatDefinition::new("GT", Number::Count(1), ValueType::String, "Genotype"),
+        FormatDefinition::new(
+            "GQ",
+            Number::Count(1),
+            ValueType::Integer,
+            "Conditional genotype quality",
+        ),
+        FormatDefinition::new(
+            "PR",
+            Number::Count(2),
+            ValueType::Integer,
+            "Spanning paired-read support for the ref and alt alleles in the order listed",
+        ),
+        FormatDefinition::new(
+            "SR",
+            Number::Count(2),
+            ValueType::Integer,
+            "Split reads for the ref and alt alleles in the order listed",
+        ),
+    ];
+    header.alts = vec![
+        AltDefinition::new("DEL", "Deletion"),
+        AltDefinition::new("INS", "Insertion"),
+        AltDefinition::new("DUP", "Duplication"),
+        AltDefinition::new("DUP:TANDEM", "Tandem duplication"),
+        AltDefinition::new("INV", "Inversion"),
+        AltDefinition::new("CNV", "Copy number variable region"),
+    ];
+
+    header
+}
+
+fn gvcf_template() -> Header {
+    let mut header = gatk_template();
+
+    header.infos.insert(
+        0,
+        InfoDefinition::new(
+            "END",
+            Number::Count(1),
+            ValueType::Integer,
+            "Stop position of the interval",
+        ),
+    );
+    header.formats.push(FormatDefinition::new(
+        "MIN_DP",
+        Number::Count(1),
+        ValueType::Integer,
+        "Minimum DP observed within the GVCF block",
+    ));
+    header.formats.push(FormatDefinition::new(
+        "SB",
+        Number::Count(4),
+        ValueType::Integer,
+        "Per-sample component statistics which comprise the Fisher's Exact Test to detect strand bias",
+    ));
+    header.alts = vec![AltDefinition::new(
+        "NON_REF",
+        "Represents any possible alternative allele not already represented at this location by REF and ALT",
+    )];
+
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_1() {
+        let header = Header::template(HeaderPreset::Germline);
+        let text = header.to_string();
+
+        assert!(text.starts_with("##fileformat=VCFv4.3\n"));
+        assert!(text.contains(
+            "##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Read depth for each allele\">\n"
+        ));
+        assert!(text.ends_with("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n"));
+    }
+
+    #[test]
+    fn test_template_2() {
+        let mut header = Header::template(HeaderPreset::Gvcf);
+        header.samples.push("NA12878".to_string());
+        let text = header.to_string();
+
+        assert_eq!(header.info("END").unwrap().value_type, ValueType::Integer);
+        assert_eq!(header.format("PL").unwrap().number, Number::G);
+        assert!(text.contains("##ALT=<ID=NON_REF,"));
+        assert!(text.ends_with("\tINFO\tFORMAT\tNA12878\n"));
+    }
+
+    #[test]
+    fn test_template_3() {
+        let header = Header::template(HeaderPreset::StructuralVariant);
+
+        assert_eq!(header.info("CIPOS").unwrap().number, Number::Count(2));
+        assert!(header.alts.iter().any(|d| d.id == "DUP:TANDEM"));
+    }
+
+    #[test]
+    fn test_parse_1() {
+        let text = Header::template(HeaderPreset::Gatk).to_string();
+        let header: Header = text.parse().unwrap();
+
+        assert_eq!(header, Header::template(HeaderPreset::Gatk));
+    }
+
+    #[test]
+    fn test_parse_2() {
+        let text = "##fileformat=VCFv4.2\n\
+            ##source=caller \"v1\"\n\
+            ##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth, \\\"raw\\\"\",Source=x>\n\
+            ##contig=<ID=1,length=249250621,species=\"Homo sapiens\">\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n";
+        let header: Header = text.parse().unwrap();
+
+        assert_eq!(header.fileformat, "VCFv4.2");
+        assert_eq!(
+            header.other,
+            vec![("source".to_string(), "caller \"v1\"".to_string())]
+        );
+        assert_eq!(header.info("DP").unwrap().description, "Depth, \"raw\"");
+        assert_eq!(header.contig("1").unwrap().length, Some(249250621));
+        assert_eq!(header.samples, vec!["S1", "S2"]);
+        assert!(header
+            .to_string()
+            .contains("##contig=<ID=1,length=249250621,species=\"Homo sapiens\">"));
+    }
+
+    #[test]
+    fn test_parse_err_1() {
+        assert!("##INFO=<ID=DP>\n".parse::<Header>().is_err());
+        assert!(
+            "##fileformat=VCFv4.2\n##INFO=<ID=DP,Number=x,Type=Integer>\n"
+                .parse::<Header>()
+                .is_err()
+        );
+        assert!("##fileformat=VCFv4.2\n##FILTER=<ID=q10,Description=\"x>\n"
+            .parse::<Header>()
+            .is_err());
+        assert!("#CHROM\tPOS\n".parse::<Header>().is_err());
+    }
+
+    #[test]
+    fn test_version_1() {
+        assert_eq!(Header::new("VCFv4.2").version(), Some(Version::V4_2));
+        assert_eq!(Header::new("VCFv3.3").version(), None);
+        assert_eq!(Version::V4_4.to_string(), "VCFv4.4");
+        assert!(Version::V4_1 < Version::V4_3);
+    }
+
+    #[test]
+    fn test_display_1() {
+        let mut header = Header::new("VCFv4.3");
+        let mut contig = ContigDefinition::new("chr1", Some(248956422));
+        contig
+            .attributes
+            .push(("assembly".to_string(), "GRCh38".to_string()));
+        header.contigs.push(contig);
+        header
+            .filters
+            .push(FilterDefinition::new("q10", "Quality \"below\" 10"));
+
+        let text = header.to_string();
+
+        assert!(text.contains("##contig=<ID=chr1,length=248956422,assembly=GRCh38>\n"));
+        assert!(text.contains("Description=\"Quality \\\"below\\\" 10\">"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_header_serde_1() {
+        let header = Header::template(HeaderPreset::Gatk);
+        let json = serde_json::to_string(&header).unwrap();
+
+        assert_eq!(serde_json::from_str::<Header>(&json).unwrap(), header);
+    }
+}
@@ -0,0 +1,250 @@
+//! Imputation of missing genotypes.
+
+use crate::errors::Result;
+use crate::genotype::Genotype;
+use crate::header::{FormatDefinition, Header, Number, ValueType};
+use crate::record::Record;
+use crate::regions::RegionSet;
+use std::collections::HashMap;
+
+/// Rules for filling missing genotypes with hom-ref calls.
+///
+/// In joint-called gVCF cohorts a sample without a call usually had no
+/// evidence for a variant, so treating it as hom-ref is the common practice.
+/// Callable regions restrict this to sites where the sample actually had
+/// coverage; outside them the missing call is kept.
+#[derive(Debug, Clone)]
+pub struct HomRefPolicy {
+    /// Callable regions by sample name.
+    pub callable: HashMap<String, RegionSet>,
+    /// Leave samples without callable regions untouched instead of imputing
+    /// them everywhere.
+    pub require_callable: bool,
+    /// Also fill the missing alleles of half-calls such as `./1`.
+    pub half_calls: bool,
+    /// Ploidy of calls imputed for samples whose `GT` value is absent or `.`.
+    pub ploidy: usize,
+    /// FORMAT key set to `1` on imputed genotypes.
+    pub flag: Option<String>,
+}
+
+impl Default for HomRefPolicy {
+    fn default() -> Self {
+        Self {
+            callable: HashMap::new(),
+            require_callable: false,
+            half_calls: false,
+            ploidy: 2,
+            flag: None,
+        }
+    }
+}
+
+impl HomRefPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds callable regions for a sample.
+    pub fn with_callable(mut self, sample: &str, regions: RegionSet) -> Self {
+        self.callable.insert(sample.to_string(), regions);
+        self
+    }
+
+    /// Declares the flag key, if any, in `header`.
+    pub fn update_header(&self, header: &mut Header) {
+        if let Some(flag) = &self.flag {
+            if header.format(flag).is_none() {
+                header.formats.push(FormatDefinition::new(
+                    flag,
+                    Number::Count(1),
+                    ValueType::Integer,
+                    "Genotype imputed as reference",
+                ));
+            }
+        }
+    }
+
+    /// Imputes the missing genotypes of `record`, whose sample columns are
+    /// named by `samples`, and returns the number of genotypes changed.
+    ///
+    /// Records without a `GT` key are left as they are.
+    pub fn apply(&self, samples: &[String], record: &mut Record) -> Result<usize> {
+        if record.format_index("GT").is_none() {
+            return Ok(0);
+        }
+
+        let end = record.pos + record.reference.len().max(1) as u64 - 1;
+        let mut imputed = 0;
+
+        for i in 0..record.samples.len() {
+            let callable = match samples.get(i).and_then(|s| self.callable.get(s)) {
+                Some(regions) => regions.covers(&record.chrom, record.pos, end),
+                None => !self.require_callable,
+            };
+            if !callable {
+                continue;
+            }
+
+            let mut genotype = match record.genotype(i)? {
+                Some(gt) if gt.ploidy() == 1 && gt.is_missing() => Genotype::missing(self.ploidy),
+                Some(gt) => gt,
+                None => Genotype::missing(self.ploidy),
+            };
+
+            if !(genotype.is_missing() || (self.half_calls && genotype.is_partially_missing())) {
+                continue;
+            }
+
+            for allele in genotype.alleles.iter_mut().filter(|a| a.is_none()) {
+                *allele = Some(0);
+            }
+
+            record.set_genotype(i, &genotype)?;
+            if let Some(flag) = &self.flag {
+                record.set_format_value(i, flag, "1")?;
+            }
+            imputed += 1;
+        }
+
+        Ok(imputed)
+    }
+}
+
+/// Iterator adapter applying a [`HomRefPolicy`] to every record.
+pub struct HomRefImputer<I> {
+    inner: I,
+    samples: Vec<String>,
+    policy: HomRefPolicy,
+    imputed: u64,
+}
+
+impl<I: Iterator<Item = Result<Record>>> HomRefImputer<I> {
+    /// Wraps `inner`, taking sample names from `header`.
+    pub fn new(inner: I, header: &Header, policy: HomRefPolicy) -> Self {
+        Self {
+            inner,
+            samples: header.samples.clone(),
+            policy,
+            imputed: 0,
+        }
+    }
+
+    /// Number of genotypes imputed so far.
+    pub fn imputed(&self) -> u64 {
+        self.imputed
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for HomRefImputer<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = match self.inner.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(self.policy.apply(&self.samples, &mut record).map(|n| {
+            self.imputed += n as u64;
+            record
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##fileformat=VCFv4.2\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3\n\
+        1\t100\t.\tA\tT\t.\t.\t.\tGT:DP\t./.:.\t0/1:9\t.\n\
+        1\t200\t.\tACG\tA\t.\t.\t.\tGT\t./1\t.|.\t./.\n";
+
+    fn samples() -> Vec<String> {
+        vec!["S1".to_string(), "S2".to_string(), "S3".to_string()]
+    }
+
+    #[test]
+    fn test_apply_1() {
+        let mut record: Record = "1\t100\t.\tA\tT\t.\t.\t.\tGT:DP\t./.:.\t0/1:9\t."
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            HomRefPolicy::new().apply(&samples(), &mut record).unwrap(),
+            2
+        );
+        assert_eq!(
+            record.to_string(),
+            "1\t100\t.\tA\tT\t.\t.\t.\tGT:DP\t0/0:.\t0/1:9\t0/0"
+        );
+    }
+
+    #[test]
+    fn test_apply_2() {
+        let mut regions = RegionSet::new();
+        regions.insert("1", 150, 201);
+        let policy = HomRefPolicy {
+            require_callable: true,
+            half_calls: true,
+            flag: Some("IMP".to_string()),
+            ..HomRefPolicy::new().with_callable("S1", regions.clone())
+        }
+        .with_callable("S2", regions);
+
+        let mut record: Record = "1\t200\t.\tACG\tA\t.\t.\t.\tGT\t./1\t.|.\t./."
+            .parse()
+            .unwrap();
+
+        // The deletion spans 200..=202, beyond the callable regions.
+        assert_eq!(policy.apply(&samples(), &mut record).unwrap(), 0);
+
+        record.reference = "AC".to_string();
+
+        assert_eq!(policy.apply(&samples(), &mut record).unwrap(), 2);
+        assert_eq!(
+            record.to_string(),
+            "1\t200\t.\tAC\tA\t.\t.\t.\tGT:IMP\t0/1:1\t0|0:1\t./."
+        );
+    }
+
+    #[test]
+    fn test_apply_3() {
+        let mut record: Record = "1\t100\t.\tA\tT\t.\t.\t.\tDP\t3".parse().unwrap();
+
+        assert_eq!(
+            HomRefPolicy::new().apply(&samples(), &mut record).unwrap(),
+            0
+        );
+
+        let mut record: Record = "1\t100\t.\tA\tT\t.\t.\t.\tGT\tx".parse().unwrap();
+
+        assert!(HomRefPolicy::new().apply(&samples(), &mut record).is_err());
+    }
+
+    #[test]
+    fn test_hom_ref_imputer_1() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let mut header = reader.header().clone();
+        let policy = HomRefPolicy {
+            flag: Some("IMP".to_string()),
+            ..HomRefPolicy::new()
+        };
+        policy.update_header(&mut header);
+
+        let mut imputer = HomRefImputer::new(reader, &header, policy);
+        let records: Vec<Record> = imputer.by_ref().map(|r| r.unwrap()).collect();
+
+        assert!(header.format("IMP").is_some());
+        assert_eq!(imputer.imputed(), 4);
+        assert_eq!(records[0].format_value(2, "GT"), Some("0/0"));
+        assert_eq!(records[1].format_value(0, "GT"), Some("./1"));
+        assert_eq!(records[1].format_value(1, "IMP"), Some("1"));
+    }
+}
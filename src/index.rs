@@ -0,0 +1,393 @@
+//! Tabix (`.tbi`) and coordinate-sorted (`.csi`) index files.
+
+use crate::bgzf;
+use crate::errors::{Error, Result};
+use std::io::Read;
+
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+/// A pair of BGZF virtual offsets delimiting compressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bin {
+    pub id: u32,
+    /// Smallest virtual offset of records in the bin (CSI only, `0` for TBI).
+    pub loffset: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferenceIndex {
+    pub bins: Vec<Bin>,
+    /// Linear index of 16 kbp windows (TBI only).
+    pub intervals: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    pub min_shift: u32,
+    pub depth: u32,
+    /// Sequence names in the order used by `references`.
+    pub names: Vec<String>,
+    pub references: Vec<ReferenceIndex>,
+    /// Number of unplaced records, when recorded.
+    pub n_no_coor: Option<u64>,
+}
+
+impl Index {
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Parses a BGZF-compressed TBI or CSI index.
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        bgzf::Reader::new(reader).read_to_end(&mut data)?;
+
+        let mut cursor = Cursor {
+            data: &data,
+            pos: 0,
+        };
+
+        match cursor.bytes(4)? {
+            b"TBI\x01" => read_tbi(&mut cursor),
+            b"CSI\x01" => read_csi(&mut cursor),
+            _ => Err(Error::IndexFormatError("unknown magic".to_string())),
+        }
+    }
+
+    /// Id of the pseudo-bin holding metadata rather than chunks.
+    pub fn pseudo_bin(&self) -> u32 {
+        (((1u64 << (3 * (self.depth + 1))) - 1) / 7 + 1) as u32
+    }
+
+    pub fn reference_id(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Chunks that may hold records of `name` overlapping `start..=end`,
+    /// 1-based inclusive, sorted and merged.
+    ///
+    /// Records in the chunks still have to be checked against the region.
+    pub fn query(&self, name: &str, start: u64, end: u64) -> Vec<Chunk> {
+        let reference = match self.reference_id(name).and_then(|i| self.references.get(i)) {
+            Some(reference) => reference,
+            None => return Vec::new(),
+        };
+
+        let max = 1u64 << (self.min_shift + 3 * self.depth);
+        let beg = start.saturating_sub(1).min(max - 1);
+        let end = end.saturating_sub(1).clamp(beg, max - 1);
+        let min_offset = reference
+            .intervals
+            .get((beg >> 14) as usize)
+            .copied()
+            .unwrap_or(0);
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for level in 0..=self.depth {
+            let first = ((1u64 << (3 * level)) - 1) / 7;
+            let shift = self.min_shift + 3 * (self.depth - level);
+            let bins = first + (beg >> shift)..=first + (end >> shift);
+
+            for bin in &reference.bins {
+                if bins.contains(&(bin.id as u64)) {
+                    chunks.extend(bin.chunks.iter().filter(|c| c.end > min_offset));
+                }
+            }
+        }
+
+        chunks.sort_by_key(|c| c.start);
+        let mut merged: Vec<Chunk> = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            match merged.last_mut() {
+                Some(last) if chunk.start <= last.end => last.end = last.end.max(chunk.end),
+                _ => merged.push(chunk),
+            }
+        }
+
+        merged
+    }
+}
+
+#[cfg(feature = "fs")]
+/// Returns the `.tbi` or `.csi` file next to `path`, if one exists.
+pub fn find_index<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    ["tbi", "csi"].iter().find_map(|ext| {
+        let mut p = path.as_ref().as_os_str().to_owned();
+        p.push(".");
+        p.push(ext);
+        let p = PathBuf::from(p);
+        p.exists().then_some(p)
+    })
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| Error::IndexFormatError("unexpected end of index".to_string()))?;
+        self.pos += n;
+
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn count(&mut self) -> Result<usize> {
+        usize::try_from(self.i32()?)
+            .map_err(|_| Error::IndexFormatError("negative count".to_string()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// Reads the tabix configuration block, returning the sequence names.
+fn read_tabix_header(cursor: &mut Cursor) -> Result<Vec<String>> {
+    // format, col_seq, col_beg, col_end, meta, skip
+    cursor.bytes(24)?;
+    let l_nm = cursor.count()?;
+    let names = cursor.bytes(l_nm)?;
+
+    Ok(names
+        .split(|&b| b == 0)
+        .filter(|n| !n.is_empty())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .collect())
+}
+
+fn read_chunks(cursor: &mut Cursor) -> Result<Vec<Chunk>> {
+    (0..cursor.count()?)
+        .map(|_| {
+            Ok(Chunk {
+                start: cursor.u64()?,
+                end: cursor.u64()?,
+            })
+        })
+        .collect()
+}
+
+fn read_n_no_coor(cursor: &mut Cursor) -> Result<Option<u64>> {
+    if cursor.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(cursor.u64()?))
+    }
+}
+
+fn read_tbi(cursor: &mut Cursor) -> Result<Index> {
+    let n_ref = cursor.count()?;
+    let names = read_tabix_header(cursor)?;
+
+    let references = (0..n_ref)
+        .map(|_| {
+            let bins = (0..cursor.count()?)
+                .map(|_| {
+                    Ok(Bin {
+                        id: cursor.u32()?,
+                        loffset: 0,
+                        chunks: read_chunks(cursor)?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            let intervals = (0..cursor.count()?)
+                .map(|_| cursor.u64())
+                .collect::<Result<_>>()?;
+
+            Ok(ReferenceIndex { bins, intervals })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Index {
+        min_shift: 14,
+        depth: 5,
+        names,
+        references,
+        n_no_coor: read_n_no_coor(cursor)?,
+    })
+}
+
+fn read_csi(cursor: &mut Cursor) -> Result<Index> {
+    let min_shift = cursor.u32()?;
+    let depth = cursor.u32()?;
+    if depth == 0 || min_shift.saturating_add(depth.saturating_mul(3)) > 63 {
+        Err(Error::IndexFormatError(format!(
+            "invalid CSI binning: min_shift {min_shift}, depth {depth}"
+        )))?
+    }
+    let l_aux = cursor.count()?;
+
+    let names = if l_aux >= 28 {
+        let mut aux = Cursor {
+            data: cursor.bytes(l_aux)?,
+            pos: 0,
+        };
+        read_tabix_header(&mut aux)?
+    } else {
+        cursor.bytes(l_aux)?;
+        Vec::new()
+    };
+
+    let n_ref = cursor.count()?;
+    let references = (0..n_ref)
+        .map(|_| {
+            let bins = (0..cursor.count()?)
+                .map(|_| {
+                    Ok(Bin {
+                        id: cursor.u32()?,
+                        loffset: cursor.u64()?,
+                        chunks: read_chunks(cursor)?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(ReferenceIndex {
+                bins,
+                intervals: Vec::new(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Index {
+        min_shift,
+        depth,
+        names,
+        references,
+        n_no_coor: read_n_no_coor(cursor)?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Encodes a TBI index for VCF with one bin per sequence.
+    pub(crate) fn tbi_bytes(names: &[&str], chunks: &[Chunk]) -> Vec<u8> {
+        let mut raw = b"TBI\x01".to_vec();
+        let names_block: Vec<u8> = names
+            .iter()
+            .flat_map(|n| [n.as_bytes(), b"\0"].concat())
+            .collect();
+
+        raw.extend_from_slice(&(names.len() as i32).to_le_bytes());
+        for v in [2i32, 1, 2, 0, b'#' as i32, 0, names_block.len() as i32] {
+            raw.extend_from_slice(&v.to_le_bytes());
+        }
+        raw.extend_from_slice(&names_block);
+        for chunk in chunks {
+            raw.extend_from_slice(&1i32.to_le_bytes());
+            raw.extend_from_slice(&4681u32.to_le_bytes());
+            raw.extend_from_slice(&1i32.to_le_bytes());
+            raw.extend_from_slice(&chunk.start.to_le_bytes());
+            raw.extend_from_slice(&chunk.end.to_le_bytes());
+            raw.extend_from_slice(&1i32.to_le_bytes());
+            raw.extend_from_slice(&chunk.start.to_le_bytes());
+        }
+
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(&raw).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_tbi_1() {
+        let chunks = [
+            Chunk { start: 0, end: 100 },
+            Chunk {
+                start: 100,
+                end: 200,
+            },
+        ];
+        let index = Index::read(&tbi_bytes(&["1", "2"], &chunks)[..]).unwrap();
+
+        assert_eq!(index.names, vec!["1", "2"]);
+        assert_eq!(index.references[1].bins[0].id, 4681);
+        assert_eq!(index.references[1].bins[0].chunks, vec![chunks[1]]);
+        assert_eq!(index.references[1].intervals, vec![100]);
+        assert_eq!(index.n_no_coor, None);
+        assert_eq!(index.pseudo_bin(), 37450);
+        assert_eq!(index.reference_id("2"), Some(1));
+    }
+
+    #[test]
+    fn test_query_1() {
+        let chunks = [
+            Chunk { start: 0, end: 100 },
+            Chunk {
+                start: 100,
+                end: 200,
+            },
+        ];
+        let index = Index::read(&tbi_bytes(&["1", "2"], &chunks)[..]).unwrap();
+
+        assert_eq!(index.query("1", 1, 1000), vec![chunks[0]]);
+        assert_eq!(index.query("2", 16_000, 17_000), vec![chunks[1]]);
+        assert_eq!(index.query("2", 17_000, 18_000), vec![]);
+        assert_eq!(index.query("3", 1, 1000), vec![]);
+    }
+
+    #[test]
+    fn test_read_err_1() {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(b"BAI\x01").unwrap();
+
+        assert!(Index::read(&writer.finish().unwrap()[..]).is_err());
+
+        let bytes = tbi_bytes(&["1"], &[Chunk { start: 0, end: 1 }]);
+        let mut raw = Vec::new();
+        bgzf::Reader::new(&bytes[..]).read_to_end(&mut raw).unwrap();
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(&raw[..raw.len() - 4]).unwrap();
+
+        assert!(Index::read(&writer.finish().unwrap()[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_err_2() {
+        let csi = |min_shift: u32, depth: u32| {
+            let mut writer = bgzf::Writer::new(Vec::new());
+            writer.write_all(b"CSI\x01").unwrap();
+            for v in [min_shift, depth, 0, 0] {
+                writer.write_all(&v.to_le_bytes()).unwrap();
+            }
+            writer.finish().unwrap()
+        };
+
+        assert_eq!(Index::read(&csi(14, 16)[..]).unwrap().depth, 16);
+        for (min_shift, depth) in [(14, 0), (14, 17), (u32::MAX, 1), (1, u32::MAX)] {
+            let err = Index::read(&csi(min_shift, depth)[..]).unwrap_err();
+
+            assert!(matches!(err, Error::IndexFormatError(_)), "{err}");
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Whole-file checks for silent corruption of VCF files and their indexes.
+
+use crate::bgzf;
+use crate::errors::Result;
+use crate::index::{find_index, Index};
+use flate2::bufread::MultiGzDecoder;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bgzf,
+}
+
+/// A problem found by [`check_integrity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The BGZF stream does not end with the standard empty EOF block.
+    MissingEofBlock,
+    /// Compressed data could not be read past this compressed byte offset.
+    CorruptData { offset: u64, message: String },
+    /// The last line is not terminated by a newline.
+    MissingFinalNewline,
+    /// The last record has fewer columns than the `#CHROM` line declares.
+    TruncatedRecord {
+        line: u64,
+        columns: usize,
+        expected: usize,
+    },
+    /// The index was modified before the data file.
+    IndexOutdated(PathBuf),
+    /// The index exists but could not be parsed.
+    IndexUnreadable { path: PathBuf, message: String },
+    /// A chromosome present in the data has no entry in the index.
+    ContigNotInIndex(String),
+    /// A chromosome listed in the index does not occur in the data.
+    ContigNotInData(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub compression: Compression,
+    /// Number of BGZF blocks read, including the EOF block.
+    pub blocks: u64,
+    pub records: u64,
+    pub index: Option<PathBuf>,
+    pub issues: Vec<Issue>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Verifies compression framing, record completeness and index consistency of a VCF file.
+///
+/// Only I/O failures on opening the file are returned as errors; everything
+/// found while scanning is reported in [`IntegrityReport::issues`].
+pub fn check_integrity<P: AsRef<Path>>(path: P) -> Result<IntegrityReport> {
+    let path = path.as_ref();
+    let mut file = BufReader::new(File::open(path)?);
+    let bytes = file.fill_buf()?;
+
+    let compression = if bgzf::is_bgzf(bytes) {
+        Compression::Bgzf
+    } else if bgzf::is_gzip(bytes) {
+        Compression::Gzip
+    } else {
+        Compression::None
+    };
+
+    let mut report = IntegrityReport {
+        compression,
+        blocks: 0,
+        records: 0,
+        index: None,
+        issues: Vec::new(),
+    };
+
+    let contigs = match compression {
+        Compression::Bgzf => {
+            let mut blocks = BlockScanner::new(file);
+            let contigs = scan_lines(BufReader::new(&mut blocks), &mut report);
+            report.blocks = blocks.blocks;
+            if let Some(message) = blocks.error {
+                report.issues.push(Issue::CorruptData {
+                    offset: blocks.offset,
+                    message,
+                });
+            } else if !blocks.last_is_eof {
+                report.issues.push(Issue::MissingEofBlock);
+            }
+            contigs
+        }
+        Compression::Gzip => scan_lines(BufReader::new(MultiGzDecoder::new(file)), &mut report),
+        Compression::None => scan_lines(file, &mut report),
+    };
+
+    if let Some(index_path) = find_index(path) {
+        check_index(path, &index_path, &contigs, &mut report);
+        report.index = Some(index_path);
+    }
+
+    Ok(report)
+}
+
+/// Reads every line, recording record count and truncation issues.
+///
+/// Returns the chromosomes seen in data lines, in order of first appearance.
+fn scan_lines<R: BufRead>(mut reader: R, report: &mut IntegrityReport) -> Vec<String> {
+    let mut contigs: Vec<String> = Vec::new();
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut expected = 8;
+    let mut last: Option<(u64, usize, bool)> = None;
+
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                if !report
+                    .issues
+                    .iter()
+                    .any(|i| matches!(i, Issue::CorruptData { .. }))
+                {
+                    report.issues.push(Issue::CorruptData {
+                        offset: 0,
+                        message: e.to_string(),
+                    });
+                }
+                return contigs;
+            }
+        }
+        line_number += 1;
+
+        let complete = line.ends_with(b"\n");
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+
+        if text.starts_with(b"#CHROM") {
+            expected = text.split(|&b| b == b'\t').count();
+        } else if text.starts_with(b"#") {
+            continue;
+        } else if !text.is_empty() {
+            report.records += 1;
+
+            let chrom = text.split(|&b| b == b'\t').next().unwrap_or_default();
+            if !contigs.iter().any(|c| c.as_bytes() == chrom) {
+                contigs.push(String::from_utf8_lossy(chrom).into_owned());
+            }
+
+            let columns = text.split(|&b| b == b'\t').count();
+            last = Some((line_number, columns, complete));
+        }
+
+        if !complete && !text.is_empty() {
+            report.issues.push(Issue::MissingFinalNewline);
+        }
+    }
+
+    if let Some((line, columns, _)) = last {
+        if columns < expected {
+            report.issues.push(Issue::TruncatedRecord {
+                line,
+                columns,
+                expected,
+            });
+        }
+    }
+
+    contigs
+}
+
+fn check_index(path: &Path, index_path: &Path, contigs: &[String], report: &mut IntegrityReport) {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    if let (Some(data), Some(index)) = (modified(path), modified(index_path)) {
+        if index < data {
+            report
+                .issues
+                .push(Issue::IndexOutdated(index_path.to_path_buf()));
+        }
+    }
+
+    let index = match Index::from_path(index_path) {
+        Ok(index) => index,
+        Err(e) => {
+            report.issues.push(Issue::IndexUnreadable {
+                path: index_path.to_path_buf(),
+                message: e.to_string(),
+            });
+            return;
+        }
+    };
+
+    for contig in contigs {
+        if !index.names.contains(contig) {
+            report.issues.push(Issue::ContigNotInIndex(contig.clone()));
+        }
+    }
+
+    for name in &index.names {
+        if !contigs.contains(name) {
+            report.issues.push(Issue::ContigNotInData(name.clone()));
+        }
+    }
+}
+
+/// Reads BGZF blocks one at a time, remembering where reading stopped.
+struct BlockScanner<R> {
+    inner: R,
+    data: Vec<u8>,
+    pos: usize,
+    offset: u64,
+    blocks: u64,
+    last_is_eof: bool,
+    error: Option<String>,
+}
+
+impl<R: Read> BlockScanner<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            data: Vec::new(),
+            pos: 0,
+            offset: 0,
+            blocks: 0,
+            last_is_eof: false,
+            error: None,
+        }
+    }
+}
+
+impl<R: Read> Read for BlockScanner<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.data.len() {
+            if self.error.is_some() {
+                return Ok(0);
+            }
+
+            let block = bgzf::read_raw_block(&mut self.inner).and_then(|b| match b {
+                Some(block) => bgzf::inflate_block(&block, &mut self.data).map(|_| Some(block)),
+                None => Ok(None),
+            });
+
+            match block {
+                Ok(Some(block)) => {
+                    self.offset += block.len() as u64;
+                    self.blocks += 1;
+                    self.last_is_eof = block == bgzf::EOF_BLOCK;
+                    self.pos = 0;
+                }
+                Ok(None) => return Ok(0),
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    return Ok(0);
+                }
+            }
+        }
+
+        let n = (self.data.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::tbi_bytes;
+    use crate::index::Chunk;
+    use std::io::Write;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+        1\t10\t.\tA\tT\t.\tPASS\t.\tGT\t0/1\n\
+        2\t20\t.\tA\tT\t.\tPASS\t.\tGT\t0/1\n";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vcf-lib-integrity-{}-{}", std::process::id(), name))
+    }
+
+    fn write_bgzf(path: &Path, data: &[u8]) -> Vec<u8> {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(data).unwrap();
+        let bytes = writer.finish().unwrap();
+        fs::write(path, &bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_check_integrity_1() {
+        let path = temp_path("ok.vcf.gz");
+        write_bgzf(&path, VCF.as_bytes());
+        let index_path = temp_path("ok.vcf.gz.tbi");
+        let chunk = Chunk { start: 0, end: 1 };
+        fs::write(&index_path, tbi_bytes(&["1", "2"], &[chunk, chunk])).unwrap();
+
+        let report = check_integrity(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&index_path).unwrap();
+
+        assert_eq!(report.compression, Compression::Bgzf);
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.records, 2);
+        assert_eq!(report.index, Some(index_path));
+        assert!(report.is_ok(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_check_integrity_2() {
+        let path = temp_path("truncated.vcf.gz");
+        let bytes = write_bgzf(&path, &VCF.as_bytes()[..VCF.len() - 8]);
+        fs::write(&path, &bytes[..bytes.len() - bgzf::EOF_BLOCK.len()]).unwrap();
+
+        let report = check_integrity(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            report.issues,
+            vec![
+                Issue::MissingFinalNewline,
+                Issue::TruncatedRecord {
+                    line: 4,
+                    columns: 8,
+                    expected: 10
+                },
+                Issue::MissingEofBlock,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_integrity_3() {
+        let path = temp_path("corrupt.vcf.gz");
+        let bytes = write_bgzf(&path, VCF.as_bytes());
+        fs::write(&path, &bytes[..bytes.len() - bgzf::EOF_BLOCK.len() - 4]).unwrap();
+
+        let report = check_integrity(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.records, 0);
+        assert!(matches!(
+            report.issues[..],
+            [Issue::CorruptData { offset: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_check_integrity_4() {
+        let path = temp_path("contigs.vcf");
+        fs::write(&path, VCF).unwrap();
+        let index_path = temp_path("contigs.vcf.tbi");
+        let chunk = Chunk { start: 0, end: 1 };
+        fs::write(&index_path, tbi_bytes(&["1", "3"], &[chunk, chunk])).unwrap();
+
+        let report = check_integrity(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&index_path).unwrap();
+
+        assert_eq!(report.compression, Compression::None);
+        assert!(report
+            .issues
+            .contains(&Issue::ContigNotInIndex("2".to_string())));
+        assert!(report
+            .issues
+            .contains(&Issue::ContigNotInData("3".to_string())));
+    }
+}
@@ -0,0 +1,311 @@
+//! Intersection and difference of two sorted call sets.
+
+use crate::errors::Result;
+use crate::header::Header;
+use crate::order::{ContigOrder, SortednessChecker};
+use crate::record::{NormalizedVariant, Record};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+
+/// One record or matched pair produced by [`Intersect`].
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum SetItem {
+    Both(Record, Record),
+    OnlyA(Record),
+    OnlyB(Record),
+}
+
+/// A buffered record that may still be matched.
+struct Pending {
+    record: Record,
+    variants: Vec<NormalizedVariant>,
+    /// Largest normalized position; no later record can match beyond it.
+    reach: u64,
+}
+
+impl Pending {
+    fn new(record: Record) -> Self {
+        let variants = record.normalized_alternates();
+        let reach = variants
+            .iter()
+            .map(|v| v.position)
+            .max()
+            .unwrap_or(record.pos);
+
+        Self {
+            record,
+            variants,
+            reach,
+        }
+    }
+
+    fn matches(&self, other: &Pending) -> bool {
+        self.variants.iter().any(|v| other.variants.contains(v))
+    }
+}
+
+/// Compares two sorted record streams.
+///
+/// Records on the same contig are paired when they share an ALT allele after
+/// normalization, so padded and trimmed representations of one variant
+/// match. Each record is paired at most once; split multiallelic records
+/// first to compare allele by allele. Contigs are ordered as declared in the
+/// header of A, followed by those only declared in B.
+///
+/// Items are yielded roughly in position order: pairs as soon as the second
+/// record is read, unpaired records once no later record can match them.
+pub struct Intersect<A: Iterator<Item = Result<Record>>, B: Iterator<Item = Result<Record>>> {
+    a: Peekable<SortednessChecker<A>>,
+    b: Peekable<SortednessChecker<B>>,
+    order: ContigOrder,
+    chrom: Option<String>,
+    pending_a: VecDeque<Pending>,
+    pending_b: VecDeque<Pending>,
+    out: VecDeque<SetItem>,
+}
+
+impl<A, B> Intersect<A, B>
+where
+    A: Iterator<Item = Result<Record>>,
+    B: Iterator<Item = Result<Record>>,
+{
+    pub fn new(a: A, header_a: &Header, b: B, header_b: &Header) -> Self {
+        let order = ContigOrder::new(
+            header_a
+                .contigs
+                .iter()
+                .chain(&header_b.contigs)
+                .map(|c| c.id.as_str()),
+        );
+
+        Self {
+            a: SortednessChecker::new(a, header_a).peekable(),
+            b: SortednessChecker::new(b, header_b).peekable(),
+            order,
+            chrom: None,
+            pending_a: VecDeque::new(),
+            pending_b: VecDeque::new(),
+            out: VecDeque::new(),
+        }
+    }
+
+    /// Records present in both streams, as (A, B) pairs.
+    pub fn intersection(self) -> impl Iterator<Item = Result<(Record, Record)>> {
+        self.filter_map(|item| match item {
+            Ok(SetItem::Both(a, b)) => Some(Ok((a, b))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Records present only in A.
+    pub fn only_a(self) -> impl Iterator<Item = Result<Record>> {
+        self.filter_map(|item| match item {
+            Ok(SetItem::OnlyA(a)) => Some(Ok(a)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Records present only in B.
+    pub fn only_b(self) -> impl Iterator<Item = Result<Record>> {
+        self.filter_map(|item| match item {
+            Ok(SetItem::OnlyB(b)) => Some(Ok(b)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Moves unpaired records that cannot be matched before `frontier` to
+    /// the output, in position order.
+    fn settle(&mut self, frontier: Option<u64>) {
+        let settled = |p: &Pending| frontier.is_none_or(|f| p.reach < f);
+
+        let mut items: Vec<(u64, SetItem)> = Vec::new();
+        for (pending, only) in [
+            (&mut self.pending_a, SetItem::OnlyA as fn(Record) -> SetItem),
+            (&mut self.pending_b, SetItem::OnlyB),
+        ] {
+            let mut kept = VecDeque::with_capacity(pending.len());
+            for p in pending.drain(..) {
+                if settled(&p) {
+                    items.push((p.record.pos, only(p.record)));
+                } else {
+                    kept.push_back(p);
+                }
+            }
+            *pending = kept;
+        }
+
+        items.sort_by_key(|(pos, _)| *pos);
+        self.out.extend(items.into_iter().map(|(_, item)| item));
+    }
+
+    /// Reads the next record of either stream; `false` once both are exhausted.
+    fn advance(&mut self) -> Result<bool> {
+        let from_a = match (self.a.peek(), self.b.peek()) {
+            (None, None) => {
+                self.settle(None);
+                return Ok(false);
+            }
+            (Some(Err(_)), _) | (Some(_), None) => true,
+            (None, Some(_)) | (_, Some(Err(_))) => false,
+            (Some(Ok(a)), Some(Ok(b))) => {
+                self.order
+                    .compare_positions((&a.chrom, a.pos), (&b.chrom, b.pos))
+                    != Ordering::Greater
+            }
+        };
+
+        let record = if from_a {
+            self.a.next().unwrap()?
+        } else {
+            self.b.next().unwrap()?
+        };
+
+        if self.chrom.as_deref() != Some(record.chrom.as_str()) {
+            self.settle(None);
+            self.chrom = Some(record.chrom.clone());
+        } else {
+            self.settle(Some(record.pos));
+        }
+
+        let pending = Pending::new(record);
+        let (own, other) = if from_a {
+            (&mut self.pending_a, &mut self.pending_b)
+        } else {
+            (&mut self.pending_b, &mut self.pending_a)
+        };
+
+        match other.iter().position(|p| p.matches(&pending)) {
+            Some(i) => {
+                let partner = other.remove(i).unwrap().record;
+                self.out.push_back(if from_a {
+                    SetItem::Both(pending.record, partner)
+                } else {
+                    SetItem::Both(partner, pending.record)
+                });
+            }
+            None => own.push_back(pending),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<A, B> Iterator for Intersect<A, B>
+where
+    A: Iterator<Item = Result<Record>>,
+    B: Iterator<Item = Result<Record>>,
+{
+    type Item = Result<SetItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.out.pop_front() {
+                return Some(Ok(item));
+            }
+
+            match self.advance() {
+                Ok(true) => {}
+                Ok(false) => return self.out.pop_front().map(Ok),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+
+    fn records(lines: &[&str]) -> Vec<Result<Record>> {
+        lines.iter().map(|l| l.parse()).collect()
+    }
+
+    fn intersect(
+        a: &[&str],
+        b: &[&str],
+    ) -> Intersect<std::vec::IntoIter<Result<Record>>, std::vec::IntoIter<Result<Record>>> {
+        let header = Header::new("VCFv4.3");
+
+        Intersect::new(
+            records(a).into_iter(),
+            &header,
+            records(b).into_iter(),
+            &header,
+        )
+    }
+
+    fn describe(item: &SetItem) -> String {
+        match item {
+            SetItem::Both(a, b) => format!("both {}:{} {}:{}", a.chrom, a.pos, b.chrom, b.pos),
+            SetItem::OnlyA(a) => format!("a {}:{}", a.chrom, a.pos),
+            SetItem::OnlyB(b) => format!("b {}:{}", b.chrom, b.pos),
+        }
+    }
+
+    const A: &[&str] = &[
+        "1\t100\t.\tA\tG\t.\t.\t.",
+        "1\t200\t.\tACGTACGT\tACGTTCGT\t.\t.\t.",
+        "1\t300\t.\tC\tT\t.\t.\t.",
+        "2\t50\t.\tG\tGA,C\t.\t.\t.",
+    ];
+
+    const B: &[&str] = &[
+        "1\t100\t.\tA\tC\t.\t.\t.",
+        "1\t150\t.\tT\tA\t.\t.\t.",
+        "1\t204\t.\tA\tT\t.\t.\t.",
+        "2\t50\t.\tg\tc\t.\t.\t.",
+        "3\t1\t.\tA\tT\t.\t.\t.",
+    ];
+
+    #[test]
+    fn test_intersect_1() {
+        let items: Vec<String> = intersect(A, B).map(|i| describe(&i.unwrap())).collect();
+
+        assert_eq!(
+            items,
+            vec![
+                "a 1:100",
+                "b 1:100",
+                "b 1:150",
+                "both 1:200 1:204",
+                "a 1:300",
+                "both 2:50 2:50",
+                "b 3:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intersect_2() {
+        let pairs: Vec<(u64, u64)> = intersect(A, B)
+            .intersection()
+            .map(|p| p.map(|(a, b)| (a.pos, b.pos)).unwrap())
+            .collect();
+        let only_a: Vec<u64> = intersect(A, B).only_a().map(|r| r.unwrap().pos).collect();
+        let only_b: Vec<u64> = intersect(A, B).only_b().map(|r| r.unwrap().pos).collect();
+
+        assert_eq!(pairs, vec![(200, 204), (50, 50)]);
+        assert_eq!(only_a, vec![100, 300]);
+        assert_eq!(only_b, vec![100, 150, 1]);
+    }
+
+    #[test]
+    fn test_intersect_3() {
+        let a = ["1\t100\t.\tA\tG\t.\t.\t.", "1\t100\t.\tA\tG\t.\t.\t."];
+        let b = ["1\t100\t.\tA\tG\t.\t.\t."];
+        let items: Vec<String> = intersect(&a, &b).map(|i| describe(&i.unwrap())).collect();
+
+        assert_eq!(items, vec!["both 1:100 1:100", "a 1:100"]);
+
+        let b = ["1\t300\t.\tA\tG\t.\t.\t.", "1\t100\t.\tA\tG\t.\t.\t."];
+        let result: Result<Vec<SetItem>> = intersect(&a, &b).collect();
+
+        assert!(matches!(result, Err(Error::UnsortedRecordError(..))));
+    }
+}
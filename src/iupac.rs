@@ -0,0 +1,112 @@
+use crate::errors::{Error, Result};
+
+/// Returns the concrete bases represented by an IUPAC nucleotide code.
+///
+/// `U` is treated as `T`. Lowercase codes are accepted.
+pub fn bases(code: char) -> Option<&'static [char]> {
+    let bases: &'static [char] = match code.to_ascii_uppercase() {
+        'A' => &['A'],
+        'C' => &['C'],
+        'G' => &['G'],
+        'T' | 'U' => &['T'],
+        'R' => &['A', 'G'],
+        'Y' => &['C', 'T'],
+        'S' => &['C', 'G'],
+        'W' => &['A', 'T'],
+        'K' => &['G', 'T'],
+        'M' => &['A', 'C'],
+        'B' => &['C', 'G', 'T'],
+        'D' => &['A', 'G', 'T'],
+        'H' => &['A', 'C', 'T'],
+        'V' => &['A', 'C', 'G'],
+        'N' => &['A', 'C', 'G', 'T'],
+        _ => return None,
+    };
+
+    Some(bases)
+}
+
+/// Returns `true` if `allele` contains any code other than `A`, `C`, `G` and `T`.
+pub fn is_ambiguous(allele: &str) -> bool {
+    allele
+        .chars()
+        .any(|c| !matches!(c.to_ascii_uppercase(), 'A' | 'C' | 'G' | 'T'))
+}
+
+/// Expands an allele into every concrete ACGT allele it can represent.
+///
+/// The number of results grows exponentially with the number of ambiguous
+/// codes, e.g. `NNN` yields 64 alleles.
+pub fn expand(allele: &str) -> Result<Vec<String>> {
+    let mut alleles = vec![String::with_capacity(allele.len())];
+
+    for c in allele.chars() {
+        let bases = bases(c).ok_or_else(|| Error::IupacInvalidSymbolError(allele.to_string()))?;
+
+        if let [base] = bases {
+            alleles.iter_mut().for_each(|a| a.push(*base));
+            continue;
+        }
+
+        alleles = alleles
+            .iter()
+            .flat_map(|a| {
+                bases.iter().map(move |b| {
+                    let mut a = a.clone();
+                    a.push(*b);
+                    a
+                })
+            })
+            .collect();
+    }
+
+    Ok(alleles)
+}
+
+/// Expands an ALT allele like [`expand`], dropping the allele equal to `reference`.
+pub fn expand_excluding(alternate: &str, reference: &str) -> Result<Vec<String>> {
+    let mut alleles = expand(alternate)?;
+    alleles.retain(|a| !a.eq_ignore_ascii_case(reference));
+
+    Ok(alleles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_1() {
+        assert_eq!(expand("ACGT").unwrap(), vec!["ACGT"]);
+        assert_eq!(expand("R").unwrap(), vec!["A", "G"]);
+        assert_eq!(expand("aYu").unwrap(), vec!["ACT", "ATT"]);
+    }
+
+    #[test]
+    fn test_expand_2() {
+        let alleles = expand("NK").unwrap();
+
+        assert_eq!(alleles.len(), 8);
+        assert_eq!(alleles[0], "AG");
+        assert_eq!(alleles[7], "TT");
+    }
+
+    #[test]
+    fn test_expand_3() {
+        assert!(expand("A*").is_err());
+        assert!(expand("<DEL>").is_err());
+    }
+
+    #[test]
+    fn test_expand_excluding_1() {
+        assert_eq!(expand_excluding("R", "A").unwrap(), vec!["G"]);
+        assert_eq!(expand_excluding("N", "c").unwrap(), vec!["A", "G", "T"]);
+        assert!(expand_excluding("A", "A").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_ambiguous_1() {
+        assert!(is_ambiguous("ACGN"));
+        assert!(!is_ambiguous("acgt"));
+    }
+}
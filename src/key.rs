@@ -0,0 +1,223 @@
+//! Stable identifiers of normalized variants.
+//!
+//! A [`VariantKey`] is the same for every spelling of a variant: bases are
+//! uppercased, then trimmed as by [`Record::normalized_alternates`], so keys
+//! built from different files join on equal variants. The text form is
+//! `CHROM-POS-REF-ALT`; [`VariantKey::hash64`] and [`VariantKey::hash128`]
+//! are FNV-1a hashes of it, identical on every platform and release.
+
+use crate::errors::{Error, Result};
+use crate::record::{NormalizedVariant, Record};
+use crate::validation::is_symbolic;
+use std::fmt;
+use std::str::FromStr;
+
+const FNV64_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV64_PRIME: u64 = 0x0000_0100_0000_01b3;
+const FNV128_OFFSET: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV128_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+/// Canonical identifier of one ALT allele of a record.
+///
+/// Keys order by CHROM as text, then position and alleles, which is
+/// deterministic but not the contig order of a header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantKey {
+    pub chrom: String,
+    pub position: u64,
+    pub reference: String,
+    pub alternate: String,
+}
+
+impl VariantKey {
+    /// Key of a variant given by its alleles; symbolic alleles and alleles
+    /// that fail to normalize only have their bases uppercased.
+    ///
+    /// Symbolic alleles such as `<INS:ME>` and the mate position of a
+    /// breakend, like `chr2:100` in `G]chr2:100]`, are kept as written.
+    pub fn new(chrom: &str, position: u64, reference: &str, alternate: &str) -> Self {
+        let reference = reference.to_ascii_uppercase();
+        if is_symbolic(alternate) {
+            return Self {
+                chrom: chrom.to_string(),
+                position,
+                reference,
+                alternate: uppercase_bases(alternate),
+            };
+        }
+
+        let alternate = alternate.to_ascii_uppercase();
+        let record = Record::new(chrom, position, &reference, &[&alternate]);
+        let variant = record.normalized_alternates().remove(0);
+
+        Self::from_variant(chrom, variant)
+    }
+
+    pub fn from_variant(chrom: &str, variant: NormalizedVariant) -> Self {
+        Self {
+            chrom: chrom.to_string(),
+            position: variant.position,
+            reference: variant.reference,
+            alternate: variant.alternate,
+        }
+    }
+
+    /// Keys of the ALT alleles of `record`, in ALT order.
+    pub fn from_record(record: &Record) -> Vec<Self> {
+        record
+            .alternates
+            .iter()
+            .map(|alt| Self::new(&record.chrom, record.pos, &record.reference, alt))
+            .collect()
+    }
+
+    /// 64-bit FNV-1a hash of the text form.
+    pub fn hash64(&self) -> u64 {
+        fnv1a64(self.to_string().as_bytes())
+    }
+
+    /// 128-bit FNV-1a hash of the text form, for sets too large for
+    /// [`VariantKey::hash64`] to be practically collision-free.
+    pub fn hash128(&self) -> u128 {
+        fnv1a128(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for VariantKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}-{}",
+            self.chrom, self.position, self.reference, self.alternate
+        )
+    }
+}
+
+impl FromStr for VariantKey {
+    type Err = Error;
+
+    /// Parses `CHROM-POS-REF-ALT`, normalizing the alleles; CHROM may
+    /// contain `-`.
+    fn from_str(s: &str) -> Result<Self> {
+        let error = || Error::VariantKeyError(s.to_string());
+
+        let mut parts = s.rsplitn(4, '-');
+        let (Some(alternate), Some(reference), Some(position), Some(chrom)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(error());
+        };
+        let position = position.parse().map_err(|_| error())?;
+        if chrom.is_empty() || reference.is_empty() || alternate.is_empty() {
+            Err(error())?
+        }
+
+        Ok(Self::new(chrom, position, reference, alternate))
+    }
+}
+
+/// Uppercases the bases of an allele, leaving symbolic alleles and the mate
+/// positions of breakends unchanged.
+fn uppercase_bases(allele: &str) -> String {
+    if allele.starts_with('<') && allele.ends_with('>') {
+        return allele.to_string();
+    }
+
+    let mut mate = false;
+    allele
+        .chars()
+        .map(|c| {
+            if matches!(c, '[' | ']') {
+                mate = !mate;
+            }
+            if mate {
+                c
+            } else {
+                c.to_ascii_uppercase()
+            }
+        })
+        .collect()
+}
+
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV64_OFFSET, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(FNV64_PRIME)
+    })
+}
+
+fn fnv1a128(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(FNV128_OFFSET, |hash, &b| {
+        (hash ^ b as u128).wrapping_mul(FNV128_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_variant_key_1() {
+        let key = VariantKey::new("chr1", 100, "cAGT", "cT");
+
+        assert_eq!(key.to_string(), "chr1-100-CAG-C");
+        assert_eq!(key, VariantKey::new("chr1", 100, "CAG", "C"));
+        assert_eq!("chr1-100-cagt-ct".parse::<VariantKey>().unwrap(), key);
+        assert_eq!(
+            "HLA-A-5-ac-ag".parse::<VariantKey>().unwrap().to_string(),
+            "HLA-A-6-C-G"
+        );
+        assert_eq!(
+            VariantKey::new("1", 5, "A", "<DEL>").to_string(),
+            "1-5-A-<DEL>"
+        );
+
+        assert_eq!(
+            VariantKey::new("1", 5, "a", "<ins:me>").alternate,
+            "<ins:me>"
+        );
+        assert_eq!(
+            VariantKey::new("1", 5, "a", "a]chrUn_gl000220v1:12]").alternate,
+            "A]chrUn_gl000220v1:12]"
+        );
+        assert_eq!(
+            VariantKey::new("1", 5, "a", "[hla-a*01:01:1[t").alternate,
+            "[hla-a*01:01:1[T"
+        );
+        assert_eq!(VariantKey::new("1", 5, "a", "a.").alternate, "A.");
+
+        for bad in ["", "1-2-A", "1-x-A-T", "-5-A-T", "1-5--T"] {
+            assert!(bad.parse::<VariantKey>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_variant_key_2() {
+        let record: Record = "1\t10\t.\tAC\tA,TC\t.\t.\t.".parse().unwrap();
+        let keys = VariantKey::from_record(&record);
+
+        assert_eq!(keys[0].to_string(), "1-10-AC-A");
+        assert_eq!(keys[1].to_string(), "1-10-A-T");
+
+        let sorted: Vec<String> = keys
+            .iter()
+            .cloned()
+            .chain([VariantKey::new("1", 9, "G", "T")])
+            .collect::<BTreeSet<_>>()
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        assert_eq!(sorted, vec!["1-9-G-T", "1-10-A-T", "1-10-AC-A"]);
+
+        let key = VariantKey::new("1", 9, "GA", "GT");
+        assert_eq!(key.hash64(), keys[1].hash64());
+        assert_eq!(key.hash128(), keys[1].hash128());
+        assert_ne!(keys[0].hash64(), keys[1].hash64());
+
+        // Published FNV-1a test vectors.
+        assert_eq!(fnv1a64(b""), FNV64_OFFSET);
+        assert_eq!(fnv1a64(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a128(b"a"), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+}
@@ -1,7 +1,67 @@
+pub mod alleles;
+#[cfg(feature = "fs")]
+pub mod annotate;
+pub mod bgzf;
+pub mod builder;
+pub mod cache;
+pub mod codec;
+pub mod compliance;
+pub mod concordance;
+pub mod contigs;
+pub mod decoder;
+pub mod decompose;
+pub mod dedup;
+pub mod duplication;
 pub mod errors;
+pub mod export;
+pub mod expression;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod field;
+pub mod genotype;
+pub mod gvcf;
+pub mod header;
+pub mod impute;
+pub mod index;
+#[cfg(feature = "fs")]
+pub mod integrity;
+pub mod isec;
+pub mod iupac;
+pub mod key;
+pub mod liftover;
+pub mod limits;
+pub mod merge;
+pub mod order;
+pub mod partition;
+pub mod pedigree;
+pub mod pipeline;
+pub mod ploidy;
+pub mod presets;
+pub mod quirks;
+pub mod raw;
+pub mod reader;
 pub mod record;
+pub mod reference;
+pub mod regions;
+pub mod remote;
+#[cfg(feature = "fs")]
+pub mod scan;
+#[cfg(feature = "fs")]
+pub mod sort;
+pub mod stats;
+pub mod summary;
+pub mod svmerge;
+#[cfg(feature = "fs")]
+pub mod transpose;
+pub mod validation;
+pub mod windows;
+pub mod writer;
 
-#[derive(Debug, PartialEq, Eq)]
+#[cfg(feature = "fs")]
+pub use integrity::check_integrity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariantType {
     SNV,
     Deletion,
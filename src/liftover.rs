@@ -0,0 +1,803 @@
+//! Conversion of records between assemblies with UCSC chain files.
+
+use crate::alleles::AlleleRemap;
+use crate::errors::{Error, Result};
+use crate::header::{
+    ContigDefinition, FilterDefinition, Header, InfoDefinition, Number, ValueType,
+};
+use crate::record::Record;
+use crate::reference::ReferenceSequence;
+use crate::validation::is_symbolic;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+
+#[cfg(feature = "fs")]
+use crate::bgzf;
+#[cfg(feature = "fs")]
+use crate::pipeline::skip;
+#[cfg(feature = "fs")]
+use crate::sort::{ExternalSorter, SortOptions};
+#[cfg(feature = "fs")]
+use crate::summary::{RunSummary, Timer};
+#[cfg(feature = "fs")]
+use crate::writer::Writer;
+#[cfg(feature = "fs")]
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufReader, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// INFO flag set on records whose REF and ALT were swapped.
+pub const SWAP_KEY: &str = "SWAP";
+
+/// Why a record could not be lifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappedReason {
+    /// The start of the record is not in an aligned block.
+    NoTarget,
+    /// The record spans a gap in the alignment.
+    SplitInterval,
+    /// REF differs from the target reference, and swapping REF and ALT does
+    /// not help.
+    MismatchedReference,
+    /// The record moves to the reverse strand and needs the target reference
+    /// for its padding base.
+    NoReference,
+}
+
+impl UnmappedReason {
+    pub const ALL: [Self; 4] = [
+        Self::NoTarget,
+        Self::SplitInterval,
+        Self::MismatchedReference,
+        Self::NoReference,
+    ];
+
+    /// FILTER ID of rejected records.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::NoTarget => "NoTarget",
+            Self::SplitInterval => "SplitInterval",
+            Self::MismatchedReference => "MismatchedRefAllele",
+            Self::NoReference => "NoReference",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::NoTarget => "Position not in the target assembly",
+            Self::SplitInterval => "Record spans a gap between aligned blocks",
+            Self::MismatchedReference => "REF does not match the target reference",
+            Self::NoReference => "Target reference needed for a reverse strand indel",
+        }
+    }
+}
+
+impl fmt::Display for UnmappedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// An interval lifted to the target assembly, 1-based inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedInterval {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    /// The interval is on the reverse strand of the target.
+    pub reverse: bool,
+}
+
+struct Chain {
+    score: f64,
+    target: usize,
+    reverse: bool,
+}
+
+/// Ungapped alignment of `start..end` to `target_start..`, 0-based half-open,
+/// with target coordinates on the strand of the chain.
+struct Block {
+    start: u64,
+    end: u64,
+    target_start: u64,
+    chain: usize,
+}
+
+#[derive(Default)]
+struct Blocks {
+    /// Sorted by start.
+    blocks: Vec<Block>,
+    /// Largest end of `blocks[..=i]`.
+    reach: Vec<u64>,
+}
+
+/// Source contig, next source and target positions, and ends of the chain
+/// being parsed.
+struct Current {
+    chrom: String,
+    source: u64,
+    source_end: u64,
+    target: u64,
+    target_end: u64,
+}
+
+/// Aligned blocks of a chain file, indexed by source contig.
+#[derive(Default)]
+pub struct ChainMap {
+    chains: Vec<Chain>,
+    /// Target contigs and their lengths, in order of appearance.
+    targets: Vec<(String, u64)>,
+    contigs: HashMap<String, Blocks>,
+}
+
+impl ChainMap {
+    /// Parses a chain file.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut map = Self::default();
+        let mut current: Option<Current> = None;
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || Error::ChainFormatError(line.to_string());
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| -> Result<u64> {
+                fields
+                    .get(i)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(malformed)
+            };
+
+            if fields[0] == "chain" {
+                if fields.len() < 12 || fields[4] != "+" || !matches!(fields[9], "+" | "-") {
+                    Err(malformed())?;
+                }
+                let score = fields[1].parse().map_err(|_| malformed())?;
+                let (source_size, source_start, source_end) = (number(3)?, number(5)?, number(6)?);
+                let (target_size, target_start, target_end) =
+                    (number(8)?, number(10)?, number(11)?);
+                if source_start > source_end
+                    || source_end > source_size
+                    || target_start > target_end
+                    || target_end > target_size
+                {
+                    Err(malformed())?;
+                }
+
+                let target = match map.targets.iter().position(|(t, _)| t == fields[7]) {
+                    Some(target) if map.targets[target].1 != target_size => Err(malformed())?,
+                    Some(target) => target,
+                    None => {
+                        map.targets.push((fields[7].to_string(), target_size));
+                        map.targets.len() - 1
+                    }
+                };
+                map.chains.push(Chain {
+                    score,
+                    target,
+                    reverse: fields[9] == "-",
+                });
+                current = Some(Current {
+                    chrom: fields[2].to_string(),
+                    source: source_start,
+                    source_end,
+                    target: target_start,
+                    target_end,
+                });
+                continue;
+            }
+
+            let Some(chain) = current.as_mut() else {
+                return Err(malformed());
+            };
+            // Blocks and gaps must stay within the span declared by the
+            // chain, which lies within the contigs.
+            let advance =
+                |position: u64, by: u64, end: u64| position.checked_add(by).filter(|p| *p <= end);
+            let size = number(0)?;
+            let source_end = advance(chain.source, size, chain.source_end).ok_or_else(malformed)?;
+            let target_end = advance(chain.target, size, chain.target_end).ok_or_else(malformed)?;
+            if size > 0 {
+                map.contigs
+                    .entry(chain.chrom.clone())
+                    .or_default()
+                    .blocks
+                    .push(Block {
+                        start: chain.source,
+                        end: source_end,
+                        target_start: chain.target,
+                        chain: map.chains.len() - 1,
+                    });
+            }
+
+            match fields.len() {
+                1 => current = None,
+                3 => {
+                    chain.source =
+                        advance(source_end, number(1)?, chain.source_end).ok_or_else(malformed)?;
+                    chain.target =
+                        advance(target_end, number(2)?, chain.target_end).ok_or_else(malformed)?;
+                }
+                _ => Err(malformed())?,
+            }
+        }
+
+        for blocks in map.contigs.values_mut() {
+            blocks.blocks.sort_by_key(|b| b.start);
+            let mut reach = 0;
+            blocks.reach = blocks
+                .blocks
+                .iter()
+                .map(|b| {
+                    reach = reach.max(b.end);
+                    reach
+                })
+                .collect();
+        }
+
+        Ok(map)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Reads a plain or gzip compressed chain file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        if bgzf::is_gzip(file.fill_buf()?) {
+            Self::from_reader(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Self::from_reader(file)
+        }
+    }
+
+    /// Target contigs and their lengths, in order of appearance.
+    pub fn targets(&self) -> &[(String, u64)] {
+        &self.targets
+    }
+
+    /// Lifts `start..=end`, 1-based, which must lie within one aligned block.
+    ///
+    /// Where chains overlap, the block of the highest scoring chain is used.
+    pub fn map_interval(
+        &self,
+        chrom: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<MappedInterval, UnmappedReason> {
+        let blocks = self.contigs.get(chrom).ok_or(UnmappedReason::NoTarget)?;
+        let (start, end) = (start.saturating_sub(1), end.max(start));
+
+        let mut covered = false;
+        let mut best: Option<&Block> = None;
+        let mut i = blocks.blocks.partition_point(|b| b.start <= start);
+        while i > 0 && blocks.reach[i - 1] > start {
+            i -= 1;
+            let block = &blocks.blocks[i];
+            if block.end <= start {
+                continue;
+            }
+            covered = true;
+            if end <= block.end
+                && best.is_none_or(|b| self.chains[block.chain].score > self.chains[b.chain].score)
+            {
+                best = Some(block);
+            }
+        }
+
+        let block = match best {
+            Some(block) => block,
+            None if covered => return Err(UnmappedReason::SplitInterval),
+            None => return Err(UnmappedReason::NoTarget),
+        };
+
+        let chain = &self.chains[block.chain];
+        let (target, size) = &self.targets[chain.target];
+        let first = block.target_start + start - block.start;
+        let last = block.target_start + end - block.start;
+        let (start, end) = if chain.reverse {
+            (size - last + 1, size - first)
+        } else {
+            (first + 1, last)
+        };
+
+        Ok(MappedInterval {
+            chrom: target.clone(),
+            start,
+            end,
+            reverse: chain.reverse,
+        })
+    }
+}
+
+/// Outcome of [`Lifter::lift`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifted {
+    Mapped,
+    /// Mapped, with REF and ALT swapped to match the target reference.
+    Swapped,
+    /// Left unchanged.
+    Unmapped(UnmappedReason),
+}
+
+/// Lifts records to the target assembly of a [`ChainMap`].
+///
+/// The span of a record, including INFO `END`, must lie within one aligned
+/// block. On the reverse strand alleles are reverse complemented, and
+/// padded indels and symbolic alleles take the base before them on the
+/// target as their padding base. Breakend mates are not lifted.
+///
+/// With a target `reference`, REF is checked against it. A biallelic record
+/// whose ALT matches instead, with REF and ALT of the same length, has them
+/// swapped: `GT` and values declared with `Number=R` or `G` are renumbered,
+/// `AF` becomes `1 - AF`, `AC` becomes `AN - AC`, other `Number=A` values
+/// become `.`, and the [`SWAP_KEY`] flag is set.
+pub struct Lifter<'a> {
+    chains: &'a ChainMap,
+    header: Header,
+    reference: Option<&'a dyn ReferenceSequence>,
+}
+
+impl<'a> Lifter<'a> {
+    /// `header` is the header of the records to lift.
+    pub fn new(
+        chains: &'a ChainMap,
+        header: &Header,
+        reference: Option<&'a dyn ReferenceSequence>,
+    ) -> Self {
+        Self {
+            chains,
+            header: header.clone(),
+            reference,
+        }
+    }
+
+    /// Header of lifted records, with the contigs of the target assembly.
+    pub fn header(&self) -> Header {
+        let mut header = self.header.clone();
+        header.contigs = self
+            .chains
+            .targets()
+            .iter()
+            .map(|(id, length)| ContigDefinition::new(id, Some(*length)))
+            .collect();
+
+        if header.info(SWAP_KEY).is_none() {
+            header.infos.push(InfoDefinition::new(
+                SWAP_KEY,
+                Number::Count(0),
+                ValueType::Flag,
+                "REF and ALT swapped by liftover",
+            ));
+        }
+
+        header
+    }
+
+    /// Header of unmapped records, with a FILTER for each [`UnmappedReason`].
+    pub fn rejects_header(&self) -> Header {
+        let mut header = self.header.clone();
+        for reason in UnmappedReason::ALL {
+            if !header.filters.iter().any(|f| f.id == reason.id()) {
+                header
+                    .filters
+                    .push(FilterDefinition::new(reason.id(), reason.description()));
+            }
+        }
+
+        header
+    }
+
+    /// Lifts `record` in place; unmapped records are left unchanged.
+    pub fn lift(&self, record: &mut Record) -> Result<Lifted> {
+        let span = record.pos + record.reference.len().max(1) as u64 - 1;
+        let end = record.info("END").and_then(|v| v.parse().ok());
+        let end = end.map_or(span, |e: u64| e.max(span));
+
+        let target = match self.chains.map_interval(&record.chrom, record.pos, end) {
+            Ok(target) => target,
+            Err(reason) => return Ok(Lifted::Unmapped(reason)),
+        };
+
+        let first = record.reference.get(0..1);
+        let padded = record
+            .alternates
+            .iter()
+            .any(|a| is_symbolic(a) || a.len() != record.reference.len())
+            && record
+                .alternates
+                .iter()
+                .all(|a| is_symbolic(a) || a == "*" || a.get(0..1) == first);
+
+        let (pos, reference, alternates, end) = if !target.reverse {
+            (
+                target.start,
+                record.reference.clone(),
+                record.alternates.clone(),
+                target.end,
+            )
+        } else if padded {
+            // The padding base moves from the start to the end of the
+            // variant; the base before the lifted interval replaces it.
+            let Some(sequence) = self.reference else {
+                return Ok(Lifted::Unmapped(UnmappedReason::NoReference));
+            };
+            let pos = target.start - 1;
+            let Some(pad) = sequence.base(&target.chrom, pos)? else {
+                return Ok(Lifted::Unmapped(UnmappedReason::NoTarget));
+            };
+            let flip = |allele: &String| match allele.get(1..) {
+                Some(rest) if !is_symbolic(allele) && allele != "*" => {
+                    format!("{}{}", pad as char, reverse_complement(rest))
+                }
+                _ => allele.clone(),
+            };
+
+            (
+                pos,
+                flip(&record.reference),
+                record.alternates.iter().map(flip).collect(),
+                target.end - 1,
+            )
+        } else {
+            (
+                target.start,
+                reverse_complement(&record.reference),
+                record
+                    .alternates
+                    .iter()
+                    .map(|a| {
+                        if is_symbolic(a) {
+                            a.clone()
+                        } else {
+                            reverse_complement(a)
+                        }
+                    })
+                    .collect(),
+                target.end,
+            )
+        };
+
+        let mut swapped = false;
+        if let Some(sequence) = self.reference {
+            let matches = |allele: &str| -> Result<bool> {
+                let bases = sequence.fetch(&target.chrom, pos, pos + allele.len() as u64 - 1)?;
+                Ok(bases.eq_ignore_ascii_case(allele.as_bytes()))
+            };
+
+            if !matches(&reference)? {
+                match alternates.as_slice() {
+                    [alt] if !is_symbolic(alt) && alt.len() == reference.len() && matches(alt)? => {
+                        swapped = true
+                    }
+                    _ => return Ok(Lifted::Unmapped(UnmappedReason::MismatchedReference)),
+                }
+            }
+        }
+
+        record.chrom = target.chrom;
+        record.pos = pos;
+        record.reference = reference;
+        record.alternates = alternates;
+        if record.has_info("END") {
+            record.set_info("END", Some(end.to_string()));
+        }
+
+        if !swapped {
+            return Ok(Lifted::Mapped);
+        }
+
+        let count = |key: &str| record.info(key).and_then(|v| v.parse::<u64>().ok());
+        let ac = count("AN")
+            .zip(count("AC"))
+            .map(|(an, ac)| an.saturating_sub(ac));
+        let af = record.info("AF").and_then(complement_frequency);
+
+        AlleleRemap::new(vec![Some(1), Some(0)], 2).apply(&self.header, record);
+        std::mem::swap(&mut record.reference, &mut record.alternates[0]);
+
+        if let Some(ac) = ac {
+            record.set_info("AC", Some(ac.to_string()));
+        }
+        if af.is_some() {
+            record.set_info("AF", af);
+        }
+        record.set_info(SWAP_KEY, None);
+
+        Ok(Lifted::Swapped)
+    }
+}
+
+/// `1 - af`, with as many decimals as `af` has, written out when it is in
+/// scientific notation.
+fn complement_frequency(af: &str) -> Option<String> {
+    let value: f64 = af.parse().ok()?;
+    let (mantissa, exponent) = match af.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (af, 0),
+    };
+    let decimals = mantissa.split_once('.').map_or(0, |(_, d)| d.len()) as i64;
+    let decimals = (decimals - exponent).clamp(0, 17) as usize;
+
+    Some(format!("{:.*}", decimals, 1.0 - value))
+}
+
+/// Reverse complement of a base sequence; other characters are kept.
+fn reverse_complement(bases: &str) -> String {
+    bases
+        .chars()
+        .rev()
+        .map(|c| match c {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            'T' => 'A',
+            'a' => 't',
+            'c' => 'g',
+            'g' => 'c',
+            't' => 'a',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(feature = "fs")]
+/// Lifts `records` and writes them, sorted, after [`Lifter::header`].
+///
+/// Unmapped records are written unchanged to `rejects` after
+/// [`Lifter::rejects_header`], with the reason as their only FILTER, and
+/// counted as skipped.
+pub fn liftover<I, W, V>(
+    records: I,
+    lifter: &Lifter,
+    writer: &mut Writer<W>,
+    rejects: &mut Writer<V>,
+    options: SortOptions,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+    V: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("liftover");
+    let header = lifter.header();
+    let mut sorter = ExternalSorter::new(&header, options);
+
+    rejects.write_header(&lifter.rejects_header())?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        let lifted = record.and_then(|mut record| lifter.lift(&mut record).map(|l| (record, l)));
+        let (mut record, lifted) = match lifted {
+            Ok(v) => v,
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        match lifted {
+            Lifted::Mapped => {}
+            Lifted::Swapped => summary.modified += 1,
+            Lifted::Unmapped(reason) => {
+                summary.skipped += 1;
+                summary.warn(format!(
+                    "{}:{}: {}",
+                    record.chrom,
+                    record.pos,
+                    reason.description()
+                ));
+                record.filters = vec![reason.id().to_string()];
+                rejects.write_record(&record)?;
+                continue;
+            }
+        }
+
+        sorter.push(record)?;
+    }
+
+    writer.write_header(&header)?;
+    for record in sorter.finish()? {
+        writer.write_record(&record?)?;
+        summary.records_out += 1;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::reader::Reader;
+    use crate::reference::MemoryReference;
+
+    // 1:1-20 and 1:23-50 align to chr1:11-30 and chr1:31-58; 1:61-80 aligns
+    // to chr2:15-34 on the reverse strand.
+    const CHAINS: &str = "chain 1000 1 100 + 0 50 chr1 100 + 10 58 1\n\
+        20 2 0\n\
+        28\n\
+        \n\
+        chain 500 1 100 + 60 80 chr2 40 - 5 25 2\n\
+        20\n";
+
+    fn chains() -> ChainMap {
+        ChainMap::from_reader(CHAINS.as_bytes()).unwrap()
+    }
+
+    fn reference() -> MemoryReference {
+        let mut reference = MemoryReference::new();
+        reference.insert("chr1", &"ACGT".repeat(25));
+        reference.insert("chr2", &format!("{}TAG{}", "A".repeat(32), "A".repeat(5)));
+
+        reference
+    }
+
+    #[test]
+    fn test_map_interval_1() {
+        let chains = chains();
+        let mapped = |start, end| chains.map_interval("1", start, end);
+
+        assert_eq!(
+            mapped(1, 1).unwrap(),
+            MappedInterval {
+                chrom: "chr1".to_string(),
+                start: 11,
+                end: 11,
+                reverse: false
+            }
+        );
+        assert_eq!(mapped(23, 25).unwrap().start, 31);
+        assert_eq!(mapped(21, 21), Err(UnmappedReason::NoTarget));
+        assert_eq!(mapped(20, 23), Err(UnmappedReason::SplitInterval));
+        assert_eq!(
+            chains.map_interval("2", 1, 1),
+            Err(UnmappedReason::NoTarget)
+        );
+
+        let reverse = mapped(62, 63).unwrap();
+        assert_eq!(
+            (reverse.start, reverse.end, reverse.reverse),
+            (33, 34, true)
+        );
+        assert_eq!(chains.targets()[1], ("chr2".to_string(), 40));
+
+        assert!(ChainMap::from_reader("chain 1 1 100 - 0 1 x 1 + 0 1\n1\n".as_bytes()).is_err());
+        assert!(ChainMap::from_reader("10 0 0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_1() {
+        let parse = |chains: &str| ChainMap::from_reader(chains.as_bytes());
+
+        assert!(parse("chain 1 1 100 + 0 10 x 20 - 0 10 1\n10\n").is_ok());
+        // Blocks past the declared ends, or ends past the contig sizes.
+        assert!(parse("chain 1 1 100 + 0 10 x 20 - 0 10 1\n30\n").is_err());
+        assert!(parse("chain 1 1 100 + 0 10 x 20 - 0 10 1\n5 0 10\n5\n").is_err());
+        assert!(parse("chain 1 1 100 + 0 10 x 20 - 0 30 1\n10\n").is_err());
+        assert!(parse("chain 1 1 100 + 0 10 x 20 - 0 10 1\n18446744073709551615\n").is_err());
+        // The same target contig with two sizes.
+        assert!(parse(
+            "chain 1 1 100 + 0 10 x 20 - 0 10 1\n10\n\
+             chain 1 1 100 + 20 30 x 30 - 0 10 2\n10\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_lift_1() {
+        let chains = chains();
+        let reference = reference();
+        let header: Header = "##fileformat=VCFv4.3\n\
+            ##INFO=<ID=AF,Number=A,Type=Float,Description=\"\">\n\
+            ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"\">\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1"
+            .parse()
+            .unwrap();
+        let with = Lifter::new(&chains, &header, Some(&reference));
+        let without = Lifter::new(&chains, &header, None);
+
+        let lift = |lifter: &Lifter, line: &str| {
+            let mut record: Record = line.parse().unwrap();
+            let lifted = lifter.lift(&mut record).unwrap();
+            (lifted, record.to_string())
+        };
+
+        assert_eq!(
+            lift(&with, "1\t5\trs1\tG\tA\t.\t.\t.\tGT\t0/1"),
+            (
+                Lifted::Mapped,
+                "chr1\t15\trs1\tG\tA\t.\t.\t.\tGT\t0/1".to_string()
+            )
+        );
+        assert_eq!(
+            lift(&with, "1\t6\t.\tC\tT\t.\t.\tAF=0.25;AN=4;AC=1\tGT\t0/0"),
+            (
+                Lifted::Swapped,
+                "chr1\t16\t.\tT\tC\t.\t.\tAF=0.75;AN=4;AC=3;SWAP\tGT\t1/1".to_string()
+            )
+        );
+        assert_eq!(
+            lift(&with, "1\t6\t.\tC\tT\t.\t.\tAF=1e-05\tGT\t0/0").1,
+            "chr1\t16\t.\tT\tC\t.\t.\tAF=0.99999;SWAP\tGT\t1/1"
+        );
+        assert_eq!(
+            lift(&with, "1\t6\t.\tC\tT\t.\t.\tAF=2.5E-1\tGT\t0/0").1,
+            "chr1\t16\t.\tT\tC\t.\t.\tAF=0.75;SWAP\tGT\t1/1"
+        );
+        assert_eq!(
+            lift(&with, "1\t6\t.\tC\tA\t.\t.\t.").0,
+            Lifted::Unmapped(UnmappedReason::MismatchedReference)
+        );
+        assert_eq!(lift(&without, "1\t6\t.\tC\tA\t.\t.\t.").0, Lifted::Mapped);
+
+        // Reverse strand: 1:61 is chr2:35, and 1:62-63 is chr2:33-34.
+        assert_eq!(
+            lift(&without, "1\t61\t.\tC\tT\t.\t.\t.").1,
+            "chr2\t35\t.\tG\tA\t.\t.\t."
+        );
+        assert_eq!(
+            lift(&with, "1\t62\t.\tCA\tC\t.\t.\t.").1,
+            "chr2\t32\t.\tAT\tA\t.\t.\t."
+        );
+        assert_eq!(
+            lift(&without, "1\t62\t.\tCA\tC\t.\t.\t.").0,
+            Lifted::Unmapped(UnmappedReason::NoReference)
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_liftover_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            ##contig=<ID=1,length=100>\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t5\ta\tG\tA\t.\tPASS\t.\n\
+            1\t21\tb\tA\tT\t.\tPASS\t.\n\
+            1\t61\tc\tC\tT\t.\tPASS\t.\n\
+            1\t70\td\tA\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=75\n";
+        let reader = Reader::new(vcf.as_bytes()).unwrap();
+        let chains = chains();
+        let lifter = Lifter::new(&chains, reader.header(), None);
+
+        let mut writer = Writer::new(Vec::new());
+        let mut rejects = Writer::new(Vec::new());
+        let summary = liftover(
+            reader,
+            &lifter,
+            &mut writer,
+            &mut rejects,
+            SortOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            (summary.records_in, summary.records_out, summary.skipped),
+            (4, 2, 2)
+        );
+
+        let output = writer.into_inner();
+        let lifted = Reader::new(output.as_slice()).unwrap();
+        assert_eq!(lifted.header().contigs.len(), 2);
+        let ids: Vec<String> = lifted.map(|r| r.unwrap().ids.join(";")).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        let rejected: Vec<String> = Reader::new(rejects.into_inner().as_slice())
+            .unwrap()
+            .map(|r| r.unwrap().filters.join(";"))
+            .collect();
+        assert_eq!(rejected, vec!["NoTarget", "NoReference"]);
+    }
+}
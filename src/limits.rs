@@ -0,0 +1,299 @@
+//! Guards against pathological records.
+
+use crate::errors::Result;
+use crate::header::Header;
+use crate::record::Record;
+use crate::writer::Writer;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Upper bounds on record shape; `None` disables a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// Maximum number of ALT alleles.
+    pub max_alternates: Option<usize>,
+    /// Maximum length of REF and of each ALT allele.
+    pub max_allele_length: Option<usize>,
+}
+
+/// What [`LimitFilter`] does with records exceeding the limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitAction {
+    /// Discard the record.
+    #[default]
+    Drop,
+    /// Remove the offending ALT alleles and keep the rest of the record.
+    ///
+    /// Records whose REF is too long or that are left without ALT alleles are
+    /// dropped.
+    Truncate,
+    /// Write the record to the side-channel writer given to
+    /// [`LimitFilter::route_to`].
+    Route,
+}
+
+/// Counts of what [`LimitFilter`] has seen and done.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LimitReport {
+    pub records: u64,
+    pub passed: u64,
+    pub dropped: u64,
+    pub truncated: u64,
+    pub routed: u64,
+    /// Records with more ALT alleles than allowed.
+    pub too_many_alternates: u64,
+    /// Records with an allele longer than allowed.
+    pub allele_too_long: u64,
+    /// Largest ALT count seen.
+    pub max_alternates: usize,
+    /// Longest allele seen.
+    pub max_allele_length: usize,
+}
+
+impl fmt::Display for LimitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "records\t{}", self.records)?;
+        writeln!(f, "passed\t{}", self.passed)?;
+        writeln!(f, "dropped\t{}", self.dropped)?;
+        writeln!(f, "truncated\t{}", self.truncated)?;
+        writeln!(f, "routed\t{}", self.routed)?;
+        writeln!(f, "too_many_alternates\t{}", self.too_many_alternates)?;
+        writeln!(f, "allele_too_long\t{}", self.allele_too_long)?;
+        writeln!(f, "max_alternates\t{}", self.max_alternates)?;
+        writeln!(f, "max_allele_length\t{}", self.max_allele_length)
+    }
+}
+
+/// Iterator adapter enforcing [`Limits`] on a stream of records.
+pub struct LimitFilter<I, W = io::Sink> {
+    inner: I,
+    header: Header,
+    limits: Limits,
+    action: LimitAction,
+    side: Option<Writer<W>>,
+    report: LimitReport,
+}
+
+impl<I: Iterator<Item = Result<Record>>> LimitFilter<I, io::Sink> {
+    /// Wraps `inner`; `header` supplies the `Number` of per-allele fields
+    /// subset by [`LimitAction::Truncate`].
+    ///
+    /// [`LimitAction::Route`] without a side channel discards records.
+    pub fn new(inner: I, header: &Header, limits: Limits, action: LimitAction) -> Self {
+        Self {
+            inner,
+            header: header.clone(),
+            limits,
+            action,
+            side: None,
+            report: LimitReport::default(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>, W: Write> LimitFilter<I, W> {
+    /// Routes offending records to `side` instead of dropping or truncating them.
+    pub fn route_to<V: Write>(self, side: Writer<V>) -> LimitFilter<I, V> {
+        LimitFilter {
+            inner: self.inner,
+            header: self.header,
+            limits: self.limits,
+            action: LimitAction::Route,
+            side: Some(side),
+            report: self.report,
+        }
+    }
+
+    pub fn report(&self) -> &LimitReport {
+        &self.report
+    }
+
+    pub fn side_channel(&self) -> Option<&Writer<W>> {
+        self.side.as_ref()
+    }
+
+    pub fn into_parts(self) -> (I, Option<Writer<W>>, LimitReport) {
+        (self.inner, self.side, self.report)
+    }
+
+    fn too_long(&self, allele: &str) -> bool {
+        self.limits
+            .max_allele_length
+            .is_some_and(|max| allele.len() > max)
+    }
+
+    /// Applies the limits, returning the record to pass downstream, if any.
+    fn check(&mut self, mut record: Record) -> Result<Option<Record>> {
+        let report = &mut self.report;
+        report.records += 1;
+
+        let longest = record
+            .alternates
+            .iter()
+            .map(|a| a.len())
+            .chain([record.reference.len()])
+            .max()
+            .unwrap_or(0);
+        report.max_alternates = report.max_alternates.max(record.alternates.len());
+        report.max_allele_length = report.max_allele_length.max(longest);
+
+        let too_many = self
+            .limits
+            .max_alternates
+            .is_some_and(|max| record.alternates.len() > max);
+        let too_long = self
+            .limits
+            .max_allele_length
+            .is_some_and(|max| longest > max);
+
+        if too_many {
+            report.too_many_alternates += 1;
+        }
+        if too_long {
+            report.allele_too_long += 1;
+        }
+        if !too_many && !too_long {
+            report.passed += 1;
+            return Ok(Some(record));
+        }
+
+        match self.action {
+            LimitAction::Drop => {
+                self.report.dropped += 1;
+                Ok(None)
+            }
+            LimitAction::Route => {
+                if let Some(side) = self.side.as_mut() {
+                    side.write_record(&record)?;
+                    self.report.routed += 1;
+                } else {
+                    self.report.dropped += 1;
+                }
+                Ok(None)
+            }
+            LimitAction::Truncate => {
+                let max_alternates = self.limits.max_alternates.unwrap_or(usize::MAX);
+                let mut count = 0;
+                let keep: Vec<bool> = record
+                    .alternates
+                    .iter()
+                    .map(|a| {
+                        let keep = count < max_alternates && !self.too_long(a);
+                        count += keep as usize;
+                        keep
+                    })
+                    .collect();
+
+                if self.too_long(&record.reference) || count == 0 {
+                    self.report.dropped += 1;
+                    return Ok(None);
+                }
+
+                record.retain_alternates(&self.header, &keep);
+                self.report.truncated += 1;
+                Ok(Some(record))
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>, W: Write> Iterator for LimitFilter<I, W> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.inner.next()? {
+                Ok(record) => record,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match self.check(record) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##fileformat=VCFv4.2\n\
+        ##INFO=<ID=AF,Number=A,Type=Float,Description=\"Allele frequency\">\n\
+        ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+        ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allelic depths\">\n\
+        ##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"Phred-scaled likelihoods\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+        1\t100\t.\tA\tT\t.\t.\tAF=0.5\tGT:AD\t0/1:5,5\n\
+        1\t200\t.\tA\tC,G,T\t.\t.\tAF=0.1,0.2,0.3\tGT:AD:PL\t1/3:1,2,3,4:0,1,2,3,4,5,6,7,8,9\n\
+        1\t300\t.\tA\tACGTACGT,C\t.\t.\tAF=0.1,0.2\tGT\t0/1\n\
+        1\t400\t.\tACGTACGT\tA\t.\t.\t.\tGT\t0/1\n";
+
+    fn limits() -> Limits {
+        Limits {
+            max_alternates: Some(2),
+            max_allele_length: Some(5),
+        }
+    }
+
+    fn positions(records: &[Record]) -> Vec<u64> {
+        records.iter().map(|r| r.pos).collect()
+    }
+
+    #[test]
+    fn test_limit_filter_1() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut filter = LimitFilter::new(reader, &header, limits(), LimitAction::Drop);
+        let records: Vec<Record> = filter.by_ref().map(|r| r.unwrap()).collect();
+        let report = filter.report();
+
+        assert_eq!(positions(&records), vec![100]);
+        assert_eq!(report.records, 4);
+        assert_eq!(report.dropped, 3);
+        assert_eq!(report.too_many_alternates, 1);
+        assert_eq!(report.allele_too_long, 2);
+        assert_eq!(report.max_alternates, 3);
+        assert_eq!(report.max_allele_length, 8);
+        assert!(report.to_string().contains("dropped\t3\n"));
+    }
+
+    #[test]
+    fn test_limit_filter_2() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut filter = LimitFilter::new(reader, &header, limits(), LimitAction::Truncate);
+        let records: Vec<Record> = filter.by_ref().map(|r| r.unwrap()).collect();
+
+        assert_eq!(positions(&records), vec![100, 200, 300]);
+        assert_eq!(
+            records[1].to_string(),
+            "1\t200\t.\tA\tC,G\t.\t.\tAF=0.1,0.2\tGT:AD:PL\t1/.:1,2,3:0,1,2,3,4,5"
+        );
+        assert_eq!(
+            records[2].to_string(),
+            "1\t300\t.\tA\tC\t.\t.\tAF=0.2\tGT\t0/."
+        );
+        assert_eq!(filter.report().truncated, 2);
+        assert_eq!(filter.report().dropped, 1);
+    }
+
+    #[test]
+    fn test_limit_filter_3() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut filter = LimitFilter::new(reader, &header, limits(), LimitAction::Drop)
+            .route_to(Writer::new(Vec::new()));
+        let records: Vec<Record> = filter.by_ref().map(|r| r.unwrap()).collect();
+        let (_, side, report) = filter.into_parts();
+        let side = String::from_utf8(side.unwrap().into_inner()).unwrap();
+
+        assert_eq!(positions(&records), vec![100]);
+        assert_eq!(report.routed, 3);
+        assert_eq!(side.lines().count(), 3);
+        assert!(side.starts_with("1\t200\t"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Merging of sorted VCFs into one multi-sample VCF.
+
+use crate::alleles::AlleleRemap;
+use crate::errors::{Error, Result};
+use crate::genotype::Genotype;
+use crate::header::{Header, Version};
+use crate::order::{ContigOrder, SortednessChecker};
+use crate::reader::Reader;
+use crate::record::Record;
+use crate::summary::{RunSummary, Timer};
+use crate::writer::Writer;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::iter::Peekable;
+
+/// Combines the headers of the inputs of a merge.
+///
+/// Definitions are merged by ID with the first one winning, contigs keep
+/// their order of first appearance, and samples are concatenated. The newest
+/// declared version is used.
+pub fn merge_headers(headers: &[&Header]) -> Result<Header> {
+    let mut merged = merge_definitions(headers);
+
+    for header in headers {
+        for sample in &header.samples {
+            if merged.samples.contains(sample) {
+                Err(Error::DuplicateSampleError(sample.clone()))?
+            }
+            merged.samples.push(sample.clone());
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Like [`merge_headers`], without samples.
+pub(crate) fn merge_definitions(headers: &[&Header]) -> Header {
+    let fileformat = headers
+        .iter()
+        .filter_map(|h| h.version())
+        .max()
+        .map(|v: Version| v.to_string())
+        .or_else(|| headers.first().map(|h| h.fileformat.clone()))
+        .unwrap_or_default();
+    let mut merged = Header::new(&fileformat);
+
+    for header in headers {
+        for d in &header.infos {
+            if merged.info(&d.id).is_none() {
+                merged.infos.push(d.clone());
+            }
+        }
+        for d in &header.formats {
+            if merged.format(&d.id).is_none() {
+                merged.formats.push(d.clone());
+            }
+        }
+        for d in &header.filters {
+            if merged.filter(&d.id).is_none() {
+                merged.filters.push(d.clone());
+            }
+        }
+        for d in &header.alts {
+            if !merged.alts.iter().any(|a| a.id == d.id) {
+                merged.alts.push(d.clone());
+            }
+        }
+        for d in &header.contigs {
+            if merged.contig(&d.id).is_none() {
+                merged.contigs.push(d.clone());
+            }
+        }
+        for line in &header.other {
+            if !merged.other.contains(line) {
+                merged.other.push(line.clone());
+            }
+        }
+    }
+
+    merged
+}
+
+struct Input<I: Iterator<Item = Result<Record>>> {
+    header: Header,
+    records: Peekable<SortednessChecker<I>>,
+    /// Records at the position being merged.
+    pending: VecDeque<Record>,
+}
+
+/// Merges sorted per-sample record streams position by position.
+///
+/// Records at the same position and with the same REF are combined into one
+/// record with the union of their ALT alleles, in order of first appearance.
+/// `GT` and values declared with `Number=A`, `R` or `G` are remapped onto the
+/// merged ALT list; samples of inputs without a record get a missing `GT`.
+/// Records with different REF at one position are written separately.
+///
+/// Inputs must be sorted in the contig order of the merged header.
+pub struct Merger<I: Iterator<Item = Result<Record>>> {
+    header: Header,
+    order: ContigOrder,
+    inputs: Vec<Input<I>>,
+    records_in: u64,
+    combined: u64,
+}
+
+impl<R: BufRead> Merger<Reader<R>> {
+    pub fn from_readers(readers: Vec<Reader<R>>) -> Result<Self> {
+        Self::new(
+            readers
+                .into_iter()
+                .map(|r| (r.header().clone(), r))
+                .collect(),
+        )
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Merger<I> {
+    pub fn new(inputs: Vec<(Header, I)>) -> Result<Self> {
+        let header = merge_headers(&inputs.iter().map(|(h, _)| h).collect::<Vec<_>>())?;
+
+        Ok(Self {
+            order: ContigOrder::from_header(&header),
+            header,
+            inputs: inputs
+                .into_iter()
+                .map(|(header, records)| Input {
+                    records: SortednessChecker::new(records, &header).peekable(),
+                    header,
+                    pending: VecDeque::new(),
+                })
+                .collect(),
+            records_in: 0,
+            combined: 0,
+        })
+    }
+
+    /// Merged header of all inputs.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Number of input records consumed so far.
+    pub fn records_in(&self) -> u64 {
+        self.records_in
+    }
+
+    /// Number of records written that combine more than one input record.
+    pub fn combined(&self) -> u64 {
+        self.combined
+    }
+
+    /// Moves the records at the next position of every input to `pending`.
+    fn fill(&mut self) -> Result<bool> {
+        let mut next: Option<(String, u64)> = None;
+
+        for input in self.inputs.iter_mut() {
+            let record = match input.records.peek() {
+                Some(Ok(record)) => record,
+                Some(Err(_)) => return Err(input.records.next().unwrap().unwrap_err()),
+                None => continue,
+            };
+
+            let earlier = next.as_ref().is_none_or(|(chrom, pos)| {
+                self.order
+                    .compare_positions((&record.chrom, record.pos), (chrom, *pos))
+                    == Ordering::Less
+            });
+            if earlier {
+                next = Some((record.chrom.clone(), record.pos));
+            }
+        }
+
+        let (chrom, pos) = match next {
+            Some(next) => next,
+            None => return Ok(false),
+        };
+
+        for input in self.inputs.iter_mut() {
+            while let Some(Ok(record)) = input.records.peek() {
+                if record.chrom != chrom || record.pos != pos {
+                    break;
+                }
+                input.pending.push_back(input.records.next().unwrap()?);
+                self.records_in += 1;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn merge_next(&mut self) -> Record {
+        let reference = self
+            .inputs
+            .iter()
+            .find_map(|input| input.pending.front())
+            .map(|r| r.reference.clone())
+            .unwrap();
+
+        let records: Vec<Option<Record>> = self
+            .inputs
+            .iter_mut()
+            .map(|input| {
+                let i = input
+                    .pending
+                    .iter()
+                    .position(|r| r.reference == reference)?;
+                input.pending.remove(i)
+            })
+            .collect();
+
+        let present = || records.iter().flatten();
+        if present().count() > 1 {
+            self.combined += 1;
+        }
+        let first = present().next().unwrap();
+        let mut merged = Record::new(&first.chrom, first.pos, &reference, &[]);
+
+        for record in present() {
+            for id in &record.ids {
+                if !merged.ids.contains(id) {
+                    merged.ids.push(id.clone());
+                }
+            }
+            for alt in &record.alternates {
+                if !merged.alternates.contains(alt) {
+                    merged.alternates.push(alt.clone());
+                }
+            }
+            for filter in &record.filters {
+                if !merged.filters.contains(filter) {
+                    merged.filters.push(filter.clone());
+                }
+            }
+            merged.qual = match (merged.qual, record.qual) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        if merged.filters.len() > 1 {
+            merged.filters.retain(|f| f != "PASS");
+        }
+
+        // Per-input records with values in the merged allele space.
+        let remapped: Vec<Option<Record>> = records
+            .into_iter()
+            .map(|record| {
+                record.map(|mut r| {
+                    AlleleRemap::between(&r.alternates, &merged.alternates)
+                        .apply(&self.header, &mut r);
+                    r
+                })
+            })
+            .collect();
+
+        for record in remapped.iter().flatten() {
+            for (key, value) in &record.info {
+                if !merged.has_info(key) {
+                    merged.info.push((key.clone(), value.clone()));
+                }
+            }
+            for key in &record.format {
+                if !merged.format.contains(key) {
+                    merged.format.push(key.clone());
+                }
+            }
+        }
+        if let Some(i) = merged.format_index("GT") {
+            let gt = merged.format.remove(i);
+            merged.format.insert(0, gt);
+        }
+
+        let has_gt = merged.format_index("GT").is_some();
+        let ploidy = remapped
+            .iter()
+            .flatten()
+            .flat_map(|r| (0..r.samples.len()).map(move |s| r.genotype(s)))
+            .find_map(|gt| gt.ok().flatten())
+            .map_or(2, |gt| gt.ploidy());
+
+        for (input, record) in self.inputs.iter().zip(&remapped) {
+            for s in 0..input.header.samples.len() {
+                let values = match record {
+                    Some(r) => merged
+                        .format
+                        .iter()
+                        .map(|key| r.format_value(s, key).unwrap_or(".").to_string())
+                        .collect(),
+                    None if has_gt => vec![Genotype::missing(ploidy).to_string()],
+                    None => vec![".".to_string()],
+                };
+                merged.samples.push(values);
+            }
+        }
+
+        merged
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for Merger<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.inputs.iter().all(|input| input.pending.is_empty()) {
+            match self.fill() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        Some(Ok(self.merge_next()))
+    }
+}
+
+/// Merges `inputs` and writes the merged header and records.
+///
+/// `modified` counts the records combined from more than one input.
+pub fn merge<I, W>(inputs: Vec<(Header, I)>, writer: &mut Writer<W>) -> Result<RunSummary>
+where
+    I: Iterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("merge");
+    let mut merger = Merger::new(inputs)?;
+
+    writer.write_header(merger.header())?;
+
+    for record in merger.by_ref() {
+        writer.write_record(&record?)?;
+        summary.records_out += 1;
+    }
+
+    summary.records_in = merger.records_in();
+    summary.modified = merger.combined();
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(sample: &str, body: &str) -> Reader<std::io::Cursor<String>> {
+        let text = format!(
+            "##fileformat=VCFv4.2\n\
+             ##contig=<ID=1>\n\
+             ##contig=<ID=2>\n\
+             ##INFO=<ID=AF,Number=A,Type=Float,Description=\"Allele frequency\">\n\
+             ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+             ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allelic depths\">\n\
+             ##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"Likelihoods\">\n\
+             #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}\n{}",
+            sample, body
+        );
+        Reader::new(std::io::Cursor::new(text)).unwrap()
+    }
+
+    fn lines(merger: Merger<Reader<std::io::Cursor<String>>>) -> Vec<String> {
+        merger.map(|r| r.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge_headers_1() {
+        let a = reader("A", "");
+        let mut b = reader("B", "").header().clone();
+        b.fileformat = "VCFv4.3".to_string();
+        b.contigs
+            .insert(0, crate::header::ContigDefinition::new("3", None));
+        let merged = merge_headers(&[a.header(), &b]).unwrap();
+
+        assert_eq!(merged.fileformat, "VCFv4.3");
+        assert_eq!(merged.samples, vec!["A", "B"]);
+        assert_eq!(merged.formats.len(), 3);
+        assert_eq!(
+            merged
+                .contigs
+                .iter()
+                .map(|c| c.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1", "2", "3"]
+        );
+        assert!(matches!(
+            merge_headers(&[a.header(), a.header()]),
+            Err(Error::DuplicateSampleError(_))
+        ));
+    }
+
+    #[test]
+    fn test_merger_1() {
+        let a = reader(
+            "A",
+            "1\t100\trs1\tA\tG\t30\tPASS\tAF=0.5\tGT:AD:PL\t0/1:4,6:50,0,60\n\
+             1\t200\t.\tC\tT\t.\t.\t.\tGT\t1/1\n",
+        );
+        let b = reader(
+            "B",
+            "1\t100\t.\tA\tT,G\t50\tq10\tAF=0.1,0.2\tGT:AD:PL\t1/2:1,2,3:9,8,7,6,5,4\n\
+             2\t5\t.\tG\tC\t.\t.\t.\tGT\t0|1\n",
+        );
+        let merger = Merger::from_readers(vec![a, b]).unwrap();
+
+        assert_eq!(merger.header().samples, vec!["A", "B"]);
+        assert_eq!(
+            lines(merger),
+            vec![
+                "1\t100\trs1\tA\tG,T\t50\tq10\tAF=0.5,.\tGT:AD:PL\t0/1:4,6,.:50,0,60,.,.,.\t2/1:1,3,2:9,6,4,8,5,7",
+                "1\t200\t.\tC\tT\t.\t.\t.\tGT\t1/1\t./.",
+                "2\t5\t.\tG\tC\t.\t.\t.\tGT\t./.\t0|1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merger_2() {
+        let a = reader(
+            "A",
+            "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\n\
+             1\t100\t.\tAT\tA\t.\t.\t.\tGT\t1/1\n",
+        );
+        let b = reader("B", "1\t100\t.\tAT\tA\t.\t.\t.\tGT\t0/1\n");
+        let merger = Merger::from_readers(vec![a, b]).unwrap();
+
+        assert_eq!(
+            lines(merger),
+            vec![
+                "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\t./.",
+                "1\t100\t.\tAT\tA\t.\t.\t.\tGT\t1/1\t0/1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merger_3() {
+        let a = reader(
+            "A",
+            "1\t200\t.\tA\tG\t.\t.\t.\tGT\t0/1\n\
+             1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\n",
+        );
+        let b = reader("B", "");
+        let mut merger = Merger::from_readers(vec![a, b]).unwrap();
+
+        assert!(merger.next().unwrap().is_ok());
+        assert!(matches!(
+            merger.next(),
+            Some(Err(Error::UnsortedRecordError(..)))
+        ));
+    }
+
+    #[test]
+    fn test_merge_1() {
+        let a = reader("A", "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\n");
+        let b = reader(
+            "B",
+            "1\t100\t.\tA\tG\t.\t.\t.\tGT\t1/1\n\
+             1\t150\t.\tA\tG\t.\t.\t.\tGT\t1/1\n",
+        );
+        let inputs = vec![a, b]
+            .into_iter()
+            .map(|r| (r.header().clone(), r))
+            .collect();
+        let mut writer = Writer::new(Vec::new());
+        let summary = merge(inputs, &mut writer).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert!(text.contains("\tFORMAT\tA\tB\n"));
+        assert_eq!(summary.records_in, 3);
+        assert_eq!(summary.records_out, 2);
+        assert_eq!(summary.modified, 1);
+    }
+}
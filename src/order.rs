@@ -0,0 +1,378 @@
+//! Ordering of records by the contig order of a header.
+
+use crate::errors::{Error, Result};
+use crate::header::Header;
+use crate::record::Record;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+/// Ranks of contigs, by default in the order of the `##contig` lines.
+///
+/// Contigs that are not declared sort after the declared ones, by name,
+/// unless they are added with [`ContigOrder::push`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContigOrder {
+    ranks: HashMap<String, usize>,
+}
+
+impl ContigOrder {
+    pub fn new<S: AsRef<str>>(names: impl IntoIterator<Item = S>) -> Self {
+        let mut order = Self::default();
+        for name in names {
+            order.push(name.as_ref());
+        }
+        order
+    }
+
+    pub fn from_header(header: &Header) -> Self {
+        Self::new(header.contigs.iter().map(|c| c.id.as_str()))
+    }
+
+    pub fn rank(&self, chrom: &str) -> Option<usize> {
+        self.ranks.get(chrom).copied()
+    }
+
+    /// Appends `chrom` after every known contig and returns its rank.
+    ///
+    /// Known contigs keep their rank.
+    pub fn push(&mut self, chrom: &str) -> usize {
+        let next = self.ranks.len();
+
+        *self.ranks.entry(chrom.to_string()).or_insert(next)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    pub fn compare_contigs(&self, a: &str, b: &str) -> Ordering {
+        match (self.rank(a), self.rank(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.cmp(b),
+        }
+    }
+
+    /// Compares two positions by contig, then coordinate.
+    pub fn compare_positions(&self, a: (&str, u64), b: (&str, u64)) -> Ordering {
+        self.compare_contigs(a.0, b.0).then(a.1.cmp(&b.1))
+    }
+
+    pub fn compare(&self, a: &Record, b: &Record) -> Ordering {
+        self.compare_positions((&a.chrom, a.pos), (&b.chrom, b.pos))
+    }
+}
+
+/// Iterator adapter that fails on records out of coordinate order.
+///
+/// Contigs must follow the header order, each in one contiguous block.
+/// Contigs missing from the header must come after the declared ones and
+/// may appear in any order among themselves, but still only once.
+pub struct SortednessChecker<I> {
+    inner: I,
+    order: ContigOrder,
+    last: Option<(String, u64)>,
+    records: u64,
+}
+
+impl<I: Iterator<Item = Result<Record>>> SortednessChecker<I> {
+    pub fn new(inner: I, header: &Header) -> Self {
+        Self::with_order(inner, ContigOrder::from_header(header))
+    }
+
+    pub fn with_order(inner: I, order: ContigOrder) -> Self {
+        Self {
+            inner,
+            order,
+            last: None,
+            records: 0,
+        }
+    }
+
+    /// Number of records checked so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    fn check(&mut self, record: &Record) -> Result<()> {
+        self.records += 1;
+
+        if let Some((chrom, pos)) = &self.last {
+            let sorted = if *chrom == record.chrom {
+                record.pos >= *pos
+            } else {
+                let previous = self.order.rank(chrom).unwrap();
+                let rank = match self.order.rank(&record.chrom) {
+                    Some(rank) => rank,
+                    None => self.order.push(&record.chrom),
+                };
+                rank > previous
+            };
+
+            if !sorted {
+                Err(Error::UnsortedRecordError(
+                    record.chrom.clone(),
+                    record.pos,
+                    chrom.clone(),
+                    *pos,
+                ))?
+            }
+        } else if self.order.rank(&record.chrom).is_none() {
+            self.order.push(&record.chrom);
+        }
+
+        self.last = Some((record.chrom.clone(), record.pos));
+
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for SortednessChecker<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.inner.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(self.check(&record).map(|_| record))
+    }
+}
+
+/// Iterator adapter restoring the order of records moved by at most
+/// `window` bases, as normalization moves indels through repeats.
+///
+/// Records are buffered until a record `window` bases further along the
+/// contig is read, and yielded by position, ties in input order. Contigs are
+/// yielded in input order, each expected in one block.
+pub struct WindowSorter<I> {
+    inner: I,
+    window: u64,
+    /// Buffered records of the current contig by position and input order.
+    buffer: BTreeMap<(u64, u64), Record>,
+    /// Records of the previous contig, yielded before the buffer.
+    flushed: Vec<Record>,
+    chrom: Option<String>,
+    /// Largest position read on the current contig.
+    reach: u64,
+    records: u64,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> WindowSorter<I> {
+    pub fn new(inner: I, window: u64) -> Self {
+        Self {
+            inner,
+            window,
+            buffer: BTreeMap::new(),
+            flushed: Vec::new(),
+            chrom: None,
+            reach: 0,
+            records: 0,
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    fn push(&mut self, record: Record) {
+        if self.chrom.as_deref() != Some(record.chrom.as_str()) {
+            let buffer = std::mem::take(&mut self.buffer);
+            // Yielded from the back.
+            self.flushed = buffer.into_values().rev().collect();
+            self.chrom = Some(record.chrom.clone());
+            self.reach = 0;
+        }
+
+        self.reach = self.reach.max(record.pos);
+        self.buffer.insert((record.pos, self.records), record);
+        self.records += 1;
+    }
+
+    fn pop(&mut self) -> Option<Record> {
+        if let Some(record) = self.flushed.pop() {
+            return Some(record);
+        }
+
+        let (&(pos, _), _) = self.buffer.first_key_value()?;
+        if self.done || pos.saturating_add(self.window) < self.reach {
+            self.buffer.pop_first().map(|(_, record)| record)
+        } else {
+            None
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for WindowSorter<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pop() {
+                return Some(Ok(record));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.inner.next() {
+                Some(Ok(record)) => self.push(record),
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.done = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::ContigDefinition;
+
+    fn header(contigs: &[&str]) -> Header {
+        let mut header = Header::new("VCFv4.3");
+        for id in contigs {
+            header.contigs.push(ContigDefinition::new(id, None));
+        }
+        header
+    }
+
+    fn records(positions: &[(&str, u64)]) -> Vec<Result<Record>> {
+        positions
+            .iter()
+            .map(|&(chrom, pos)| Ok(Record::new(chrom, pos, "A", &["T"])))
+            .collect()
+    }
+
+    fn check(header: &Header, positions: &[(&str, u64)]) -> Result<u64> {
+        let mut checker = SortednessChecker::new(records(positions).into_iter(), header);
+        for record in checker.by_ref() {
+            record?;
+        }
+        Ok(checker.records())
+    }
+
+    #[test]
+    fn test_contig_order_1() {
+        let order = ContigOrder::from_header(&header(&["chr2", "chr10", "chr1"]));
+        let mut records: Vec<Record> = records(&[
+            ("chr1", 5),
+            ("chrM", 1),
+            ("chr10", 9),
+            ("chr2", 7),
+            ("chr10", 3),
+            ("chrA", 1),
+        ])
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect();
+        records.sort_by(|a, b| order.compare(a, b));
+
+        let sorted: Vec<(&str, u64)> = records.iter().map(|r| (r.chrom.as_str(), r.pos)).collect();
+
+        assert_eq!(
+            sorted,
+            vec![
+                ("chr2", 7),
+                ("chr10", 3),
+                ("chr10", 9),
+                ("chr1", 5),
+                ("chrA", 1),
+                ("chrM", 1)
+            ]
+        );
+        assert_eq!(order.rank("chr10"), Some(1));
+        assert_eq!(order.rank("chrM"), None);
+    }
+
+    #[test]
+    fn test_contig_order_2() {
+        let mut order = ContigOrder::new(["1", "2"]);
+
+        assert_eq!(order.push("X"), 2);
+        assert_eq!(order.push("1"), 0);
+        assert_eq!(order.len(), 3);
+        assert_eq!(
+            order.compare_positions(("X", 1), ("2", 100)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_sortedness_checker_1() {
+        let header = header(&["chr1", "chr2"]);
+
+        assert_eq!(
+            check(
+                &header,
+                &[
+                    ("chr1", 1),
+                    ("chr1", 1),
+                    ("chr2", 5),
+                    ("chrX", 1),
+                    ("chrY", 2)
+                ]
+            )
+            .unwrap(),
+            5
+        );
+        assert!(matches!(
+            check(&header, &[("chr1", 10), ("chr1", 9)]),
+            Err(Error::UnsortedRecordError(_, 9, _, 10))
+        ));
+        assert!(check(&header, &[("chr2", 10), ("chr1", 20)]).is_err());
+        assert!(check(&header, &[("chr1", 10), ("chrX", 1), ("chr1", 20)]).is_err());
+    }
+
+    #[test]
+    fn test_sortedness_checker_2() {
+        let header = header(&[]);
+
+        assert!(check(&header, &[("B", 1), ("A", 1), ("C", 1)]).is_ok());
+        assert!(check(&header, &[("B", 1), ("A", 1), ("B", 2)]).is_err());
+    }
+
+    #[test]
+    fn test_window_sorter_1() {
+        let positions = [
+            ("1", 10),
+            ("1", 4),
+            ("1", 30),
+            ("1", 20),
+            ("1", 10),
+            ("1", 45),
+            ("1", 12),
+            ("2", 5),
+            ("2", 1),
+        ];
+        let sorted: Vec<(String, u64)> = WindowSorter::new(records(&positions).into_iter(), 10)
+            .map(|r| r.map(|r| (r.chrom, r.pos)).unwrap())
+            .collect();
+
+        // 12 moved by more than the window and stays out of order.
+        let expected = [
+            ("1", 4),
+            ("1", 10),
+            ("1", 10),
+            ("1", 20),
+            ("1", 30),
+            ("1", 12),
+            ("1", 45),
+            ("2", 1),
+            ("2", 5),
+        ];
+        assert_eq!(sorted, expected.map(|(c, p)| (c.to_string(), p)).to_vec());
+    }
+}
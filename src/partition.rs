@@ -0,0 +1,256 @@
+//! Contig-partitioned execution over indexed files.
+//!
+//! An [`ExecutionPlan`] splits a BGZF file into one partition per indexed
+//! contig. Each partition is read through the index and processed on its
+//! own, in parallel with the `rayon` feature, and the per-partition results
+//! are merged in plan order. As long as a result type merges
+//! deterministically, the merged result is identical however the partitions
+//! were scheduled.
+
+use crate::summary::{RunSummary, MAX_MESSAGES};
+
+#[cfg(feature = "fs")]
+use crate::bgzf;
+#[cfg(feature = "fs")]
+use crate::errors::{Error, Result};
+#[cfg(feature = "fs")]
+use crate::header::Header;
+#[cfg(feature = "fs")]
+use crate::index::{find_index, Index};
+#[cfg(feature = "fs")]
+use crate::reader::Reader;
+#[cfg(feature = "fs")]
+use crate::regions::{RegionQuery, RegionSet};
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(all(feature = "fs", feature = "rayon"))]
+use rayon::prelude::*;
+
+/// Per-partition results that combine into a result for the whole file.
+///
+/// [`ExecutionPlan::run`] starts from `Default` and merges partition results
+/// in plan order.
+pub trait Mergeable: Default {
+    /// Adds `other`, the result of a later partition, to `self`.
+    fn merge(&mut self, other: Self);
+}
+
+impl<T> Mergeable for Vec<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl Mergeable for RunSummary {
+    /// Adds the counts and elapsed times, and keeps the first
+    /// [`MAX_MESSAGES`] messages.
+    fn merge(&mut self, other: Self) {
+        if self.operation.is_empty() {
+            self.operation = other.operation;
+        }
+        self.records_in += other.records_in;
+        self.records_out += other.records_out;
+        self.modified += other.modified;
+        self.skipped += other.skipped;
+        self.warnings += other.warnings;
+        self.elapsed += other.elapsed;
+
+        let room = MAX_MESSAGES.saturating_sub(self.messages.len());
+        self.messages.extend(other.messages.into_iter().take(room));
+    }
+}
+
+/// One partition per indexed contig of a BGZF file.
+#[cfg(feature = "fs")]
+pub struct ExecutionPlan {
+    path: PathBuf,
+    header: Header,
+    index: Index,
+    partitions: Vec<String>,
+}
+
+#[cfg(feature = "fs")]
+impl ExecutionPlan {
+    /// Plans the file at `path`, which needs a `.tbi` or `.csi` index next
+    /// to it.
+    ///
+    /// Partitions follow the contig order of the header; indexed contigs
+    /// missing from the header come last, in index order.
+    pub fn for_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let index_path =
+            find_index(path).ok_or_else(|| Error::MissingIndexError(path.display().to_string()))?;
+        let index = Index::from_path(index_path)?;
+        let header = Reader::new(bgzf::Reader::new(File::open(path)?))?
+            .header()
+            .clone();
+
+        Ok(Self::new(path, header, index))
+    }
+
+    /// Plans the file at `path` with an already loaded header and index.
+    pub fn new<P: AsRef<Path>>(path: P, header: Header, index: Index) -> Self {
+        let mut partitions: Vec<String> = header
+            .contigs
+            .iter()
+            .filter(|c| index.reference_id(&c.id).is_some())
+            .map(|c| c.id.clone())
+            .collect();
+        for name in &index.names {
+            if !partitions.contains(name) {
+                partitions.push(name.clone());
+            }
+        }
+
+        Self {
+            path: path.as_ref().to_path_buf(),
+            header,
+            index,
+            partitions,
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Contig of each partition, in plan order.
+    pub fn partitions(&self) -> &[String] {
+        &self.partitions
+    }
+
+    /// Opens the records of partition `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of range.
+    pub fn records(&self, i: usize) -> Result<RegionQuery<File>> {
+        let mut regions = RegionSet::new();
+        regions.insert(&self.partitions[i], 1, u64::MAX);
+        let reader = Reader::new(bgzf::Reader::new(File::open(&self.path)?))?;
+
+        Ok(RegionQuery::new(reader, &self.index, regions))
+    }
+
+    /// Calls `task` with the contig and records of every partition, and
+    /// merges the results in plan order.
+    ///
+    /// With the `rayon` feature partitions run on the rayon thread pool. The
+    /// first error, in plan order, is returned.
+    pub fn run<T, F>(&self, task: F) -> Result<T>
+    where
+        T: Mergeable + Send,
+        F: Fn(&str, RegionQuery<File>) -> Result<T> + Sync,
+    {
+        let run = |i: usize| task(&self.partitions[i], self.records(i)?);
+
+        #[cfg(feature = "rayon")]
+        let results: Vec<Result<T>> = (0..self.partitions.len())
+            .into_par_iter()
+            .map(run)
+            .collect();
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<Result<T>> = (0..self.partitions.len()).map(run).collect();
+
+        let mut merged = T::default();
+        for result in results {
+            merged.merge(result?);
+        }
+
+        Ok(merged)
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::index::tests::tbi_bytes;
+    use crate::index::Chunk;
+    use std::io::Write;
+
+    pub(crate) const VCF: &str = "##fileformat=VCFv4.3\n\
+        ##contig=<ID=2>\n\
+        ##contig=<ID=1>\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t10\t.\tA\tT\t.\t.\t.\n\
+        1\t20\t.\tA\tT\t.\t.\t.\n\
+        2\t5\t.\tA\tT\t.\t.\t.\n\
+        3\t7\t.\tA\tT\t.\t.\t.\n";
+
+    /// Writes `VCF` and a TBI index with one chunk per contig.
+    pub(crate) fn write_indexed(path: &Path) {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(VCF.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = Reader::new(bgzf::Reader::new(std::io::Cursor::new(&bytes))).unwrap();
+        // Start of the records, then the end of 1:10, 1:20, 2:5 and 3:7.
+        let mut offsets = vec![reader.voffset()];
+        while let Some(record) = reader.next() {
+            record.unwrap();
+            offsets.push(reader.voffset());
+        }
+        let chunks = [
+            Chunk {
+                start: offsets[0],
+                end: offsets[2],
+            },
+            Chunk {
+                start: offsets[2],
+                end: offsets[3],
+            },
+            Chunk {
+                start: offsets[3],
+                end: offsets[4],
+            },
+        ];
+
+        std::fs::write(path, &bytes).unwrap();
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".tbi");
+        std::fs::write(index_path, tbi_bytes(&["1", "2", "3"], &chunks)).unwrap();
+    }
+
+    #[test]
+    fn test_execution_plan_1() {
+        let path =
+            std::env::temp_dir().join(format!("vcf-lib-partition-{}.vcf.gz", std::process::id()));
+        write_indexed(&path);
+
+        let plan = ExecutionPlan::for_path(&path).unwrap();
+        assert_eq!(plan.partitions(), ["2", "1", "3"]);
+
+        let positions: Vec<String> = plan
+            .run(|contig, records| {
+                records
+                    .map(|r| r.map(|r| format!("{}:{}", contig, r.pos)))
+                    .collect()
+            })
+            .unwrap();
+        let summary = plan
+            .run(|_, records| {
+                let mut summary = RunSummary::new("count");
+                for record in records {
+                    record?;
+                    summary.records_in += 1;
+                }
+                Ok(summary)
+            })
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        let mut index_path = path.into_os_string();
+        index_path.push(".tbi");
+        std::fs::remove_file(index_path).unwrap();
+
+        assert_eq!(positions, vec!["2:5", "1:10", "1:20", "3:7"]);
+        assert_eq!(
+            (summary.operation.as_str(), summary.records_in),
+            ("count", 4)
+        );
+        assert!(ExecutionPlan::for_path("missing.vcf.gz").is_err());
+    }
+}
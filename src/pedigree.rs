@@ -0,0 +1,349 @@
+//! Pedigrees and Mendelian inheritance checks.
+//!
+//! A [`Pedigree`] is read from a PED or PLINK FAM file. [`MendelChecker`]
+//! resolves its trios (and duos, when only one parent was sequenced) against
+//! the samples of a header, and checks the genotypes of every record for
+//! alleles a child cannot have inherited. Quads are checked as one trio per
+//! child.
+
+use crate::errors::{Error, Result};
+use crate::genotype::Genotype;
+use crate::header::Header;
+use crate::record::Record;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sex {
+    Male,
+    Female,
+    #[default]
+    Unknown,
+}
+
+/// One line of a PED file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Individual {
+    pub family: String,
+    pub id: String,
+    /// `None` for founders, written as `0`.
+    pub father: Option<String>,
+    pub mother: Option<String>,
+    pub sex: Sex,
+    /// The phenotype column as written, e.g. `2` for affected.
+    pub phenotype: String,
+}
+
+/// Individuals of one or more families.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pedigree {
+    individuals: Vec<Individual>,
+    ids: HashMap<String, usize>,
+}
+
+impl Pedigree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the first six whitespace-separated columns of a PED or FAM file.
+    ///
+    /// `#` lines are skipped; further columns, such as PED genotypes, are
+    /// ignored.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut pedigree = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 6 {
+                Err(Error::PedigreeFormatError(line.to_string()))?
+            }
+            let parent = |id: &str| (id != "0").then(|| id.to_string());
+
+            pedigree.insert(Individual {
+                family: columns[0].to_string(),
+                id: columns[1].to_string(),
+                father: parent(columns[2]),
+                mother: parent(columns[3]),
+                sex: match columns[4] {
+                    "1" => Sex::Male,
+                    "2" => Sex::Female,
+                    _ => Sex::Unknown,
+                },
+                phenotype: columns[5].to_string(),
+            })?;
+        }
+
+        Ok(pedigree)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Adds an individual; fails if the ID is already taken.
+    pub fn insert(&mut self, individual: Individual) -> Result<()> {
+        if self.ids.contains_key(&individual.id) {
+            Err(Error::PedigreeFormatError(format!(
+                "duplicate individual {}",
+                individual.id
+            )))?
+        }
+
+        self.ids
+            .insert(individual.id.clone(), self.individuals.len());
+        self.individuals.push(individual);
+
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Individual> {
+        self.ids.get(id).map(|&i| &self.individuals[i])
+    }
+
+    pub fn individuals(&self) -> &[Individual] {
+        &self.individuals
+    }
+
+    /// Children among `samples` with at least one parent among `samples`,
+    /// as sample indices.
+    pub fn trios(&self, samples: &[String]) -> Vec<Trio> {
+        let index: HashMap<&str, usize> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.as_str(), i))
+            .collect();
+        let find = |id: &Option<String>| id.as_deref().and_then(|id| index.get(id).copied());
+
+        self.individuals
+            .iter()
+            .filter_map(|individual| {
+                let child = *index.get(individual.id.as_str())?;
+                let (father, mother) = (find(&individual.father), find(&individual.mother));
+
+                (father.is_some() || mother.is_some()).then_some(Trio {
+                    child,
+                    father,
+                    mother,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Sample indices of a child and its sequenced parents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trio {
+    pub child: usize,
+    pub father: Option<usize>,
+    pub mother: Option<usize>,
+}
+
+/// Result of checking one trio at one record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MendelStatus {
+    /// The child's alleles can be inherited from its parents.
+    Consistent,
+    /// The child has an allele it cannot have inherited.
+    Inconsistent,
+    /// An inconsistency where both parents are homozygous REF and the child
+    /// carries an ALT allele: a de novo candidate.
+    DeNovo,
+    /// Some genotype is missing, partially called or neither haploid nor
+    /// diploid.
+    Unknown,
+}
+
+impl MendelStatus {
+    /// Returns `true` for [`MendelStatus::Inconsistent`] and [`MendelStatus::DeNovo`].
+    pub fn is_error(&self) -> bool {
+        matches!(self, MendelStatus::Inconsistent | MendelStatus::DeNovo)
+    }
+}
+
+/// Checks the trios of a pedigree record by record.
+pub struct MendelChecker {
+    trios: Vec<Trio>,
+}
+
+impl MendelChecker {
+    pub fn new(pedigree: &Pedigree, header: &Header) -> Self {
+        Self {
+            trios: pedigree.trios(&header.samples),
+        }
+    }
+
+    pub fn trios(&self) -> &[Trio] {
+        &self.trios
+    }
+
+    /// Status of each trio at `record`, in the order of [`MendelChecker::trios`].
+    pub fn check(&self, record: &Record) -> Result<Vec<MendelStatus>> {
+        self.trios
+            .iter()
+            .map(|trio| {
+                // A sequenced parent without GT makes the trio unknown.
+                let parent = |i: Option<usize>| -> Result<Option<Option<Genotype>>> {
+                    Ok(match i {
+                        Some(i) => record.genotype(i)?.map(Some),
+                        None => Some(None),
+                    })
+                };
+                let (Some(child), Some(father), Some(mother)) = (
+                    record.genotype(trio.child)?,
+                    parent(trio.father)?,
+                    parent(trio.mother)?,
+                ) else {
+                    return Ok(MendelStatus::Unknown);
+                };
+
+                Ok(mendel_status(&child, father.as_ref(), mother.as_ref()))
+            })
+            .collect()
+    }
+}
+
+/// Checks that `child` can inherit one allele from each given parent; a
+/// haploid child inherits its allele from either. Without any parent the
+/// status is unknown.
+pub fn mendel_status(
+    child: &Genotype,
+    father: Option<&Genotype>,
+    mother: Option<&Genotype>,
+) -> MendelStatus {
+    if father.is_none() && mother.is_none() {
+        return MendelStatus::Unknown;
+    }
+    let called = |g: &Genotype| g.alleles.iter().copied().collect::<Option<Vec<usize>>>();
+    let (Some(alleles), Some(father), Some(mother)) = (
+        called(child),
+        father.map(called).unwrap_or(Some(Vec::new())),
+        mother.map(called).unwrap_or(Some(Vec::new())),
+    ) else {
+        return MendelStatus::Unknown;
+    };
+    // An absent parent may have passed on any allele.
+    let from = |parent: &[usize], allele: usize| parent.is_empty() || parent.contains(&allele);
+
+    let consistent = match alleles[..] {
+        [a] => {
+            (!father.is_empty() && father.contains(&a))
+                || (!mother.is_empty() && mother.contains(&a))
+        }
+        [a, b] => (from(&father, a) && from(&mother, b)) || (from(&father, b) && from(&mother, a)),
+        _ => return MendelStatus::Unknown,
+    };
+
+    if consistent {
+        MendelStatus::Consistent
+    } else if !father.is_empty()
+        && !mother.is_empty()
+        && father.iter().chain(&mother).all(|&a| a == 0)
+    {
+        MendelStatus::DeNovo
+    } else {
+        MendelStatus::Inconsistent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PED: &str = "# family child father mother sex phenotype\n\
+        F1 kid dad mom 1 2\n\
+        F1 dad 0 0 1 1\n\
+        F1 mom 0 0 2 1\n\
+        F1 sib dad mom 2 1\n\
+        F2 lone 0 absent 0 -9\n";
+
+    fn gt(s: &str) -> Genotype {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_pedigree_1() {
+        let pedigree = Pedigree::from_reader(PED.as_bytes()).unwrap();
+
+        assert_eq!(pedigree.individuals().len(), 5);
+        let kid = pedigree.get("kid").unwrap();
+        assert_eq!(kid.father.as_deref(), Some("dad"));
+        assert_eq!(kid.sex, Sex::Male);
+        assert_eq!(pedigree.get("lone").unwrap().father, None);
+
+        let samples: Vec<String> = ["mom", "kid", "sib", "lone"].map(String::from).to_vec();
+        assert_eq!(
+            pedigree.trios(&samples),
+            vec![
+                Trio {
+                    child: 1,
+                    father: None,
+                    mother: Some(0)
+                },
+                Trio {
+                    child: 2,
+                    father: None,
+                    mother: Some(0)
+                },
+            ]
+        );
+
+        assert!(Pedigree::from_reader("F1 kid dad\n".as_bytes()).is_err());
+        assert!(Pedigree::from_reader("F1 a 0 0 1 1\nF1 a 0 0 1 1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_mendel_status_1() {
+        let status = |c, f, m| mendel_status(&gt(c), Some(&gt(f)), Some(&gt(m)));
+
+        assert_eq!(status("0/1", "0/1", "0/0"), MendelStatus::Consistent);
+        assert_eq!(status("1|0", "0/0", "1/1"), MendelStatus::Consistent);
+        assert_eq!(status("1/1", "0/1", "0/0"), MendelStatus::Inconsistent);
+        assert_eq!(status("0/1", "0/0", "0/0"), MendelStatus::DeNovo);
+        assert_eq!(status("0/1", "./.", "0/0"), MendelStatus::Unknown);
+        assert_eq!(status("1", "0/0", "0/1"), MendelStatus::Consistent);
+        assert_eq!(status("2", "0/0", "0/1"), MendelStatus::Inconsistent);
+        assert!(status("0/1", "0/0", "0/0").is_error());
+
+        assert_eq!(
+            mendel_status(&gt("1/1"), None, Some(&gt("0/1"))),
+            MendelStatus::Consistent
+        );
+        assert_eq!(
+            mendel_status(&gt("1/1"), None, Some(&gt("0/0"))),
+            MendelStatus::Inconsistent
+        );
+    }
+
+    #[test]
+    fn test_mendel_checker_1() {
+        let pedigree = Pedigree::from_reader(PED.as_bytes()).unwrap();
+        let mut header = Header::new("VCFv4.3");
+        header.samples = ["kid", "dad", "mom", "sib"].map(String::from).to_vec();
+        let checker = MendelChecker::new(&pedigree, &header);
+        assert_eq!(checker.trios().len(), 2);
+
+        let record: Record = "1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\t0/0\t0/0\t./."
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            checker.check(&record).unwrap(),
+            vec![MendelStatus::DeNovo, MendelStatus::Unknown]
+        );
+    }
+}
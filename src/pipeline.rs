@@ -0,0 +1,412 @@
+//! Whole-stream operations reporting a [`RunSummary`].
+//!
+//! Records that fail to parse or to process are skipped and reported as
+//! warnings; I/O errors abort the operation.
+
+use crate::cache::{AnnotationCache, CacheKey};
+use crate::compliance::Compliance;
+use crate::decompose::{decompose_mnv, MnvPolicy};
+use crate::errors::{Error, ErrorCategory, Result};
+use crate::header::Header;
+use crate::record::{normalize_with, normalize_with_reference, NormalizeOptions, Record};
+use crate::reference::ReferenceSequence;
+use crate::summary::{RunSummary, Timer};
+use crate::validation::is_symbolic;
+use crate::writer::Writer;
+use std::io::Write;
+
+/// Normalizes the biallelic records of a stream and writes them after `header`.
+///
+/// With a `reference`, indels are also shifted in `options.direction`.
+/// Multiallelic records and symbolic alleles are written unchanged.
+pub fn normalize_stream<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    reference: Option<&dyn ReferenceSequence>,
+    options: &NormalizeOptions,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("normalize");
+
+    writer.write_header(header)?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        let mut record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        match normalize_record(&mut record, reference, options) {
+            Ok(true) => summary.modified += 1,
+            Ok(false) => {}
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        }
+
+        writer.write_record(&record)?;
+        summary.records_out += 1;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+/// Sets the INFO `key` of every record from a per-ALT annotation and writes
+/// the records after `header`.
+///
+/// `annotate` is called once per distinct normalized variant; results are
+/// kept in `cache`. Values of multiallelic records are joined with `,`, and
+/// symbolic alleles get `.`. Empty annotations are not stored in INFO.
+pub fn annotate<I, W, C, F>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    key: &str,
+    cache: &mut C,
+    mut annotate: F,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+    C: AnnotationCache<String>,
+    F: FnMut(&CacheKey) -> Result<String>,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("annotate");
+
+    writer.write_header(header)?;
+
+    'records: for record in records {
+        summary.records_in += 1;
+
+        let mut record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        let mut values = Vec::with_capacity(record.alternates.len());
+        for alt in &record.alternates {
+            if is_symbolic(alt) {
+                values.push(".".to_string());
+                continue;
+            }
+
+            let value = CacheKey::new(&record.chrom, record.pos, &record.reference, alt)
+                .and_then(|k| cache.get_or_insert_with(&k, || annotate(&k)));
+            match value {
+                Ok(value) => values.push(value),
+                Err(e) => {
+                    skip(&mut summary, e)?;
+                    continue 'records;
+                }
+            }
+        }
+
+        if values.iter().any(|v| !v.is_empty() && v != ".") {
+            let values: Vec<&str> = values
+                .iter()
+                .map(|v| if v.is_empty() { "." } else { v.as_str() })
+                .collect();
+            record.set_info(key, Some(values.join(",")));
+            summary.modified += 1;
+        }
+
+        writer.write_record(&record)?;
+        summary.records_out += 1;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+/// Splits the MNVs of a stream according to `policy`, as by
+/// [`decompose_mnv`], and writes the resulting records after `header`.
+///
+/// `modified` counts the input records that were split or normalized.
+///
+/// Output is not re-sorted: the parts of a split MNV keep their place in the
+/// stream, so they may come after records starting within the MNV. Sort the
+/// output, for example with `ExternalSorter`, when order matters.
+pub fn decompose_stream<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    policy: MnvPolicy,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("decompose");
+
+    writer.write_header(header)?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        let parts = match record.and_then(|r| Ok((decompose_mnv(&r, policy)?, r))) {
+            Ok((parts, record)) => {
+                if parts.len() != 1 || parts[0] != record {
+                    summary.modified += 1;
+                }
+                parts
+            }
+            Err(e) => {
+                skip(&mut summary, e)?;
+                continue;
+            }
+        };
+
+        for part in &parts {
+            writer.write_record(part)?;
+            summary.records_out += 1;
+        }
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+/// Checks every record against the rules of the version declared by `header`.
+///
+/// `records_out` counts the valid records and `skipped` the invalid ones.
+pub fn validate<I>(records: I, header: &Header) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("validate");
+    let compliance = Compliance::for_header(header)?;
+
+    for record in records {
+        summary.records_in += 1;
+
+        match record.and_then(|r| compliance.check_record(&r)) {
+            Ok(()) => summary.records_out += 1,
+            Err(e) => skip(&mut summary, e)?,
+        }
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+/// Normalizes a biallelic record in place, like [`normalize_stream`];
+/// returns `true` if the record changed.
+pub(crate) fn normalize_record(
+    record: &mut Record,
+    reference: Option<&dyn ReferenceSequence>,
+    options: &NormalizeOptions,
+) -> Result<bool> {
+    if record.alternates.len() != 1 || is_symbolic(&record.alternates[0]) {
+        return Ok(false);
+    }
+
+    let (position, bases, alternate) = match reference {
+        Some(sequence) => normalize_with_reference(
+            &record.chrom,
+            record.pos,
+            &record.reference,
+            &record.alternates[0],
+            sequence,
+            options,
+        )
+        .map(|v| (v.position, v.reference, v.alternate))?,
+        None => normalize_with(
+            record.pos,
+            &record.reference,
+            &record.alternates[0],
+            options,
+        )
+        .map(|(p, r, a)| (p, r.into_owned(), a.into_owned()))?,
+    };
+
+    if position == record.pos && bases == record.reference && alternate == record.alternates[0] {
+        return Ok(false);
+    }
+
+    record.pos = position;
+    record.reference = bases;
+    record.alternates[0] = alternate;
+
+    Ok(true)
+}
+
+/// Records a skipped record, propagating I/O errors.
+pub(crate) fn skip(summary: &mut RunSummary, error: Error) -> Result<()> {
+    if error.category() == ErrorCategory::Io {
+        return Err(error);
+    }
+
+    summary.skip(&error);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::LruCache;
+    use crate::reader::Reader;
+    use crate::reference::MemoryReference;
+    use crate::VariantType;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t4\t.\tAA\tA\t.\t.\t.\n\
+        1\t5\t.\tG\tT,GA\t.\t.\t.\n\
+        1\tx\t.\tG\tT\t.\t.\t.\n\
+        1\t7\t.\tG\tR\t.\t.\t.\n\
+        1\t8\t.\tAT\tGT\t.\t.\t.\n";
+
+    fn run<F>(f: F) -> (RunSummary, String)
+    where
+        F: FnOnce(Reader<&[u8]>, &Header, &mut Writer<Vec<u8>>) -> Result<RunSummary>,
+    {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut writer = Writer::new(Vec::new());
+        let summary = f(reader, &header, &mut writer).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        (summary, text)
+    }
+
+    fn body(text: &str) -> Vec<&str> {
+        text.lines().filter(|l| !l.starts_with('#')).collect()
+    }
+
+    #[test]
+    fn test_normalize_stream_1() {
+        let (summary, text) = run(|reader, header, writer| {
+            normalize_stream(reader, header, writer, None, &NormalizeOptions::default())
+        });
+
+        assert_eq!(
+            body(&text),
+            vec![
+                "1\t4\t.\tAA\tA\t.\t.\t.",
+                "1\t5\t.\tG\tT,GA\t.\t.\t.",
+                "1\t7\t.\tG\tR\t.\t.\t.",
+                "1\t8\t.\tA\tG\t.\t.\t."
+            ]
+        );
+        assert_eq!(summary.records_in, 5);
+        assert_eq!(summary.records_out, 4);
+        assert_eq!(summary.modified, 1);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_stream_2() {
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "ACAAAGTATG");
+
+        let (summary, text) = run(|reader, header, writer| {
+            normalize_stream(
+                reader,
+                header,
+                writer,
+                Some(&reference),
+                &NormalizeOptions::default(),
+            )
+        });
+
+        // The deletion is shifted to the start of the A run; REF of 1:7 does
+        // not match the genome.
+        assert_eq!(body(&text)[0], "1\t2\t.\tCA\tC\t.\t.\t.");
+        assert_eq!(summary.modified, 2);
+        assert_eq!(summary.skipped, 2);
+    }
+
+    #[test]
+    fn test_annotate_1() {
+        let mut cache = LruCache::new(16);
+        let mut calls = 0;
+
+        let (summary, text) = run(|reader, header, writer| {
+            annotate(reader, header, writer, "CSQ", &mut cache, |key| {
+                calls += 1;
+                Ok(match key.variant.variant_type() {
+                    Some(VariantType::SNV) => "snv".to_string(),
+                    _ => String::new(),
+                })
+            })
+        });
+
+        assert_eq!(
+            body(&text),
+            vec![
+                "1\t4\t.\tAA\tA\t.\t.\t.",
+                "1\t5\t.\tG\tT,GA\t.\t.\tCSQ=snv,.",
+                "1\t7\t.\tG\tR\t.\t.\tCSQ=snv",
+                "1\t8\t.\tAT\tGT\t.\t.\tCSQ=snv"
+            ]
+        );
+        assert_eq!(calls, 5);
+        assert_eq!(summary.modified, 3);
+        assert_eq!(summary.skipped, 1);
+    }
+
+    #[test]
+    fn test_decompose_stream_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t4\t.\tAGT\tCGA\t.\t.\tDP=3\n\
+            1\t5\t.\tG\tT,GA\t.\t.\t.\n\
+            1\tx\t.\tG\tT\t.\t.\t.\n\
+            1\t8\t.\tAT\tGT\t.\t.\t.\n";
+        let reader = Reader::new(vcf.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut writer = Writer::new(Vec::new());
+        let summary = decompose_stream(reader, &header, &mut writer, MnvPolicy::Split).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert_eq!(
+            body(&text),
+            vec![
+                "1\t4\t.\tA\tC\t.\t.\tDP=3",
+                "1\t6\t.\tT\tA\t.\t.\tDP=3",
+                "1\t5\t.\tG\tT,GA\t.\t.\t.",
+                "1\t8\t.\tA\tG\t.\t.\t."
+            ]
+        );
+        assert_eq!(summary.records_in, 4);
+        assert_eq!(summary.records_out, 4);
+        assert_eq!(summary.modified, 2);
+        assert_eq!(summary.skipped, 1);
+    }
+
+    #[test]
+    fn test_validate_1() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let summary = validate(reader, &header).unwrap();
+
+        assert_eq!(summary.records_in, 5);
+        assert_eq!(summary.records_out, 3);
+        assert_eq!(summary.skipped, 2);
+        assert!(summary.to_json().starts_with("{\"operation\":\"validate\""));
+    }
+}
@@ -0,0 +1,274 @@
+//! Harmonization of genotype encodings across call sets.
+//!
+//! Callers disagree on how to write half-calls (`./1`), haploid calls on
+//! autosomes, and calls on sex chromosomes. A [`PloidyNormalizer`] rewrites
+//! the `GT` of every sample to the ploidy its contig and sex imply, with a
+//! [`GenotypePolicy`] for each kind of call that cannot be kept as it is.
+
+use crate::errors::Result;
+use crate::genotype::Genotype;
+use crate::header::Header;
+use crate::pedigree::{Pedigree, Sex};
+use crate::record::Record;
+use crate::regions::RegionSet;
+
+/// What to do with a genotype of an unexpected shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GenotypePolicy {
+    /// Leave the genotype as it is.
+    #[default]
+    Keep,
+    /// Replace it with a missing call of the expected ploidy.
+    ToMissing,
+    /// Replace it with a homozygous call of its called allele.
+    ToHom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PloidyOptions {
+    /// Half-calls such as `./1`; [`GenotypePolicy::ToHom`] makes `1/1`.
+    pub half_calls: GenotypePolicy,
+    /// Haploid calls where diploid ones are expected;
+    /// [`GenotypePolicy::ToHom`] makes `1` into `1/1`.
+    pub haploid_calls: GenotypePolicy,
+    /// Heterozygous calls on haploid contigs and of males outside the
+    /// pseudo-autosomal regions of X and Y, and any call of females on Y.
+    /// [`GenotypePolicy::ToHom`] acts like [`GenotypePolicy::ToMissing`].
+    pub sex_conflicts: GenotypePolicy,
+    pub x_contigs: Vec<String>,
+    pub y_contigs: Vec<String>,
+    /// Contigs haploid in every sample, such as the mitochondrial genome.
+    pub haploid_contigs: Vec<String>,
+    /// Pseudo-autosomal regions, diploid in males, e.g. `X:10001-2781479`
+    /// for PAR1 of GRCh38.
+    pub par: RegionSet,
+}
+
+impl Default for PloidyOptions {
+    fn default() -> Self {
+        Self {
+            half_calls: GenotypePolicy::Keep,
+            haploid_calls: GenotypePolicy::Keep,
+            sex_conflicts: GenotypePolicy::Keep,
+            x_contigs: vec!["X".to_string(), "chrX".to_string()],
+            y_contigs: vec!["Y".to_string(), "chrY".to_string()],
+            haploid_contigs: ["MT", "chrM", "M"].map(String::from).to_vec(),
+            par: RegionSet::new(),
+        }
+    }
+}
+
+/// Rewrites genotypes to the ploidy expected for each sample.
+///
+/// Without known sexes only half-calls, haploid autosomal calls and calls
+/// on haploid contigs are rewritten. Diploid homozygous calls on haploid
+/// contigs, and for males outside the pseudo-autosomal regions of X and Y,
+/// become haploid.
+pub struct PloidyNormalizer {
+    options: PloidyOptions,
+    sexes: Vec<Sex>,
+}
+
+impl PloidyNormalizer {
+    pub fn new(header: &Header, options: PloidyOptions) -> Self {
+        Self {
+            options,
+            sexes: vec![Sex::Unknown; header.samples.len()],
+        }
+    }
+
+    /// Takes the sex of each sample from `pedigree`.
+    pub fn with_pedigree(header: &Header, pedigree: &Pedigree, options: PloidyOptions) -> Self {
+        Self {
+            options,
+            sexes: header
+                .samples
+                .iter()
+                .map(|s| pedigree.get(s).map_or(Sex::Unknown, |i| i.sex))
+                .collect(),
+        }
+    }
+
+    /// Normalizes the `GT` of every sample; returns the number changed.
+    pub fn normalize(&self, record: &mut Record) -> Result<usize> {
+        if record.format_index("GT").is_none() {
+            return Ok(0);
+        }
+
+        let mut changed = 0;
+        for sample in 0..record.samples.len() {
+            let Some(genotype) = record.genotype(sample)? else {
+                continue;
+            };
+            let sex = self.sexes.get(sample).copied().unwrap_or_default();
+            let normalized = self.normalize_genotype(&genotype, self.ploidy(record, sex));
+
+            if normalized != genotype {
+                record.set_genotype(sample, &normalized)?;
+                changed += 1;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// Normalizes one genotype given the expected ploidy, if known.
+    pub fn normalize_genotype(&self, genotype: &Genotype, ploidy: Option<usize>) -> Genotype {
+        let options = &self.options;
+        let called = genotype.alleles.iter().flatten().next().copied();
+
+        let genotype = match (genotype.is_partially_missing(), options.half_calls) {
+            (true, GenotypePolicy::ToMissing) => Genotype::missing(genotype.ploidy()),
+            (true, GenotypePolicy::ToHom) => hom(called, genotype.ploidy()),
+            _ => genotype.clone(),
+        };
+
+        match (ploidy, genotype.ploidy()) {
+            (Some(0), _)
+                if genotype.is_missing() || options.sex_conflicts != GenotypePolicy::Keep =>
+            {
+                Genotype::missing(1)
+            }
+            (Some(1), 2..) => {
+                let first = genotype.alleles[0];
+                if genotype.alleles.iter().all(|&a| a == first) {
+                    Genotype::unphased(&[first])
+                } else if options.sex_conflicts == GenotypePolicy::Keep {
+                    genotype
+                } else {
+                    Genotype::missing(1)
+                }
+            }
+            (Some(2), 1) => match options.haploid_calls {
+                GenotypePolicy::Keep => genotype,
+                GenotypePolicy::ToMissing => Genotype::missing(2),
+                GenotypePolicy::ToHom => hom(genotype.alleles[0], 2),
+            },
+            _ => genotype,
+        }
+    }
+
+    /// Expected ploidy at `record`; `Some(0)` for females on Y.
+    fn ploidy(&self, record: &Record, sex: Sex) -> Option<usize> {
+        let options = &self.options;
+        let is = |contigs: &[String]| contigs.contains(&record.chrom);
+
+        if is(&options.haploid_contigs) {
+            return Some(1);
+        }
+        let sex_chromosome = is(&options.x_contigs) || is(&options.y_contigs);
+        if !sex_chromosome || options.par.contains(&record.chrom, record.pos) {
+            return Some(2);
+        }
+
+        match (sex, is(&options.y_contigs)) {
+            (Sex::Male, _) => Some(1),
+            (Sex::Female, false) => Some(2),
+            (Sex::Female, true) => Some(0),
+            (Sex::Unknown, _) => None,
+        }
+    }
+}
+
+fn hom(allele: Option<usize>, ploidy: usize) -> Genotype {
+    Genotype::unphased(&vec![allele; ploidy.max(1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pedigree::Individual;
+
+    fn gt(s: &str) -> Genotype {
+        s.parse().unwrap()
+    }
+
+    fn normalizer(options: PloidyOptions) -> PloidyNormalizer {
+        let mut header = Header::new("VCFv4.3");
+        header.samples = vec!["M".to_string(), "F".to_string()];
+
+        let mut pedigree = Pedigree::new();
+        for (id, sex) in [("M", Sex::Male), ("F", Sex::Female)] {
+            pedigree
+                .insert(Individual {
+                    family: "F1".to_string(),
+                    id: id.to_string(),
+                    father: None,
+                    mother: None,
+                    sex,
+                    phenotype: "0".to_string(),
+                })
+                .unwrap();
+        }
+
+        PloidyNormalizer::with_pedigree(&header, &pedigree, options)
+    }
+
+    #[test]
+    fn test_normalize_genotype_1() {
+        let keep = normalizer(PloidyOptions::default());
+        assert_eq!(keep.normalize_genotype(&gt("./1"), Some(2)), gt("./1"));
+        assert_eq!(keep.normalize_genotype(&gt("1"), Some(2)), gt("1"));
+        assert_eq!(keep.normalize_genotype(&gt("1/1"), Some(1)), gt("1"));
+        assert_eq!(keep.normalize_genotype(&gt("0/1"), Some(1)), gt("0/1"));
+
+        let hom = normalizer(PloidyOptions {
+            half_calls: GenotypePolicy::ToHom,
+            haploid_calls: GenotypePolicy::ToHom,
+            ..PloidyOptions::default()
+        });
+        assert_eq!(hom.normalize_genotype(&gt(".|1"), Some(2)), gt("1/1"));
+        assert_eq!(hom.normalize_genotype(&gt("1"), Some(2)), gt("1/1"));
+        assert_eq!(hom.normalize_genotype(&gt("./1"), Some(1)), gt("1"));
+
+        let missing = normalizer(PloidyOptions {
+            half_calls: GenotypePolicy::ToMissing,
+            haploid_calls: GenotypePolicy::ToMissing,
+            sex_conflicts: GenotypePolicy::ToMissing,
+            ..PloidyOptions::default()
+        });
+        assert_eq!(missing.normalize_genotype(&gt("./1"), Some(2)), gt("./."));
+        assert_eq!(missing.normalize_genotype(&gt("1"), Some(2)), gt("./."));
+        assert_eq!(missing.normalize_genotype(&gt("0/1"), Some(1)), gt("."));
+        assert_eq!(missing.normalize_genotype(&gt("0/1"), Some(0)), gt("."));
+        assert_eq!(missing.normalize_genotype(&gt("0/1"), None), gt("0/1"));
+    }
+
+    #[test]
+    fn test_normalize_1() {
+        let mut par = RegionSet::new();
+        par.insert("X", 1, 100);
+        let normalizer = normalizer(PloidyOptions {
+            sex_conflicts: GenotypePolicy::ToMissing,
+            par,
+            ..PloidyOptions::default()
+        });
+
+        let mut record: Record = "X\t500\t.\tA\tT\t.\t.\t.\tGT\t1/1\t0/1".parse().unwrap();
+        assert_eq!(normalizer.normalize(&mut record).unwrap(), 1);
+        assert_eq!(record.to_string(), "X\t500\t.\tA\tT\t.\t.\t.\tGT\t1\t0/1");
+
+        let mut record: Record = "X\t50\t.\tA\tT\t.\t.\t.\tGT\t1/1\t0/1".parse().unwrap();
+        assert_eq!(normalizer.normalize(&mut record).unwrap(), 0);
+
+        let mut record: Record = "chrY\t50\t.\tA\tT\t.\t.\t.\tGT\t0/1\t1".parse().unwrap();
+        assert_eq!(normalizer.normalize(&mut record).unwrap(), 2);
+        assert_eq!(record.to_string(), "chrY\t50\t.\tA\tT\t.\t.\t.\tGT\t.\t.");
+    }
+
+    #[test]
+    fn test_normalize_2() {
+        let normalizer = normalizer(PloidyOptions {
+            haploid_calls: GenotypePolicy::ToMissing,
+            ..PloidyOptions::default()
+        });
+
+        let mut record: Record = "MT\t50\t.\tA\tT\t.\t.\t.\tGT\t1\t1/1".parse().unwrap();
+        assert_eq!(normalizer.normalize(&mut record).unwrap(), 1);
+        assert_eq!(record.to_string(), "MT\t50\t.\tA\tT\t.\t.\t.\tGT\t1\t1");
+
+        let mut record: Record = "1\t50\t.\tA\tT\t.\t.\t.\tGT\t1\t1/1".parse().unwrap();
+        assert_eq!(normalizer.normalize(&mut record).unwrap(), 1);
+        assert_eq!(record.to_string(), "1\t50\t.\tA\tT\t.\t.\t.\tGT\t./.\t1/1");
+    }
+}
@@ -0,0 +1,570 @@
+//! Ready-made pipelines for common workflows.
+//!
+//! Each preset chains existing steps with defaults suited to its workflow,
+//! and takes an options struct whose fields can all be changed. Presets
+//! write their records after `header`, extended with the definitions of the
+//! tags and filters they add, and report a [`RunSummary`] together with the
+//! [`Stats`] of the written records.
+//!
+//! Normalization can move records left. [`germline_postprocess`] puts
+//! records moved by up to [`GermlineOptions::sort_window`] bases back in
+//! order; other presets never sort, so sort their output if a sorted file
+//! is needed.
+
+use crate::decompose::{decompose_mnv, MnvPolicy};
+use crate::dedup::{Deduplicator, DuplicatePolicy};
+use crate::errors::Result;
+use crate::expression::Expression;
+use crate::header::{FilterDefinition, Header};
+use crate::order::WindowSorter;
+use crate::pipeline::{normalize_record, skip};
+use crate::record::{NormalizeOptions, Record};
+use crate::reference::ReferenceSequence;
+use crate::stats::{SiteStats, Stats};
+use crate::summary::{RunSummary, Timer};
+use crate::writer::Writer;
+use std::cell::Cell;
+use std::io::Write;
+
+/// Result of a preset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetReport {
+    /// `modified` counts records changed by normalization or decomposition,
+    /// or failing a soft filter.
+    pub summary: RunSummary,
+    /// Statistics of the written records.
+    pub stats: Stats,
+}
+
+/// A FILTER added to records matching an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftFilter {
+    pub id: String,
+    pub description: String,
+    pub expression: Expression,
+}
+
+impl SoftFilter {
+    pub fn new(id: &str, description: &str, expression: &str) -> Result<Self> {
+        Ok(Self {
+            id: id.to_string(),
+            description: description.to_string(),
+            expression: expression.parse()?,
+        })
+    }
+
+    /// A filter of the defaults below, whose expressions are known to parse.
+    fn preset(id: &str, description: &str, expression: &str) -> Self {
+        Self::new(id, description, expression).unwrap()
+    }
+}
+
+/// Options of [`germline_postprocess`].
+#[derive(Debug, Clone)]
+pub struct GermlineOptions<'a> {
+    pub normalize: NormalizeOptions,
+    /// How MNVs are split before normalization.
+    pub mnv: MnvPolicy<'a>,
+    /// Re-sorts records moved by normalization by up to this many bases,
+    /// so that duplicates moved past other records are still found; `None`
+    /// keeps the order of normalization.
+    pub sort_window: Option<u64>,
+    /// Collapses the duplicates that decomposition and normalization create;
+    /// `None` keeps them.
+    pub duplicates: Option<DuplicatePolicy>,
+    pub filters: Vec<SoftFilter>,
+    /// Leaves out records with a FILTER other than `PASS`, including those
+    /// set by the caller.
+    pub drop_filtered: bool,
+    /// Recomputes AC, AN, AF, F_MISSING and HWE from the genotypes; ignored
+    /// without samples.
+    pub fill_tags: bool,
+}
+
+impl Default for GermlineOptions<'_> {
+    /// Splits MNVs, re-sorts within 1000 bases like `bcftools norm`, keeps
+    /// the first of duplicates and tags records with QUAL below 30 as
+    /// `LowQual`, as GATK does.
+    fn default() -> Self {
+        Self {
+            normalize: NormalizeOptions::default(),
+            mnv: MnvPolicy::Split,
+            sort_window: Some(1000),
+            duplicates: Some(DuplicatePolicy::KeepFirst),
+            filters: vec![SoftFilter::preset(
+                "LowQual",
+                "Variant quality below 30",
+                "QUAL<30",
+            )],
+            drop_filtered: false,
+            fill_tags: true,
+        }
+    }
+}
+
+/// Options of [`somatic_filter`].
+#[derive(Debug, Clone)]
+pub struct SomaticOptions {
+    pub normalize: NormalizeOptions,
+    pub filters: Vec<SoftFilter>,
+    /// Leaves out records with a FILTER other than `PASS`, including those
+    /// set by the caller.
+    pub drop_filtered: bool,
+}
+
+impl SomaticOptions {
+    /// Default filters for the tumor at sample index `tumor`: depth below
+    /// 10, fewer than 3 ALT reads or an allele fraction below 5%.
+    pub fn for_tumor(tumor: usize) -> Self {
+        let filters = [
+            ("LowDepth", "Tumor depth below 10", "FMT/DP[{}]<10"),
+            (
+                "LowAltReads",
+                "Fewer than 3 tumor ALT reads",
+                "FMT/AD[{}:1]<3",
+            ),
+            (
+                "LowVAF",
+                "Tumor allele fraction below 0.05",
+                "FMT/AF[{}]<0.05",
+            ),
+        ];
+
+        Self {
+            normalize: NormalizeOptions::default(),
+            filters: filters
+                .into_iter()
+                .map(|(id, description, expression)| {
+                    let expression = expression.replace("{}", &tumor.to_string());
+                    SoftFilter::preset(id, description, &expression)
+                })
+                .collect(),
+            drop_filtered: true,
+        }
+    }
+}
+
+impl Default for SomaticOptions {
+    /// Defaults for a tumor in the first sample column.
+    fn default() -> Self {
+        Self::for_tumor(0)
+    }
+}
+
+/// Options of [`cohort_qc`].
+#[derive(Debug, Clone)]
+pub struct CohortQcOptions {
+    /// Filters over the records with AC, AN, AF, F_MISSING and HWE filled.
+    pub filters: Vec<SoftFilter>,
+    /// Leaves out records with a FILTER other than `PASS`.
+    pub drop_filtered: bool,
+}
+
+impl Default for CohortQcOptions {
+    /// Tags sites with more than 5% missing genotypes as `LowCallRate`, and
+    /// sites with an HWE p-value below 1e-6 as `HweFail`.
+    fn default() -> Self {
+        Self {
+            filters: vec![
+                SoftFilter::preset(
+                    "LowCallRate",
+                    "More than 5% missing genotypes",
+                    "INFO/F_MISSING>0.05",
+                ),
+                SoftFilter::preset(
+                    "HweFail",
+                    "Hardy-Weinberg exact test p-value below 1e-6",
+                    "INFO/HWE<1e-6",
+                ),
+            ],
+            drop_filtered: false,
+        }
+    }
+}
+
+/// Post-processes the output of a germline caller: splits MNVs, normalizes
+/// biallelic records, re-sorts them, removes the resulting duplicates,
+/// recomputes the allele count tags and applies soft filters.
+pub fn germline_postprocess<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    reference: Option<&dyn ReferenceSequence>,
+    options: &GermlineOptions,
+) -> Result<PresetReport>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("germline_postprocess");
+    let records_in = Cell::new(0);
+    let modified = Cell::new(0);
+
+    let prepared = records.into_iter().flat_map(|record| {
+        records_in.set(records_in.get() + 1);
+
+        let prepare = |record: Record| -> Result<Vec<Record>> {
+            let mut parts = decompose_mnv(&record, options.mnv)?;
+            let mut changed = parts.len() != 1 || parts[0] != record;
+            for part in &mut parts {
+                changed |= normalize_record(part, reference, &options.normalize)?;
+            }
+            if changed {
+                modified.set(modified.get() + 1);
+            }
+            Ok(parts)
+        };
+
+        match record.and_then(prepare) {
+            Ok(parts) => parts.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        }
+    });
+
+    let prepared: Box<dyn Iterator<Item = Result<Record>>> = match options.sort_window {
+        Some(window) => Box::new(WindowSorter::new(prepared, window)),
+        None => Box::new(prepared),
+    };
+    let prepared: Box<dyn Iterator<Item = Result<Record>>> = match options.duplicates {
+        Some(policy) => Box::new(Deduplicator::new(prepared, policy)),
+        None => Box::new(prepared),
+    };
+    let fill_tags = options.fill_tags && !header.samples.is_empty();
+    let stats = write(
+        prepared,
+        header,
+        writer,
+        &options.filters,
+        options.drop_filtered,
+        fill_tags,
+        &mut summary,
+    )?;
+
+    summary.records_in = records_in.get();
+    summary.modified += modified.get();
+    summary.elapsed = started.elapsed();
+
+    Ok(PresetReport { summary, stats })
+}
+
+/// Filters the output of a somatic caller: normalizes biallelic records and
+/// applies soft filters on the tumor sample.
+pub fn somatic_filter<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    reference: Option<&dyn ReferenceSequence>,
+    options: &SomaticOptions,
+) -> Result<PresetReport>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("somatic_filter");
+    let records_in = Cell::new(0);
+    let modified = Cell::new(0);
+
+    let prepared = records.into_iter().map(|record| {
+        records_in.set(records_in.get() + 1);
+
+        let mut record = record?;
+        if normalize_record(&mut record, reference, &options.normalize)? {
+            modified.set(modified.get() + 1);
+        }
+        Ok(record)
+    });
+
+    let stats = write(
+        prepared,
+        header,
+        writer,
+        &options.filters,
+        options.drop_filtered,
+        false,
+        &mut summary,
+    )?;
+
+    summary.records_in = records_in.get();
+    summary.modified += modified.get();
+    summary.elapsed = started.elapsed();
+
+    Ok(PresetReport { summary, stats })
+}
+
+/// Quality control of a multi-sample call set: fills AC, AN, AF, F_MISSING
+/// and HWE and applies soft filters over them.
+pub fn cohort_qc<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    options: &CohortQcOptions,
+) -> Result<PresetReport>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("cohort_qc");
+    let records_in = Cell::new(0);
+
+    let counted = records.into_iter().inspect(|_| {
+        records_in.set(records_in.get() + 1);
+    });
+    let stats = write(
+        counted,
+        header,
+        writer,
+        &options.filters,
+        options.drop_filtered,
+        true,
+        &mut summary,
+    )?;
+
+    summary.records_in = records_in.get();
+    summary.elapsed = started.elapsed();
+
+    Ok(PresetReport { summary, stats })
+}
+
+/// Fills tags, applies `filters` and writes the records, the steps shared by
+/// every preset.
+fn write<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    filters: &[SoftFilter],
+    drop_filtered: bool,
+    fill_tags: bool,
+    summary: &mut RunSummary,
+) -> Result<Stats>
+where
+    I: Iterator<Item = Result<Record>>,
+    W: Write,
+{
+    let mut header = header.clone();
+    if fill_tags {
+        SiteStats::update_header(&mut header);
+    }
+    for filter in filters {
+        if header.filter(&filter.id).is_none() {
+            header
+                .filters
+                .push(FilterDefinition::new(&filter.id, &filter.description));
+        }
+    }
+    writer.write_header(&header)?;
+
+    let mut stats = Stats::from_header(&header);
+    for record in records {
+        let mut record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                skip(summary, e)?;
+                continue;
+            }
+        };
+
+        if fill_tags {
+            SiteStats::from_record(&record).fill_tags(&mut record);
+        }
+        if apply_filters(&mut record, filters) {
+            summary.modified += 1;
+        }
+        if drop_filtered && !record.filters.iter().all(|f| f == "PASS") {
+            continue;
+        }
+
+        stats.add(&record);
+        writer.write_record(&record)?;
+        summary.records_out += 1;
+    }
+
+    Ok(stats)
+}
+
+/// Adds the ID of every filter `record` matches to its FILTER, or sets
+/// `PASS` on unfiltered records; returns `true` if a filter matched.
+fn apply_filters(record: &mut Record, filters: &[SoftFilter]) -> bool {
+    if filters.is_empty() {
+        return false;
+    }
+
+    let failed: Vec<&str> = filters
+        .iter()
+        .filter(|f| f.expression.matches(record))
+        .map(|f| f.id.as_str())
+        .collect();
+
+    if failed.is_empty() {
+        if record.filters.is_empty() {
+            record.filters.push("PASS".to_string());
+        }
+        return false;
+    }
+
+    record.filters.retain(|f| f != "PASS");
+    for id in failed {
+        if !record.filters.iter().any(|f| f == id) {
+            record.filters.push(id.to_string());
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+    use crate::reference::MemoryReference;
+
+    fn run<F>(vcf: &str, f: F) -> (PresetReport, Vec<String>)
+    where
+        F: FnOnce(Reader<&[u8]>, &Header, &mut Writer<Vec<u8>>) -> Result<PresetReport>,
+    {
+        let reader = Reader::new(vcf.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let mut writer = Writer::new(Vec::new());
+        let report = f(reader, &header, &mut writer).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        (report, text.lines().map(|l| l.to_string()).collect())
+    }
+
+    fn body(lines: &[String]) -> Vec<&str> {
+        lines
+            .iter()
+            .filter(|l| !l.starts_with('#'))
+            .map(|l| l.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_germline_postprocess_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+            1\t10\t.\tACG\tTCA\t50\t.\t.\tGT\t0/1\n\
+            1\t12\t.\tG\tA\t40\t.\t.\tGT\t0/1\n\
+            1\t20\t.\tCAA\tCA\t10\t.\t.\tGT\t1/1\n\
+            1\tx\t.\tG\tA\t.\t.\t.\tGT\t0/1\n";
+
+        let (report, lines) = run(vcf, |reader, header, writer| {
+            germline_postprocess(reader, header, writer, None, &GermlineOptions::default())
+        });
+
+        assert_eq!(
+            body(&lines),
+            vec![
+                "1\t10\t.\tA\tT\t50\tPASS\tAC=1;AN=2;AF=0.5;F_MISSING=0;HWE=1\tGT\t0/1",
+                "1\t12\t.\tG\tA\t50\tPASS\tAC=1;AN=2;AF=0.5;F_MISSING=0;HWE=1\tGT\t0/1",
+                "1\t20\t.\tCA\tC\t10\tLowQual\tAC=2;AN=2;AF=1;F_MISSING=0;HWE=1\tGT\t1/1",
+            ]
+        );
+        assert!(lines.iter().any(|l| l.starts_with("##FILTER=<ID=LowQual,")));
+        assert!(lines.iter().any(|l| l.starts_with("##INFO=<ID=HWE,")));
+
+        let summary = &report.summary;
+        assert_eq!((summary.records_in, summary.records_out), (4, 3));
+        assert_eq!((summary.modified, summary.skipped), (3, 1));
+        assert_eq!(report.stats.snvs, 2);
+        assert_eq!(report.stats.deletions, 1);
+    }
+
+    #[test]
+    fn test_germline_postprocess_2() {
+        // 1:5 ACA>A left-shifts to 1:1 TCA>T, past the SNV at 1:3.
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t1\t.\tTCA\tT\t50\t.\t.\n\
+            1\t3\t.\tA\tG\t50\t.\t.\n\
+            1\t5\t.\tACA\tA\t50\t.\t.\n";
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "TCACACAGTT");
+
+        let (report, lines) = run(vcf, |reader, header, writer| {
+            germline_postprocess(
+                reader,
+                header,
+                writer,
+                Some(&reference),
+                &GermlineOptions::default(),
+            )
+        });
+        assert_eq!(
+            body(&lines),
+            vec!["1\t1\t.\tTCA\tT\t50\tPASS\t.", "1\t3\t.\tA\tG\t50\tPASS\t."]
+        );
+        assert_eq!(report.summary.records_out, 2);
+
+        let options = GermlineOptions {
+            sort_window: None,
+            ..GermlineOptions::default()
+        };
+        let (_, lines) = run(vcf, |reader, header, writer| {
+            germline_postprocess(reader, header, writer, Some(&reference), &options)
+        });
+        assert_eq!(body(&lines).len(), 3);
+    }
+
+    #[test]
+    fn test_somatic_filter_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tTUMOR\tNORMAL\n\
+            1\t10\t.\tA\tT\t.\t.\t.\tGT:AD:AF:DP\t0/1:20,10:0.33:30\t0/0:30,0:0:30\n\
+            1\t20\t.\tA\tT\t.\t.\t.\tGT:AD:AF:DP\t0/1:28,2:0.07:30\t0/0:30,0:0:30\n\
+            1\t30\t.\tAT\tGT\t.\tgermline\t.\tGT:AD:AF:DP\t0/1:5,5:0.5:10\t0/1:5,5:0.5:10\n";
+
+        let (report, lines) = run(vcf, |reader, header, writer| {
+            somatic_filter(reader, header, writer, None, &SomaticOptions::default())
+        });
+
+        assert_eq!(body(&lines).len(), 1);
+        assert!(body(&lines)[0].starts_with("1\t10\t.\tA\tT\t.\tPASS\t"));
+        assert_eq!(report.summary.records_in, 3);
+        // 1:20 fails LowAltReads; 1:30 is normalized and keeps its filter.
+        assert_eq!(report.summary.modified, 2);
+
+        let options = SomaticOptions {
+            drop_filtered: false,
+            ..SomaticOptions::for_tumor(1)
+        };
+        let (_, lines) = run(vcf, |reader, header, writer| {
+            somatic_filter(reader, header, writer, None, &options)
+        });
+        let filters: Vec<&str> = body(&lines)
+            .iter()
+            .map(|l| l.split('\t').nth(6).unwrap())
+            .collect();
+        assert_eq!(
+            filters,
+            vec!["LowAltReads;LowVAF", "LowAltReads;LowVAF", "germline"]
+        );
+    }
+
+    #[test]
+    fn test_cohort_qc_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3\tS4\n\
+            1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\t0/0\t0/1\t0/0\n\
+            1\t20\t.\tA\tT\t.\t.\t.\tGT\t0/1\t./.\t0/0\t0/0\n";
+
+        let options = CohortQcOptions {
+            drop_filtered: true,
+            ..CohortQcOptions::default()
+        };
+        let (report, lines) = run(vcf, |reader, header, writer| {
+            cohort_qc(reader, header, writer, &options)
+        });
+
+        assert_eq!(
+            body(&lines),
+            vec!["1\t10\t.\tA\tT\t.\tPASS\tAC=2;AN=8;AF=0.25;F_MISSING=0;HWE=1\tGT\t0/1\t0/0\t0/1\t0/0"]
+        );
+        assert_eq!(report.summary.records_in, 2);
+        assert_eq!(report.summary.modified, 1);
+        assert_eq!(report.stats.records, 1);
+        assert!(SoftFilter::new("X", "", "QUAL<").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Known malformations in the output of popular variant callers.
+//!
+//! A [`Quirks`] detects every [`Quirk`] it sees while a file is read, and
+//! repairs only the quirks it was asked to fix. Quirks that are detected but
+//! not fixed are passed through unchanged, so they fail or parse exactly as
+//! they would without it. The repairs are deterministic: the same input and
+//! the same set of fixes always give the same output.
+
+use crate::header::{
+    FilterDefinition, FormatDefinition, Header, InfoDefinition, Number, ValueType,
+};
+use crate::record::Record;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// `##fileformat` assumed for headers that lack one.
+pub const ASSUMED_FILEFORMAT: &str = "VCFv4.2";
+
+/// Description of definitions added for undefined fields.
+const UNDEFINED_DESCRIPTION: &str = "Not defined in the original header";
+
+/// A documented malformation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quirk {
+    /// `Number=-1` (older GATK and FreeBayes) or a lowercase `a`, `r` or `g`
+    /// in an INFO or FORMAT definition. Fixed to `.` or the uppercase letter.
+    NonstandardNumber,
+    /// A lowercase or abbreviated `Type`, e.g. `Type=string` or `Type=Int`.
+    /// Fixed to the spec spelling.
+    NonstandardType,
+    /// A `##` line without `=`, e.g. `##source FreeBayes`. Fixed by dropping
+    /// the line.
+    MalformedHeaderLine,
+    /// No `##fileformat` line. Fixed by assuming [`ASSUMED_FILEFORMAT`].
+    MissingFileformat,
+    /// INFO, FORMAT or FILTER IDs used by records but not defined in the
+    /// header. Fixed by adding definitions with `Number=.` and `Type=String`,
+    /// or `Type=Flag` for INFO keys without a value.
+    UndefinedField,
+    /// Spaces in INFO values, as written by some annotators. Fixed by
+    /// percent-encoding them as `%20`.
+    InfoWhitespace,
+    /// Empty INFO entries, e.g. `DP=3;;AF=0.5` or a trailing `;`. Fixed by
+    /// dropping them.
+    EmptyInfoEntry,
+}
+
+impl Quirk {
+    pub const ALL: [Self; 7] = [
+        Self::NonstandardNumber,
+        Self::NonstandardType,
+        Self::MalformedHeaderLine,
+        Self::MissingFileformat,
+        Self::UndefinedField,
+        Self::InfoWhitespace,
+        Self::EmptyInfoEntry,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::NonstandardNumber => "NonstandardNumber",
+            Self::NonstandardType => "NonstandardType",
+            Self::MalformedHeaderLine => "MalformedHeaderLine",
+            Self::MissingFileformat => "MissingFileformat",
+            Self::UndefinedField => "UndefinedField",
+            Self::InfoWhitespace => "InfoWhitespace",
+            Self::EmptyInfoEntry => "EmptyInfoEntry",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::NonstandardNumber => "Number is -1 or a lowercase letter",
+            Self::NonstandardType => "Type is lowercase or abbreviated",
+            Self::MalformedHeaderLine => "Header line without '='",
+            Self::MissingFileformat => "No ##fileformat line",
+            Self::UndefinedField => "INFO, FORMAT or FILTER ID missing from the header",
+            Self::InfoWhitespace => "Spaces in INFO values",
+            Self::EmptyInfoEntry => "Empty INFO entries",
+        }
+    }
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// Detects quirks and repairs the opted-in ones.
+#[derive(Debug, Clone, Default)]
+pub struct Quirks {
+    fixes: Vec<Quirk>,
+    detected: BTreeMap<Quirk, u64>,
+}
+
+impl Quirks {
+    /// Detects quirks without fixing any.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Detects quirks and fixes those in `fixes`.
+    pub fn fixing(fixes: &[Quirk]) -> Self {
+        Self {
+            fixes: fixes.to_vec(),
+            detected: BTreeMap::new(),
+        }
+    }
+
+    /// Detects and fixes every known quirk.
+    pub fn fixing_all() -> Self {
+        Self::fixing(&Quirk::ALL)
+    }
+
+    pub fn fixes(&self, quirk: Quirk) -> bool {
+        self.fixes.contains(&quirk)
+    }
+
+    /// Number of header lines and records in which each quirk was seen.
+    pub fn detected(&self) -> &BTreeMap<Quirk, u64> {
+        &self.detected
+    }
+
+    /// Records `quirk` and returns whether to fix it.
+    fn detect(&mut self, quirk: Quirk) -> bool {
+        *self.detected.entry(quirk).or_insert(0) += 1;
+        self.fixes(quirk)
+    }
+
+    /// Repairs one header line before it is parsed; `None` drops the line.
+    pub fn repair_header_line<'a>(&mut self, line: &'a str) -> Option<Cow<'a, str>> {
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        let Some(meta) = trimmed.strip_prefix("##") else {
+            return Some(Cow::Borrowed(line));
+        };
+
+        if !meta.contains('=') {
+            if self.detect(Quirk::MalformedHeaderLine) {
+                return None;
+            }
+            return Some(Cow::Borrowed(line));
+        }
+        if !meta.starts_with("INFO=") && !meta.starts_with("FORMAT=") {
+            return Some(Cow::Borrowed(line));
+        }
+
+        let mut repaired = Cow::Borrowed(line);
+        if let Some(number) = structured_value(&repaired, "Number") {
+            let fixed = match number {
+                "-1" => Some("."),
+                "a" => Some("A"),
+                "r" => Some("R"),
+                "g" => Some("G"),
+                _ => None,
+            };
+            if let Some(fixed) = fixed {
+                if self.detect(Quirk::NonstandardNumber) {
+                    repaired = Cow::Owned(replace_value(&repaired, "Number", fixed));
+                }
+            }
+        }
+        if let Some(value_type) = structured_value(&repaired, "Type") {
+            let fixed = match value_type.to_ascii_lowercase().as_str() {
+                "integer" | "int" => Some("Integer"),
+                "float" | "double" => Some("Float"),
+                "flag" | "bool" | "boolean" => Some("Flag"),
+                "character" | "char" => Some("Character"),
+                "string" | "str" => Some("String"),
+                _ => None,
+            };
+            if let Some(fixed) = fixed.filter(|&fixed| fixed != value_type) {
+                if self.detect(Quirk::NonstandardType) {
+                    repaired = Cow::Owned(replace_value(&repaired, "Type", fixed));
+                }
+            }
+        }
+
+        Some(repaired)
+    }
+
+    /// Repairs a header once all its lines are parsed.
+    pub fn repair_header(&mut self, header: &mut Header) {
+        if header.fileformat.is_empty() && self.detect(Quirk::MissingFileformat) {
+            header.fileformat = ASSUMED_FILEFORMAT.to_string();
+        }
+    }
+
+    /// Repairs a parsed record; fixing [`Quirk::UndefinedField`] adds
+    /// definitions to `header`.
+    pub fn repair_record(&mut self, header: &mut Header, record: &mut Record) {
+        if record.info.iter().any(|(k, v)| k.is_empty() && v.is_none())
+            && self.detect(Quirk::EmptyInfoEntry)
+        {
+            record.info.retain(|(k, v)| !k.is_empty() || v.is_some());
+        }
+
+        if record
+            .info
+            .iter()
+            .any(|(_, v)| v.as_ref().is_some_and(|v| v.contains(' ')))
+            && self.detect(Quirk::InfoWhitespace)
+        {
+            for value in record.info.iter_mut().filter_map(|(_, v)| v.as_mut()) {
+                if value.contains(' ') {
+                    *value = value.replace(' ', "%20");
+                }
+            }
+        }
+
+        let undefined = record
+            .info
+            .iter()
+            .any(|(k, _)| !k.is_empty() && header.info(k).is_none())
+            || record.format.iter().any(|k| header.format(k).is_none())
+            || record
+                .filters
+                .iter()
+                .any(|f| f != "PASS" && header.filter(f).is_none());
+        if undefined && self.detect(Quirk::UndefinedField) {
+            for (key, value) in &record.info {
+                if !key.is_empty() && header.info(key).is_none() {
+                    let (number, value_type) = match value {
+                        Some(_) => (Number::Unknown, ValueType::String),
+                        None => (Number::Count(0), ValueType::Flag),
+                    };
+                    header.infos.push(InfoDefinition::new(
+                        key,
+                        number,
+                        value_type,
+                        UNDEFINED_DESCRIPTION,
+                    ));
+                }
+            }
+            for key in &record.format {
+                if header.format(key).is_none() {
+                    header.formats.push(FormatDefinition::new(
+                        key,
+                        Number::Unknown,
+                        ValueType::String,
+                        UNDEFINED_DESCRIPTION,
+                    ));
+                }
+            }
+            for filter in &record.filters {
+                if filter != "PASS" && header.filter(filter).is_none() {
+                    header
+                        .filters
+                        .push(FilterDefinition::new(filter, UNDEFINED_DESCRIPTION));
+                }
+            }
+        }
+    }
+}
+
+/// Byte range of the value of `key` in a structured line such as
+/// `##INFO=<ID=DP,Number=1,...>`, ignoring the quoted description.
+fn value_range(line: &str, key: &str) -> Option<(usize, usize)> {
+    let end = line.find("Description=").unwrap_or(line.len());
+    let head = &line[..end];
+
+    [format!("<{}=", key), format!(",{}=", key)]
+        .iter()
+        .find_map(|prefix| head.find(prefix.as_str()).map(|i| i + prefix.len()))
+        .map(|start| {
+            let len = line[start..].find([',', '>']).unwrap_or(line.len() - start);
+            (start, start + len)
+        })
+}
+
+fn structured_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    value_range(line, key).map(|(start, end)| &line[start..end])
+}
+
+fn replace_value(line: &str, key: &str, value: &str) -> String {
+    match value_range(line, key) {
+        Some((start, end)) => format!("{}{}{}", &line[..start], value, &line[end..]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Result;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##INFO=<ID=AO,Number=-1,Type=Int,Description=\"Type=Int, Number=-1\">\n\
+        ##FORMAT=<ID=GT,Number=1,Type=string,Description=\"Genotype\">\n\
+        ##source FreeBayes\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+        1\t10\t.\tA\tT\t.\tlowqual\tAO=3;;ANN=a b;DB;\tGT:DP\t0/1:4\n";
+
+    #[test]
+    fn test_repair_header_line_1() {
+        let mut quirks = Quirks::fixing(&[Quirk::NonstandardNumber]);
+
+        let line = "##INFO=<ID=AO,Number=-1,Type=int,Description=\"Number=-1\">";
+        assert_eq!(
+            quirks.repair_header_line(line).unwrap(),
+            "##INFO=<ID=AO,Number=.,Type=int,Description=\"Number=-1\">"
+        );
+        assert_eq!(
+            quirks.repair_header_line("##source x").unwrap(),
+            "##source x"
+        );
+        assert_eq!(
+            quirks.repair_header_line("##contig=<ID=1,Type=x>").unwrap(),
+            "##contig=<ID=1,Type=x>"
+        );
+
+        let counts: Vec<(Quirk, u64)> = quirks.detected().iter().map(|(&q, &n)| (q, n)).collect();
+        assert_eq!(
+            counts,
+            vec![
+                (Quirk::NonstandardNumber, 1),
+                (Quirk::NonstandardType, 1),
+                (Quirk::MalformedHeaderLine, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_quirks_1() {
+        let quirks = Quirks::new();
+        assert!(Reader::with_quirks(VCF.as_bytes(), quirks).is_err());
+
+        let mut reader = Reader::with_quirks(VCF.as_bytes(), Quirks::fixing_all()).unwrap();
+        assert_eq!(reader.header().fileformat, ASSUMED_FILEFORMAT);
+        assert_eq!(reader.header().info("AO").unwrap().number, Number::Unknown);
+        assert_eq!(
+            reader.header().info("AO").unwrap().value_type,
+            ValueType::Integer
+        );
+
+        let records: Vec<Record> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(
+            records[0].to_string(),
+            "1\t10\t.\tA\tT\t.\tlowqual\tAO=3;ANN=a%20b;DB\tGT:DP\t0/1:4"
+        );
+
+        let header = reader.header();
+        assert_eq!(header.info("DB").unwrap().value_type, ValueType::Flag);
+        assert_eq!(header.info("ANN").unwrap().value_type, ValueType::String);
+        assert!(header.format("DP").is_some());
+        assert!(header.filter("lowqual").is_some());
+        assert_eq!(reader.quirks().unwrap().detected().len(), Quirk::ALL.len());
+    }
+}
@@ -0,0 +1,312 @@
+//! Zero-copy parsing of data lines.
+//!
+//! A [`RawRecord`] holds the column offsets of a line borrowed from the
+//! input buffer. Fields are byte slices of that buffer, split only when
+//! accessed and never validated as UTF-8; numbers are decoded on access.
+//! [`RawRecord::to_record`] builds an owned [`Record`] when one is needed.
+
+use crate::errors::{Error, Result};
+use crate::header::Header;
+use crate::record::Record;
+use std::io::{self, BufRead};
+
+/// Number of fixed columns whose offsets are recorded, up to FORMAT.
+const COLUMNS: usize = 9;
+
+/// A data line borrowed from a byte buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct RawRecord<'a> {
+    line: &'a [u8],
+    /// End offset of each fixed column; columns past the last one present
+    /// end at the end of the line.
+    ends: [usize; COLUMNS],
+    columns: usize,
+}
+
+impl<'a> RawRecord<'a> {
+    /// Finds the columns of `line`, which may end with `\n` or `\r\n`.
+    pub fn parse(line: &'a [u8]) -> Result<Self> {
+        let line = trim_line_end(line);
+
+        let mut ends = [line.len(); COLUMNS];
+        let mut columns = 1;
+        for (i, _) in line.iter().enumerate().filter(|(_, &b)| b == b'\t') {
+            ends[columns - 1] = i;
+            columns += 1;
+            if columns > COLUMNS {
+                break;
+            }
+        }
+        if columns < 8 {
+            Err(Error::RecordColumnsError(columns))?
+        }
+
+        Ok(Self {
+            line,
+            ends,
+            columns: columns.min(COLUMNS),
+        })
+    }
+
+    /// The line without its line terminator.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.line
+    }
+
+    fn column(&self, i: usize) -> &'a [u8] {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] + 1 };
+        &self.line[start.min(self.line.len())..self.ends[i]]
+    }
+
+    pub fn chrom(&self) -> &'a [u8] {
+        self.column(0)
+    }
+
+    pub fn pos(&self) -> Result<u64> {
+        let pos = self.column(1);
+        parse_u64(pos).ok_or_else(|| Error::RecordPositionError(lossy(pos)))
+    }
+
+    pub fn ids(&self) -> Fields<'a> {
+        Fields::new(self.column(2), b';')
+    }
+
+    pub fn reference(&self) -> &'a [u8] {
+        self.column(3)
+    }
+
+    pub fn alternates(&self) -> Fields<'a> {
+        Fields::new(self.column(4), b',')
+    }
+
+    pub fn qual(&self) -> Result<Option<f64>> {
+        match self.column(5) {
+            b"." => Ok(None),
+            q => std::str::from_utf8(q)
+                .ok()
+                .and_then(|q| q.parse().ok())
+                .map(Some)
+                .ok_or_else(|| Error::RecordQualError(lossy(q))),
+        }
+    }
+
+    pub fn filters(&self) -> Fields<'a> {
+        Fields::new(self.column(6), b';')
+    }
+
+    /// INFO entries in order; flags have no value.
+    pub fn info(&self) -> impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)> {
+        Fields::new(self.column(7), b';').map(|kv| match kv.iter().position(|&b| b == b'=') {
+            Some(i) => (&kv[..i], Some(&kv[i + 1..])),
+            None => (kv, None),
+        })
+    }
+
+    /// Value of an INFO key: `Some(None)` for a flag, `None` if absent.
+    pub fn info_value(&self, key: &[u8]) -> Option<Option<&'a [u8]>> {
+        self.info().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    pub fn format(&self) -> Fields<'a> {
+        match self.columns {
+            COLUMNS => Fields::new(self.column(8), b':'),
+            _ => Fields::new(b".", b':'),
+        }
+    }
+
+    /// The sample columns, unsplit.
+    pub fn samples(&self) -> Fields<'a> {
+        match self.columns {
+            COLUMNS if self.ends[8] < self.line.len() => {
+                Fields::new(&self.line[self.ends[8] + 1..], b'\t')
+            }
+            _ => Fields::new(b".", b'\t'),
+        }
+    }
+
+    /// Value of FORMAT `key` of sample `sample`.
+    pub fn format_value(&self, sample: usize, key: &[u8]) -> Option<&'a [u8]> {
+        let j = self.format().position(|k| k == key)?;
+        self.samples().nth(sample)?.split(|&b| b == b':').nth(j)
+    }
+
+    /// Decodes the line into an owned record.
+    pub fn to_record(&self) -> Result<Record> {
+        let line = std::str::from_utf8(self.line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "record is not valid UTF-8"))?;
+
+        line.parse()
+    }
+}
+
+/// Values of a field split on a separator; `.` has none.
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    rest: Option<&'a [u8]>,
+    separator: u8,
+}
+
+impl<'a> Fields<'a> {
+    fn new(field: &'a [u8], separator: u8) -> Self {
+        Self {
+            rest: (field != b".").then_some(field),
+            separator,
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.rest?;
+        match rest.iter().position(|&b| b == self.separator) {
+            Some(i) => {
+                self.rest = Some(&rest[i + 1..]);
+                Some(&rest[..i])
+            }
+            None => {
+                self.rest = None;
+                Some(rest)
+            }
+        }
+    }
+}
+
+/// Reader of data lines as [`RawRecord`]s, reusing one line buffer.
+///
+/// Obtained from [`Reader::into_raw`](crate::reader::Reader::into_raw) once
+/// the header has been read.
+pub struct RawReader<R> {
+    inner: R,
+    header: Header,
+    line: Vec<u8>,
+    line_number: u64,
+}
+
+impl<R: BufRead> RawReader<R> {
+    pub(crate) fn new(inner: R, header: Header, line_number: u64) -> Self {
+        Self {
+            inner,
+            header,
+            line: Vec::new(),
+            line_number,
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    /// Reads the next data line, skipping blank lines; returns `None` at EOF.
+    ///
+    /// The record borrows the reader's buffer until the next call.
+    pub fn next_record(&mut self) -> Option<Result<RawRecord<'_>>> {
+        loop {
+            self.line.clear();
+            match self.inner.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+            self.line_number += 1;
+
+            if !trim_line_end(&self.line).is_empty() {
+                return Some(RawRecord::parse(&self.line));
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn parse_u64(bytes: &[u8]) -> Option<u64> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    bytes.iter().try_fold(0u64, |n, &b| {
+        let digit = (b as char).to_digit(10)?;
+        n.checked_mul(10)?.checked_add(digit as u64)
+    })
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    #[test]
+    fn test_raw_record_1() {
+        let line =
+            b"1\t10\trs1;rs2\tA\tT,G\t30.5\tPASS\tDP=3;DB;AF=0.1,0.2\tGT:DP\t0/1:7\t1/1:9\r\n";
+        let record = RawRecord::parse(line).unwrap();
+
+        assert_eq!(record.chrom(), b"1");
+        assert_eq!(record.pos().unwrap(), 10);
+        assert_eq!(record.ids().collect::<Vec<_>>(), vec![b"rs1", b"rs2"]);
+        assert_eq!(record.reference(), b"A");
+        assert_eq!(record.alternates().count(), 2);
+        assert_eq!(record.qual().unwrap(), Some(30.5));
+        assert_eq!(record.filters().collect::<Vec<_>>(), vec![b"PASS"]);
+        assert_eq!(record.info_value(b"AF"), Some(Some(&b"0.1,0.2"[..])));
+        assert_eq!(record.info_value(b"DB"), Some(None));
+        assert_eq!(record.info_value(b"X"), None);
+        assert_eq!(record.format_value(1, b"DP"), Some(&b"9"[..]));
+        assert_eq!(record.format_value(2, b"DP"), None);
+
+        let owned: Record = std::str::from_utf8(line).unwrap().parse().unwrap();
+        assert_eq!(record.to_record().unwrap(), owned);
+
+        let sites = RawRecord::parse(b"1\t5\t.\tA\t.\t.\t.\t.").unwrap();
+        assert_eq!(sites.ids().count(), 0);
+        assert_eq!(sites.alternates().count(), 0);
+        assert_eq!(sites.qual().unwrap(), None);
+        assert_eq!(sites.format().count(), 0);
+        assert_eq!(sites.samples().count(), 0);
+
+        assert!(RawRecord::parse(b"1\t5\t.\tA").is_err());
+        assert!(RawRecord::parse(b"1\tx\t.\tA\tT\t.\t.\t.")
+            .unwrap()
+            .pos()
+            .is_err());
+        assert!(RawRecord::parse(b"1\t5\t.\tA\tT\tq\t.\t.")
+            .unwrap()
+            .qual()
+            .is_err());
+    }
+
+    #[test]
+    fn test_raw_reader_1() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t10\t.\tA\tT\t.\t.\t.\n\
+            \n\
+            1\t20\t.\tAT\tA\t.\t.\tDP=4\n";
+        let mut reader = Reader::new(vcf.as_bytes()).unwrap().into_raw();
+
+        let mut positions = Vec::new();
+        while let Some(record) = reader.next_record() {
+            positions.push(record.unwrap().pos().unwrap());
+        }
+
+        assert_eq!(positions, vec![10, 20]);
+        assert_eq!(reader.line_number(), 5);
+        assert_eq!(reader.header().fileformat, "VCFv4.3");
+    }
+}
@@ -0,0 +1,435 @@
+use crate::bgzf;
+use crate::compliance::Compliance;
+use crate::errors::{Error, ErrorContext, Result};
+use crate::header::{Header, Version};
+use crate::quirks::Quirks;
+use crate::raw::RawReader;
+use crate::record::Record;
+use std::io::{BufRead, Read, Seek};
+use std::path::PathBuf;
+
+#[cfg(feature = "fs")]
+use crate::transpose::{BySample, TransposeOptions, Transposed};
+#[cfg(feature = "fs")]
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Streaming reader of VCF records.
+///
+/// The header is read eagerly by [`Reader::new`]; records are then yielded by
+/// iterating over the reader. Parse errors carry an [`ErrorContext`] with
+/// the line number, and the path for [`Reader::from_path`].
+pub struct Reader<R> {
+    inner: R,
+    header: Header,
+    line: String,
+    line_number: u64,
+    path: Option<PathBuf>,
+    skip_invalid: bool,
+    diagnostics: Vec<Error>,
+    version: Option<Version>,
+    compliance_checks: bool,
+    quirks: Option<Quirks>,
+}
+
+#[cfg(feature = "fs")]
+impl Reader<Box<dyn BufRead>> {
+    /// Opens a plain, gzip or BGZF compressed VCF file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let context = || ErrorContext::path(path);
+
+        let mut file =
+            BufReader::new(File::open(path).map_err(|e| Error::from(e).with_context(context()))?);
+        let bytes = file.fill_buf()?;
+
+        let inner: Box<dyn BufRead> = if bgzf::is_bgzf(bytes) {
+            Box::new(BufReader::new(bgzf::Reader::new(file)))
+        } else if bgzf::is_gzip(bytes) {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+
+        let mut reader = Self::new(inner).map_err(|e| e.with_context(context()))?;
+        reader.path = Some(path.to_path_buf());
+
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(inner: R) -> Result<Self> {
+        Self::open(inner, None)
+    }
+
+    /// Reads with `quirks` detecting known caller malformations in the
+    /// header and records, and repairing those it fixes.
+    pub fn with_quirks(inner: R, quirks: Quirks) -> Result<Self> {
+        Self::open(inner, Some(quirks))
+    }
+
+    fn open(inner: R, quirks: Option<Quirks>) -> Result<Self> {
+        let mut line_number = 0;
+
+        Self::read_header(inner, quirks, &mut line_number)
+            .map_err(|e| e.with_context(ErrorContext::line(line_number)))
+    }
+
+    fn read_header(
+        mut inner: R,
+        mut quirks: Option<Quirks>,
+        line_number: &mut u64,
+    ) -> Result<Self> {
+        let mut header = Header::new("");
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if inner.read_line(&mut line)? == 0 {
+                break;
+            }
+            *line_number += 1;
+
+            match &mut quirks {
+                Some(quirks) => {
+                    if let Some(repaired) = quirks.repair_header_line(&line) {
+                        header.parse_line(&repaired)?;
+                    }
+                }
+                None => header.parse_line(&line)?,
+            }
+
+            if line.starts_with("#CHROM") {
+                break;
+            }
+        }
+
+        if let Some(quirks) = &mut quirks {
+            quirks.repair_header(&mut header);
+        }
+        if header.fileformat.is_empty() {
+            Err(Error::HeaderMissingFileformatError())?
+        }
+
+        Ok(Self {
+            version: header.version(),
+            inner,
+            header,
+            line,
+            line_number: *line_number,
+            path: None,
+            skip_invalid: false,
+            diagnostics: Vec::new(),
+            compliance_checks: false,
+            quirks,
+        })
+    }
+
+    /// Version detected from `##fileformat`, unless forced with [`Reader::set_version`].
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Applies the rules of `version` regardless of what the header declares.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = Some(version);
+    }
+
+    /// Checks every record against the rules of [`Reader::version`] while reading.
+    ///
+    /// Fails with [`Error::UnsupportedVersionError`] if no version is known.
+    pub fn set_compliance_checks(&mut self, enabled: bool) {
+        self.compliance_checks = enabled;
+    }
+
+    /// Skips records that fail to parse or check instead of yielding their
+    /// errors, which are collected as [`Reader::diagnostics`].
+    ///
+    /// I/O errors are still yielded.
+    pub fn set_skip_invalid(&mut self, enabled: bool) {
+        self.skip_invalid = enabled;
+    }
+
+    /// Errors of the records skipped so far with [`Reader::set_skip_invalid`],
+    /// in input order and with their [`ErrorContext`].
+    pub fn diagnostics(&self) -> &[Error] {
+        &self.diagnostics
+    }
+
+    /// Returns and clears the collected diagnostics.
+    pub fn take_diagnostics(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn check(&self, record: Result<Record>) -> Result<Record> {
+        let record = record?;
+
+        if self.compliance_checks {
+            let version = self
+                .version
+                .ok_or_else(|| Error::UnsupportedVersionError(self.header.fileformat.clone()))?;
+            Compliance::new(version).check_record(&record)?;
+        }
+
+        Ok(record)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Quirks detected so far, if reading with [`Reader::with_quirks`].
+    ///
+    /// Repairs may add definitions to [`Reader::header`] while reading.
+    pub fn quirks(&self) -> Option<&Quirks> {
+        self.quirks.as_ref()
+    }
+
+    /// Number of lines consumed so far, including header lines.
+    pub fn line_number(&self) -> u64 {
+        self.line_number
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    #[cfg(feature = "fs")]
+    /// Reads all records and yields them sample by sample.
+    ///
+    /// The records are transposed into temporary files first; see
+    /// [`Transposed`] to choose the block size or directory.
+    pub fn by_sample(self) -> Result<BySample> {
+        let header = self.header.clone();
+
+        Ok(Transposed::new(self, &header, TransposeOptions::default())?.into_iter())
+    }
+
+    /// Continues reading the data lines as borrowed [`RawRecord`]s.
+    ///
+    /// Quirk repairs and compliance checks do not apply to raw records.
+    ///
+    /// [`RawRecord`]: crate::raw::RawRecord
+    pub fn into_raw(self) -> RawReader<R> {
+        RawReader::new(self.inner, self.header, self.line_number)
+    }
+
+    /// Reads the next data line into `line`; returns `false` at EOF.
+    ///
+    /// Blank lines are skipped. The trailing newline is kept.
+    pub fn read_line(&mut self, line: &mut String) -> Result<bool> {
+        loop {
+            line.clear();
+            if self.inner.read_line(line)? == 0 {
+                return Ok(false);
+            }
+            self.line_number += 1;
+
+            if !line.trim_end().is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: Read> Reader<bgzf::Reader<R>> {
+    /// Virtual offset of the next record, suitable for building custom indexes.
+    pub fn voffset(&self) -> u64 {
+        self.inner.voffset()
+    }
+}
+
+impl<R: Read + Seek> Reader<bgzf::Reader<R>> {
+    /// Resumes reading at a record start obtained from [`Reader::voffset`].
+    ///
+    /// [`Reader::line_number`] is not meaningful after seeking.
+    pub fn seek_voffset(&mut self, voffset: u64) -> Result<()> {
+        Ok(self.inner.seek_voffset(voffset)?)
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = std::mem::take(&mut self.line);
+
+        let item = loop {
+            match self.read_line(&mut line) {
+                Ok(true) => {}
+                Ok(false) => break None,
+                Err(e) => break Some(Err(e)),
+            }
+
+            let record = line.parse().map(|mut record| {
+                if let Some(quirks) = &mut self.quirks {
+                    quirks.repair_record(&mut self.header, &mut record);
+                }
+                record
+            });
+            match self.check(record) {
+                Ok(record) => break Some(Ok(record)),
+                Err(e) => {
+                    let e = e.with_context(ErrorContext {
+                        path: self.path.clone(),
+                        line: Some(self.line_number),
+                        ..ErrorContext::default()
+                    });
+                    match self.skip_invalid {
+                        true => self.diagnostics.push(e),
+                        false => break Some(Err(e)),
+                    }
+                }
+            }
+        };
+
+        self.line = line;
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCategory;
+    use std::io::Write;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        ##contig=<ID=1,length=1000>\n\
+        ##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+        1\t10\t.\tA\tT\t.\tPASS\tDP=3\tGT\t0/1\n\
+        \n\
+        1\t20\trs1\tAT\tA\t50\t.\t.\tGT\t1/1\n";
+
+    #[test]
+    fn test_reader_1() {
+        let mut reader = Reader::new(VCF.as_bytes()).unwrap();
+
+        assert_eq!(reader.header().samples, vec!["S1"]);
+        assert_eq!(reader.line_number(), 4);
+
+        let records: Vec<Record> = reader.by_ref().collect::<Result<_>>().unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].pos, 20);
+        assert_eq!(records[1].ids, vec!["rs1"]);
+        assert_eq!(reader.line_number(), 7);
+    }
+
+    #[test]
+    fn test_reader_2() {
+        assert!(Reader::new("1\t10\t.\tA\tT\t.\t.\t.\n".as_bytes()).is_err());
+
+        let text = "##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n1\t10\n";
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_skip_invalid_1() {
+        let text = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\tx\t.\tA\tT\t.\t.\t.\n\
+            1\t10\t.\tA\tT\t.\t.\t.\n\
+            1\t20\t.\tA\tT\tq\t.\t.\n";
+
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.category(), ErrorCategory::Record);
+        assert_eq!(error.context().unwrap().line, Some(3));
+        assert_eq!(error.context().unwrap().field.as_deref(), Some("POS"));
+
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        reader.set_skip_invalid(true);
+        let records: Vec<Record> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 1);
+
+        let lines: Vec<_> = reader
+            .diagnostics()
+            .iter()
+            .map(|e| e.context().unwrap().line)
+            .collect();
+        assert_eq!(lines, vec![Some(3), Some(5)]);
+        assert_eq!(
+            reader.take_diagnostics()[1].context().unwrap().column,
+            Some(6)
+        );
+        assert!(reader.diagnostics().is_empty());
+
+        let error = Reader::new("##fileformat=VCFv4.3\n##INFO=<ID=DP\n".as_bytes())
+            .err()
+            .unwrap();
+        assert_eq!(error.category(), ErrorCategory::Header);
+        assert_eq!(error.context().unwrap().line, Some(2));
+    }
+
+    #[test]
+    fn test_compliance_checks_1() {
+        let text = "##fileformat=VCFv4.1\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            1\t10\t.\tA\t*\t.\t.\t.\n";
+
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        assert_eq!(reader.version(), Some(Version::V4_1));
+        assert!(reader.next().unwrap().is_ok());
+
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        reader.set_compliance_checks(true);
+        assert!(reader.next().unwrap().is_err());
+
+        let mut reader = Reader::new(text.as_bytes()).unwrap();
+        reader.set_compliance_checks(true);
+        reader.set_version(Version::V4_2);
+        assert!(reader.next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_voffset_1() {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(VCF.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = Reader::new(bgzf::Reader::new(std::io::Cursor::new(bytes))).unwrap();
+        let first = reader.voffset();
+        let second = {
+            reader.next().unwrap().unwrap();
+            reader.voffset()
+        };
+        let record = reader.next().unwrap().unwrap();
+
+        reader.seek_voffset(second).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), record);
+
+        reader.seek_voffset(first).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap().pos, 10);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_from_path_1() {
+        let path =
+            std::env::temp_dir().join(format!("vcf-lib-reader-{}.vcf.gz", std::process::id()));
+        let mut writer = bgzf::Writer::new(File::create(&path).unwrap());
+        writer.write_all(VCF.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let reader = Reader::from_path(&path).unwrap();
+        let records: Vec<Record> = reader.collect::<Result<_>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].info("DP"), Some("3"));
+    }
+}
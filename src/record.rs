@@ -1,34 +1,291 @@
+use crate::alleles::AlleleRemap;
 use crate::errors::{Error, Result};
+use crate::genotype::Genotype;
+use crate::header::Header;
+use crate::reference::ReferenceSequence;
+use crate::validation::{is_non_ref, is_symbolic, Lowercase, ValidationPolicy};
 use crate::VariantType;
-use once_cell::sync::Lazy;
-use regex::Regex;
+#[cfg(feature = "ndarray")]
+use ndarray::Array2;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 
-static REGEX_ALLELES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\A[ACGTURYKMSWBDHVN]+\z").unwrap());
+/// Direction in which [`normalize_with_reference`] shifts indels through repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    /// Towards lower coordinates, as VCF tools expect.
+    #[default]
+    Left,
+    /// Towards higher coordinates (the HGVS 3' rule).
+    Right,
+}
+
+/// How [`normalize_with`] trims bases shared by REF and ALT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Trimming {
+    /// Trim the shared suffix, then the shared prefix.
+    #[default]
+    Sequential,
+    /// Measure the shared prefix and suffix of the original alleles at once
+    /// and cut both ends in one step.
+    ///
+    /// Meant for callers that pad every allele to the full haplotype, such as
+    /// `ACGTACGT`/`ACGTTCGT` for a single SNV. Debug builds check the result
+    /// against trimming both ends repeatedly until nothing changes.
+    FullParsimony,
+}
+
+/// Options controlling [`normalize_with`] and [`normalize_with_reference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NormalizeOptions {
+    pub validation: ValidationPolicy,
+    /// Only used when a reference sequence is available.
+    pub direction: Direction,
+    /// Only used without a reference sequence.
+    pub trimming: Trimming,
+}
 
 pub fn normalize<'a>(
     position: u64,
     reference: &'a str,
     alternate: &'a str,
 ) -> Result<(u64, &'a str, &'a str)> {
-    if reference.len() == 0 {
-        Err(Error::RefBasesEmptyError())?
+    let policy = ValidationPolicy::default();
+
+    policy.validate_reference(reference)?;
+    policy.validate_alternate(alternate)?;
+
+    let (r, a) = trim_trailing_shared_bases(reference, alternate);
+
+    Ok(trim_leading_shared_bases(position, r, a))
+}
+
+/// Normalizes a variant after validating its alleles with `options.validation`.
+///
+/// Alleles are borrowed from the inputs unless the policy asks for uppercasing.
+/// Symbolic ALT alleles, when allowed, are returned without trimming.
+pub fn normalize_with<'a>(
+    position: u64,
+    reference: &'a str,
+    alternate: &'a str,
+    options: &NormalizeOptions,
+) -> Result<(u64, Cow<'a, str>, Cow<'a, str>)> {
+    let policy = &options.validation;
+
+    policy.validate_reference(reference)?;
+    policy.validate_alternate(alternate)?;
+
+    if policy.lowercase == Lowercase::Uppercase {
+        let reference = reference.to_ascii_uppercase();
+        let alternate = alternate.to_ascii_uppercase();
+
+        if policy.passes_through(&alternate) {
+            return Ok((position, Cow::Owned(reference), Cow::Owned(alternate)));
+        }
+
+        let (p, r, a) = trim(position, &reference, &alternate, options.trimming);
+
+        return Ok((p, Cow::Owned(r.to_string()), Cow::Owned(a.to_string())));
     }
 
-    if alternate.len() == 0 {
-        Err(Error::AltBasesEmptyError())?
+    if policy.passes_through(alternate) {
+        return Ok((position, Cow::Borrowed(reference), Cow::Borrowed(alternate)));
     }
 
-    if !REGEX_ALLELES.is_match(reference) {
-        Err(Error::RefBasesInvalidSymbolError(reference.to_string()))?
+    let (p, r, a) = trim(position, reference, alternate, options.trimming);
+
+    Ok((p, Cow::Borrowed(r), Cow::Borrowed(a)))
+}
+
+/// Normalized position and alleles together with their classification.
+#[cfg(feature = "rayon")]
+pub type ClassifiedVariant<'a> = (u64, &'a str, &'a str, Option<VariantType>);
+
+/// Normalizes and classifies variants in parallel, preserving input order.
+#[cfg(feature = "rayon")]
+pub fn normalize_batch<'a>(
+    variants: &[(u64, &'a str, &'a str)],
+) -> Vec<Result<ClassifiedVariant<'a>>> {
+    variants
+        .par_iter()
+        .with_min_len(1024)
+        .map(|&(position, reference, alternate)| {
+            let (p, r, a) = normalize(position, reference, alternate)?;
+            Ok((p, r, a, variant_type(r, a)))
+        })
+        .collect()
+}
+
+/// Normalized alleles that own their storage.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NormalizedVariant {
+    pub position: u64,
+    pub reference: String,
+    pub alternate: String,
+}
+
+impl NormalizedVariant {
+    pub fn variant_type(&self) -> Option<VariantType> {
+        variant_type(&self.reference, &self.alternate)
     }
+}
+
+/// Like [`normalize`], but takes ownership of the alleles and trims them in place.
+///
+/// The input buffers are reused, so no allocation happens when nothing is trimmed.
+pub fn normalize_owned(
+    position: u64,
+    reference: String,
+    alternate: String,
+) -> Result<NormalizedVariant> {
+    normalize_owned_with(position, reference, alternate, &NormalizeOptions::default())
+}
 
-    if !REGEX_ALLELES.is_match(alternate) {
-        Err(Error::AltBasesInvalidSymbolError(alternate.to_string()))?
+/// Like [`normalize_with`], but takes ownership of the alleles and trims them in place.
+pub fn normalize_owned_with(
+    position: u64,
+    mut reference: String,
+    mut alternate: String,
+    options: &NormalizeOptions,
+) -> Result<NormalizedVariant> {
+    let mut options = *options;
+
+    if options.validation.lowercase == Lowercase::Uppercase {
+        options.validation.validate_reference(&reference)?;
+        options.validation.validate_alternate(&alternate)?;
+        reference.make_ascii_uppercase();
+        alternate.make_ascii_uppercase();
+        options.validation.lowercase = Lowercase::Accept;
     }
 
-    let (r, a) = trim_trailing_shared_bases(reference, alternate);
+    let (p, r_len, a_len) = {
+        let (p, r, a) = normalize_with(position, &reference, &alternate, &options)?;
+        (p, r.len(), a.len())
+    };
 
-    Ok(trim_leading_shared_bases(position, r, a))
+    let start = (p - position) as usize;
+    retain_range(&mut reference, start, start + r_len);
+    retain_range(&mut alternate, start, start + a_len);
+
+    Ok(NormalizedVariant {
+        position: p,
+        reference,
+        alternate,
+    })
+}
+
+fn retain_range(s: &mut String, start: usize, end: usize) {
+    s.truncate(end);
+    s.drain(..start);
+}
+
+/// Normalizes a variant and shifts indels through repeats using the reference genome.
+///
+/// REF must match `sequence` at `position`. Alleles are returned uppercase and
+/// padded with the base preceding the event, or following it at the contig start.
+pub fn normalize_with_reference<S: ReferenceSequence + ?Sized>(
+    chrom: &str,
+    position: u64,
+    reference: &str,
+    alternate: &str,
+    sequence: &S,
+    options: &NormalizeOptions,
+) -> Result<NormalizedVariant> {
+    let policy = &options.validation;
+
+    policy.validate_reference(reference)?;
+    policy.validate_alternate(alternate)?;
+
+    let end = position + reference.len() as u64 - 1;
+    let expected = sequence.fetch(chrom, position, end)?;
+    if !expected.eq_ignore_ascii_case(reference.as_bytes()) {
+        Err(Error::RefMismatchError(
+            chrom.to_string(),
+            position,
+            reference.to_string(),
+            String::from_utf8_lossy(&expected).into_owned(),
+        ))?
+    }
+
+    if policy.passes_through(alternate) {
+        return Ok(NormalizedVariant {
+            position,
+            reference: reference.to_ascii_uppercase(),
+            alternate: alternate.to_string(),
+        });
+    }
+
+    let mut r = reference.to_ascii_uppercase().into_bytes();
+    let mut a = alternate.to_ascii_uppercase().into_bytes();
+    let mut p = position;
+
+    while !r.is_empty() && !a.is_empty() && r.last() == a.last() {
+        r.pop();
+        a.pop();
+    }
+    let n = r.iter().zip(&a).take_while(|(x, y)| x == y).count();
+    r.drain(..n);
+    a.drain(..n);
+    p += n as u64;
+
+    if r.is_empty() == a.is_empty() {
+        let mut options = *options;
+        options.validation.lowercase = Lowercase::Uppercase;
+
+        return normalize_owned_with(
+            position,
+            reference.to_string(),
+            alternate.to_string(),
+            &options,
+        );
+    }
+
+    let span = r.len() as u64;
+    let indel = if r.is_empty() { &mut a } else { &mut r };
+
+    match options.direction {
+        Direction::Left => {
+            while let Some(b) = sequence.base(chrom, p - 1)? {
+                if Some(&b) != indel.last() {
+                    break;
+                }
+                indel.rotate_right(1);
+                p -= 1;
+            }
+        }
+        Direction::Right => {
+            while let Some(b) = sequence.base(chrom, p + span)? {
+                if b != indel[0] {
+                    break;
+                }
+                indel.rotate_left(1);
+                p += 1;
+            }
+        }
+    }
+
+    if let Some(b) = sequence.base(chrom, p - 1)? {
+        r.insert(0, b);
+        a.insert(0, b);
+        p -= 1;
+    } else {
+        let b = sequence
+            .base(chrom, p + span)?
+            .ok_or_else(|| Error::UnknownContigError(chrom.to_string()))?;
+        r.push(b);
+        a.push(b);
+    }
+
+    Ok(NormalizedVariant {
+        position: p,
+        reference: String::from_utf8_lossy(&r).into_owned(),
+        alternate: String::from_utf8_lossy(&a).into_owned(),
+    })
 }
 
 pub fn variant_type(reference: &str, alternate: &str) -> Option<VariantType> {
@@ -46,9 +303,78 @@ pub fn variant_type(reference: &str, alternate: &str) -> Option<VariantType> {
     }
 }
 
+fn trim<'b>(
+    position: u64,
+    reference: &'b str,
+    alternate: &'b str,
+    trimming: Trimming,
+) -> (u64, &'b str, &'b str) {
+    match trimming {
+        Trimming::Sequential => {
+            let (r, a) = trim_trailing_shared_bases(reference, alternate);
+            trim_leading_shared_bases(position, r, a)
+        }
+        Trimming::FullParsimony => {
+            let trimmed = trim_both_ends(position, reference, alternate);
+            debug_assert_eq!(trimmed, trim_iterated(position, reference, alternate));
+            trimmed
+        }
+    }
+}
+
+/// Cuts the shared suffix and the shared prefix of the original alleles in
+/// one step, keeping one anchor base when an allele would become empty.
+///
+/// The suffix is measured first so that indels end up left-aligned, as with
+/// [`Trimming::Sequential`].
+fn trim_both_ends<'b>(
+    position: u64,
+    reference: &'b str,
+    alternate: &'b str,
+) -> (u64, &'b str, &'b str) {
+    let min = reference.len().min(alternate.len());
+    let mut suffix = count_shared(&mut reference.bytes().rev(), &mut alternate.bytes().rev());
+    if suffix == min {
+        suffix -= 1;
+    }
+    let mut prefix = count_shared(&mut reference.bytes(), &mut alternate.bytes()).min(min - suffix);
+
+    if prefix + suffix == min {
+        if prefix > 0 {
+            prefix -= 1;
+        } else {
+            suffix -= 1;
+        }
+    }
+
+    (
+        position + prefix as u64,
+        &reference[prefix..reference.len() - suffix],
+        &alternate[prefix..alternate.len() - suffix],
+    )
+}
+
+/// Trims both ends alternately until nothing changes.
+fn trim_iterated<'b>(
+    position: u64,
+    reference: &'b str,
+    alternate: &'b str,
+) -> (u64, &'b str, &'b str) {
+    let mut current = (position, reference, alternate);
+
+    loop {
+        let (r, a) = trim_trailing_shared_bases(current.1, current.2);
+        let next = trim_leading_shared_bases(current.0, r, a);
+        if next == current {
+            return current;
+        }
+        current = next;
+    }
+}
+
 fn trim_trailing_shared_bases<'b>(reference: &'b str, alternate: &'b str) -> (&'b str, &'b str) {
-    let mut itr_r = reference.chars().rev();
-    let mut itr_a = alternate.chars().rev();
+    let mut itr_r = reference.bytes().rev();
+    let mut itr_a = alternate.bytes().rev();
     let i = count_shared(&mut itr_r, &mut itr_a);
 
     let mut p1 = reference.len() - i;
@@ -67,8 +393,8 @@ fn trim_leading_shared_bases<'b>(
     reference: &'b str,
     alternate: &'b str,
 ) -> (u64, &'b str, &'b str) {
-    let mut itr_r = reference.chars();
-    let mut itr_a = alternate.chars();
+    let mut itr_r = reference.bytes();
+    let mut itr_a = alternate.bytes();
     let mut i = count_shared(&mut itr_r, &mut itr_a);
 
     if i == reference.len() || i == alternate.len() {
@@ -78,11 +404,12 @@ fn trim_leading_shared_bases<'b>(
     (position + i as u64, &reference[i..], &alternate[i..])
 }
 
-fn count_shared<T: Iterator<Item = char>>(itr_r: &mut T, itr_a: &mut T) -> usize {
+/// Counts shared bytes, which are the bases of validated alleles.
+fn count_shared<T: Iterator<Item = u8>>(itr_r: &mut T, itr_a: &mut T) -> usize {
     let mut i = 0;
 
     while let (Some(c1), Some(c2)) = (itr_r.next(), itr_a.next()) {
-        if c1 == c2 {
+        if c1.eq_ignore_ascii_case(&c2) {
             i += 1
         } else {
             break;
@@ -92,9 +419,396 @@ fn count_shared<T: Iterator<Item = char>>(itr_r: &mut T, itr_a: &mut T) -> usize
     i
 }
 
+/// A single VCF data line.
+///
+/// Missing values (`.`) are represented by empty collections or `None`.
+/// INFO values and sample fields are kept as text and decoded on access.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    pub chrom: String,
+    pub pos: u64,
+    pub ids: Vec<String>,
+    pub reference: String,
+    pub alternates: Vec<String>,
+    pub qual: Option<f64>,
+    pub filters: Vec<String>,
+    /// INFO entries in order; flags have no value.
+    pub info: Vec<(String, Option<String>)>,
+    pub format: Vec<String>,
+    /// Per-sample values, one entry per FORMAT key.
+    pub samples: Vec<Vec<String>>,
+}
+
+impl Record {
+    pub fn new(chrom: &str, pos: u64, reference: &str, alternates: &[&str]) -> Self {
+        Self {
+            chrom: chrom.to_string(),
+            pos,
+            ids: Vec::new(),
+            reference: reference.to_string(),
+            alternates: alternates.iter().map(|a| a.to_string()).collect(),
+            qual: None,
+            filters: Vec::new(),
+            info: Vec::new(),
+            format: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if the INFO key is present, including flags.
+    pub fn has_info(&self, key: &str) -> bool {
+        self.info.iter().any(|(k, _)| k == key)
+    }
+
+    /// Returns the raw value of an INFO key; flags and absent keys yield `None`.
+    pub fn info(&self, key: &str) -> Option<&str> {
+        self.info
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Sets an INFO key, replacing any existing value in place.
+    pub fn set_info(&mut self, key: &str, value: Option<String>) {
+        match self.info.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.info.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn remove_info(&mut self, key: &str) {
+        self.info.retain(|(k, _)| k != key);
+    }
+
+    pub fn format_index(&self, key: &str) -> Option<usize> {
+        self.format.iter().position(|k| k == key)
+    }
+
+    /// Returns the raw value of a FORMAT key for a sample.
+    ///
+    /// Trailing fields dropped from a sample column yield `None`.
+    pub fn format_value(&self, sample: usize, key: &str) -> Option<&str> {
+        let i = self.format_index(key)?;
+
+        self.samples.get(sample)?.get(i).map(|v| v.as_str())
+    }
+
+    /// Sets a FORMAT value for a sample, adding the key to FORMAT if needed.
+    ///
+    /// Fields of the sample before the key are padded with `.`; other samples
+    /// are left as they are, their missing trailing fields reading as
+    /// missing. Fails, leaving the record unchanged, if there is no such
+    /// sample.
+    pub fn set_format_value(&mut self, sample: usize, key: &str, value: &str) -> Result<()> {
+        if sample >= self.samples.len() {
+            Err(Error::SampleIndexError(sample))?
+        }
+
+        let i = match self.format_index(key) {
+            Some(i) => i,
+            None => {
+                self.format.push(key.to_string());
+                self.format.len() - 1
+            }
+        };
+
+        let values = &mut self.samples[sample];
+        if values.len() <= i {
+            values.resize(i + 1, ".".to_string());
+        }
+        values[i] = value.to_string();
+
+        Ok(())
+    }
+
+    /// Last position covered by the record: the end of REF, or INFO `END` if
+    /// it lies further.
+    pub fn end(&self) -> u64 {
+        let end = self.pos + self.reference.len().max(1) as u64 - 1;
+
+        self.info("END")
+            .and_then(|v| v.parse().ok())
+            .map_or(end, |e: u64| e.max(end))
+    }
+
+    /// Returns `true` for gVCF reference blocks, whose ALT alleles are all
+    /// `<NON_REF>` or `<*>`.
+    pub fn is_reference_block(&self) -> bool {
+        !self.alternates.is_empty() && self.alternates.iter().all(|a| is_non_ref(a))
+    }
+
+    /// Positions covered by a gVCF reference block, or `None` for other
+    /// records.
+    pub fn block_range(&self) -> Option<RangeInclusive<u64>> {
+        self.is_reference_block().then(|| self.pos..=self.end())
+    }
+
+    /// ALT alleles other than `<NON_REF>` and `<*>`.
+    pub fn variant_alternates(&self) -> impl Iterator<Item = &String> {
+        self.alternates.iter().filter(|a| !is_non_ref(a))
+    }
+
+    /// Trimmed, uppercased form of each ALT allele, for comparing records
+    /// written with different padding.
+    ///
+    /// Symbolic alleles, and alleles that fail to normalize, are kept as
+    /// written.
+    pub fn normalized_alternates(&self) -> Vec<NormalizedVariant> {
+        self.alternates
+            .iter()
+            .map(|alt| {
+                let raw = || NormalizedVariant {
+                    position: self.pos,
+                    reference: self.reference.to_ascii_uppercase(),
+                    alternate: alt.to_ascii_uppercase(),
+                };
+                if is_symbolic(alt) {
+                    return raw();
+                }
+                normalize_owned(self.pos, self.reference.clone(), alt.clone())
+                    .map(|mut v| {
+                        v.reference.make_ascii_uppercase();
+                        v.alternate.make_ascii_uppercase();
+                        v
+                    })
+                    .unwrap_or_else(|_| raw())
+            })
+            .collect()
+    }
+
+    /// Parses the `GT` value of a sample; `None` if the sample has no `GT`.
+    pub fn genotype(&self, sample: usize) -> Result<Option<Genotype>> {
+        self.format_value(sample, "GT").map(str::parse).transpose()
+    }
+
+    /// Sets the `GT` of a sample, as [`Record::set_format_value`].
+    pub fn set_genotype(&mut self, sample: usize, genotype: &Genotype) -> Result<()> {
+        self.set_format_value(sample, "GT", &genotype.to_string())
+    }
+
+    /// Keeps the ALT alleles for which `keep` is `true`; alleles past the end
+    /// of `keep` are kept.
+    ///
+    /// INFO and FORMAT values declared with `Number=A`, `R` or `G` in `header`
+    /// are subset to match, and `GT` is renumbered with removed alleles
+    /// becoming missing. Values whose count does not match the declaration are
+    /// left untouched.
+    pub fn retain_alternates(&mut self, header: &Header, keep: &[bool]) {
+        let keep: Vec<bool> = (0..self.alternates.len())
+            .map(|i| keep.get(i).copied().unwrap_or(true))
+            .collect();
+
+        AlleleRemap::retain(&keep).apply(header, self);
+
+        let mut i = 0;
+        self.alternates.retain(|_| {
+            i += 1;
+            keep[i - 1]
+        });
+    }
+
+    /// Reorders the ALT alleles by descending `AC`, so the first ALT is the
+    /// most common one; see [`Record::reorder_alts_by`].
+    ///
+    /// Records without an `AC` value per ALT are left unchanged. Missing
+    /// counts sort last.
+    pub fn reorder_alts_by_frequency(&mut self, header: &Header) -> bool {
+        let Some(counts) = self.info("AC") else {
+            return false;
+        };
+        let frequencies: Vec<f64> = counts
+            .split(',')
+            .map(|c| c.parse().unwrap_or(f64::NEG_INFINITY))
+            .collect();
+
+        self.reorder_alts_by(header, &frequencies)
+    }
+
+    /// Reorders the ALT alleles by descending `frequencies`, one per ALT;
+    /// ties keep their order. Returns `false`, leaving the record unchanged,
+    /// if the order is already right or `frequencies` has the wrong length.
+    ///
+    /// INFO and FORMAT values declared with `Number=A`, `R` or `G` in `header`
+    /// are reordered to match, and `GT` is renumbered.
+    pub fn reorder_alts_by(&mut self, header: &Header, frequencies: &[f64]) -> bool {
+        if frequencies.len() != self.alternates.len() {
+            return false;
+        }
+
+        let mut order: Vec<usize> = (0..self.alternates.len()).collect();
+        order.sort_by(|&a, &b| frequencies[b].total_cmp(&frequencies[a]));
+        if order.iter().enumerate().all(|(i, &j)| i == j) {
+            return false;
+        }
+
+        self.select_alternates(header, &order);
+        true
+    }
+
+    /// Keeps the ALT alleles at `order`, counted from 0, in that order;
+    /// indices past the end and repeated indices are ignored.
+    ///
+    /// INFO and FORMAT values declared with `Number=A`, `R` or `G` in `header`
+    /// are subset and reordered to match, including `G` values such as `PL`
+    /// of any ploidy up to [`MAX_PLOIDY`](crate::alleles::MAX_PLOIDY). `GT` is
+    /// renumbered with removed alleles becoming missing.
+    pub fn select_alternates(&mut self, header: &Header, order: &[usize]) {
+        let mut seen = vec![false; self.alternates.len()];
+        let order: Vec<usize> = order
+            .iter()
+            .copied()
+            .filter(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true))
+            .collect();
+
+        AlleleRemap::select(self.alternates.len(), &order).apply(header, self);
+
+        self.alternates = order.iter().map(|&i| self.alternates[i].clone()).collect();
+    }
+
+    /// Builds a samples × values matrix for a FORMAT key.
+    ///
+    /// The number of columns is the largest value count among samples; shorter
+    /// rows, missing values (`.`) and values that fail to parse are `None`.
+    #[cfg(feature = "ndarray")]
+    pub fn format_matrix<T: FromStr + Clone>(&self, key: &str) -> Array2<Option<T>> {
+        let rows: Vec<Vec<Option<T>>> = (0..self.samples.len())
+            .map(|i| match self.format_value(i, key) {
+                Some(".") | None => Vec::new(),
+                Some(v) => v.split(',').map(|x| x.parse().ok()).collect(),
+            })
+            .collect();
+
+        let ncols = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+
+        Array2::from_shape_fn((rows.len(), ncols), |(i, j)| {
+            rows[i].get(j).cloned().flatten()
+        })
+    }
+}
+
+impl FromStr for Record {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let columns: Vec<&str> = line.split('\t').collect();
+
+        if columns.len() < 8 {
+            Err(Error::RecordColumnsError(columns.len()))?
+        }
+
+        let pos = columns[1]
+            .parse()
+            .map_err(|_| Error::RecordPositionError(columns[1].to_string()))?;
+
+        let qual = match columns[5] {
+            "." => None,
+            q => Some(
+                q.parse()
+                    .map_err(|_| Error::RecordQualError(q.to_string()))?,
+            ),
+        };
+
+        let info = match columns[7] {
+            "." => Vec::new(),
+            s => s
+                .split(';')
+                .map(|kv| match kv.split_once('=') {
+                    Some((k, v)) => (k.to_string(), Some(v.to_string())),
+                    None => (kv.to_string(), None),
+                })
+                .collect(),
+        };
+
+        let (format, samples) = match columns.get(8) {
+            Some(f) => (
+                split_field(f, ':'),
+                columns[9..]
+                    .iter()
+                    .map(|s| s.split(':').map(|v| v.to_string()).collect())
+                    .collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        Ok(Self {
+            chrom: columns[0].to_string(),
+            pos,
+            ids: split_field(columns[2], ';'),
+            reference: columns[3].to_string(),
+            alternates: split_field(columns[4], ','),
+            qual,
+            filters: split_field(columns[6], ';'),
+            info,
+            format,
+            samples,
+        })
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t",
+            self.chrom,
+            self.pos,
+            join_field(&self.ids, ";"),
+            self.reference,
+            join_field(&self.alternates, ",")
+        )?;
+
+        match self.qual {
+            Some(q) => write!(f, "{}\t", q)?,
+            None => write!(f, ".\t")?,
+        }
+
+        write!(f, "{}\t", join_field(&self.filters, ";"))?;
+
+        if self.info.is_empty() {
+            write!(f, ".")?;
+        }
+        for (i, (k, v)) in self.info.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            match v {
+                Some(v) => write!(f, "{}={}", k, v)?,
+                None => write!(f, "{}", k)?,
+            }
+        }
+
+        if !self.format.is_empty() {
+            write!(f, "\t{}", self.format.join(":"))?;
+            for sample in &self.samples {
+                write!(f, "\t{}", sample.join(":"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn split_field(s: &str, separator: char) -> Vec<String> {
+    match s {
+        "." => Vec::new(),
+        s => s.split(separator).map(|v| v.to_string()).collect(),
+    }
+}
+
+fn join_field(values: &[String], separator: &str) -> String {
+    if values.is_empty() {
+        ".".to_string()
+    } else {
+        values.join(separator)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::reference::MemoryReference;
 
     #[test]
     fn test_normalize_1() {
@@ -185,4 +899,395 @@ mod tests {
     fn test_normalize_err_4() {
         assert!(normalize(1000, "A", ".").is_err());
     }
+
+    #[test]
+    fn test_normalize_with_1() {
+        let options = NormalizeOptions {
+            validation: ValidationPolicy::strict(),
+            ..Default::default()
+        };
+
+        assert!(normalize_with(1000, "AT", "ATC", &options).is_ok());
+        assert!(normalize_with(1000, "AR", "A", &options).is_err());
+    }
+
+    #[test]
+    fn test_normalize_with_2() {
+        let options = NormalizeOptions {
+            validation: ValidationPolicy::lowercase_tolerant(false),
+            ..Default::default()
+        };
+        let (p, r, a) = normalize_with(1000, "acTCC", "AGTtcc", &options).unwrap();
+
+        assert_eq!(p, 1001);
+        assert_eq!(r, "c");
+        assert_eq!(a, "GT");
+        assert!(matches!(r, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_with_3() {
+        let options = NormalizeOptions {
+            validation: ValidationPolicy::lowercase_tolerant(true),
+            ..Default::default()
+        };
+        let (p, r, a) = normalize_with(1000, "atcc", "ataCC", &options).unwrap();
+
+        assert_eq!(p, 1001);
+        assert_eq!(r, "T");
+        assert_eq!(a, "TA");
+    }
+
+    #[test]
+    fn test_normalize_with_4() {
+        let options = NormalizeOptions {
+            validation: ValidationPolicy::symbolic_tolerant(),
+            ..Default::default()
+        };
+        let (p, r, a) = normalize_with(1000, "AT", "<DEL>", &options).unwrap();
+
+        assert_eq!(p, 1000);
+        assert_eq!(r, "AT");
+        assert_eq!(a, "<DEL>");
+        assert!(normalize(1000, "AT", "<DEL>").is_err());
+    }
+
+    #[test]
+    fn test_normalize_owned_1() {
+        let reference = String::from("ACTCC");
+        let alternate = String::from("AGTTCC");
+        let v = normalize_owned(1000, reference, alternate).unwrap();
+
+        assert_eq!(v.position, 1001);
+        assert_eq!(v.reference, "C");
+        assert_eq!(v.alternate, "GT");
+        assert_eq!(v.variant_type(), Some(VariantType::Indel));
+    }
+
+    #[test]
+    fn test_normalize_owned_2() {
+        let reference = String::from("A");
+        let alternate = String::from("AT");
+        let (r_ptr, a_ptr) = (reference.as_ptr(), alternate.as_ptr());
+        let v = normalize_owned(1000, reference, alternate).unwrap();
+
+        assert_eq!(v.reference.as_ptr(), r_ptr);
+        assert_eq!(v.alternate.as_ptr(), a_ptr);
+        assert_eq!(v.variant_type(), Some(VariantType::Insertion));
+    }
+
+    #[test]
+    fn test_normalize_owned_3() {
+        let options = NormalizeOptions {
+            validation: ValidationPolicy::lowercase_tolerant(true),
+            ..Default::default()
+        };
+        let v =
+            normalize_owned_with(1000, "atcc".to_string(), "ataCC".to_string(), &options).unwrap();
+
+        assert_eq!(v.position, 1001);
+        assert_eq!(v.reference, "T");
+        assert_eq!(v.alternate, "TA");
+        assert!(normalize_owned(1000, "".to_string(), "A".to_string()).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_normalize_batch_1() {
+        let variants: Vec<(u64, &str, &str)> = (0..5000)
+            .map(|i| match i % 3 {
+                0 => (i, "ATCC", "ATACC"),
+                1 => (i, "A", "T"),
+                _ => (i, "A", "."),
+            })
+            .collect();
+        let results = normalize_batch(&variants);
+
+        assert_eq!(results.len(), 5000);
+        assert_eq!(
+            results[3].as_ref().unwrap(),
+            &(4, "T", "TA", Some(VariantType::Insertion))
+        );
+        assert_eq!(
+            results[4].as_ref().unwrap(),
+            &(4, "A", "T", Some(VariantType::SNV))
+        );
+        assert!(results[4999].is_ok());
+        assert!(results[5].is_err());
+    }
+
+    fn genome() -> MemoryReference {
+        let mut genome = MemoryReference::new();
+        // 1-based:      1234567890
+        genome.insert("1", "TTCAAAAGGG");
+        genome
+    }
+
+    #[test]
+    fn test_normalize_with_5() {
+        let options = NormalizeOptions {
+            trimming: Trimming::FullParsimony,
+            ..Default::default()
+        };
+
+        // Haplotype-padded alleles as emitted by MNV-heavy callers.
+        let cases = [
+            ((100, "ACGTACGT", "ACGTTCGT"), (104, "A", "T")),
+            ((100, "TTGACCA", "TTCACGA"), (102, "GACC", "CACG")),
+            ((100, "ACGTTTA", "ACGTTA"), (102, "GT", "G")),
+            ((100, "ACGTA", "ACGTCGTA"), (100, "A", "ACGT")),
+            ((100, "ACAC", "AC"), (100, "ACA", "A")),
+            ((100, "GATTACA", "GATTACA"), (100, "G", "G")),
+            ((100, "CGTACG", "CGGACG"), (102, "T", "G")),
+        ];
+
+        for ((pos, r, a), expected) in cases {
+            let (p, r2, a2) = normalize_with(pos, r, a, &options).unwrap();
+            assert_eq!((p, r2.as_ref(), a2.as_ref()), expected, "{} {}", r, a);
+            assert_eq!(
+                (p, r2.as_ref(), a2.as_ref()),
+                trim_iterated(pos, r, a),
+                "{} {}",
+                r,
+                a
+            );
+
+            let (p, r2, a2) = normalize_with(pos, r, a, &NormalizeOptions::default()).unwrap();
+            assert_eq!((p, r2.as_ref(), a2.as_ref()), expected, "{} {}", r, a);
+        }
+    }
+
+    #[test]
+    fn test_normalize_with_6() {
+        for trimming in [Trimming::Sequential, Trimming::FullParsimony] {
+            let options = NormalizeOptions {
+                validation: ValidationPolicy::lowercase_tolerant(false),
+                trimming,
+                ..Default::default()
+            };
+
+            assert!(normalize_with(1, "A\u{17f}", "A\u{17f}\u{17f}", &options).is_err());
+            assert!(normalize_with(1, "A\u{212a}", "Ak", &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_normalize_with_reference_1() {
+        let left = NormalizeOptions::default();
+        let right = NormalizeOptions {
+            direction: Direction::Right,
+            ..Default::default()
+        };
+
+        let v = normalize_with_reference("1", 7, "A", "AA", &genome(), &left).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (3, "C", "CA")
+        );
+
+        let v = normalize_with_reference("1", 3, "C", "CA", &genome(), &right).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (7, "A", "AA")
+        );
+    }
+
+    #[test]
+    fn test_normalize_with_reference_2() {
+        let left = NormalizeOptions::default();
+        let right = NormalizeOptions {
+            direction: Direction::Right,
+            ..Default::default()
+        };
+
+        let v = normalize_with_reference("1", 5, "AAA", "A", &genome(), &left).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (3, "CAA", "C")
+        );
+
+        let v = normalize_with_reference("1", 3, "CAAAAG", "CAAAG", &genome(), &right).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (6, "AA", "A")
+        );
+    }
+
+    #[test]
+    fn test_normalize_with_reference_3() {
+        let options = NormalizeOptions::default();
+
+        let v = normalize_with_reference("1", 2, "TC", "C", &genome(), &options).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (1, "TT", "T")
+        );
+
+        let v = normalize_with_reference("1", 3, "CAA", "CGA", &genome(), &options).unwrap();
+        assert_eq!(
+            (v.position, v.reference.as_str(), v.alternate.as_str()),
+            (4, "A", "G")
+        );
+    }
+
+    #[test]
+    fn test_normalize_with_reference_err_1() {
+        let options = NormalizeOptions::default();
+
+        assert!(normalize_with_reference("1", 3, "G", "GA", &genome(), &options).is_err());
+        assert!(normalize_with_reference("2", 3, "C", "CA", &genome(), &options).is_err());
+        assert!(normalize_with_reference("1", 3, "C", "", &genome(), &options).is_err());
+    }
+
+    #[test]
+    fn test_record_parse_1() {
+        let line =
+            "1\t1000\trs1;rs2\tA\tT,AT\t30.5\tPASS\tDP=10;DB;AF=0.5,0.1\tGT:AD\t0/1:5,5,0\t./.";
+        let record: Record = line.parse().unwrap();
+
+        assert_eq!(record.chrom, "1");
+        assert_eq!(record.pos, 1000);
+        assert_eq!(record.ids, vec!["rs1", "rs2"]);
+        assert_eq!(record.alternates, vec!["T", "AT"]);
+        assert_eq!(record.qual, Some(30.5));
+        assert_eq!(record.info("DP"), Some("10"));
+        assert_eq!(record.info("DB"), None);
+        assert!(record.has_info("DB"));
+        assert_eq!(record.format_value(0, "AD"), Some("5,5,0"));
+        assert_eq!(record.format_value(1, "AD"), None);
+        assert_eq!(record.to_string(), line);
+    }
+
+    #[test]
+    fn test_record_parse_2() {
+        let line = "chrX\t5\t.\tG\t.\t.\t.\t.\n";
+        let record: Record = line.parse().unwrap();
+
+        assert!(record.ids.is_empty());
+        assert!(record.alternates.is_empty());
+        assert_eq!(record.qual, None);
+        assert!(record.info.is_empty());
+        assert_eq!(record.to_string(), line.trim_end());
+    }
+
+    #[test]
+    fn test_record_parse_err_1() {
+        assert!("1\t1000\t.\tA".parse::<Record>().is_err());
+        assert!("1\tx\t.\tA\tT\t.\t.\t.".parse::<Record>().is_err());
+        assert!("1\t1\t.\tA\tT\tq\t.\t.".parse::<Record>().is_err());
+    }
+
+    #[test]
+    fn test_record_set_format_value_1() {
+        let mut record: Record = "1\t1\t.\tA\tT\t.\t.\t.\tGT\t0/1\t1/1".parse().unwrap();
+        record.set_format_value(1, "DP", "12").unwrap();
+
+        assert_eq!(
+            record.to_string(),
+            "1\t1\t.\tA\tT\t.\t.\t.\tGT:DP\t0/1\t1/1:12"
+        );
+        assert_eq!(record.format_value(0, "DP"), None);
+
+        record.set_format_value(0, "AD", "3,4").unwrap();
+        assert_eq!(
+            record.to_string(),
+            "1\t1\t.\tA\tT\t.\t.\t.\tGT:DP:AD\t0/1:.:3,4\t1/1:12"
+        );
+
+        assert!(matches!(
+            record.set_format_value(2, "GQ", "30"),
+            Err(Error::SampleIndexError(2))
+        ));
+        assert_eq!(record.format_index("GQ"), None);
+    }
+
+    #[test]
+    fn test_record_genotype_1() {
+        let mut record: Record = "1\t1\t.\tA\tT\t.\t.\t.\tGT:DP\t0|1:3\t.:4\tx"
+            .parse()
+            .unwrap();
+
+        assert!(record.genotype(0).unwrap().unwrap().is_phased());
+        assert!(record.genotype(1).unwrap().unwrap().is_missing());
+        assert!(record.genotype(2).is_err());
+        assert_eq!(record.genotype(3).unwrap(), None);
+
+        record
+            .set_genotype(1, &Genotype::unphased(&[Some(0), Some(0)]))
+            .unwrap();
+
+        assert_eq!(record.format_value(1, "GT"), Some("0/0"));
+    }
+
+    #[test]
+    fn test_reorder_alts_by_frequency_1() {
+        let header: Header = "##fileformat=VCFv4.3\n\
+            ##INFO=<ID=AC,Number=A,Type=Integer,Description=\"Count\">\n\
+            ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Depth\">\n"
+            .parse()
+            .unwrap();
+        let mut record: Record =
+            "1\t1\t.\tA\tC,G,T\t.\t.\tAC=1,5,.\tGT:AD\t0/2:3,1,6,0\t3|1:0,2,0,4"
+                .parse()
+                .unwrap();
+
+        assert!(record.reorder_alts_by_frequency(&header));
+        assert_eq!(
+            record.to_string(),
+            "1\t1\t.\tA\tG,C,T\t.\t.\tAC=5,1,.\tGT:AD\t0/1:3,6,1,0\t3|2:0,0,2,4"
+        );
+        assert!(!record.reorder_alts_by_frequency(&header));
+        assert!(!record.reorder_alts_by(&header, &[0.1, 0.2]));
+        assert!(record.reorder_alts_by(&header, &[0.1, 0.2, 0.3]));
+        assert_eq!(record.alternates, vec!["T", "C", "G"]);
+    }
+
+    #[test]
+    fn test_select_alternates_1() {
+        let header: Header = "##fileformat=VCFv4.3\n\
+            ##INFO=<ID=AF,Number=A,Type=Float,Description=\"Frequency\">\n\
+            ##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"Likelihoods\">\n"
+            .parse()
+            .unwrap();
+        let mut record: Record =
+            "1\t1\t.\tA\tC,G\t.\t.\tAF=0.1,0.2\tGT:PL\t1/2:0,1,2,3,4,5\t0/0/1:0,1,2,3,4,5,6,7,8,9"
+                .parse()
+                .unwrap();
+
+        record.select_alternates(&header, &[1, 5, 1]);
+        assert_eq!(
+            record.to_string(),
+            "1\t1\t.\tA\tG\t.\t.\tAF=0.2\tGT:PL\t./1:0,3,5\t0/0/.:0,4,7,9"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_record_serde_1() {
+        let record: Record = "1\t1000\trs1\tA\tT,AT\t30.5\tPASS\tDP=10;DB\tGT\t0/1"
+            .parse()
+            .unwrap();
+        let json = serde_json::to_string(&record).unwrap();
+
+        assert!(json.contains("\"info\":[[\"DP\",\"10\"],[\"DB\",null]]"));
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+        assert_eq!(
+            serde_json::to_string(&VariantType::Deletion).unwrap(),
+            "\"Deletion\""
+        );
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_format_matrix_1() {
+        let line = "1\t1\t.\tA\tT,G\t.\t.\t.\tGT:AD\t0/1:5,5,0\t./.:.\t1/1:0,x";
+        let record: Record = line.parse().unwrap();
+        let m: Array2<Option<i32>> = record.format_matrix("AD");
+
+        assert_eq!(m.dim(), (3, 3));
+        assert_eq!(m.row(0).to_vec(), vec![Some(5), Some(5), Some(0)]);
+        assert_eq!(m.row(1).to_vec(), vec![None, None, None]);
+        assert_eq!(m.row(2).to_vec(), vec![Some(0), None, None]);
+        assert_eq!(record.format_matrix::<i32>("PL").dim(), (3, 0));
+    }
 }
@@ -0,0 +1,248 @@
+//! Reference genome sequence providers.
+
+use crate::errors::{Error, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Random access to reference bases by 1-based inclusive coordinates.
+pub trait ReferenceSequence {
+    /// Length of `chrom`, or `None` if the contig is unknown.
+    fn length(&self, chrom: &str) -> Option<u64>;
+
+    /// Returns the uppercase bases in `start..=end`, clipped to the contig end.
+    fn fetch(&self, chrom: &str, start: u64, end: u64) -> Result<Vec<u8>>;
+
+    /// Returns the base at `pos`, or `None` outside the contig.
+    fn base(&self, chrom: &str, pos: u64) -> Result<Option<u8>> {
+        if pos == 0 {
+            return Ok(None);
+        }
+
+        Ok(self.fetch(chrom, pos, pos)?.first().copied())
+    }
+}
+
+/// Reference sequences held in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryReference {
+    sequences: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryReference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, chrom: &str, sequence: &str) {
+        self.sequences.insert(
+            chrom.to_string(),
+            sequence.to_ascii_uppercase().into_bytes(),
+        );
+    }
+}
+
+impl ReferenceSequence for MemoryReference {
+    fn length(&self, chrom: &str) -> Option<u64> {
+        self.sequences.get(chrom).map(|s| s.len() as u64)
+    }
+
+    fn fetch(&self, chrom: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let sequence = self
+            .sequences
+            .get(chrom)
+            .ok_or_else(|| Error::UnknownContigError(chrom.to_string()))?;
+
+        let start = (start.max(1) - 1) as usize;
+        let end = (end as usize).min(sequence.len());
+
+        Ok(sequence.get(start..end).unwrap_or_default().to_vec())
+    }
+}
+
+/// One line of a samtools `.fai` index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiEntry {
+    pub name: String,
+    pub length: u64,
+    /// Byte offset of the first base.
+    pub offset: u64,
+    /// Bases per full line.
+    pub line_bases: u64,
+    /// Bytes per full line, including the line terminator.
+    pub line_width: u64,
+}
+
+/// A FASTA index, listing contigs in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaiIndex {
+    entries: Vec<FaiEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl FaiIndex {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut index = Self::default();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split('\t').collect();
+            let number = |i: usize| -> Result<u64> {
+                columns
+                    .get(i)
+                    .and_then(|c| c.trim().parse().ok())
+                    .ok_or_else(|| Error::IndexFormatError(line.clone()))
+            };
+            let entry = FaiEntry {
+                name: columns[0].to_string(),
+                length: number(1)?,
+                offset: number(2)?,
+                line_bases: number(3)?,
+                line_width: number(4)?,
+            };
+            if entry.line_bases == 0 || entry.line_width < entry.line_bases {
+                Err(Error::IndexFormatError(line.clone()))?
+            }
+
+            index.names.insert(entry.name.clone(), index.entries.len());
+            index.entries.push(entry);
+        }
+
+        Ok(index)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FaiEntry> {
+        self.names.get(name).map(|&i| &self.entries[i])
+    }
+
+    pub fn entries(&self) -> &[FaiEntry] {
+        &self.entries
+    }
+}
+
+/// An uncompressed FASTA file read through its `.fai` index.
+pub struct IndexedFasta<R> {
+    inner: RefCell<R>,
+    index: FaiIndex,
+}
+
+#[cfg(feature = "fs")]
+impl IndexedFasta<BufReader<File>> {
+    /// Opens `path` with the index at `path.fai`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut fai = path.as_os_str().to_owned();
+        fai.push(".fai");
+        if !Path::new(&fai).exists() {
+            Err(Error::MissingIndexError(path.display().to_string()))?
+        }
+
+        Ok(Self::new(
+            BufReader::new(File::open(path)?),
+            FaiIndex::from_path(fai)?,
+        ))
+    }
+}
+
+impl<R: Read + Seek> IndexedFasta<R> {
+    pub fn new(inner: R, index: FaiIndex) -> Self {
+        Self {
+            inner: RefCell::new(inner),
+            index,
+        }
+    }
+
+    pub fn index(&self) -> &FaiIndex {
+        &self.index
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read + Seek> ReferenceSequence for IndexedFasta<R> {
+    fn length(&self, chrom: &str) -> Option<u64> {
+        self.index.get(chrom).map(|e| e.length)
+    }
+
+    fn fetch(&self, chrom: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let entry = self
+            .index
+            .get(chrom)
+            .ok_or_else(|| Error::UnknownContigError(chrom.to_string()))?;
+
+        let start = start.max(1) - 1;
+        let end = end.min(entry.length);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+
+        // Byte offset of the 0-based base `i`.
+        let offset =
+            |i: u64| entry.offset + i / entry.line_bases * entry.line_width + i % entry.line_bases;
+        let mut bytes = vec![0; (offset(end - 1) + 1 - offset(start)) as usize];
+
+        let mut inner = self.inner.borrow_mut();
+        inner.seek(SeekFrom::Start(offset(start)))?;
+        inner.read_exact(&mut bytes)?;
+
+        bytes.retain(|b| !b.is_ascii_whitespace());
+        bytes.make_ascii_uppercase();
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_reference_1() {
+        let mut reference = MemoryReference::new();
+        reference.insert("1", "acgtAC");
+
+        assert_eq!(reference.length("1"), Some(6));
+        assert_eq!(reference.fetch("1", 2, 4).unwrap(), b"CGT");
+        assert_eq!(reference.fetch("1", 5, 100).unwrap(), b"AC");
+        assert_eq!(reference.base("1", 6).unwrap(), Some(b'C'));
+        assert_eq!(reference.base("1", 7).unwrap(), None);
+        assert_eq!(reference.base("1", 0).unwrap(), None);
+        assert!(reference.fetch("2", 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_indexed_fasta_1() {
+        let fasta = ">1 first\nACGTA\nCGTac\ngt\n>2\nTTTT\n";
+        let fai = "1\t12\t9\t5\t6\n2\t4\t27\t4\t5\n";
+        let index = FaiIndex::from_reader(fai.as_bytes()).unwrap();
+        let reference = IndexedFasta::new(std::io::Cursor::new(fasta), index);
+
+        assert_eq!(reference.length("1"), Some(12));
+        assert_eq!(reference.fetch("1", 1, 12).unwrap(), b"ACGTACGTACGT");
+        assert_eq!(reference.fetch("1", 4, 7).unwrap(), b"TACG");
+        assert_eq!(reference.fetch("1", 11, 100).unwrap(), b"GT");
+        assert_eq!(reference.fetch("2", 2, 3).unwrap(), b"TT");
+        assert_eq!(reference.base("1", 13).unwrap(), None);
+        assert!(reference.fetch("3", 1, 1).is_err());
+
+        assert!(FaiIndex::from_reader("1\t12\tx\t5\t6\n".as_bytes()).is_err());
+    }
+}
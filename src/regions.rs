@@ -0,0 +1,561 @@
+//! Sets of genomic intervals and region filtering of records.
+
+use crate::bgzf;
+use crate::errors::{Error, Result};
+use crate::index::{Chunk, Index};
+use crate::reader::Reader;
+use crate::record::Record;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{BufRead, Read, Seek};
+
+#[cfg(feature = "fs")]
+use crate::header::Header;
+#[cfg(feature = "fs")]
+use crate::index::find_index;
+#[cfg(feature = "fs")]
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Intervals grouped by contig, merged as they are inserted.
+///
+/// Coordinates are 1-based and inclusive like VCF positions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionSet {
+    contigs: HashMap<String, BTreeMap<u64, u64>>,
+}
+
+impl RegionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the intervals of a BED file.
+    ///
+    /// Only the first three columns are used; `track`, `browser` and `#`
+    /// lines are skipped.
+    pub fn from_bed<R: BufRead>(reader: R) -> Result<Self> {
+        let mut regions = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            let (chrom, start, end) = match (columns.next(), columns.next(), columns.next()) {
+                (Some(chrom), Some(start), Some(end)) => (chrom, start, end),
+                _ => Err(Error::RegionFormatError(line.to_string()))?,
+            };
+            // BED intervals are 0-based and half-open.
+            let start = start
+                .parse::<u64>()
+                .ok()
+                .and_then(|start| start.checked_add(1))
+                .ok_or_else(|| Error::RegionFormatError(line.to_string()))?;
+            let end: u64 = end
+                .parse()
+                .map_err(|_| Error::RegionFormatError(line.to_string()))?;
+
+            regions.insert(chrom, start, end);
+        }
+
+        Ok(regions)
+    }
+
+    #[cfg(feature = "fs")]
+    /// Reads a plain or gzip compressed BED file.
+    pub fn from_bed_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        if bgzf::is_gzip(file.fill_buf()?) {
+            Self::from_bed(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Self::from_bed(file)
+        }
+    }
+
+    /// Parses regions written as `chrom`, `chrom:pos`, `chrom:start-end` or
+    /// `chrom:start-`, 1-based and inclusive.
+    ///
+    /// Numbers may contain `,` separators. A `chrom:pos` region extends to
+    /// the end of the contig, like a region without coordinates.
+    pub fn from_regions<I, S>(regions: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut set = Self::new();
+        for region in regions {
+            let (chrom, start, end) = parse_region(region.as_ref())?;
+            set.insert(&chrom, start, end);
+        }
+
+        Ok(set)
+    }
+
+    /// Adds `start..=end`, merging it with overlapping or adjacent intervals.
+    pub fn insert(&mut self, chrom: &str, start: u64, end: u64) {
+        if end < start {
+            return;
+        }
+
+        let intervals = self.contigs.entry(chrom.to_string()).or_default();
+        let (mut start, mut end) = (start, end);
+
+        let touching: Vec<(u64, u64)> = intervals
+            .range(..=end.saturating_add(1))
+            .rev()
+            .take_while(|&(_, &e)| e.saturating_add(1) >= start)
+            .map(|(&s, &e)| (s, e))
+            .collect();
+
+        for (s, e) in touching {
+            intervals.remove(&s);
+            start = start.min(s);
+            end = end.max(e);
+        }
+
+        intervals.insert(start, end);
+    }
+
+    /// Returns `true` if `pos` lies in one of the intervals.
+    pub fn contains(&self, chrom: &str, pos: u64) -> bool {
+        self.covers(chrom, pos, pos)
+    }
+
+    /// Returns `true` if `start..=end` lies entirely within one interval.
+    pub fn covers(&self, chrom: &str, start: u64, end: u64) -> bool {
+        self.containing(chrom, start).is_some_and(|(_, e)| e >= end)
+    }
+
+    /// Returns `true` if any position of `start..=end` lies in an interval.
+    pub fn overlaps(&self, chrom: &str, start: u64, end: u64) -> bool {
+        self.contigs.get(chrom).is_some_and(|intervals| {
+            intervals
+                .range(..=end)
+                .next_back()
+                .is_some_and(|(_, &e)| e >= start)
+        })
+    }
+
+    /// Returns `true` if `record` overlaps an interval.
+    ///
+    /// A record spans its REF allele, or up to its INFO `END`.
+    pub fn overlaps_record(&self, record: &Record) -> bool {
+        self.overlaps(&record.chrom, record.pos, record.end())
+    }
+
+    /// Contigs with intervals, in no particular order.
+    pub fn contigs(&self) -> impl Iterator<Item = &str> {
+        self.contigs
+            .iter()
+            .filter(|(_, intervals)| !intervals.is_empty())
+            .map(|(chrom, _)| chrom.as_str())
+    }
+
+    /// Intervals of `chrom` in ascending order.
+    pub fn intervals(&self, chrom: &str) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.contigs
+            .get(chrom)
+            .into_iter()
+            .flat_map(|intervals| intervals.iter().map(|(&s, &e)| (s, e)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contigs.values().all(|intervals| intervals.is_empty())
+    }
+
+    fn containing(&self, chrom: &str, pos: u64) -> Option<(u64, u64)> {
+        let (&s, &e) = self.contigs.get(chrom)?.range(..=pos).next_back()?;
+
+        (e >= pos).then_some((s, e))
+    }
+}
+
+/// Parses one region of [`RegionSet::from_regions`] into `(chrom, start, end)`.
+pub fn parse_region(region: &str) -> Result<(String, u64, u64)> {
+    let error = || Error::RegionFormatError(region.to_string());
+    let number = |s: &str| s.replace(',', "").parse::<u64>().map_err(|_| error());
+
+    // Contig names may themselves contain `:`, as in HLA alleles; only a
+    // trailing numeric part is read as coordinates.
+    let (chrom, range) = match region.rsplit_once(':') {
+        Some((chrom, range)) if range.starts_with(|c: char| c.is_ascii_digit()) => (chrom, range),
+        _ => (region, ""),
+    };
+    if chrom.is_empty() {
+        Err(error())?
+    }
+
+    let (start, end) = match range.split_once('-') {
+        _ if range.is_empty() => (1, u64::MAX),
+        Some((start, "")) => (number(start)?, u64::MAX),
+        Some((start, end)) => (number(start)?, number(end)?),
+        None => (number(range)?, u64::MAX),
+    };
+    if start == 0 || end < start {
+        Err(error())?
+    }
+
+    Ok((chrom.to_string(), start, end))
+}
+
+/// Keeps the records of a stream that overlap a [`RegionSet`], or those that
+/// do not.
+pub struct RegionFilter<I: Iterator<Item = Result<Record>>> {
+    inner: I,
+    regions: RegionSet,
+    exclude: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> RegionFilter<I> {
+    /// Keeps the records overlapping `regions`.
+    pub fn new(inner: I, regions: RegionSet) -> Self {
+        Self {
+            inner,
+            regions,
+            exclude: false,
+        }
+    }
+
+    /// Keeps the records not overlapping `regions`.
+    pub fn excluding(inner: I, regions: RegionSet) -> Self {
+        Self {
+            inner,
+            regions,
+            exclude: true,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for RegionFilter<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(record) if self.regions.overlaps_record(&record) == self.exclude => continue,
+                item => return Some(item),
+            }
+        }
+    }
+}
+
+/// Records of a BGZF-compressed VCF overlapping a [`RegionSet`], read through
+/// its index.
+///
+/// Only the chunks the index lists for the regions are decompressed.
+/// Records are yielded in file order, each once.
+pub struct RegionQuery<R: Read + Seek> {
+    reader: Reader<bgzf::Reader<R>>,
+    regions: RegionSet,
+    chunks: VecDeque<Chunk>,
+    /// End of the chunk being read.
+    end: Option<u64>,
+}
+
+impl<R: Read + Seek> RegionQuery<R> {
+    pub fn new(reader: Reader<bgzf::Reader<R>>, index: &Index, regions: RegionSet) -> Self {
+        let mut chunks: Vec<Chunk> = regions
+            .contigs()
+            .flat_map(|chrom| {
+                regions
+                    .intervals(chrom)
+                    .flat_map(|(start, end)| index.query(chrom, start, end))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        chunks.sort_by_key(|c| c.start);
+        let mut merged: VecDeque<Chunk> = VecDeque::with_capacity(chunks.len());
+        for chunk in chunks {
+            match merged.back_mut() {
+                Some(last) if chunk.start <= last.end => last.end = last.end.max(chunk.end),
+                _ => merged.push_back(chunk),
+            }
+        }
+
+        Self {
+            reader,
+            regions,
+            chunks: merged,
+            end: None,
+        }
+    }
+
+    pub fn into_inner(self) -> Reader<bgzf::Reader<R>> {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> Iterator for RegionQuery<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let end = match self.end {
+                Some(end) if self.reader.voffset() < end => end,
+                _ => {
+                    let chunk = self.chunks.pop_front()?;
+                    if let Err(e) = self.reader.seek_voffset(chunk.start) {
+                        return Some(Err(e));
+                    }
+                    self.end = Some(chunk.end);
+                    continue;
+                }
+            };
+
+            match self.reader.next() {
+                Some(Ok(record)) if !self.regions.overlaps_record(&record) => {}
+                Some(item) => return Some(item),
+                None => self.end = Some(end.min(self.reader.voffset())),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+/// Records of [`read_regions`].
+pub enum RegionRecords {
+    Indexed(RegionQuery<File>),
+    Filtered(RegionFilter<Reader<Box<dyn BufRead>>>),
+}
+
+#[cfg(feature = "fs")]
+impl Iterator for RegionRecords {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            RegionRecords::Indexed(records) => records.next(),
+            RegionRecords::Filtered(records) => records.next(),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+/// Reads the records of a VCF file overlapping `regions`.
+///
+/// BGZF files with a `.tbi` or `.csi` index next to them are queried through
+/// the index; other files are read in full and filtered.
+pub fn read_regions<P: AsRef<Path>>(
+    path: P,
+    regions: RegionSet,
+) -> Result<(Header, RegionRecords)> {
+    let path = path.as_ref();
+
+    if let Some(index_path) = find_index(path) {
+        let mut file = BufReader::new(File::open(path)?);
+        if bgzf::is_bgzf(file.fill_buf()?) {
+            let index = Index::from_path(index_path)?;
+            let mut file = file.into_inner();
+            file.rewind()?;
+            let reader = Reader::new(bgzf::Reader::new(file))?;
+            let header = reader.header().clone();
+
+            return Ok((
+                header,
+                RegionRecords::Indexed(RegionQuery::new(reader, &index, regions)),
+            ));
+        }
+    }
+
+    let reader = Reader::from_path(path)?;
+    let header = reader.header().clone();
+
+    Ok((
+        header,
+        RegionRecords::Filtered(RegionFilter::new(reader, regions)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_1() {
+        let mut regions = RegionSet::new();
+        regions.insert("1", 10, 20);
+        regions.insert("1", 30, 40);
+        regions.insert("1", 21, 25);
+        regions.insert("1", 5, 5);
+
+        assert_eq!(
+            regions.intervals("1").collect::<Vec<_>>(),
+            vec![(5, 5), (10, 25), (30, 40)]
+        );
+
+        regions.insert("1", 6, 35);
+
+        assert_eq!(regions.intervals("1").collect::<Vec<_>>(), vec![(5, 40)]);
+        assert_eq!(regions.intervals("2").count(), 0);
+    }
+
+    #[test]
+    fn test_covers_1() {
+        let mut regions = RegionSet::new();
+        regions.insert("1", 10, 20);
+        regions.insert("1", 30, 40);
+
+        assert!(regions.contains("1", 10));
+        assert!(regions.contains("1", 20));
+        assert!(!regions.contains("1", 21));
+        assert!(!regions.contains("2", 15));
+        assert!(regions.covers("1", 12, 20));
+        assert!(!regions.covers("1", 15, 30));
+        assert!(regions.overlaps("1", 15, 30));
+        assert!(regions.overlaps("1", 1, 10));
+        assert!(!regions.overlaps("1", 21, 29));
+    }
+    #[test]
+    fn test_from_bed_1() {
+        let bed = "track name=targets\n# comment\n1\t9\t20\tgene\n\n2\t0\t5\n1\t20\t30\n";
+        let regions = RegionSet::from_bed(bed.as_bytes()).unwrap();
+
+        assert_eq!(regions.intervals("1").collect::<Vec<_>>(), vec![(10, 30)]);
+        assert_eq!(regions.intervals("2").collect::<Vec<_>>(), vec![(1, 5)]);
+        assert!(RegionSet::from_bed("1\t9\n".as_bytes()).is_err());
+        assert!(RegionSet::from_bed("1\tx\t9\n".as_bytes()).is_err());
+        assert!(matches!(
+            RegionSet::from_bed(format!("1\t{}\t{}\n", u64::MAX, u64::MAX).as_bytes()),
+            Err(Error::RegionFormatError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_region_1() {
+        assert_eq!(
+            parse_region("chr1:1,000-2,000").unwrap(),
+            ("chr1".to_string(), 1000, 2000)
+        );
+        assert_eq!(
+            parse_region("chr1:500").unwrap(),
+            ("chr1".to_string(), 500, u64::MAX)
+        );
+        assert_eq!(
+            parse_region("chrX").unwrap(),
+            ("chrX".to_string(), 1, u64::MAX)
+        );
+        assert_eq!(
+            parse_region("HLA-A*01:01:5-10").unwrap(),
+            ("HLA-A*01:01".to_string(), 5, 10)
+        );
+        assert!(parse_region("1:20-10").is_err());
+        assert!(parse_region("1:0-10").is_err());
+        assert!(parse_region(":1-10").is_err());
+    }
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t10\t.\tA\tT\t.\t.\t.\n\
+        1\t18\t.\tACGT\tA\t.\t.\t.\n\
+        1\t300\t.\tN\t<DEL>\t.\t.\tEND=400\n\
+        2\t15\t.\tG\tC\t.\t.\t.\n";
+
+    fn positions<I: Iterator<Item = Result<Record>>>(records: I) -> Vec<(String, u64)> {
+        records
+            .map(|r| r.map(|r| (r.chrom, r.pos)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_region_filter_1() {
+        let regions = RegionSet::from_regions(["1:20-25", "1:350-360", "3"]).unwrap();
+
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        assert_eq!(
+            positions(RegionFilter::new(reader, regions.clone())),
+            vec![("1".to_string(), 18), ("1".to_string(), 300)]
+        );
+
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        assert_eq!(
+            positions(RegionFilter::excluding(reader, regions)),
+            vec![("1".to_string(), 10), ("2".to_string(), 15)]
+        );
+    }
+
+    #[test]
+    fn test_region_query_1() {
+        use crate::index::tests::tbi_bytes;
+        use std::io::Write;
+
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(VCF.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        // Virtual offsets of the start of each contig and of the end.
+        let mut reader = Reader::new(bgzf::Reader::new(std::io::Cursor::new(&bytes))).unwrap();
+        let mut offsets = vec![reader.voffset()];
+        while let Some(record) = reader.next() {
+            if record.unwrap().pos == 300 {
+                offsets.push(reader.voffset());
+            }
+        }
+        offsets.push(reader.voffset());
+
+        let chunks = [
+            Chunk {
+                start: offsets[0],
+                end: offsets[1],
+            },
+            Chunk {
+                start: offsets[1],
+                end: offsets[2],
+            },
+        ];
+        let index = Index::read(&tbi_bytes(&["1", "2"], &chunks)[..]).unwrap();
+
+        let query = |regions: &[&str]| {
+            let reader =
+                Reader::new(bgzf::Reader::new(std::io::Cursor::new(bytes.clone()))).unwrap();
+            let regions = RegionSet::from_regions(regions).unwrap();
+            positions(RegionQuery::new(reader, &index, regions))
+        };
+
+        assert_eq!(
+            query(&["2:1-100", "1:15"]),
+            vec![
+                ("1".to_string(), 18),
+                ("1".to_string(), 300),
+                ("2".to_string(), 15)
+            ]
+        );
+        assert_eq!(query(&["1:1-5", "3"]), vec![]);
+
+        #[cfg(feature = "fs")]
+        {
+            let path =
+                std::env::temp_dir().join(format!("vcf-lib-regions-{}.vcf.gz", std::process::id()));
+            let mut index_path = path.clone().into_os_string();
+            index_path.push(".tbi");
+            std::fs::write(&path, &bytes).unwrap();
+            std::fs::write(&index_path, tbi_bytes(&["1", "2"], &chunks)).unwrap();
+
+            let (header, records) =
+                read_regions(&path, RegionSet::from_regions(["2"]).unwrap()).unwrap();
+            let found = positions(records);
+            std::fs::remove_file(&path).unwrap();
+            std::fs::remove_file(&index_path).unwrap();
+
+            assert_eq!(header.fileformat, "VCFv4.3");
+            assert_eq!(found, vec![("2".to_string(), 15)]);
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! Indexed reading from sources other than local files.
+//!
+//! A [`ByteSource`] serves reads at arbitrary offsets. [`SourceReader`]
+//! turns one into a buffered [`Read`] + [`Seek`], so the BGZF reader and
+//! [`RegionQuery`](crate::regions::RegionQuery) work on it as on a file.
+//!
+//! With the `http` feature, [`HttpSource`] implements [`ByteSource`] with
+//! HTTP/1.1 range requests to plain `http://` URLs. HTTPS, redirects,
+//! authentication and htsget tickets are not supported; S3, GCS and other
+//! stores need a [`ByteSource`] over a client of their own.
+
+use crate::bgzf;
+use crate::errors::Result;
+use crate::index::Index;
+use crate::reader::Reader;
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(feature = "fs")]
+use std::fs::File;
+
+/// Bytes read from a source at once by [`SourceReader`].
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Random-access bytes.
+pub trait ByteSource {
+    /// Reads up to `buf.len()` bytes at `offset`; returns 0 at or past the end.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Total size, if the source knows it.
+    fn size(&mut self) -> io::Result<Option<u64>>;
+}
+
+#[cfg(feature = "fs")]
+impl ByteSource for File {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read(buf)
+    }
+
+    fn size(&mut self) -> io::Result<Option<u64>> {
+        Ok(Some(self.metadata()?.len()))
+    }
+}
+
+impl ByteSource for Vec<u8> {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let start = (offset as usize).min(self.len());
+        let n = buf.len().min(self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+
+        Ok(n)
+    }
+
+    fn size(&mut self) -> io::Result<Option<u64>> {
+        Ok(Some(self.len() as u64))
+    }
+}
+
+/// Buffered [`Read`] + [`Seek`] over a [`ByteSource`].
+///
+/// Each read of the source fetches a whole chunk, so the many small reads of
+/// the BGZF reader become few range requests.
+pub struct SourceReader<S> {
+    source: S,
+    chunk_size: usize,
+    position: u64,
+    buffer: Vec<u8>,
+    /// Offset of `buffer` in the source.
+    buffer_start: u64,
+}
+
+impl<S: ByteSource> SourceReader<S> {
+    pub fn new(source: S) -> Self {
+        Self::with_chunk_size(source, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(source: S, chunk_size: usize) -> Self {
+        Self {
+            source,
+            chunk_size: chunk_size.max(1),
+            position: 0,
+            buffer: Vec::new(),
+            buffer_start: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: ByteSource> Read for SourceReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let buffered = self.buffer_start..self.buffer_start + self.buffer.len() as u64;
+        if !buffered.contains(&self.position) {
+            self.buffer.resize(self.chunk_size, 0);
+            let n = self.source.read_at(self.position, &mut self.buffer)?;
+            self.buffer.truncate(n);
+            self.buffer_start = self.position;
+        }
+
+        let within = (self.position - self.buffer_start) as usize;
+        let available = &self.buffer[within.min(self.buffer.len())..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl<S: ByteSource> Seek for SourceReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(delta) => (self.position, delta),
+            SeekFrom::End(delta) => {
+                let size = self.source.size()?.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Unsupported, "source size is unknown")
+                })?;
+                (size, delta)
+            }
+        };
+
+        self.position = base
+            .checked_add_signed(delta)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+
+        Ok(self.position)
+    }
+}
+
+/// Opens a BGZF-compressed VCF and its TBI or CSI index from two sources,
+/// ready for [`RegionQuery::new`](crate::regions::RegionQuery::new).
+pub fn open_indexed<S, T>(
+    vcf: S,
+    index: T,
+) -> Result<(Reader<bgzf::Reader<SourceReader<S>>>, Index)>
+where
+    S: ByteSource,
+    T: ByteSource,
+{
+    let index = Index::read(SourceReader::new(index))?;
+    let reader = Reader::new(bgzf::Reader::new(SourceReader::new(vcf)))?;
+
+    Ok((reader, index))
+}
+
+#[cfg(feature = "http")]
+pub use http::HttpSource;
+
+#[cfg(feature = "http")]
+mod http {
+    use super::ByteSource;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+
+    fn error(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    /// Object served over plain HTTP, read with one `Range` request per
+    /// [`ByteSource::read_at`].
+    ///
+    /// Only `http://` URLs are supported; HTTPS endpoints need a
+    /// [`ByteSource`] over a TLS-capable client. The host may be an IPv6
+    /// address in brackets, and bodies may be sent chunked.
+    pub struct HttpSource {
+        /// Host and port as written in the URL, for the `Host` header.
+        authority: String,
+        host: String,
+        port: u16,
+        path: String,
+        size: Option<u64>,
+    }
+
+    /// Status, `Content-Length`, `Content-Range` total and chunked
+    /// `Transfer-Encoding` of a response.
+    struct Response {
+        status: u16,
+        length: Option<u64>,
+        total: Option<u64>,
+        chunked: bool,
+    }
+
+    /// Decodes a chunked body; trailers are not read.
+    struct Chunked<R> {
+        inner: R,
+        /// Bytes left in the current chunk.
+        remaining: u64,
+        done: bool,
+    }
+
+    impl<R: BufRead> Read for Chunked<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+
+            if self.remaining == 0 {
+                let mut line = String::new();
+                // Skips the line break that ends the previous chunk.
+                while line.trim().is_empty() {
+                    line.clear();
+                    if self.inner.read_line(&mut line)? == 0 {
+                        Err(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                    }
+                }
+                let size = line.split(';').next().unwrap_or_default().trim();
+                self.remaining = u64::from_str_radix(size, 16)
+                    .map_err(|_| error(format!("malformed chunk size: {}", line.trim_end())))?;
+                if self.remaining == 0 {
+                    self.done = true;
+                    return Ok(0);
+                }
+            }
+
+            let n = buf.len().min(self.remaining as usize);
+            let n = self.inner.read(&mut buf[..n])?;
+            if n == 0 {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            }
+            self.remaining -= n as u64;
+
+            Ok(n)
+        }
+    }
+
+    impl HttpSource {
+        pub fn new(url: &str) -> io::Result<Self> {
+            let rest = url.strip_prefix("http://").ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported URL: {}", url),
+                )
+            })?;
+            let (authority, path) = match rest.find('/') {
+                Some(i) => rest.split_at(i),
+                None => (rest, "/"),
+            };
+            let (host, port) = match authority.strip_prefix('[') {
+                Some(bracketed) => bracketed
+                    .split_once(']')
+                    .map(|(host, port)| (host, port.strip_prefix(':')))
+                    .filter(|(_, port)| port.is_some() || authority.ends_with(']'))
+                    .ok_or_else(|| error(format!("invalid host in URL: {}", url)))?,
+                None => match authority.rsplit_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (authority, None),
+                },
+            };
+            let port = match port {
+                Some(port) => port
+                    .parse()
+                    .map_err(|_| error(format!("invalid port in URL: {}", url)))?,
+                None => 80,
+            };
+
+            Ok(Self {
+                authority: authority.to_string(),
+                host: host.to_string(),
+                port,
+                path: path.to_string(),
+                size: None,
+            })
+        }
+
+        /// Sends a GET for bytes `start..=end` and reads the response head.
+        fn request(&mut self, start: u64, end: u64) -> io::Result<(Response, Box<dyn Read>)> {
+            let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+            write!(
+                stream,
+                "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nConnection: close\r\n\r\n",
+                self.path, self.authority, start, end
+            )?;
+
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let status = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| error(format!("malformed HTTP status line: {}", line.trim_end())))?;
+
+            let mut response = Response {
+                status,
+                length: None,
+                total: None,
+                chunked: false,
+            };
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                    break;
+                }
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("content-length") {
+                    response.length = value.parse().ok();
+                } else if name.eq_ignore_ascii_case("content-range") {
+                    response.total = value.rsplit_once('/').and_then(|(_, t)| t.parse().ok());
+                } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                    response.chunked = value
+                        .rsplit(',')
+                        .next()
+                        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+                }
+            }
+            if response.total.is_some() {
+                self.size = response.total;
+            }
+
+            let body: Box<dyn Read> = if response.chunked {
+                // A chunked body's length is given by its chunks alone.
+                response.length = None;
+                Box::new(Chunked {
+                    inner: reader,
+                    remaining: 0,
+                    done: false,
+                })
+            } else {
+                Box::new(reader)
+            };
+
+            Ok((response, body))
+        }
+    }
+
+    impl ByteSource for HttpSource {
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+
+            let (response, mut body) = self.request(offset, offset + buf.len() as u64 - 1)?;
+            match response.status {
+                206 => {}
+                // The server ignored the range and sends the whole object.
+                200 => {
+                    io::copy(&mut (&mut body).take(offset), &mut io::sink())?;
+                }
+                416 => return Ok(0),
+                status => Err(error(format!("HTTP status {} for {}", status, self.path)))?,
+            }
+
+            let limit = response.length.unwrap_or(u64::MAX).min(buf.len() as u64);
+            let mut body = body.take(limit);
+            let mut n = 0;
+            while n < buf.len() {
+                match body.read(&mut buf[n..])? {
+                    0 => break,
+                    read => n += read,
+                }
+            }
+
+            Ok(n)
+        }
+
+        fn size(&mut self) -> io::Result<Option<u64>> {
+            if self.size.is_none() {
+                let (response, _) = self.request(0, 0)?;
+                if response.status == 200 {
+                    self.size = response.length;
+                }
+            }
+
+            Ok(self.size)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::tbi_bytes;
+    use crate::index::Chunk;
+    use crate::regions::{RegionQuery, RegionSet};
+    use std::io::Write;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+        1\t10\t.\tA\tT\t.\t.\t.\n\
+        1\t20\t.\tG\tC\t.\t.\t.\n\
+        2\t30\t.\tC\tA\t.\t.\t.\n";
+
+    /// BGZF file of `VCF` with one block per record, and its TBI index.
+    fn indexed() -> (Vec<u8>, Vec<u8>) {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        let mut offsets = Vec::new();
+        for (i, line) in VCF.split_inclusive('\n').enumerate() {
+            if i >= 2 {
+                writer.flush_block().unwrap();
+                offsets.push(writer.voffset());
+            }
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush_block().unwrap();
+        offsets.push(writer.voffset());
+        let bytes = writer.finish().unwrap();
+
+        let chunks = [
+            Chunk {
+                start: offsets[0],
+                end: offsets[2],
+            },
+            Chunk {
+                start: offsets[2],
+                end: offsets[3],
+            },
+        ];
+
+        (bytes, tbi_bytes(&["1", "2"], &chunks))
+    }
+
+    /// Counts the reads made of the source.
+    struct Counting(Vec<u8>, usize);
+
+    impl ByteSource for Counting {
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+            self.1 += 1;
+            self.0.read_at(offset, buf)
+        }
+
+        fn size(&mut self) -> io::Result<Option<u64>> {
+            self.0.size()
+        }
+    }
+
+    #[test]
+    fn test_source_reader_1() {
+        let (bytes, tbi) = indexed();
+
+        let mut reader = SourceReader::with_chunk_size(bytes.clone(), 16);
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, bytes);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-4)).unwrap(),
+            bytes.len() as u64 - 4
+        );
+        assert!(reader
+            .seek(SeekFrom::Current(-(bytes.len() as i64)))
+            .is_err());
+
+        let (reader, index) = open_indexed(Counting(bytes, 0), tbi).unwrap();
+        let mut regions = RegionSet::new();
+        regions.insert("2", 1, 100);
+        let mut query = RegionQuery::new(reader, &index, regions);
+
+        let records: Vec<_> = query.by_ref().map(|r| r.unwrap().pos).collect();
+        assert_eq!(records, vec![30]);
+        // The whole file fits in one chunk.
+        let reader = query.into_inner();
+        assert_eq!(reader.get_ref().get_ref().get_ref().1, 1);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_source_1() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let (bytes, _) = indexed();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file.vcf.gz", listener.local_addr().unwrap());
+
+        let served = bytes.clone();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(3) {
+                let mut stream = stream.unwrap();
+                let mut range = (0, 0);
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if let Some(r) = line.strip_prefix("Range: bytes=") {
+                        let (a, b) = r.split_once('-').unwrap();
+                        range = (a.parse().unwrap(), b.parse::<usize>().unwrap());
+                    }
+                    if line.is_empty() {
+                        break;
+                    }
+                }
+                let end = range.1.min(served.len() - 1);
+                let body = served.get(range.0..=end).unwrap_or_default();
+                if body.is_empty() {
+                    write!(stream, "HTTP/1.1 416 Range Not Satisfiable\r\n\r\n").unwrap();
+                    continue;
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    body.len(),
+                    range.0,
+                    end,
+                    served.len()
+                )
+                .unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+
+        let mut source = HttpSource::new(&url).unwrap();
+        let mut buf = vec![0; 10];
+        assert_eq!(source.read_at(5, &mut buf).unwrap(), 10);
+        assert_eq!(buf, bytes[5..15]);
+        assert_eq!(source.size().unwrap(), Some(bytes.len() as u64));
+
+        let mut tail = vec![0; 100];
+        let n = source.read_at(bytes.len() as u64 - 3, &mut tail).unwrap();
+        assert_eq!(tail[..n], bytes[bytes.len() - 3..]);
+        assert_eq!(source.read_at(bytes.len() as u64, &mut buf).unwrap(), 0);
+        server.join().unwrap();
+
+        assert!(HttpSource::new("https://example.org/a.vcf.gz").is_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_source_2() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        // A server on an IPv6 address that ignores ranges and sends the
+        // whole object chunked.
+        let (bytes, _) = indexed();
+        let listener = TcpListener::bind("[::1]:0").unwrap();
+        let url = format!("http://{}/file.vcf.gz", listener.local_addr().unwrap());
+
+        let served = bytes.clone();
+        let server = std::thread::spawn(move || {
+            let mut stream = listener.incoming().next().unwrap().unwrap();
+            let mut host = String::new();
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if let Some(h) = line.strip_prefix("Host: ") {
+                    host = h.to_string();
+                }
+                if line.is_empty() {
+                    break;
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+            )
+            .unwrap();
+            for chunk in served.chunks(7) {
+                write!(stream, "{:x};ext=1\r\n", chunk.len()).unwrap();
+                stream.write_all(chunk).unwrap();
+                write!(stream, "\r\n").unwrap();
+            }
+            write!(stream, "0\r\n\r\n").unwrap();
+
+            host
+        });
+
+        let mut source = HttpSource::new(&url).unwrap();
+        let mut buf = vec![0; 10];
+        assert_eq!(source.read_at(5, &mut buf).unwrap(), 10);
+        assert_eq!(buf, bytes[5..15]);
+        assert!(server.join().unwrap().starts_with("[::1]:"));
+
+        assert!(HttpSource::new("http://[::1]/a.vcf.gz").is_ok());
+        assert!(HttpSource::new("http://[::1/a.vcf.gz").is_err());
+        assert!(HttpSource::new("http://[::1]8080/a.vcf.gz").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! Parallel scans of whole files.
+//!
+//! A [`FileScan`] holds the data lines of a file in memory: read in, or
+//! memory-mapped by [`FileScan::open_mapped`] with the `mmap` feature on
+//! Unix for uncompressed files.
+//! BGZF blocks are decompressed independently, in parallel with the `rayon`
+//! feature. [`FileScan::run`] splits the data lines into chunks at line
+//! boundaries, parses each chunk into [`RawRecord`]s on the rayon thread
+//! pool, and merges the chunk results in file order.
+
+use crate::bgzf;
+use crate::errors::Result;
+use crate::header::Header;
+use crate::partition::Mergeable;
+use crate::raw::RawRecord;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Bytes of data lines parsed by one task.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 << 20;
+
+/// The bytes of a file.
+enum Data {
+    Owned(Vec<u8>),
+    #[cfg(all(unix, feature = "mmap"))]
+    Mapped(mmap::Mmap),
+}
+
+impl Data {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Data::Owned(data) => data,
+            #[cfg(all(unix, feature = "mmap"))]
+            Data::Mapped(map) => map.as_slice(),
+        }
+    }
+}
+
+/// A VCF file prepared for parallel scans.
+pub struct FileScan {
+    data: Data,
+    header: Header,
+    /// Offset of the first data line.
+    body: usize,
+    chunk_size: usize,
+}
+
+impl FileScan {
+    /// Reads a plain, gzip or BGZF compressed VCF file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        Self::from_bytes(data)
+    }
+
+    /// Like [`FileScan::open`], but maps an uncompressed file into memory
+    /// instead of reading it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified while the scan is alive:
+    /// reading a mapped page past a truncated end raises `SIGBUS`, and
+    /// changes made by other processes may show through the mapping.
+    #[cfg(all(unix, feature = "mmap"))]
+    pub unsafe fn open_mapped<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut magic = [0u8; bgzf::HEADER_SIZE];
+        let n = file.read(&mut magic)?;
+
+        let data = if bgzf::is_gzip(&magic[..n]) {
+            let mut compressed = magic[..n].to_vec();
+            file.read_to_end(&mut compressed)?;
+            decompress(&compressed)?
+        } else if file.metadata()?.len() == 0 {
+            Data::Owned(Vec::new())
+        } else {
+            Data::Mapped(mmap::Mmap::map(&file)?)
+        };
+
+        Self::new(data)
+    }
+
+    /// Scans a VCF held in memory, plain or compressed.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let data = match bgzf::is_gzip(&data) {
+            true => decompress(&data)?,
+            false => Data::Owned(data),
+        };
+
+        Self::new(data)
+    }
+
+    fn new(data: Data) -> Result<Self> {
+        let bytes = data.as_slice();
+
+        let mut body = 0;
+        while body < bytes.len() && bytes[body] == b'#' {
+            body = next_line(bytes, body);
+        }
+        let header = crate::reader::Reader::new(&bytes[..body])?.header().clone();
+
+        Ok(Self {
+            data,
+            header,
+            body,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    /// Sets the approximate size of the chunks handed to tasks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Byte ranges of the chunks of data lines, each ending after a newline
+    /// or at the end of the file.
+    pub fn chunks(&self) -> Vec<Range<usize>> {
+        let bytes = self.data.as_slice();
+
+        let mut chunks = Vec::new();
+        let mut start = self.body;
+        while start < bytes.len() {
+            let last = start.saturating_add(self.chunk_size - 1);
+            let end = next_line(bytes, last.min(bytes.len() - 1));
+            chunks.push(start..end);
+            start = end;
+        }
+
+        chunks
+    }
+
+    /// Calls `task` on every data line of a chunk, starting from
+    /// `T::default()`, and merges the chunk results in file order.
+    ///
+    /// Blank lines are skipped. With the `rayon` feature chunks run on the
+    /// rayon thread pool. The first error, in file order, is returned.
+    pub fn run<T, F>(&self, task: F) -> Result<T>
+    where
+        T: Mergeable + Send,
+        F: Fn(&mut T, Result<RawRecord<'_>>) -> Result<()> + Sync,
+    {
+        let bytes = self.data.as_slice();
+        let run = |chunk: Range<usize>| {
+            let mut result = T::default();
+            for line in bytes[chunk].split(|&b| b == b'\n') {
+                if !line.iter().all(|&b| b == b'\r') {
+                    task(&mut result, RawRecord::parse(line))?;
+                }
+            }
+            Ok(result)
+        };
+
+        #[cfg(feature = "rayon")]
+        let results: Vec<Result<T>> = self.chunks().into_par_iter().map(run).collect();
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<Result<T>> = self.chunks().into_iter().map(run).collect();
+
+        let mut merged = T::default();
+        for result in results {
+            merged.merge(result?);
+        }
+
+        Ok(merged)
+    }
+}
+
+/// Offset after the newline ending the line that contains `offset`.
+fn next_line(bytes: &[u8], offset: usize) -> usize {
+    match bytes[offset..].iter().position(|&b| b == b'\n') {
+        Some(i) => offset + i + 1,
+        None => bytes.len(),
+    }
+}
+
+/// Decompresses BGZF block by block, or other gzip as one stream.
+fn decompress(compressed: &[u8]) -> Result<Data> {
+    if !bgzf::is_bgzf(compressed) {
+        let mut data = Vec::new();
+        MultiGzDecoder::new(compressed).read_to_end(&mut data)?;
+        return Ok(Data::Owned(data));
+    }
+
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while offset < compressed.len() {
+        let size = bgzf::block_size(&compressed[offset..])?;
+        blocks.push(compressed.get(offset..offset + size).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated BGZF block")
+        })?);
+        offset += size;
+    }
+
+    let inflate = |block: &&[u8]| -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        bgzf::inflate_block(block, &mut data)?;
+        Ok(data)
+    };
+    #[cfg(feature = "rayon")]
+    let inflated: Vec<Result<Vec<u8>>> = blocks.par_iter().map(inflate).collect();
+    #[cfg(not(feature = "rayon"))]
+    let inflated: Vec<Result<Vec<u8>>> = blocks.iter().map(inflate).collect();
+
+    let mut data = Vec::new();
+    for block in inflated {
+        data.extend_from_slice(&block?);
+    }
+
+    Ok(Data::Owned(data))
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+mod mmap {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    /// Read-only private mapping of a whole file.
+    pub(super) struct Mmap {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only and owned by this value.
+    unsafe impl Send for Mmap {}
+    unsafe impl Sync for Mmap {}
+
+    impl Mmap {
+        pub(super) fn map(file: &File) -> io::Result<Self> {
+            let len = file.metadata()?.len() as usize;
+
+            // SAFETY: a fresh mapping of a valid descriptor; the result is
+            // checked for failure.
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { ptr, len })
+        }
+
+        pub(super) fn as_slice(&self) -> &[u8] {
+            // SAFETY: `ptr` maps `len` readable bytes until `drop`.
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            // SAFETY: unmaps the mapping created in `map` exactly once.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn vcf(records: usize) -> String {
+        let mut vcf = "##fileformat=VCFv4.3\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n"
+            .to_string();
+        for i in 0..records {
+            vcf.push_str(&format!("1\t{}\t.\tA\tT\t.\t.\tDP={}\n", i + 1, i % 7));
+        }
+
+        vcf
+    }
+
+    fn positions(scan: &FileScan) -> Vec<u64> {
+        scan.run(|positions: &mut Vec<u64>, record| {
+            positions.push(record?.pos()?);
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_file_scan_1() {
+        let text = vcf(500);
+        let expected: Vec<u64> = (1..=500).collect();
+
+        let scan = FileScan::from_bytes(text.clone().into_bytes())
+            .unwrap()
+            .with_chunk_size(100);
+        assert!(scan.chunks().len() > 10);
+        assert!(scan
+            .chunks()
+            .iter()
+            .all(|c| text.as_bytes()[c.end - 1] == b'\n'));
+        assert_eq!(positions(&scan), expected);
+        assert_eq!(scan.header().fileformat, "VCFv4.3");
+        let scan = scan.with_chunk_size(usize::MAX);
+        assert_eq!(scan.chunks().len(), 1);
+        assert_eq!(positions(&scan), expected);
+
+        let mut writer = bgzf::Writer::new(Vec::new());
+        for line in text.split_inclusive('\n') {
+            writer.write_all(line.as_bytes()).unwrap();
+            if line.ends_with("0\n") {
+                writer.flush_block().unwrap();
+            }
+        }
+        let compressed = writer.finish().unwrap();
+        let scan = FileScan::from_bytes(compressed.clone())
+            .unwrap()
+            .with_chunk_size(64);
+        assert_eq!(positions(&scan), expected);
+
+        let bad = FileScan::from_bytes(vcf(3).replace("\t2\t", "\tx\t").into_bytes()).unwrap();
+        assert!(bad
+            .run(|_: &mut Vec<u64>, record| record?.pos().map(|_| ()))
+            .is_err());
+        assert!(FileScan::from_bytes(compressed[..compressed.len() - 30].to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_file_scan_2() {
+        let path = std::env::temp_dir().join(format!("vcf-lib-scan-{}.vcf", std::process::id()));
+        std::fs::write(&path, vcf(20)).unwrap();
+
+        let scan = FileScan::open(&path).unwrap().with_chunk_size(50);
+        let count = scan
+            .run(|count: &mut Vec<()>, record| {
+                record?;
+                count.push(());
+                Ok(())
+            })
+            .unwrap()
+            .len();
+        drop(scan);
+
+        #[cfg(all(unix, feature = "mmap"))]
+        {
+            // SAFETY: the file is not modified while the scan is alive.
+            let scan = unsafe { FileScan::open_mapped(&path) }.unwrap();
+            assert_eq!(positions(&scan), (1..=20).collect::<Vec<u64>>());
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(count, 20);
+    }
+}
@@ -0,0 +1,383 @@
+//! External-memory sorting of records.
+
+use crate::codec::{Codec, CodecReader};
+use crate::errors::Result;
+use crate::header::Header;
+use crate::order::ContigOrder;
+use crate::record::Record;
+use crate::summary::{RunSummary, Timer};
+use crate::writer::Writer;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+use std::vec;
+
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Number of records buffered before a sorted chunk is spilled to disk.
+    pub max_records_in_memory: usize,
+    /// Directory for spill files.
+    pub temp_dir: PathBuf,
+    /// Compression of spill files.
+    pub codec: Codec,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            max_records_in_memory: 100_000,
+            temp_dir: std::env::temp_dir(),
+            codec: Codec::default(),
+        }
+    }
+}
+
+/// Sorts records by the contig order of a header, then position.
+///
+/// Records are buffered in memory and spilled to temporary files in sorted
+/// chunks, which are merged when reading back. The sort is stable, so
+/// records at the same position keep their input order.
+pub struct ExternalSorter {
+    order: ContigOrder,
+    options: SortOptions,
+    buffer: Vec<Record>,
+    spills: Vec<PathBuf>,
+}
+
+impl ExternalSorter {
+    pub fn new(header: &Header, options: SortOptions) -> Self {
+        Self {
+            order: ContigOrder::from_header(header),
+            options,
+            buffer: Vec::new(),
+            spills: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, record: Record) -> Result<()> {
+        self.buffer.push(record);
+
+        if self.buffer.len() >= self.options.max_records_in_memory.max(1) {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// Number of chunks spilled to disk so far.
+    pub fn spills(&self) -> usize {
+        self.spills.len()
+    }
+
+    /// Returns the records in sorted order.
+    pub fn finish(mut self) -> Result<SortedRecords> {
+        let order = self.order.clone();
+        self.buffer.sort_by(|a, b| order.compare(a, b));
+
+        let mut sources = Vec::with_capacity(self.spills.len() + 1);
+        for path in &self.spills {
+            let file = self.options.codec.reader(File::open(path)?)?;
+            sources.push(Source::File(BufReader::new(file)));
+        }
+        sources.push(Source::Memory(std::mem::take(&mut self.buffer).into_iter()));
+
+        let mut sorted = SortedRecords {
+            order: self.order.clone(),
+            sources,
+            heap: BinaryHeap::new(),
+            spills: std::mem::take(&mut self.spills),
+            line: String::new(),
+        };
+
+        for i in 0..sorted.sources.len() {
+            sorted.refill(i)?;
+        }
+
+        Ok(sorted)
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        let order = &self.order;
+        self.buffer.sort_by(|a, b| order.compare(a, b));
+
+        let path = self.options.temp_dir.join(format!(
+            "vcf-lib-sort-{}-{}.vcf{}",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, atomic::Ordering::Relaxed),
+            self.options.codec.extension()
+        ));
+        self.spills.push(path.clone());
+
+        let mut file = BufWriter::new(self.options.codec.writer(File::create(&path)?)?);
+        for record in self.buffer.drain(..) {
+            writeln!(file, "{}", record)?;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.finish()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for ExternalSorter {
+    fn drop(&mut self) {
+        for path in &self.spills {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+enum Source {
+    Memory(vec::IntoIter<Record>),
+    File(BufReader<CodecReader<File>>),
+}
+
+/// Head record of a source, ordered for a min-heap.
+struct Entry {
+    /// Contig rank; undeclared contigs come last, ordered by name.
+    rank: Option<usize>,
+    record: Record,
+    source: usize,
+}
+
+impl Entry {
+    fn cmp_with(&self, other: &Self) -> Ordering {
+        let contig = match (self.rank, other.rank) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => self.record.chrom.cmp(&other.record.chrom),
+        };
+
+        contig
+            .then(self.record.pos.cmp(&other.record.pos))
+            .then(self.source.cmp(&other.source))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp_with(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_with(other)
+    }
+}
+
+/// Iterator over the output of an [`ExternalSorter`].
+///
+/// Spill files are removed when the iterator is dropped.
+pub struct SortedRecords {
+    order: ContigOrder,
+    sources: Vec<Source>,
+    heap: BinaryHeap<Reverse<Entry>>,
+    spills: Vec<PathBuf>,
+    line: String,
+}
+
+impl SortedRecords {
+    fn refill(&mut self, source: usize) -> Result<()> {
+        let record = match &mut self.sources[source] {
+            Source::Memory(records) => records.next(),
+            Source::File(reader) => {
+                self.line.clear();
+                if reader.read_line(&mut self.line)? == 0 {
+                    None
+                } else {
+                    Some(self.line.parse()?)
+                }
+            }
+        };
+
+        if let Some(record) = record {
+            self.heap.push(Reverse(Entry {
+                rank: self.order.rank(&record.chrom),
+                record,
+                source,
+            }));
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(entry) = self.heap.pop()?;
+
+        Some(self.refill(entry.source).map(|_| entry.record))
+    }
+}
+
+impl Drop for SortedRecords {
+    fn drop(&mut self) {
+        for path in &self.spills {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Sorts `records` and writes them after `header`.
+///
+/// Wrap the output in a [`crate::bgzf::Writer`] to obtain a file that can be
+/// indexed with tabix.
+pub fn sort<I, W>(
+    records: I,
+    header: &Header,
+    writer: &mut Writer<W>,
+    options: SortOptions,
+) -> Result<RunSummary>
+where
+    I: IntoIterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("sort");
+    let mut sorter = ExternalSorter::new(header, options);
+
+    for record in records {
+        summary.records_in += 1;
+        sorter.push(record?)?;
+    }
+
+    writer.write_header(header)?;
+
+    for record in sorter.finish()? {
+        writer.write_record(&record?)?;
+        summary.records_out += 1;
+    }
+
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bgzf;
+    use crate::header::ContigDefinition;
+    use crate::order::SortednessChecker;
+    use crate::reader::Reader;
+
+    fn header() -> Header {
+        let mut header = Header::new("VCFv4.3");
+        for id in ["chr2", "chr1"] {
+            header.contigs.push(ContigDefinition::new(id, None));
+        }
+        header
+    }
+
+    fn options(max_records_in_memory: usize) -> SortOptions {
+        SortOptions {
+            max_records_in_memory,
+            ..SortOptions::default()
+        }
+    }
+
+    /// Deterministic shuffle of positions over three contigs.
+    fn shuffled(n: u64) -> Vec<Record> {
+        (0..n)
+            .map(|i| {
+                let chrom = ["chr1", "chr2", "chrX"][(i % 3) as usize];
+                let mut record = Record::new(chrom, (i * 7919) % 1000 + 1, "A", &["T"]);
+                record.ids = vec![format!("id{}", i)];
+                record
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_external_sorter_1() {
+        let records = shuffled(250);
+        let mut expected = records.clone();
+        let order = ContigOrder::from_header(&header());
+        expected.sort_by(|a, b| order.compare(a, b));
+
+        for codec in Codec::available() {
+            let options = SortOptions {
+                codec,
+                ..options(40)
+            };
+            let mut sorter = ExternalSorter::new(&header(), options);
+            for record in records.clone() {
+                sorter.push(record).unwrap();
+            }
+
+            assert_eq!(sorter.spills(), 6);
+
+            let sorted: Vec<Record> = sorter.finish().unwrap().map(|r| r.unwrap()).collect();
+
+            assert_eq!(sorted, expected, "{}", codec);
+            assert_eq!(sorted[0].chrom, "chr2");
+            assert_eq!(sorted[249].chrom, "chrX");
+        }
+    }
+
+    #[test]
+    fn test_external_sorter_2() {
+        let mut sorter = ExternalSorter::new(&header(), options(2));
+        for (i, pos) in [5, 5, 1, 5, 5].into_iter().enumerate() {
+            let mut record = Record::new("chr1", pos, "A", &["T"]);
+            record.ids = vec![i.to_string()];
+            sorter.push(record).unwrap();
+        }
+
+        let ids: Vec<String> = sorter
+            .finish()
+            .unwrap()
+            .map(|r| r.unwrap().ids[0].clone())
+            .collect();
+
+        assert_eq!(ids, vec!["2", "0", "1", "3", "4"]);
+    }
+
+    #[test]
+    fn test_sort_1() {
+        let dir = std::env::temp_dir().join(format!("vcf-lib-sort-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut writer = Writer::new(bgzf::Writer::new(Vec::new()));
+        let options = SortOptions {
+            max_records_in_memory: 16,
+            temp_dir: dir.clone(),
+            ..SortOptions::default()
+        };
+        let summary = sort(
+            shuffled(100).into_iter().map(Ok),
+            &header(),
+            &mut writer,
+            options,
+        )
+        .unwrap();
+        let leftover = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir(&dir).unwrap();
+        let bytes = writer.into_inner().finish().unwrap();
+
+        assert_eq!(summary.records_out, 100);
+        assert_eq!(leftover, 0);
+
+        let reader = Reader::new(bgzf::Reader::new(&bytes[..])).unwrap();
+        let header = reader.header().clone();
+        let checker = SortednessChecker::new(reader, &header);
+
+        assert_eq!(checker.collect::<Result<Vec<_>>>().unwrap().len(), 100);
+    }
+}
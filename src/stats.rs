@@ -0,0 +1,601 @@
+//! Summary statistics of a call set, like the core of `bcftools stats`.
+
+use crate::errors::Result;
+use crate::header::{Header, InfoDefinition, Number, ValueType};
+use crate::partition::Mergeable;
+use crate::record::Record;
+use crate::validation::{is_non_ref, is_symbolic};
+use crate::VariantType;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "fs")]
+use crate::partition::ExecutionPlan;
+
+/// Genotype counts of one sample.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleStats {
+    pub hom_ref: u64,
+    pub het: u64,
+    pub hom_alt: u64,
+    /// Genotypes with a missing allele, including half-calls, or that fail
+    /// to parse.
+    pub missing: u64,
+    /// ALT alleles this sample carries the only copy of.
+    pub singletons: u64,
+}
+
+/// Counts over the records of a file.
+///
+/// ALT alleles are classified after normalization, so an allele counts once
+/// however it is padded. Genotype counts come from `GT`; samples without it
+/// are not counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    pub samples: Vec<String>,
+    pub records: u64,
+    /// Records with more than one ALT allele, not counting gVCF `<NON_REF>`.
+    pub multiallelic: u64,
+    pub snvs: u64,
+    pub mnvs: u64,
+    pub insertions: u64,
+    pub deletions: u64,
+    /// Indels that are not a pure insertion or deletion.
+    pub complex_indels: u64,
+    /// Symbolic ALT alleles other than `<NON_REF>`, which is not counted.
+    pub symbolic: u64,
+    /// `*`, missing and unchanged ALT alleles.
+    pub other: u64,
+    /// SNVs between purines or between pyrimidines.
+    pub transitions: u64,
+    pub transversions: u64,
+    /// Number of indel alleles by ALT length minus REF length.
+    pub indel_lengths: BTreeMap<i64, u64>,
+    /// ALT alleles with a single copy across samples.
+    pub singletons: u64,
+    /// Per-sample counts, in the order of `samples`.
+    pub per_sample: Vec<SampleStats>,
+}
+
+impl Stats {
+    /// Empty statistics for the samples of `header`.
+    pub fn from_header(header: &Header) -> Self {
+        Self {
+            samples: header.samples.clone(),
+            per_sample: vec![SampleStats::default(); header.samples.len()],
+            ..Self::default()
+        }
+    }
+
+    /// Counts ALT alleles of `variant_type`.
+    pub fn variant_count(&self, variant_type: VariantType) -> u64 {
+        match variant_type {
+            VariantType::SNV => self.snvs,
+            VariantType::MNV => self.mnvs,
+            VariantType::Insertion => self.insertions,
+            VariantType::Deletion => self.deletions,
+            VariantType::Indel => self.complex_indels,
+        }
+    }
+
+    /// Transition to transversion ratio; `None` without transversions.
+    pub fn ts_tv(&self) -> Option<f64> {
+        (self.transversions > 0).then(|| self.transitions as f64 / self.transversions as f64)
+    }
+
+    /// Fraction of missing genotypes of sample `sample` over all records.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample` is out of range.
+    pub fn missing_rate(&self, sample: usize) -> Option<f64> {
+        (self.records > 0).then(|| self.per_sample[sample].missing as f64 / self.records as f64)
+    }
+
+    pub fn add(&mut self, record: &Record) {
+        self.records += 1;
+        if record.variant_alternates().count() > 1 {
+            self.multiallelic += 1;
+        }
+
+        for v in record.normalized_alternates() {
+            if is_non_ref(&v.alternate) {
+                continue;
+            }
+            if v.alternate == "*" {
+                self.other += 1;
+                continue;
+            }
+            if is_symbolic(&v.alternate) {
+                self.symbolic += 1;
+                continue;
+            }
+
+            match v.variant_type() {
+                Some(VariantType::SNV) => {
+                    self.snvs += 1;
+                    if is_transition(&v.reference, &v.alternate) {
+                        self.transitions += 1;
+                    } else {
+                        self.transversions += 1;
+                    }
+                }
+                Some(VariantType::MNV) => self.mnvs += 1,
+                Some(t) => {
+                    match t {
+                        VariantType::Insertion => self.insertions += 1,
+                        VariantType::Deletion => self.deletions += 1,
+                        _ => self.complex_indels += 1,
+                    }
+                    let change = v.alternate.len() as i64 - v.reference.len() as i64;
+                    *self.indel_lengths.entry(change).or_default() += 1;
+                }
+                None => self.other += 1,
+            }
+        }
+
+        let mut copies = vec![0usize; record.alternates.len() + 1];
+        let mut carrier = vec![None; record.alternates.len() + 1];
+        for (i, sample) in self.per_sample.iter_mut().enumerate() {
+            let gt = match record.genotype(i) {
+                Ok(Some(gt)) => gt,
+                Ok(None) => continue,
+                Err(_) => {
+                    sample.missing += 1;
+                    continue;
+                }
+            };
+
+            if gt.alleles.iter().any(|a| a.is_none()) {
+                sample.missing += 1;
+            } else if gt.is_hom_ref() {
+                sample.hom_ref += 1;
+            } else if gt.is_het() {
+                sample.het += 1;
+            } else {
+                sample.hom_alt += 1;
+            }
+
+            for allele in gt.alleles.iter().flatten() {
+                if let Some(n) = copies.get_mut(*allele) {
+                    *n += 1;
+                    carrier[*allele] = Some(i);
+                }
+            }
+        }
+
+        for allele in 1..copies.len() {
+            if copies[allele] == 1 {
+                self.singletons += 1;
+                if let Some(i) = carrier[allele] {
+                    self.per_sample[i].singletons += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Mergeable for Stats {
+    /// Adds the counts of `other`, which must have the same samples or none.
+    fn merge(&mut self, other: Self) {
+        if self.samples.is_empty() {
+            self.samples = other.samples;
+            self.per_sample = vec![SampleStats::default(); self.samples.len()];
+        }
+
+        self.records += other.records;
+        self.multiallelic += other.multiallelic;
+        self.snvs += other.snvs;
+        self.mnvs += other.mnvs;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+        self.complex_indels += other.complex_indels;
+        self.symbolic += other.symbolic;
+        self.other += other.other;
+        self.transitions += other.transitions;
+        self.transversions += other.transversions;
+        self.singletons += other.singletons;
+        for (change, n) in other.indel_lengths {
+            *self.indel_lengths.entry(change).or_default() += n;
+        }
+        for (sample, other) in self.per_sample.iter_mut().zip(other.per_sample) {
+            sample.hom_ref += other.hom_ref;
+            sample.het += other.het;
+            sample.hom_alt += other.hom_alt;
+            sample.missing += other.missing;
+            sample.singletons += other.singletons;
+        }
+    }
+}
+
+/// Genotype statistics of one record, for site-level QC.
+///
+/// Computed from `GT`; samples without it count as not called.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SiteStats {
+    /// Copies of each allele among called alleles, REF first.
+    pub allele_counts: Vec<u64>,
+    pub samples: u64,
+    /// Samples without a missing allele.
+    pub called: u64,
+    /// Called diploid genotypes by class.
+    pub hom_ref: u64,
+    pub het: u64,
+    pub hom_alt: u64,
+    /// Called genotypes of another ploidy.
+    pub non_diploid: u64,
+}
+
+impl SiteStats {
+    pub fn from_record(record: &Record) -> Self {
+        let mut stats = Self {
+            allele_counts: vec![0; record.alternates.len() + 1],
+            samples: record.samples.len() as u64,
+            ..Self::default()
+        };
+
+        for sample in 0..record.samples.len() {
+            let Ok(Some(gt)) = record.genotype(sample) else {
+                continue;
+            };
+
+            for &allele in gt.alleles.iter().flatten() {
+                if allele >= stats.allele_counts.len() {
+                    stats.allele_counts.resize(allele + 1, 0);
+                }
+                stats.allele_counts[allele] += 1;
+            }
+
+            if gt.alleles.is_empty() || gt.alleles.iter().any(|a| a.is_none()) {
+                continue;
+            }
+            stats.called += 1;
+            match gt.ploidy() {
+                2 if gt.is_hom_ref() => stats.hom_ref += 1,
+                2 if gt.is_het() => stats.het += 1,
+                2 => stats.hom_alt += 1,
+                _ => stats.non_diploid += 1,
+            }
+        }
+
+        stats
+    }
+
+    /// Number of called alleles (`AN`).
+    pub fn allele_number(&self) -> u64 {
+        self.allele_counts.iter().sum()
+    }
+
+    /// Frequency of each ALT allele among called alleles; `None` if no
+    /// allele is called.
+    pub fn allele_frequencies(&self) -> Option<Vec<f64>> {
+        let an = self.allele_number();
+        (an > 0).then(|| {
+            self.allele_counts[1..]
+                .iter()
+                .map(|&n| n as f64 / an as f64)
+                .collect()
+        })
+    }
+
+    /// Fraction of samples with a full call.
+    pub fn call_rate(&self) -> Option<f64> {
+        (self.samples > 0).then(|| self.called as f64 / self.samples as f64)
+    }
+
+    /// Observed fraction of heterozygous diploid calls.
+    pub fn heterozygosity(&self) -> Option<f64> {
+        let diploid = self.hom_ref + self.het + self.hom_alt;
+        (diploid > 0).then(|| self.het as f64 / diploid as f64)
+    }
+
+    /// Heterozygosity expected under Hardy-Weinberg equilibrium from the
+    /// allele frequencies, `1 - sum(p^2)`.
+    pub fn expected_heterozygosity(&self) -> Option<f64> {
+        let an = self.allele_number();
+        (an > 0).then(|| {
+            1.0 - self
+                .allele_counts
+                .iter()
+                .map(|&n| (n as f64 / an as f64).powi(2))
+                .sum::<f64>()
+        })
+    }
+
+    /// Hardy-Weinberg exact test p-value of a biallelic site with only
+    /// diploid calls; `None` otherwise.
+    pub fn hwe_p(&self) -> Option<f64> {
+        (self.allele_counts.len() == 2 && self.non_diploid == 0)
+            .then(|| hwe_exact(self.het, self.hom_ref, self.hom_alt))
+    }
+
+    /// Sets INFO `AC`, `AN`, `AF`, `F_MISSING` and, for biallelic diploid
+    /// sites, `HWE`, of `record`.
+    pub fn fill_tags(&self, record: &mut Record) {
+        let join = |values: Vec<String>| values.join(",");
+
+        record.set_info(
+            "AC",
+            Some(join(
+                self.allele_counts[1..]
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect(),
+            )),
+        );
+        record.set_info("AN", Some(self.allele_number().to_string()));
+        if let Some(af) = self.allele_frequencies() {
+            record.set_info("AF", Some(join(af.into_iter().map(format_float).collect())));
+        }
+        if let Some(rate) = self.call_rate() {
+            record.set_info("F_MISSING", Some(format_float(1.0 - rate)));
+        }
+        if let Some(p) = self.hwe_p() {
+            record.set_info("HWE", Some(format_float(p)));
+        }
+    }
+
+    /// Adds the definitions used by [`SiteStats::fill_tags`] that are
+    /// missing from `header`.
+    pub fn update_header(header: &mut Header) {
+        let infos = [
+            (
+                "AC",
+                Number::A,
+                ValueType::Integer,
+                "Allele count in genotypes",
+            ),
+            (
+                "AN",
+                Number::Count(1),
+                ValueType::Integer,
+                "Total number of called alleles",
+            ),
+            ("AF", Number::A, ValueType::Float, "Allele frequency"),
+            (
+                "F_MISSING",
+                Number::Count(1),
+                ValueType::Float,
+                "Fraction of samples not called",
+            ),
+            (
+                "HWE",
+                Number::Count(1),
+                ValueType::Float,
+                "Hardy-Weinberg equilibrium exact test p-value",
+            ),
+        ];
+        for (id, number, value_type, description) in infos {
+            if header.info(id).is_none() {
+                header
+                    .infos
+                    .push(InfoDefinition::new(id, number, value_type, description));
+            }
+        }
+    }
+}
+
+/// Hardy-Weinberg exact test p-value of diploid genotype counts at a
+/// biallelic site (Wigginton et al., 2005).
+///
+/// The p-value sums the probabilities of all heterozygote counts, given the
+/// allele counts, no more likely than the one observed.
+pub fn hwe_exact(het: u64, hom_ref: u64, hom_alt: u64) -> f64 {
+    let genotypes = het + hom_ref + hom_alt;
+    if genotypes == 0 {
+        return 1.0;
+    }
+
+    let rare = 2 * hom_ref.min(hom_alt) + het;
+    let mut probs = vec![0.0; rare as usize + 1];
+
+    // Start from the most likely heterozygote count, of the parity of `rare`.
+    let mut mid = rare * (2 * genotypes - rare) / (2 * genotypes);
+    if mid % 2 != rare % 2 {
+        mid += 1;
+    }
+    probs[mid as usize] = 1.0;
+
+    let (mut hets, mut homr) = (mid, (rare - mid) / 2);
+    let mut homc = genotypes - hets - homr;
+    while hets >= 2 {
+        probs[hets as usize - 2] = probs[hets as usize] * (hets * (hets - 1)) as f64
+            / (4 * (homr + 1) * (homc + 1)) as f64;
+        hets -= 2;
+        homr += 1;
+        homc += 1;
+    }
+
+    let (mut hets, mut homr) = (mid, (rare - mid) / 2);
+    let mut homc = genotypes - hets - homr;
+    while hets + 2 <= rare {
+        probs[hets as usize + 2] =
+            probs[hets as usize] * (4 * homr * homc) as f64 / ((hets + 2) * (hets + 1)) as f64;
+        hets += 2;
+        homr -= 1;
+        homc -= 1;
+    }
+
+    let total: f64 = probs.iter().sum();
+    let observed = probs[het as usize];
+    let p: f64 = probs.iter().filter(|&&p| p <= observed).sum::<f64>() / total;
+
+    p.min(1.0)
+}
+
+/// Formats a computed INFO value with up to six decimals, or in scientific
+/// notation when small.
+fn format_float(value: f64) -> String {
+    if value != 0.0 && value.abs() < 1e-4 {
+        return format!("{:.3e}", value);
+    }
+
+    let s = format!("{:.6}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn is_transition(reference: &str, alternate: &str) -> bool {
+    matches!(
+        (reference, alternate),
+        ("A", "G") | ("G", "A") | ("C", "T") | ("T", "C")
+    )
+}
+
+/// Computes the statistics of a stream of records.
+pub fn stats<I>(records: I, header: &Header) -> Result<Stats>
+where
+    I: IntoIterator<Item = Result<Record>>,
+{
+    let mut stats = Stats::from_header(header);
+    for record in records {
+        stats.add(&record?);
+    }
+
+    Ok(stats)
+}
+
+#[cfg(feature = "fs")]
+/// Computes the statistics of an indexed file, one contig at a time.
+///
+/// The result is the same as [`stats`] over the whole file, however the
+/// contigs are scheduled.
+pub fn stats_partitioned(plan: &ExecutionPlan) -> Result<Stats> {
+    plan.run(|_, records| stats(records, plan.header()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3\n\
+        1\t10\t.\tA\tG\t.\t.\t.\tGT\t0/1\t0/0\t0/0\n\
+        1\t20\t.\tC\tA,CTT\t.\t.\t.\tGT\t1/2\t0/2\t./.\n\
+        1\t30\t.\tCAG\tC\t.\t.\t.\tGT\t1/1\t0/1\t0/.\n\
+        1\t40\t.\tTAC\tTGC\t.\t.\t.\tGT\t0/0\t0/0\t1|1\n\
+        1\t50\t.\tA\t<DEL>,*\t.\t.\tSVTYPE=DEL\n\
+        2\t5\t.\tAT\tGC\t.\t.\t.\tGT\t0/1\t0/0\t0/0\n";
+
+    fn compute() -> Stats {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        stats(reader, &header).unwrap()
+    }
+
+    #[test]
+    fn test_stats_1() {
+        let stats = compute();
+
+        assert_eq!((stats.records, stats.multiallelic), (6, 2));
+        // TAC>TGC normalizes to an A>G transition.
+        assert_eq!(stats.variant_count(VariantType::SNV), 3);
+        assert_eq!((stats.transitions, stats.transversions), (2, 1));
+        assert_eq!(stats.ts_tv(), Some(2.0));
+        assert_eq!(stats.variant_count(VariantType::MNV), 1);
+        assert_eq!(stats.variant_count(VariantType::Insertion), 1);
+        assert_eq!(stats.variant_count(VariantType::Deletion), 1);
+        assert_eq!((stats.symbolic, stats.other), (1, 1));
+        assert_eq!(
+            stats.indel_lengths.into_iter().collect::<Vec<_>>(),
+            vec![(-2, 1), (2, 1)]
+        );
+    }
+
+    #[test]
+    fn test_stats_2() {
+        let stats = compute();
+        let s1 = &stats.per_sample[0];
+        let s3 = &stats.per_sample[2];
+
+        assert_eq!((s1.hom_ref, s1.het, s1.hom_alt, s1.missing), (1, 3, 1, 0));
+        assert_eq!((s3.hom_ref, s3.hom_alt, s3.missing), (2, 1, 2));
+        assert_eq!(stats.missing_rate(2), Some(2.0 / 6.0));
+
+        // 1:10 G, 1:20 A and 2:5 GC have one copy each.
+        assert_eq!(stats.singletons, 3);
+        assert_eq!(s1.singletons, 3);
+
+        let mut merged = Stats::default();
+        let half = |lines: &[&str]| {
+            let header = "##fileformat=VCFv4.3\n\
+                #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3";
+            let vcf = format!("{}\n{}\n", header, lines.join("\n"));
+            let reader = Reader::new(vcf.as_bytes()).unwrap();
+            let header = reader.header().clone();
+            super::stats(reader, &header).unwrap()
+        };
+        let lines: Vec<&str> = VCF.lines().skip(2).collect();
+        merged.merge(half(&lines[..3]));
+        merged.merge(half(&lines[3..]));
+
+        assert_eq!(merged, stats);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_stats_partitioned_1() {
+        use crate::partition::tests::{write_indexed, VCF};
+
+        let path =
+            std::env::temp_dir().join(format!("vcf-lib-stats-{}.vcf.gz", std::process::id()));
+        write_indexed(&path);
+
+        let plan = ExecutionPlan::for_path(&path).unwrap();
+        let partitioned = stats_partitioned(&plan).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        let mut index_path = path.into_os_string();
+        index_path.push(".tbi");
+        std::fs::remove_file(index_path).unwrap();
+
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        assert_eq!(partitioned, stats(reader, &header).unwrap());
+        assert_eq!(partitioned.records, 4);
+    }
+
+    #[test]
+    fn test_site_stats_1() {
+        let record: Record = "1\t10\t.\tA\tG\t.\t.\t.\tGT\t0/0\t0/1\t1/1\t./.\t0/1\t1"
+            .parse()
+            .unwrap();
+        let site = SiteStats::from_record(&record);
+
+        assert_eq!(site.allele_counts, vec![4, 5]);
+        assert_eq!((site.called, site.samples, site.non_diploid), (5, 6, 1));
+        assert_eq!(site.allele_frequencies(), Some(vec![5.0 / 9.0]));
+        assert_eq!(site.heterozygosity(), Some(0.5));
+        assert_eq!(site.hwe_p(), None);
+
+        let mut record: Record = "1\t10\t.\tA\tG\t.\t.\t.\tGT\t0/0\t1/1\t1/1\t./."
+            .parse()
+            .unwrap();
+        let site = SiteStats::from_record(&record);
+        site.fill_tags(&mut record);
+
+        assert_eq!(record.info("AF"), Some("0.666667"));
+        assert_eq!(record.info("F_MISSING"), Some("0.25"));
+        assert_eq!(record.info("AC"), Some("4"));
+        assert_eq!(record.info("HWE"), Some("0.2"));
+
+        let mut header = Header::new("VCFv4.3");
+        SiteStats::update_header(&mut header);
+        assert_eq!(header.infos.len(), 5);
+    }
+
+    #[test]
+    fn test_hwe_exact_1() {
+        // Two copies of the rare allele in three samples: P(0 het) = 0.2,
+        // P(2 het) = 0.8.
+        assert!((hwe_exact(0, 1, 2) - 0.2).abs() < 1e-12);
+        assert!((hwe_exact(2, 0, 1) - 1.0).abs() < 1e-12);
+        assert_eq!(hwe_exact(0, 0, 0), 1.0);
+
+        assert!(hwe_exact(500, 250, 250) > 0.9);
+        assert!(hwe_exact(0, 500, 500) < 1e-100);
+        assert!((hwe_exact(57, 14, 50) - hwe_exact(57, 50, 14)).abs() < 1e-15);
+    }
+}
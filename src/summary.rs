@@ -0,0 +1,164 @@
+//! Machine-readable results of high-level operations.
+
+use crate::errors::Error;
+use std::fmt::Write;
+use std::time::Duration;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/// Number of warning messages kept by [`RunSummary::warn`]; later warnings
+/// are only counted.
+pub const MAX_MESSAGES: usize = 100;
+
+/// Metrics of one pipeline step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunSummary {
+    /// Name of the operation, e.g. `normalize`.
+    pub operation: String,
+    pub records_in: u64,
+    pub records_out: u64,
+    /// Records written with changes.
+    pub modified: u64,
+    /// Records left out of the output.
+    pub skipped: u64,
+    pub warnings: u64,
+    /// The first [`MAX_MESSAGES`] warning messages.
+    pub messages: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl RunSummary {
+    pub fn new(operation: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.warnings += 1;
+        if self.messages.len() < MAX_MESSAGES {
+            self.messages.push(message);
+        }
+    }
+
+    /// Counts a record left out because of `error`.
+    pub fn skip(&mut self, error: &Error) {
+        self.skipped += 1;
+        self.warn(error.to_string());
+    }
+
+    /// Returns `true` if no record was skipped and nothing was warned about.
+    pub fn is_clean(&self) -> bool {
+        self.skipped == 0 && self.warnings == 0
+    }
+
+    /// Serializes the summary as a single-line JSON object.
+    ///
+    /// `elapsed` is written as fractional seconds.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+
+        out.push('{');
+        write!(out, "\"operation\":{}", json_string(&self.operation)).unwrap();
+        write!(out, ",\"records_in\":{}", self.records_in).unwrap();
+        write!(out, ",\"records_out\":{}", self.records_out).unwrap();
+        write!(out, ",\"modified\":{}", self.modified).unwrap();
+        write!(out, ",\"skipped\":{}", self.skipped).unwrap();
+        write!(out, ",\"warnings\":{}", self.warnings).unwrap();
+        out.push_str(",\"messages\":[");
+        for (i, message) in self.messages.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&json_string(message));
+        }
+        write!(out, "],\"elapsed\":{}", self.elapsed.as_secs_f64()).unwrap();
+        out.push('}');
+
+        out
+    }
+}
+
+/// Measures [`RunSummary::elapsed`].
+///
+/// `wasm32-unknown-unknown` has no clock, and [`Instant::now`] panics there,
+/// so durations are always zero on it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timer {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    started: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        return self.started.elapsed();
+
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        Duration::ZERO
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_1() {
+        let mut summary = RunSummary::new("validate");
+        summary.records_in = 3;
+        summary.records_out = 2;
+        summary.skip(&Error::RecordPositionError("x".to_string()));
+        summary.warn("tab\there \"quoted\"".to_string());
+        summary.elapsed = Duration::from_millis(1500);
+
+        assert!(!summary.is_clean());
+        assert_eq!(
+            summary.to_json(),
+            "{\"operation\":\"validate\",\"records_in\":3,\"records_out\":2,\"modified\":0,\
+             \"skipped\":1,\"warnings\":2,\"messages\":[\"Record has an invalid position: x\",\
+             \"tab\\there \\\"quoted\\\"\"],\"elapsed\":1.5}"
+        );
+    }
+
+    #[test]
+    fn test_warn_1() {
+        let mut summary = RunSummary::new("normalize");
+        for i in 0..MAX_MESSAGES + 5 {
+            summary.warn(i.to_string());
+        }
+
+        assert_eq!(summary.warnings, MAX_MESSAGES as u64 + 5);
+        assert_eq!(summary.messages.len(), MAX_MESSAGES);
+    }
+}
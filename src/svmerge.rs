@@ -0,0 +1,639 @@
+//! Population merging of structural variant calls.
+
+use crate::errors::Result;
+use crate::genotype::Genotype;
+use crate::header::{FormatDefinition, Header, InfoDefinition, Number, ValueType};
+use crate::merge::merge_definitions;
+use crate::order::{ContigOrder, SortednessChecker};
+use crate::record::Record;
+use crate::summary::{RunSummary, Timer};
+use crate::writer::Writer;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::iter::Peekable;
+
+/// Extent of a structural variant call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvEvent {
+    pub chrom: String,
+    pub pos: u64,
+    pub end: u64,
+    /// `SVTYPE`, e.g. `DEL`.
+    pub svtype: String,
+    /// Confidence interval around `pos`, `(0, 0)` if not given.
+    pub cipos: (i64, i64),
+    /// Confidence interval around `end`, `(0, 0)` if not given.
+    pub ciend: (i64, i64),
+}
+
+impl SvEvent {
+    /// Reads the event of a record with `SVTYPE` or a symbolic ALT such as
+    /// `<DEL>`; `None` for other records.
+    ///
+    /// The end is taken from `END`, then from `SVLEN` except for insertions
+    /// and breakends, then from the length of REF.
+    pub fn from_record(record: &Record) -> Option<Self> {
+        let svtype = match record.info("SVTYPE") {
+            Some(svtype) => svtype.to_string(),
+            None => {
+                let symbolic = record.alternates.first()?.strip_prefix('<')?;
+                let symbolic = symbolic.strip_suffix('>')?;
+                symbolic.split(':').next()?.to_string()
+            }
+        };
+
+        let svlen = || {
+            let len: i64 = record.info("SVLEN")?.split(',').next()?.parse().ok()?;
+            (svtype != "INS" && svtype != "BND").then(|| record.pos + len.unsigned_abs())
+        };
+        let end = record
+            .info("END")
+            .and_then(|v| v.parse().ok())
+            .or_else(svlen)
+            .unwrap_or(record.pos + record.reference.len().max(1) as u64 - 1);
+
+        Some(Self {
+            chrom: record.chrom.clone(),
+            pos: record.pos,
+            end,
+            svtype,
+            cipos: interval(record.info("CIPOS")),
+            ciend: interval(record.info("CIEND")),
+        })
+    }
+}
+
+fn interval(value: Option<&str>) -> (i64, i64) {
+    value
+        .and_then(|v| v.split_once(','))
+        .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+        .unwrap_or((0, 0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SvMergeOptions {
+    /// Largest distance between the starts, and between the ends, of
+    /// clustered events, beyond their confidence intervals.
+    pub max_distance: u64,
+    /// Widen positions by `CIPOS` and `CIEND`, each side clipped to
+    /// `max_distance`.
+    pub use_confidence_intervals: bool,
+    /// Only cluster events with the same `SVTYPE`.
+    pub match_type: bool,
+}
+
+impl Default for SvMergeOptions {
+    fn default() -> Self {
+        Self {
+            max_distance: 500,
+            use_confidence_intervals: true,
+            match_type: true,
+        }
+    }
+}
+
+impl SvMergeOptions {
+    fn interval(&self, ci: (i64, i64)) -> (i64, i64) {
+        if !self.use_confidence_intervals {
+            return (0, 0);
+        }
+        let d = self.max_distance as i64;
+
+        (ci.0.clamp(-d, 0), ci.1.clamp(0, d))
+    }
+
+    fn near(&self, a: u64, a_ci: (i64, i64), b: u64, b_ci: (i64, i64)) -> bool {
+        let (a_ci, b_ci) = (self.interval(a_ci), self.interval(b_ci));
+        let (a, b, d) = (a as i64, b as i64, self.max_distance as i64);
+
+        a + a_ci.0 - d <= b + b_ci.1 && b + b_ci.0 - d <= a + a_ci.1
+    }
+
+    /// Returns `true` if `a` and `b` describe the same event.
+    pub fn matches(&self, a: &SvEvent, b: &SvEvent) -> bool {
+        a.chrom == b.chrom
+            && (!self.match_type || a.svtype == b.svtype)
+            && self.near(a.pos, a.cipos, b.pos, b.cipos)
+            && (a.svtype == "BND" || self.near(a.end, a.ciend, b.end, b.ciend))
+    }
+}
+
+struct Input<I: Iterator<Item = Result<Record>>> {
+    records: Peekable<SortednessChecker<I>>,
+    caller: usize,
+    /// Output index of each sample.
+    samples: Vec<usize>,
+}
+
+struct Member {
+    input: usize,
+    record: Record,
+    event: SvEvent,
+}
+
+/// Clusters sorted SV calls of many samples and callers into one population
+/// VCF.
+///
+/// Each input is a call set of one caller, named by its label; a sample may
+/// appear in several inputs. An event joins the first open cluster whose
+/// first event it matches, taking at most one event per input, so clusters
+/// do not chain along nearby calls.
+///
+/// A cluster is written with the columns of its first event, the union of
+/// IDs, `CIPOS` and `CIEND` spanning the intervals of all its events, and
+/// `SUPP`, the number of supporting samples. Samples get `GT` and `SC`, one
+/// 0/1 support flag per caller in the order of the `##callers` header line.
+/// A sample supports an event when its `GT` carries an ALT allele; in
+/// single-sample inputs every record counts as support. `GT` is taken from the
+/// first supporting call and is missing without support.
+pub struct SvMerger<I: Iterator<Item = Result<Record>>> {
+    header: Header,
+    order: ContigOrder,
+    callers: Vec<String>,
+    options: SvMergeOptions,
+    inputs: Vec<Input<I>>,
+    clusters: VecDeque<Vec<Member>>,
+    ready: VecDeque<Record>,
+    records_in: u64,
+    skipped: u64,
+    combined: u64,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> SvMerger<I> {
+    /// Merges `(caller, header, records)` inputs.
+    pub fn new(inputs: Vec<(String, Header, I)>, options: SvMergeOptions) -> Self {
+        let mut header = merge_definitions(&inputs.iter().map(|(_, h, _)| h).collect::<Vec<_>>());
+        let mut callers: Vec<String> = Vec::new();
+
+        for (caller, h, _) in &inputs {
+            if !callers.contains(caller) {
+                callers.push(caller.clone());
+            }
+            for sample in &h.samples {
+                if !header.samples.contains(sample) {
+                    header.samples.push(sample.clone());
+                }
+            }
+        }
+
+        let infos = [
+            (
+                "SVTYPE",
+                Number::Count(1),
+                ValueType::String,
+                "Type of structural variant",
+            ),
+            (
+                "END",
+                Number::Count(1),
+                ValueType::Integer,
+                "End position of the variant described in this record",
+            ),
+            (
+                "SVLEN",
+                Number::A,
+                ValueType::Integer,
+                "Difference in length between REF and ALT alleles",
+            ),
+            (
+                "CIPOS",
+                Number::Count(2),
+                ValueType::Integer,
+                "Confidence interval around POS for imprecise variants",
+            ),
+            (
+                "CIEND",
+                Number::Count(2),
+                ValueType::Integer,
+                "Confidence interval around END for imprecise variants",
+            ),
+            (
+                "SUPP",
+                Number::Count(1),
+                ValueType::Integer,
+                "Number of samples supporting the variant",
+            ),
+        ];
+        for (id, number, value_type, description) in infos {
+            if header.info(id).is_none() {
+                header
+                    .infos
+                    .push(InfoDefinition::new(id, number, value_type, description));
+            }
+        }
+        let formats = [
+            ("GT", Number::Count(1), ValueType::String, "Genotype"),
+            (
+                "SC",
+                Number::Unknown,
+                ValueType::Integer,
+                "Support per caller, in the order of ##callers",
+            ),
+        ];
+        for (id, number, value_type, description) in formats {
+            if header.format(id).is_none() {
+                header
+                    .formats
+                    .push(FormatDefinition::new(id, number, value_type, description));
+            }
+        }
+        header.other.retain(|(key, _)| key != "callers");
+        header
+            .other
+            .push(("callers".to_string(), callers.join(",")));
+
+        let inputs = inputs
+            .into_iter()
+            .map(|(caller, h, records)| Input {
+                records: SortednessChecker::new(records, &h).peekable(),
+                caller: callers.iter().position(|c| *c == caller).unwrap(),
+                samples: h
+                    .samples
+                    .iter()
+                    .map(|s| header.samples.iter().position(|o| o == s).unwrap())
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            order: ContigOrder::from_header(&header),
+            header,
+            callers,
+            options,
+            inputs,
+            clusters: VecDeque::new(),
+            ready: VecDeque::new(),
+            records_in: 0,
+            skipped: 0,
+            combined: 0,
+            done: false,
+        }
+    }
+
+    /// Population header, with every sample once.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Caller labels in the order of the `SC` values.
+    pub fn callers(&self) -> &[String] {
+        &self.callers
+    }
+
+    /// Number of input records consumed so far.
+    pub fn records_in(&self) -> u64 {
+        self.records_in
+    }
+
+    /// Number of input records that do not describe a structural variant.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Number of records written that cluster more than one call.
+    pub fn combined(&self) -> u64 {
+        self.combined
+    }
+
+    /// Reads the earliest next record of all inputs.
+    fn pull(&mut self) -> Result<Option<(usize, Record)>> {
+        let mut next: Option<(usize, String, u64)> = None;
+
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            let record = match input.records.peek() {
+                Some(Ok(record)) => record,
+                Some(Err(_)) => return Err(input.records.next().unwrap().unwrap_err()),
+                None => continue,
+            };
+
+            let earlier = next.as_ref().is_none_or(|(_, chrom, pos)| {
+                self.order
+                    .compare_positions((&record.chrom, record.pos), (chrom, *pos))
+                    == Ordering::Less
+            });
+            if earlier {
+                next = Some((i, record.chrom.clone(), record.pos));
+            }
+        }
+
+        match next {
+            Some((i, _, _)) => Ok(Some((i, self.inputs[i].records.next().unwrap()?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes out the clusters that no event at or after `next` can join.
+    fn close(&mut self, next: Option<&SvEvent>) {
+        let d = self.options.max_distance as i64;
+        let slack = if self.options.use_confidence_intervals {
+            2 * d
+        } else {
+            d
+        };
+
+        while let Some(cluster) = self.clusters.front() {
+            let first = &cluster[0].event;
+            let closed = next.is_none_or(|e| {
+                let reach = first.pos as i64 + self.options.interval(first.cipos).1;
+                e.chrom != first.chrom || e.pos as i64 - slack > reach
+            });
+            if !closed {
+                break;
+            }
+
+            let cluster = self.clusters.pop_front().unwrap();
+            let record = self.build(cluster);
+            self.ready.push_back(record);
+        }
+    }
+
+    fn add(&mut self, input: usize, record: Record, event: SvEvent) {
+        let member = Member {
+            input,
+            record,
+            event,
+        };
+
+        let cluster = self.clusters.iter_mut().find(|c| {
+            c.iter().all(|m| m.input != input) && self.options.matches(&c[0].event, &member.event)
+        });
+        match cluster {
+            Some(cluster) => cluster.push(member),
+            None => self.clusters.push_back(vec![member]),
+        }
+    }
+
+    fn build(&mut self, members: Vec<Member>) -> Record {
+        if members.len() > 1 {
+            self.combined += 1;
+        }
+
+        let first = &members[0];
+        let mut merged = Record::new(
+            &first.record.chrom,
+            first.record.pos,
+            &first.record.reference,
+            &[],
+        );
+        merged.alternates = first.record.alternates.clone();
+        merged.filters = first.record.filters.clone();
+
+        for m in &members {
+            for id in &m.record.ids {
+                if !merged.ids.contains(id) {
+                    merged.ids.push(id.clone());
+                }
+            }
+            merged.qual = match (merged.qual, m.record.qual) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        let span = |at: fn(&SvEvent) -> (u64, (i64, i64)), origin: u64| {
+            let lo = members
+                .iter()
+                .map(|m| {
+                    let (p, ci) = at(&m.event);
+                    p as i64 + ci.0
+                })
+                .min()
+                .unwrap();
+            let hi = members
+                .iter()
+                .map(|m| {
+                    let (p, ci) = at(&m.event);
+                    p as i64 + ci.1
+                })
+                .max()
+                .unwrap();
+            format!("{},{}", lo - origin as i64, hi - origin as i64)
+        };
+        let event = &first.event;
+        merged.set_info("SVTYPE", Some(event.svtype.clone()));
+        if event.svtype != "BND" {
+            merged.set_info("END", Some(event.end.to_string()));
+        }
+        if let Some(svlen) = first.record.info("SVLEN") {
+            merged.set_info("SVLEN", Some(svlen.to_string()));
+        }
+        merged.set_info("CIPOS", Some(span(|e| (e.pos, e.cipos), event.pos)));
+        if event.svtype != "BND" {
+            merged.set_info("CIEND", Some(span(|e| (e.end, e.ciend), event.end)));
+        }
+
+        let n = self.header.samples.len();
+        let mut genotypes: Vec<Option<String>> = vec![None; n];
+        let mut support = vec![vec![false; self.callers.len()]; n];
+
+        for m in &members {
+            let input = &self.inputs[m.input];
+            let single = input.samples.len() == 1;
+
+            for (j, &s) in input.samples.iter().enumerate() {
+                let supports = single
+                    || m.record
+                        .genotype(j)
+                        .ok()
+                        .flatten()
+                        .is_some_and(|gt| gt.alleles.iter().any(|a| a.is_some_and(|i| i > 0)));
+                if !supports {
+                    continue;
+                }
+
+                support[s][input.caller] = true;
+                if genotypes[s].is_none() {
+                    genotypes[s] = m.record.format_value(j, "GT").map(|v| v.to_string());
+                }
+            }
+        }
+
+        let supporting = support.iter().filter(|s| s.contains(&true)).count();
+        merged.set_info("SUPP", Some(supporting.to_string()));
+
+        merged.format = vec!["GT".to_string(), "SC".to_string()];
+        for (gt, support) in genotypes.into_iter().zip(support) {
+            let flags: Vec<&str> = support.iter().map(|&s| if s { "1" } else { "0" }).collect();
+            merged.samples.push(vec![
+                gt.unwrap_or_else(|| Genotype::missing(2).to_string()),
+                flags.join(","),
+            ]);
+        }
+
+        merged
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for SvMerger<I> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.ready.pop_front() {
+                return Some(Ok(record));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.pull() {
+                Ok(Some((input, record))) => {
+                    self.records_in += 1;
+                    match SvEvent::from_record(&record) {
+                        Some(event) => {
+                            self.close(Some(&event));
+                            self.add(input, record, event);
+                        }
+                        None => self.skipped += 1,
+                    }
+                }
+                Ok(None) => {
+                    self.done = true;
+                    self.close(None);
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Clusters `(caller, header, records)` inputs with [`SvMerger`] and writes
+/// the population header and records.
+///
+/// `modified` counts the records clustering more than one call, and
+/// `skipped` the input records that are not structural variants.
+pub fn merge_sv<I, W>(
+    inputs: Vec<(String, Header, I)>,
+    options: SvMergeOptions,
+    writer: &mut Writer<W>,
+) -> Result<RunSummary>
+where
+    I: Iterator<Item = Result<Record>>,
+    W: Write,
+{
+    let started = Timer::start();
+    let mut summary = RunSummary::new("merge_sv");
+    let mut merger = SvMerger::new(inputs, options);
+
+    writer.write_header(merger.header())?;
+
+    for record in merger.by_ref() {
+        writer.write_record(&record?)?;
+        summary.records_out += 1;
+    }
+
+    summary.records_in = merger.records_in();
+    summary.modified = merger.combined();
+    summary.skipped = merger.skipped();
+    summary.elapsed = started.elapsed();
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::ContigDefinition;
+
+    fn input(
+        caller: &str,
+        sample: &str,
+        lines: &[&str],
+    ) -> (String, Header, std::vec::IntoIter<Result<Record>>) {
+        let mut header = Header::new("VCFv4.3");
+        header.contigs.push(ContigDefinition::new("1", None));
+        header.samples.push(sample.to_string());
+        let records: Vec<Result<Record>> = lines.iter().map(|l| l.parse()).collect();
+
+        (caller.to_string(), header, records.into_iter())
+    }
+
+    fn inputs() -> Vec<(String, Header, std::vec::IntoIter<Result<Record>>)> {
+        vec![
+            input(
+                "manta",
+                "S1",
+                &[
+                    "1\t1000\tm1\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=2000;SVLEN=-1000;CIPOS=-10,10\tGT\t0/1",
+                    "1\t3000\t.\tA\tG\t.\t.\t.\tGT\t0/1",
+                ],
+            ),
+            input(
+                "delly",
+                "S1",
+                &[
+                    "1\t1040\td1\tN\t<DEL>\t.\tPASS\tEND=2030\tGT\t1/1",
+                    "1\t5020\td2\tN\t<DUP>\t.\tPASS\tSVTYPE=DUP;END=6000\tGT\t0/1",
+                ],
+            ),
+            input(
+                "manta",
+                "S2",
+                &[
+                    "1\t1010\tm2\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=1990\tGT\t1/1",
+                    "1\t1200\tm3\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=2000\tGT\t0/1",
+                    "1\t5000\tm4\tN\t<INS>\t.\tPASS\tSVTYPE=INS;SVLEN=300\tGT\t0/1",
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_sv_event_1() {
+        let record: Record = "1\t100\t.\tN\t<DEL:ME>\t.\t.\tSVLEN=-50;CIEND=-5,5"
+            .parse()
+            .unwrap();
+        let event = SvEvent::from_record(&record).unwrap();
+
+        assert_eq!(event.svtype, "DEL");
+        assert_eq!((event.pos, event.end), (100, 150));
+        assert_eq!((event.cipos, event.ciend), ((0, 0), (-5, 5)));
+
+        let record: Record = "1\t100\t.\tA\tAT\t.\t.\t.".parse().unwrap();
+        assert_eq!(SvEvent::from_record(&record), None);
+    }
+
+    #[test]
+    fn test_sv_merger_1() {
+        let options = SvMergeOptions {
+            max_distance: 50,
+            ..SvMergeOptions::default()
+        };
+        let mut merger = SvMerger::new(inputs(), options);
+
+        assert_eq!(merger.header().samples, vec!["S1", "S2"]);
+        assert_eq!(merger.callers(), ["manta", "delly"]);
+
+        let lines: Vec<String> = merger.by_ref().map(|r| r.unwrap().to_string()).collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "1\t1000\tm1;m2;d1\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=2000;SVLEN=-1000;CIPOS=-10,40;CIEND=-10,30;SUPP=2\tGT:SC\t0/1:1,1\t1/1:1,0",
+                "1\t1200\tm3\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=2000;CIPOS=0,0;CIEND=0,0;SUPP=1\tGT:SC\t./.:0,0\t0/1:1,0",
+                "1\t5000\tm4\tN\t<INS>\t.\tPASS\tSVTYPE=INS;END=5000;SVLEN=300;CIPOS=0,0;CIEND=0,0;SUPP=1\tGT:SC\t./.:0,0\t0/1:1,0",
+                "1\t5020\td2\tN\t<DUP>\t.\tPASS\tSVTYPE=DUP;END=6000;CIPOS=0,0;CIEND=0,0;SUPP=1\tGT:SC\t0/1:0,1\t./.:0,0",
+            ]
+        );
+        assert_eq!(merger.records_in(), 7);
+        assert_eq!(merger.skipped(), 1);
+        assert_eq!(merger.combined(), 1);
+    }
+
+    #[test]
+    fn test_merge_sv_1() {
+        let mut writer = Writer::new(Vec::new());
+        let summary = merge_sv(inputs(), SvMergeOptions::default(), &mut writer).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+
+        // 1:1200 is close enough to 1:1000, but that cluster already has a
+        // call of the same input.
+        assert_eq!(summary.records_out, 4);
+        assert_eq!(summary.modified, 1);
+        assert_eq!(summary.skipped, 1);
+        assert!(text.contains("##callers=manta,delly\n"));
+        assert!(text.contains("##FORMAT=<ID=SC,Number=.,Type=Integer"));
+    }
+}
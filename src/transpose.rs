@@ -0,0 +1,346 @@
+//! Sample-major iteration over cohort records.
+
+use crate::codec::{Codec, CodecReader};
+use crate::errors::Result;
+use crate::header::Header;
+use crate::record::Record;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Arc;
+
+static TRANSPOSE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransposeOptions {
+    /// Number of records transposed at a time.
+    pub block_size: usize,
+    /// Directory for the transposed files.
+    pub temp_dir: PathBuf,
+    /// Compression of the site columns. Sample columns are read with seeks
+    /// and stay uncompressed.
+    pub codec: Codec,
+}
+
+impl Default for TransposeOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 10_000,
+            temp_dir: std::env::temp_dir(),
+            codec: Codec::default(),
+        }
+    }
+}
+
+/// Files of a transposed cohort, removed when the last user is dropped.
+struct Files {
+    header: Header,
+    /// Records without sample columns, in input order.
+    sites: PathBuf,
+    /// Sample columns, grouped by block, then by sample.
+    cells: PathBuf,
+    /// Start of each sample in `cells`, per block.
+    offsets: Vec<Vec<u64>>,
+    block_size: usize,
+    codec: Codec,
+}
+
+impl Drop for Files {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.sites);
+        let _ = fs::remove_file(&self.cells);
+    }
+}
+
+/// Records of a cohort rearranged for reading one sample at a time.
+///
+/// Records are read once and buffered in blocks; each block is written to
+/// disk sample by sample, so reading a sample seeks once per block and reads
+/// only that sample's columns. Site columns are stored once for all samples.
+pub struct Transposed {
+    files: Arc<Files>,
+    records: usize,
+}
+
+impl Transposed {
+    pub fn new<I>(records: I, header: &Header, options: TransposeOptions) -> Result<Self>
+    where
+        I: IntoIterator<Item = Result<Record>>,
+    {
+        let id = format!(
+            "vcf-lib-transpose-{}-{}",
+            std::process::id(),
+            TRANSPOSE_COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+        );
+
+        // The files are removed on error once `files` is dropped.
+        let mut files = Files {
+            header: header.clone(),
+            sites: options
+                .temp_dir
+                .join(format!("{}.sites.vcf{}", id, options.codec.extension())),
+            cells: options.temp_dir.join(format!("{}.cells", id)),
+            offsets: Vec::new(),
+            block_size: options.block_size.max(1),
+            codec: options.codec,
+        };
+
+        let mut sites = BufWriter::new(options.codec.writer(File::create(&files.sites)?)?);
+        let mut cells = BufWriter::new(File::create(&files.cells)?);
+        let mut offset = 0;
+        let mut block = Vec::with_capacity(files.block_size);
+        let mut count = 0;
+
+        let mut records = records.into_iter();
+        loop {
+            let record = records.next().transpose()?;
+            let end = record.is_none();
+
+            if let Some(mut record) = record {
+                block.push(std::mem::take(&mut record.samples));
+                writeln!(sites, "{}", record)?;
+                count += 1;
+            }
+
+            if block.len() == files.block_size || (end && !block.is_empty()) {
+                let mut starts = Vec::with_capacity(header.samples.len());
+                for sample in 0..header.samples.len() {
+                    starts.push(offset);
+                    for columns in &block {
+                        let cell = columns.get(sample).map_or(".".to_string(), |c| c.join(":"));
+                        writeln!(cells, "{}", cell)?;
+                        offset += cell.len() as u64 + 1;
+                    }
+                }
+                files.offsets.push(starts);
+                block.clear();
+            }
+
+            if end {
+                break;
+            }
+        }
+
+        sites.into_inner().map_err(|e| e.into_error())?.finish()?;
+        cells.flush()?;
+
+        Ok(Self {
+            files: Arc::new(files),
+            records: count,
+        })
+    }
+
+    pub fn samples(&self) -> &[String] {
+        &self.files.header.samples
+    }
+
+    /// Number of records.
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    /// Records of sample `sample`, in input order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample` is out of range.
+    pub fn sample(&self, sample: usize) -> SampleRecords {
+        assert!(sample < self.samples().len(), "sample out of range");
+
+        SampleRecords {
+            files: self.files.clone(),
+            sample,
+            handles: None,
+            index: 0,
+            line: String::new(),
+        }
+    }
+}
+
+impl IntoIterator for Transposed {
+    type Item = (String, SampleRecords);
+    type IntoIter = BySample;
+
+    fn into_iter(self) -> BySample {
+        BySample {
+            transposed: self,
+            next: 0,
+        }
+    }
+}
+
+/// Iterator over the samples of a [`Transposed`] cohort.
+pub struct BySample {
+    transposed: Transposed,
+    next: usize,
+}
+
+impl Iterator for BySample {
+    type Item = (String, SampleRecords);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.transposed.samples().get(self.next)?.clone();
+        let records = self.transposed.sample(self.next);
+        self.next += 1;
+
+        Some((name, records))
+    }
+}
+
+/// Records of one sample, with a single sample column.
+///
+/// The transposed files are opened on the first call to `next`.
+pub struct SampleRecords {
+    files: Arc<Files>,
+    sample: usize,
+    handles: Option<(BufReader<CodecReader<File>>, BufReader<File>)>,
+    index: usize,
+    line: String,
+}
+
+impl SampleRecords {
+    /// Header with this sample only.
+    pub fn header(&self) -> Header {
+        let mut header = self.files.header.clone();
+        header.samples = vec![header.samples[self.sample].clone()];
+
+        header
+    }
+
+    fn read(&mut self) -> Result<Option<Record>> {
+        let files = &self.files;
+        let (sites, cells) = match &mut self.handles {
+            Some(handles) => handles,
+            None => self.handles.insert((
+                BufReader::new(files.codec.reader(File::open(&files.sites)?)?),
+                BufReader::new(File::open(&files.cells)?),
+            )),
+        };
+
+        self.line.clear();
+        if sites.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        let mut record: Record = self.line.parse()?;
+
+        if self.index.is_multiple_of(files.block_size) {
+            let start = files.offsets[self.index / files.block_size][self.sample];
+            cells.seek(SeekFrom::Start(start))?;
+        }
+        self.index += 1;
+
+        self.line.clear();
+        cells.read_line(&mut self.line)?;
+        if !record.format.is_empty() {
+            record.samples = vec![self
+                .line
+                .trim_end_matches('\n')
+                .split(':')
+                .map(|v| v.to_string())
+                .collect()];
+        }
+
+        Ok(Some(record))
+    }
+}
+
+impl Iterator for SampleRecords {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const VCF: &str = "##fileformat=VCFv4.3\n\
+        #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3\n\
+        1\t10\t.\tA\tT\t.\t.\t.\tGT:DP\t0/1:3\t0/0:5\t1/1:7\n\
+        1\t20\t.\tC\tG\t.\t.\t.\tGT\t0/0\t0/1\t./.\n\
+        1\t30\t.\tG\tA\t.\t.\tDP=4\n\
+        2\t5\t.\tT\tC\t.\t.\t.\tGT\t1|0\t0|0\t0|1\n";
+
+    fn transpose(block_size: usize, codec: Codec) -> Transposed {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let header = reader.header().clone();
+        let options = TransposeOptions {
+            block_size,
+            codec,
+            ..TransposeOptions::default()
+        };
+
+        Transposed::new(reader, &header, options).unwrap()
+    }
+
+    #[test]
+    fn test_transposed_1() {
+        for (block_size, codec) in [
+            (1, Codec::None),
+            (3, Codec::Deflate),
+            (100, Codec::default()),
+        ] {
+            let transposed = transpose(block_size, codec);
+            assert_eq!(transposed.len(), 4);
+
+            let samples: Vec<(String, Vec<String>)> = transposed
+                .into_iter()
+                .map(|(name, records)| {
+                    let lines = records.map(|r| r.unwrap().to_string()).collect();
+                    (name, lines)
+                })
+                .collect();
+
+            assert_eq!(samples.len(), 3);
+            assert_eq!(samples[1].0, "S2");
+            assert_eq!(
+                samples[1].1,
+                vec![
+                    "1\t10\t.\tA\tT\t.\t.\t.\tGT:DP\t0/0:5",
+                    "1\t20\t.\tC\tG\t.\t.\t.\tGT\t0/1",
+                    "1\t30\t.\tG\tA\t.\t.\tDP=4",
+                    "2\t5\t.\tT\tC\t.\t.\t.\tGT\t0|0"
+                ]
+            );
+            assert_eq!(samples[2].1[0], "1\t10\t.\tA\tT\t.\t.\t.\tGT:DP\t1/1:7");
+        }
+    }
+
+    #[test]
+    fn test_by_sample_1() {
+        let reader = Reader::new(VCF.as_bytes()).unwrap();
+        let mut carriers = Vec::new();
+
+        for (sample, records) in reader.by_sample().unwrap() {
+            assert_eq!(records.header().samples, vec![sample.clone()]);
+
+            let positions: Vec<u64> = records
+                .map(Result::unwrap)
+                .filter(|r| {
+                    r.genotype(0)
+                        .unwrap()
+                        .is_some_and(|gt| gt.is_het() || gt.is_hom_alt())
+                })
+                .map(|r| r.pos)
+                .collect();
+            carriers.push((sample, positions));
+        }
+
+        assert_eq!(
+            carriers,
+            vec![
+                ("S1".to_string(), vec![10, 5]),
+                ("S2".to_string(), vec![20]),
+                ("S3".to_string(), vec![10, 5]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::errors::{Error, Result};
+
+/// Bytes accepted by each alphabet, indexed by byte value.
+static STRICT: [bool; 256] = table(b"ACGTN", false);
+static STRICT_ANY_CASE: [bool; 256] = table(b"ACGTN", true);
+static IUPAC: [bool; 256] = table(b"ACGTURYKMSWBDHVN", false);
+static IUPAC_ANY_CASE: [bool; 256] = table(b"ACGTURYKMSWBDHVN", true);
+
+const fn table(symbols: &[u8], any_case: bool) -> [bool; 256] {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < symbols.len() {
+        table[symbols[i] as usize] = true;
+        if any_case {
+            table[symbols[i].to_ascii_lowercase() as usize] = true;
+        }
+        i += 1;
+    }
+
+    table
+}
+
+/// Set of base symbols accepted in REF and ALT alleles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alphabet {
+    /// `A`, `C`, `G`, `T` and `N` only.
+    Strict,
+    /// All IUPAC nucleotide codes (`ACGTURYKMSWBDHVN`).
+    #[default]
+    Iupac,
+}
+
+/// How lowercase (soft-masked) bases are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lowercase {
+    /// Lowercase bases are rejected.
+    #[default]
+    Reject,
+    /// Lowercase bases are accepted and kept as they are.
+    Accept,
+    /// Lowercase bases are accepted and converted to uppercase.
+    Uppercase,
+}
+
+/// Rules applied to alleles before normalization.
+///
+/// The default policy accepts uppercase IUPAC codes only, which is what
+/// [`normalize`](crate::record::normalize) has always enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ValidationPolicy {
+    pub alphabet: Alphabet,
+    pub lowercase: Lowercase,
+    /// Accept symbolic ALT alleles such as `<DEL>`, `*` and breakends.
+    pub symbolic: bool,
+}
+
+impl ValidationPolicy {
+    /// Accepts uppercase `ACGTN` only.
+    pub fn strict() -> Self {
+        Self {
+            alphabet: Alphabet::Strict,
+            ..Default::default()
+        }
+    }
+
+    /// Accepts uppercase IUPAC codes.
+    pub fn iupac() -> Self {
+        Self::default()
+    }
+
+    /// Accepts IUPAC codes in either case, optionally converting them to uppercase.
+    pub fn lowercase_tolerant(uppercase: bool) -> Self {
+        Self {
+            lowercase: if uppercase {
+                Lowercase::Uppercase
+            } else {
+                Lowercase::Accept
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Accepts uppercase IUPAC codes and symbolic ALT alleles.
+    pub fn symbolic_tolerant() -> Self {
+        Self {
+            symbolic: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn validate_reference(&self, reference: &str) -> Result<()> {
+        if reference.is_empty() {
+            Err(Error::RefBasesEmptyError())?
+        }
+
+        if !self.is_valid_bases(reference) {
+            Err(Error::RefBasesInvalidSymbolError(reference.to_string()))?
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_alternate(&self, alternate: &str) -> Result<()> {
+        if alternate.is_empty() {
+            Err(Error::AltBasesEmptyError())?
+        }
+
+        if self.passes_through(alternate) {
+            return Ok(());
+        }
+
+        if !self.is_valid_bases(alternate) {
+            Err(Error::AltBasesInvalidSymbolError(alternate.to_string()))?
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` for ALT alleles accepted without checking or trimming
+    /// their bases: gVCF non-ref alleles always, and other symbolic alleles
+    /// if [`ValidationPolicy::symbolic`] is set.
+    pub fn passes_through(&self, alternate: &str) -> bool {
+        is_non_ref(alternate) || (self.symbolic && is_symbolic(alternate))
+    }
+
+    fn is_valid_bases(&self, bases: &str) -> bool {
+        let table = match (self.alphabet, self.lowercase) {
+            (Alphabet::Strict, Lowercase::Reject) => &STRICT,
+            (Alphabet::Strict, _) => &STRICT_ANY_CASE,
+            (Alphabet::Iupac, Lowercase::Reject) => &IUPAC,
+            (Alphabet::Iupac, _) => &IUPAC_ANY_CASE,
+        };
+
+        !bases.is_empty() && bases.bytes().all(|b| table[b as usize])
+    }
+}
+
+/// The gVCF allele standing for any allele not called at a site.
+pub const NON_REF: &str = "<NON_REF>";
+
+/// Returns `true` for GATK's `<NON_REF>` and its bcftools spelling `<*>`.
+pub fn is_non_ref(allele: &str) -> bool {
+    allele == NON_REF || allele == "<*>"
+}
+
+/// Returns `true` for symbolic (`<ID>`), overlapping deletion (`*`) and breakend alleles.
+pub fn is_symbolic(allele: &str) -> bool {
+    (allele.starts_with('<') && allele.ends_with('>'))
+        || allele == "*"
+        || allele.contains('[')
+        || allele.contains(']')
+        || (allele.len() > 1 && (allele.starts_with('.') || allele.ends_with('.')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    /// The expression the lookup tables replaced.
+    fn regex(policy: &ValidationPolicy) -> Regex {
+        let pattern = match policy.alphabet {
+            Alphabet::Strict => "[ACGTN]",
+            Alphabet::Iupac => "[ACGTURYKMSWBDHVN]",
+        };
+        let flags = match policy.lowercase {
+            Lowercase::Reject => "",
+            _ => "(?i-u)",
+        };
+
+        Regex::new(&format!(r"{}\A{}+\z", flags, pattern)).unwrap()
+    }
+
+    #[test]
+    fn test_is_symbolic_1() {
+        assert!(is_symbolic("<DEL>"));
+        assert!(is_symbolic("*"));
+        assert!(is_symbolic("G]17:198982]"));
+        assert!(is_symbolic(".A"));
+        assert!(!is_symbolic("A"));
+        assert!(!is_symbolic("."));
+    }
+
+    #[test]
+    fn test_validate_1() {
+        let policy = ValidationPolicy::strict();
+
+        assert!(policy.validate_reference("ACGTN").is_ok());
+        assert!(policy.validate_reference("ACGR").is_err());
+        assert!(policy.validate_alternate("acgt").is_err());
+    }
+
+    #[test]
+    fn test_validate_2() {
+        let policy = ValidationPolicy::iupac();
+
+        assert!(policy.validate_reference("ACGR").is_ok());
+        assert!(policy.validate_alternate("acgt").is_err());
+        assert!(policy.validate_alternate("<DEL>").is_err());
+        assert!(policy.validate_alternate("<NON_REF>").is_ok());
+        assert!(policy.validate_alternate("<*>").is_ok());
+    }
+
+    #[test]
+    fn test_validate_3() {
+        let policy = ValidationPolicy::lowercase_tolerant(false);
+
+        assert!(policy.validate_reference("acgT").is_ok());
+        assert!(policy.validate_alternate("nnn").is_ok());
+        assert!(policy.validate_alternate("acg.").is_err());
+    }
+
+    #[test]
+    fn test_validate_4() {
+        let policy = ValidationPolicy::symbolic_tolerant();
+
+        assert!(policy.validate_alternate("<DEL>").is_ok());
+        assert!(policy.validate_alternate("*").is_ok());
+        assert!(policy.validate_reference("<DEL>").is_err());
+        assert!(policy.validate_alternate("").is_err());
+    }
+
+    #[test]
+    fn test_is_valid_bases_1() {
+        let policies = [
+            ValidationPolicy::strict(),
+            ValidationPolicy::iupac(),
+            ValidationPolicy::lowercase_tolerant(true),
+            ValidationPolicy {
+                alphabet: Alphabet::Strict,
+                ..ValidationPolicy::lowercase_tolerant(false)
+            },
+        ];
+        let mut inputs: Vec<String> = (0..=0x24ff)
+            .filter_map(char::from_u32)
+            .flat_map(|c| [c.to_string(), format!("A{}", c), format!("{}c", c)])
+            .collect();
+        inputs.extend(["", "ACGT", "acgt", "AC GT", "ACGT\n"].map(String::from));
+
+        for policy in &policies {
+            let regex = regex(policy);
+            for bases in &inputs {
+                assert_eq!(
+                    policy.is_valid_bases(bases),
+                    regex.is_match(bases),
+                    "{:?} {:?}",
+                    policy,
+                    bases
+                );
+            }
+        }
+
+        let policy = ValidationPolicy::lowercase_tolerant(false);
+        assert!(!policy.is_valid_bases("ryk\u{212a}"));
+        assert!(!policy.is_valid_bases("\u{17f}\u{17f}"));
+    }
+}
@@ -0,0 +1,319 @@
+//! Grouping of sorted records into genomic windows.
+//!
+//! [`Windows`] reads a stream sorted by position and yields each window with
+//! the records overlapping it, as soon as no later record can overlap it.
+//! Only the records of open windows are buffered, so window statistics such
+//! as variant density or burden tests run in the memory of a few windows.
+
+use crate::errors::{Error, Result};
+use crate::record::Record;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// An interval of a contig, 1-based and inclusive like VCF positions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Interval {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Interval {
+    pub fn new(chrom: &str, start: u64, end: u64) -> Self {
+        Self {
+            chrom: chrom.to_string(),
+            start,
+            end,
+        }
+    }
+
+    /// Returns `true` if `record` overlaps the interval.
+    ///
+    /// A record spans its REF allele, or up to its INFO `END`.
+    pub fn overlaps_record(&self, record: &Record) -> bool {
+        record.chrom == self.chrom && record.pos <= self.end && record.end() >= self.start
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.chrom, self.start, self.end)
+    }
+}
+
+enum Source {
+    /// Windows starting at 1, then every `step` bases; `next` is the start
+    /// of the next window to open.
+    Fixed { size: u64, step: u64, next: u64 },
+    /// Intervals not yet opened, by contig, in ascending order.
+    Intervals(HashMap<String, VecDeque<Interval>>),
+}
+
+/// Iterator adapter yielding `(interval, records)` for the windows of a
+/// sorted stream.
+///
+/// Records overlapping several windows are in each of them. Windows are
+/// yielded in order of start within a contig, and contigs in stream order;
+/// a record before the previous one, or on a contig seen before, fails with
+/// [`Error::UnsortedRecordError`].
+pub struct Windows<I: Iterator<Item = Result<Record>>> {
+    inner: I,
+    source: Source,
+    /// Windows of the current contig that later records may still overlap.
+    open: VecDeque<(Interval, Vec<Record>)>,
+    ready: VecDeque<(Interval, Vec<Record>)>,
+    last: Option<(String, u64)>,
+    seen: HashSet<String>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Record>>> Windows<I> {
+    /// Windows of `size` bases starting every `step` bases, overlapping when
+    /// `step` is less than `size`; both are at least 1.
+    ///
+    /// Every window of a contig up to the last one overlapping a record is
+    /// yielded, including empty ones.
+    pub fn fixed(inner: I, size: u64, step: u64) -> Self {
+        Self::with_source(
+            inner,
+            Source::Fixed {
+                size: size.max(1),
+                step: step.max(1),
+                next: 1,
+            },
+        )
+    }
+
+    /// The given intervals, which may overlap.
+    ///
+    /// Every interval is yielded, including empty ones. Intervals on contigs
+    /// without records come last, by contig name.
+    pub fn intervals(inner: I, intervals: impl IntoIterator<Item = Interval>) -> Self {
+        let mut by_contig: HashMap<String, Vec<Interval>> = HashMap::new();
+        for interval in intervals {
+            by_contig
+                .entry(interval.chrom.clone())
+                .or_default()
+                .push(interval);
+        }
+
+        let by_contig = by_contig
+            .into_iter()
+            .map(|(chrom, mut intervals)| {
+                intervals.sort_by_key(|i| (i.start, i.end));
+                (chrom, intervals.into())
+            })
+            .collect();
+
+        Self::with_source(inner, Source::Intervals(by_contig))
+    }
+
+    fn with_source(inner: I, source: Source) -> Self {
+        Self {
+            inner,
+            source,
+            open: VecDeque::new(),
+            ready: VecDeque::new(),
+            last: None,
+            seen: HashSet::new(),
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    fn push(&mut self, record: Record) -> Result<()> {
+        match &self.last {
+            Some((chrom, pos)) if *chrom == record.chrom => {
+                if record.pos < *pos {
+                    Err(Error::UnsortedRecordError(
+                        record.chrom.clone(),
+                        record.pos,
+                        chrom.clone(),
+                        *pos,
+                    ))?
+                }
+            }
+            last => {
+                if self.seen.contains(&record.chrom) {
+                    let (chrom, pos) = last.clone().unwrap_or_default();
+                    Err(Error::UnsortedRecordError(
+                        record.chrom.clone(),
+                        record.pos,
+                        chrom,
+                        pos,
+                    ))?
+                }
+
+                self.close_contig();
+                self.seen.insert(record.chrom.clone());
+                if let Source::Fixed { next, .. } = &mut self.source {
+                    *next = 1;
+                }
+            }
+        }
+        self.last = Some((record.chrom.clone(), record.pos));
+
+        self.open_until(&record.chrom, record.end());
+        while self.open.front().is_some_and(|(w, _)| w.end < record.pos) {
+            self.ready.extend(self.open.pop_front());
+        }
+        for (window, records) in &mut self.open {
+            if window.overlaps_record(&record) {
+                records.push(record.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens the windows of `chrom` starting at or before `end`.
+    fn open_until(&mut self, chrom: &str, end: u64) {
+        match &mut self.source {
+            Source::Fixed { size, step, next } => {
+                while *next <= end {
+                    let window = Interval::new(chrom, *next, *next + *size - 1);
+                    self.open.push_back((window, Vec::new()));
+                    *next += *step;
+                }
+            }
+            Source::Intervals(by_contig) => {
+                let Some(intervals) = by_contig.get_mut(chrom) else {
+                    return;
+                };
+                while intervals.front().is_some_and(|i| i.start <= end) {
+                    self.open
+                        .extend(intervals.pop_front().map(|i| (i, Vec::new())));
+                }
+            }
+        }
+    }
+
+    /// Moves the windows of the current contig to the ready windows.
+    fn close_contig(&mut self) {
+        self.ready.append(&mut self.open);
+
+        if let (Some((chrom, _)), Source::Intervals(by_contig)) = (&self.last, &mut self.source) {
+            if let Some(intervals) = by_contig.remove(chrom) {
+                self.ready
+                    .extend(intervals.into_iter().map(|i| (i, Vec::new())));
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.close_contig();
+
+        if let Source::Intervals(by_contig) = &mut self.source {
+            let mut rest: Vec<(String, VecDeque<Interval>)> = by_contig.drain().collect();
+            rest.sort_by(|a, b| a.0.cmp(&b.0));
+            for (_, intervals) in rest {
+                self.ready
+                    .extend(intervals.into_iter().map(|i| (i, Vec::new())));
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record>>> Iterator for Windows<I> {
+    type Item = Result<(Interval, Vec<Record>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(window) = self.ready.pop_front() {
+                return Some(Ok(window));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.inner.next() {
+                Some(Ok(record)) => {
+                    if let Err(e) = self.push(record) {
+                        return Some(Err(e));
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    self.finish();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDS: &[&str] = &[
+        "1\t5\t.\tA\tT\t.\t.\t.",
+        "1\t12\t.\tA\tT\t.\t.\t.",
+        "1\t25\t.\tA\tT\t.\t.\t.",
+        "2\t3\t.\tA\tT\t.\t.\t.",
+    ];
+
+    fn records() -> impl Iterator<Item = Result<Record>> {
+        RECORDS.iter().map(|l| l.parse())
+    }
+
+    /// Each window as `interval: positions`.
+    fn collect<I: Iterator<Item = Result<Record>>>(windows: Windows<I>) -> Vec<String> {
+        windows
+            .map(|w| {
+                let (interval, records) = w.unwrap();
+                let positions: Vec<String> = records.iter().map(|r| r.pos.to_string()).collect();
+                format!("{}: {}", interval, positions.join(","))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_windows_fixed_1() {
+        assert_eq!(
+            collect(Windows::fixed(records(), 10, 10)),
+            vec!["1:1-10: 5", "1:11-20: 12", "1:21-30: 25", "2:1-10: 3"]
+        );
+        assert_eq!(
+            collect(Windows::fixed(records(), 10, 5)),
+            vec![
+                "1:1-10: 5",
+                "1:6-15: 12",
+                "1:11-20: 12",
+                "1:16-25: 25",
+                "1:21-30: 25",
+                "2:1-10: 3"
+            ]
+        );
+
+        let unsorted = [RECORDS[1], RECORDS[0]].map(|l| l.parse());
+        assert!(Windows::fixed(unsorted.into_iter(), 10, 10).any(|w| w.is_err()));
+        let unsorted = [RECORDS[0], RECORDS[3], RECORDS[1]].map(|l| l.parse());
+        assert!(Windows::fixed(unsorted.into_iter(), 10, 10).any(|w| w.is_err()));
+    }
+
+    #[test]
+    fn test_windows_intervals_1() {
+        let intervals = [
+            Interval::new("3", 1, 5),
+            Interval::new("1", 30, 40),
+            Interval::new("1", 4, 6),
+            Interval::new("1", 1, 12),
+            Interval::new("2", 1, 2),
+        ];
+
+        assert_eq!(
+            collect(Windows::intervals(records(), intervals)),
+            vec![
+                "1:1-12: 5,12",
+                "1:4-6: 5",
+                "1:30-40: ",
+                "2:1-2: ",
+                "3:1-5: "
+            ]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Writing of VCF text.
+
+use crate::errors::Result;
+use crate::header::Header;
+use crate::record::Record;
+use std::io::Write;
+
+/// Streaming writer of VCF headers and records.
+pub struct Writer<W> {
+    inner: W,
+    records: u64,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, records: 0 }
+    }
+
+    /// Writes the meta-information lines and the `#CHROM` line.
+    pub fn write_header(&mut self, header: &Header) -> Result<()> {
+        write!(self.inner, "{}", header)?;
+
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        writeln!(self.inner, "{}", record)?;
+        self.records += 1;
+
+        Ok(())
+    }
+
+    /// Number of records written so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    #[test]
+    fn test_writer_1() {
+        let text = "##fileformat=VCFv4.2\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+            1\t100\t.\tA\tT\t.\tPASS\tDP=3\tGT\t0/1\n";
+        let reader = Reader::new(text.as_bytes()).unwrap();
+        let mut writer = Writer::new(Vec::new());
+
+        writer.write_header(reader.header()).unwrap();
+        for record in reader {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+
+        assert_eq!(writer.records(), 1);
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), text);
+    }
+}